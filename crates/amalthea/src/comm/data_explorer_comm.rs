@@ -31,6 +31,20 @@ pub struct ExportedData {
 	pub format: ExportFormat
 }
 
+/// Code snippet for the data view
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConvertedCode {
+	/// Lines of code that implement filters and sort keys
+	pub converted_code: Vec<String>
+}
+
+/// Syntax to use for code conversion
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CodeSyntaxName {
+	/// The name of the code syntax, eg, base, dplyr, data.table, etc.
+	pub code_syntax_name: String
+}
+
 /// The result of applying filters to a table
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct FilterResult {
@@ -512,7 +526,10 @@ pub struct SupportedFeatures {
 	pub set_sort_columns: SetSortColumnsFeatures,
 
 	/// Support for 'export_data_selection' RPC and its features
-	pub export_data_selection: ExportDataSelectionFeatures
+	pub export_data_selection: ExportDataSelectionFeatures,
+
+	/// Support for 'convert_to_code' RPC and its features
+	pub convert_to_code: Option<ConvertToCodeFeatures>
 }
 
 /// Feature flags for 'search_schema' RPC
@@ -568,6 +585,16 @@ pub struct ExportDataSelectionFeatures {
 	pub supported_formats: Vec<ExportFormat>
 }
 
+/// Feature flags for 'convert_to_code' RPC
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConvertToCodeFeatures {
+	/// The support status for this RPC method
+	pub support_status: SupportStatus,
+
+	/// The syntaxes for converted code
+	pub code_syntaxes: Option<Vec<CodeSyntaxName>>
+}
+
 /// Feature flags for 'set_sort_columns' RPC
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct SetSortColumnsFeatures {
@@ -896,7 +923,19 @@ pub enum ExportFormat {
 
 	#[serde(rename = "html")]
 	#[strum(to_string = "html")]
-	Html
+	Html,
+
+	#[serde(rename = "markdown")]
+	#[strum(to_string = "markdown")]
+	Markdown,
+
+	#[serde(rename = "json")]
+	#[strum(to_string = "json")]
+	Json,
+
+	#[serde(rename = "excel")]
+	#[strum(to_string = "excel")]
+	Excel
 }
 
 /// Possible values for SupportStatus
@@ -1037,6 +1076,13 @@ pub struct ExportDataSelectionParams {
 	pub format: ExportFormat,
 }
 
+/// Parameters for the ConvertToCode method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConvertToCodeParams {
+	/// The code syntax to use for conversion
+	pub code_syntax_name: CodeSyntaxName,
+}
+
 /// Parameters for the SetColumnFilters method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct SetColumnFiltersParams {
@@ -1119,6 +1165,20 @@ pub enum DataExplorerBackendRequest {
 	#[serde(rename = "export_data_selection")]
 	ExportDataSelection(ExportDataSelectionParams),
 
+	/// Converts the current data view into a code snippet.
+	///
+	/// Converts the current filters, sort keys and column filters into code
+	/// in the desired syntax
+	#[serde(rename = "convert_to_code")]
+	ConvertToCode(ConvertToCodeParams),
+
+	/// Suggest code syntax for code conversion
+	///
+	/// Suggest code syntax for code conversion based on the current backend
+	/// state
+	#[serde(rename = "suggest_code_syntax")]
+	SuggestCodeSyntax,
+
 	/// Set column filters to select subset of table columns
 	///
 	/// Set or clear column filters on table, replacing any previous filters
@@ -1173,6 +1233,12 @@ pub enum DataExplorerBackendReply {
 	/// Exported result
 	ExportDataSelectionReply(ExportedData),
 
+	/// Code snippet for the data view
+	ConvertToCodeReply(ConvertedCode),
+
+	/// Syntax to use for code conversion
+	SuggestCodeSyntaxReply(CodeSyntaxName),
+
 	/// Reply for the set_column_filters method (no result)
 	SetColumnFiltersReply(),
 
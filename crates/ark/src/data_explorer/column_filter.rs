@@ -0,0 +1,245 @@
+//
+// column_filter.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use amalthea::comm::data_explorer_comm::ColumnDisplayType;
+use amalthea::comm::data_explorer_comm::ColumnFilter;
+use amalthea::comm::data_explorer_comm::ColumnFilterParams;
+use amalthea::comm::data_explorer_comm::ColumnFilterType;
+use amalthea::comm::data_explorer_comm::ColumnFilterTypeSupportStatus;
+use amalthea::comm::data_explorer_comm::ColumnSchema;
+use amalthea::comm::data_explorer_comm::FilterTextSearch;
+use amalthea::comm::data_explorer_comm::SupportStatus;
+use amalthea::comm::data_explorer_comm::TextSearchType;
+use anyhow::anyhow;
+use regex::Regex;
+use regex::RegexBuilder;
+
+/// The column filter types supported by `search_schema` and
+/// `set_column_filters`
+pub fn supported_column_filter_types() -> Vec<ColumnFilterTypeSupportStatus> {
+    [
+        ColumnFilterType::TextSearch,
+        ColumnFilterType::MatchDataTypes,
+    ]
+    .into_iter()
+    .map(|column_filter_type| ColumnFilterTypeSupportStatus {
+        column_filter_type,
+        support_status: SupportStatus::Supported,
+    })
+    .collect()
+}
+
+/// Returns the indices of the columns that match all the filters, in the
+/// order of the table.
+///
+/// Fails if a filter is invalid, e.g. if its parameters don't match its type
+/// or if its regular expression doesn't compile.
+pub fn filter_columns(
+    columns: &[ColumnSchema],
+    filters: &[ColumnFilter],
+) -> anyhow::Result<Vec<usize>> {
+    let matchers = filters
+        .iter()
+        .map(ColumnMatcher::new)
+        .collect::<anyhow::Result<Vec<ColumnMatcher>>>()?;
+
+    let indices = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| matchers.iter().all(|matcher| matcher.matches(column)))
+        .map(|(index, _)| index)
+        .collect();
+
+    Ok(indices)
+}
+
+/// A column filter prepared for matching many columns
+enum ColumnMatcher<'a> {
+    Text(TextMatcher),
+    DataTypes(&'a [ColumnDisplayType]),
+}
+
+enum TextMatcher {
+    Contains(String, bool),
+    NotContains(String, bool),
+    StartsWith(String, bool),
+    EndsWith(String, bool),
+    Regex(Regex),
+}
+
+impl<'a> ColumnMatcher<'a> {
+    fn new(filter: &'a ColumnFilter) -> anyhow::Result<Self> {
+        match (&filter.filter_type, &filter.params) {
+            (ColumnFilterType::TextSearch, ColumnFilterParams::TextSearch(params)) => {
+                Ok(Self::Text(TextMatcher::new(params)?))
+            },
+            (ColumnFilterType::MatchDataTypes, ColumnFilterParams::MatchDataTypes(params)) => {
+                Ok(Self::DataTypes(&params.display_types))
+            },
+            (filter_type, _) => Err(anyhow!(
+                "Invalid parameters for column filter of type '{filter_type}'"
+            )),
+        }
+    }
+
+    fn matches(&self, column: &ColumnSchema) -> bool {
+        match self {
+            Self::Text(matcher) => matcher.matches(&column.column_name),
+            Self::DataTypes(display_types) => display_types.contains(&column.type_display),
+        }
+    }
+}
+
+impl TextMatcher {
+    fn new(params: &FilterTextSearch) -> anyhow::Result<Self> {
+        let case_sensitive = params.case_sensitive;
+
+        // Case-insensitive searches compare lowercase names with a lowercase
+        // term
+        let term = if case_sensitive {
+            params.term.clone()
+        } else {
+            params.term.to_lowercase()
+        };
+
+        let matcher = match params.search_type {
+            TextSearchType::Contains => Self::Contains(term, case_sensitive),
+            TextSearchType::NotContains => Self::NotContains(term, case_sensitive),
+            TextSearchType::StartsWith => Self::StartsWith(term, case_sensitive),
+            TextSearchType::EndsWith => Self::EndsWith(term, case_sensitive),
+            TextSearchType::RegexMatch => {
+                let regex = RegexBuilder::new(&params.term)
+                    .case_insensitive(!case_sensitive)
+                    .build()
+                    .map_err(|err| {
+                        anyhow!("Invalid regular expression '{}': {err}", params.term)
+                    })?;
+                Self::Regex(regex)
+            },
+        };
+
+        Ok(matcher)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let normalize = |case_sensitive: bool| {
+            if case_sensitive {
+                name.to_string()
+            } else {
+                name.to_lowercase()
+            }
+        };
+
+        match self {
+            Self::Contains(term, case_sensitive) => normalize(*case_sensitive).contains(term),
+            Self::NotContains(term, case_sensitive) => !normalize(*case_sensitive).contains(term),
+            Self::StartsWith(term, case_sensitive) => normalize(*case_sensitive).starts_with(term),
+            Self::EndsWith(term, case_sensitive) => normalize(*case_sensitive).ends_with(term),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amalthea::comm::data_explorer_comm::FilterMatchDataTypes;
+
+    use super::*;
+
+    fn column(name: &str, type_display: ColumnDisplayType) -> ColumnSchema {
+        ColumnSchema {
+            column_name: String::from(name),
+            column_index: 0,
+            type_name: String::from(""),
+            type_display,
+            description: None,
+            children: None,
+            precision: None,
+            scale: None,
+            timezone: None,
+            type_size: None,
+        }
+    }
+
+    fn text_search(search_type: TextSearchType, term: &str, case_sensitive: bool) -> ColumnFilter {
+        ColumnFilter {
+            filter_type: ColumnFilterType::TextSearch,
+            params: ColumnFilterParams::TextSearch(FilterTextSearch {
+                search_type,
+                term: String::from(term),
+                case_sensitive,
+            }),
+        }
+    }
+
+    fn columns() -> Vec<ColumnSchema> {
+        vec![
+            column("Age", ColumnDisplayType::Number),
+            column("age_group", ColumnDisplayType::String),
+            column("income", ColumnDisplayType::Number),
+            column("survey_date", ColumnDisplayType::Date),
+        ]
+    }
+
+    #[test]
+    fn test_text_search_column_filters() {
+        let columns = columns();
+        let filter = |search_type, term, case_sensitive| {
+            filter_columns(&columns, &[text_search(search_type, term, case_sensitive)]).unwrap()
+        };
+
+        assert_eq!(filter(TextSearchType::Contains, "age", false), vec![0, 1]);
+        assert_eq!(filter(TextSearchType::Contains, "age", true), vec![1]);
+        assert_eq!(filter(TextSearchType::NotContains, "AGE", false), vec![
+            2, 3
+        ]);
+        assert_eq!(filter(TextSearchType::StartsWith, "a", false), vec![0, 1]);
+        assert_eq!(filter(TextSearchType::EndsWith, "E", false), vec![0, 2, 3]);
+        assert_eq!(filter(TextSearchType::RegexMatch, "^[a-z]+_", true), vec![
+            1, 3
+        ]);
+        assert_eq!(filter(TextSearchType::RegexMatch, "^AGE$", false), vec![0]);
+
+        // Invalid regular expressions are reported
+        assert!(filter_columns(&columns, &[text_search(
+            TextSearchType::RegexMatch,
+            "(",
+            false
+        )])
+        .is_err());
+    }
+
+    #[test]
+    fn test_match_data_types_column_filters() {
+        let columns = columns();
+        let types = ColumnFilter {
+            filter_type: ColumnFilterType::MatchDataTypes,
+            params: ColumnFilterParams::MatchDataTypes(FilterMatchDataTypes {
+                display_types: vec![ColumnDisplayType::Number, ColumnDisplayType::Date],
+            }),
+        };
+
+        assert_eq!(filter_columns(&columns, &[types.clone()]).unwrap(), vec![
+            0, 2, 3
+        ]);
+
+        // Filters are combined
+        let search = text_search(TextSearchType::Contains, "a", false);
+        assert_eq!(filter_columns(&columns, &[types, search]).unwrap(), vec![
+            0, 3
+        ]);
+        let search = text_search(TextSearchType::StartsWith, "in", false);
+        assert_eq!(
+            filter_columns(&columns, &[
+                search,
+                text_search(TextSearchType::Contains, "x", false)
+            ])
+            .unwrap(),
+            Vec::<usize>::new()
+        );
+    }
+}
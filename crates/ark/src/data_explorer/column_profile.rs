@@ -5,6 +5,9 @@
 //
 //
 
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::data_explorer_comm::ColumnFrequencyTable;
 use amalthea::comm::data_explorer_comm::ColumnHistogram;
@@ -18,25 +21,26 @@ use amalthea::comm::data_explorer_comm::DataExplorerFrontendEvent;
 use amalthea::comm::data_explorer_comm::FormatOptions;
 use amalthea::comm::data_explorer_comm::GetColumnProfilesParams;
 use amalthea::comm::data_explorer_comm::ReturnColumnProfilesParams;
+use amalthea::comm::data_explorer_comm::RowFilter;
 use amalthea::socket::comm::CommSocket;
 use anyhow::anyhow;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
-use harp::tbl_get_column;
 use harp::RObject;
-use harp::TableKind;
-use stdext::unwrap;
 
 use crate::data_explorer::histogram;
 use crate::data_explorer::summary_stats::summary_stats;
+use crate::data_explorer::summary_stats::summary_stats_from;
 use crate::data_explorer::table::Table;
+use crate::data_explorer::table_backend::TableBackend;
 use crate::data_explorer::utils::display_type;
 use crate::modules::ARK_ENVS;
 
 pub struct ProcessColumnsProfilesParams {
     pub table: Table,
     pub indices: Option<Vec<i32>>,
-    pub kind: TableKind,
+    pub row_filters: Vec<RowFilter>,
+    pub backend: Arc<dyn TableBackend>,
     pub request: GetColumnProfilesParams,
 }
 
@@ -50,7 +54,8 @@ pub async fn handle_columns_profiles_requests(
     let profiles = process_columns_profiles_requests(
         params.table,
         params.indices,
-        params.kind,
+        params.row_filters,
+        params.backend,
         params.request.profiles,
         params.request.format_options,
     )
@@ -78,7 +83,8 @@ pub async fn handle_columns_profiles_requests(
 async fn process_columns_profiles_requests(
     table: Table,
     indices: Option<Vec<i32>>,
-    kind: TableKind,
+    row_filters: Vec<RowFilter>,
+    backend: Arc<dyn TableBackend>,
     profiles: Vec<ColumnProfileRequest>,
     format_options: FormatOptions,
 ) -> anyhow::Result<Vec<ColumnProfileResult>> {
@@ -95,9 +101,10 @@ async fn process_columns_profiles_requests(
             profile_column(
                 data.clone(),
                 indices.clone(),
+                &row_filters,
                 profile,
                 &format_options,
-                kind,
+                backend.as_ref(),
             )
             .await,
         );
@@ -115,28 +122,60 @@ async fn process_columns_profiles_requests(
 async fn profile_column(
     table: RObject,
     filtered_indices: Option<Vec<i32>>,
+    row_filters: &[RowFilter],
     request: ColumnProfileRequest,
     format_options: &FormatOptions,
-    kind: TableKind,
+    backend: &dyn TableBackend,
 ) -> ColumnProfileResult {
     let mut output = empty_column_profile_result();
 
-    let filtered_column = unwrap!(tbl_get_filtered_column(
-        &table,
-        request.column_index,
-        &filtered_indices,
-        kind,
-    ), Err(e) =>  {
-        // In the case something goes wrong here we log the error and return an empty output.
-        // This might still work for the other columns in the request.
-        log::error!("Error applying filter indices for column: {}. Err: {e}", request.column_index);
-        return output;
-    });
+    // The column is only brought into memory, once, for the profiles that
+    // the backend can't compute natively
+    let mut filtered_column: Option<RObject> = None;
 
     for profile_req in request.profiles {
+        // Profiles that fail natively are still computed in memory
+        let native = backend
+            .column_profile(
+                &table,
+                request.column_index,
+                &filtered_indices,
+                row_filters,
+                &profile_req,
+            )
+            .unwrap_or_else(|err| {
+                log::warn!(
+                    "Can't compute profile of column {} with the table backend: {err}",
+                    request.column_index
+                );
+                None
+            });
+
+        let data = match native {
+            Some(profile) => ProfileData::Native(profile),
+            None => {
+                if filtered_column.is_none() {
+                    filtered_column =
+                        match backend.column(&table, request.column_index, &filtered_indices) {
+                            Ok(column) => Some(column),
+                            Err(e) => {
+                                // In the case something goes wrong here we log the error and return an empty output.
+                                // This might still work for the other columns in the request.
+                                log::error!(
+                                    "Error applying filter indices for column: {}. Err: {e}",
+                                    request.column_index
+                                );
+                                return output;
+                            },
+                        };
+                }
+                ProfileData::Column(filtered_column.clone().unwrap())
+            },
+        };
+
         match profile_req.profile_type {
             ColumnProfileType::NullCount => {
-                output.null_count = profile_null_count(filtered_column.clone())
+                output.null_count = profile_null_count(data)
                     .map_err(|err| {
                         log::error!(
                             "Error getting summary stats for column {}: {}",
@@ -147,28 +186,26 @@ async fn profile_column(
                     .ok();
             },
             ColumnProfileType::SummaryStats => {
-                output.summary_stats =
-                    profile_summary_stats(filtered_column.clone(), format_options)
-                        .map_err(|err| {
-                            log::error!(
-                                "Error getting null count for column {}: {}",
-                                request.column_index,
-                                err
-                            );
-                        })
-                        .ok()
+                output.summary_stats = profile_summary_stats(data, format_options)
+                    .map_err(|err| {
+                        log::error!(
+                            "Error getting null count for column {}: {}",
+                            request.column_index,
+                            err
+                        );
+                    })
+                    .ok()
             },
             ColumnProfileType::SmallHistogram | ColumnProfileType::LargeHistogram => {
-                let histogram =
-                    profile_histogram(filtered_column.clone(), format_options, &profile_req)
-                        .map_err(|err| {
-                            log::error!(
-                                "Error getting histogram for column {}: {}",
-                                request.column_index,
-                                err
-                            );
-                        })
-                        .ok();
+                let histogram = profile_histogram(data, format_options, &profile_req)
+                    .map_err(|err| {
+                        log::error!(
+                            "Error getting histogram for column {}: {}",
+                            request.column_index,
+                            err
+                        );
+                    })
+                    .ok();
 
                 match profile_req.profile_type {
                     ColumnProfileType::SmallHistogram => {
@@ -185,16 +222,15 @@ async fn profile_column(
                 }
             },
             ColumnProfileType::SmallFrequencyTable | ColumnProfileType::LargeFrequencyTable => {
-                let frequency_table =
-                    profile_frequency_table(filtered_column.clone(), format_options, &profile_req)
-                        .map_err(|err| {
-                            log::error!(
-                                "Error getting frequency table for column {}: {}",
-                                request.column_index,
-                                err
-                            );
-                        })
-                        .ok();
+                let frequency_table = profile_frequency_table(data, format_options, &profile_req)
+                    .map_err(|err| {
+                        log::error!(
+                            "Error getting frequency table for column {}: {}",
+                            request.column_index,
+                            err
+                        );
+                    })
+                    .ok();
 
                 match profile_req.profile_type {
                     ColumnProfileType::SmallFrequencyTable => {
@@ -229,8 +265,18 @@ pub fn empty_column_profile_result() -> ColumnProfileResult {
     }
 }
 
+/// The data a profile is computed from
+enum ProfileData {
+    /// The column, limited to the filtered rows
+    Column(RObject),
+
+    /// The profile computed natively by the table backend, in the shape of
+    /// the output of the R function computing it in memory
+    Native(RObject),
+}
+
 fn profile_frequency_table(
-    column: RObject,
+    data: ProfileData,
     format_options: &FormatOptions,
     profile_spec: &ColumnProfileSpec,
 ) -> anyhow::Result<ColumnFrequencyTable> {
@@ -242,13 +288,19 @@ fn profile_frequency_table(
             _ => return Err(anyhow!("Wrong type of parameters for the frequency table.")),
         },
     };
-    let frequency_table =
-        histogram::profile_frequency_table(column.sexp, &params, &format_options)?;
+    let frequency_table = match data {
+        ProfileData::Column(column) => {
+            histogram::profile_frequency_table(column.sexp, &params, &format_options)?
+        },
+        ProfileData::Native(profile) => {
+            histogram::frequency_table_from(profile, &params, &format_options)?
+        },
+    };
     Ok(frequency_table)
 }
 
 fn profile_histogram(
-    column: RObject,
+    data: ProfileData,
     format_options: &FormatOptions,
     profile_spec: &ColumnProfileSpec,
 ) -> anyhow::Result<ColumnHistogram> {
@@ -260,16 +312,46 @@ fn profile_histogram(
             _ => return Err(anyhow!("Wrong type of parameters for the histogram.")),
         },
     };
-    let histogram = histogram::profile_histogram(column.sexp, &params, &format_options)?;
+    let histogram = match data {
+        ProfileData::Column(column) => {
+            histogram::profile_histogram(column.sexp, &params, &format_options)?
+        },
+        ProfileData::Native(profile) => {
+            histogram::histogram_from(profile, &params, &format_options)?
+        },
+    };
     Ok(histogram)
 }
 
 fn profile_summary_stats(
-    column: RObject,
+    data: ProfileData,
     format_options: &FormatOptions,
 ) -> anyhow::Result<ColumnSummaryStats> {
-    let dtype = display_type(column.sexp);
-    Ok(summary_stats(column.sexp, dtype, format_options)?)
+    match data {
+        ProfileData::Column(column) => {
+            let dtype = display_type(column.sexp);
+            Ok(summary_stats(column.sexp, dtype, format_options)?)
+        },
+        ProfileData::Native(profile) => {
+            // Native summary stats come with a prototype of the column, which
+            // determines the type of the stats
+            let mut profile = HashMap::<String, RObject>::try_from(profile)?;
+            let (Some(prototype), Some(stats)) =
+                (profile.remove("prototype"), profile.remove("stats"))
+            else {
+                return Err(anyhow!(
+                    "Native summary stats need a `prototype` and `stats`"
+                ));
+            };
+            let dtype = display_type(prototype.sexp);
+            Ok(summary_stats_from(
+                stats,
+                prototype.sexp,
+                dtype,
+                format_options,
+            )?)
+        },
+    }
 }
 
 /// Counts the number of nulls in a column. As the intent is to provide an
@@ -279,30 +361,16 @@ fn profile_summary_stats(
 /// Expects data to be filtered by the view indices.
 ///
 /// - `column_index`: The index of the column to count nulls in; 0-based.
-fn profile_null_count(column: RObject) -> anyhow::Result<i64> {
+fn profile_null_count(data: ProfileData) -> anyhow::Result<i64> {
     // Compute the number of nulls in the column
-    let result: i32 = RFunction::new("", ".ps.null_count")
-        .param("column", column)
-        .call_in(ARK_ENVS.positron_ns)?
-        .try_into()?;
+    let result: i32 = match data {
+        ProfileData::Column(column) => RFunction::new("", ".ps.null_count")
+            .param("column", column)
+            .call_in(ARK_ENVS.positron_ns)?
+            .try_into()?,
+        ProfileData::Native(profile) => profile.try_into()?,
+    };
 
     // Return the count of nulls and NA values
     Ok(result.try_into()?)
 }
-
-fn tbl_get_filtered_column(
-    x: &RObject,
-    column_index: i64,
-    indices: &Option<Vec<i32>>,
-    kind: TableKind,
-) -> anyhow::Result<RObject> {
-    let column = tbl_get_column(x.sexp, column_index as i32, kind)?;
-
-    Ok(match &indices {
-        Some(indices) => RFunction::from("col_filter_indices")
-            .add(column)
-            .add(RObject::try_from(indices)?)
-            .call_in(ARK_ENVS.positron_ns)?,
-        None => column,
-    })
-}
@@ -0,0 +1,436 @@
+//
+// export_file.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use amalthea::comm::data_explorer_comm::BackendState;
+use amalthea::comm::data_explorer_comm::DataExplorerBackendReply;
+use amalthea::comm::data_explorer_comm::DataExplorerBackendRequest;
+use amalthea::comm::data_explorer_comm::SupportStatus;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::data_explorer::table_backend::TableBackend;
+use crate::modules::ARK_ENVS;
+
+/// The number of rows brought into memory at once when exporting a table
+const EXPORT_CHUNK_SIZE: usize = 10_000;
+
+// The `export_data_to_file` RPC isn't described by the data explorer comm
+// schema yet, so its types are declared here rather than in the generated
+// `data_explorer_comm.rs`. Requests and replies of the comm are wrapped in
+// `DataExplorerRequest` and `DataExplorerReply` to include it.
+
+/// Requests of the data explorer comm
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum DataExplorerRequest {
+    Backend(DataExplorerBackendRequest),
+    Extension(DataExplorerExtensionRequest),
+}
+
+/// Replies of the data explorer comm
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum DataExplorerReply {
+    Backend(DataExplorerBackendReply),
+    Extension(DataExplorerExtensionReply),
+}
+
+/// Requests of the data explorer comm that aren't in the comm schema
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "params")]
+pub enum DataExplorerExtensionRequest {
+    /// Export the rows of the view, after sorts and filters, and the columns
+    /// that pass the column filters to a file
+    #[serde(rename = "export_data_to_file")]
+    ExportDataToFile(ExportDataToFileParams),
+}
+
+/// Replies of the data explorer comm that aren't in the comm schema
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "result")]
+pub enum DataExplorerExtensionReply {
+    ExportDataToFileReply(ExportedFile),
+
+    /// The backend state, with the features of the RPCs that aren't in the
+    /// comm schema
+    GetStateReply(serde_json::Value),
+}
+
+/// Parameters of the `export_data_to_file` RPC
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportDataToFileParams {
+    /// Path of the file to write
+    pub path: String,
+
+    /// File format
+    pub format: ExportFileFormat,
+}
+
+/// Result of the `export_data_to_file` RPC
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportedFile {
+    /// Path of the written file
+    pub path: String,
+
+    /// Number of rows written to the file
+    pub num_rows: i64,
+
+    /// Number of columns written to the file
+    pub num_columns: i64,
+}
+
+/// Formats of the `export_data_to_file` RPC
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ExportFileFormat {
+    #[serde(rename = "csv")]
+    Csv,
+
+    #[serde(rename = "parquet")]
+    Parquet,
+}
+
+impl std::fmt::Display for ExportFileFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFileFormat::Csv => write!(f, "csv"),
+            ExportFileFormat::Parquet => write!(f, "parquet"),
+        }
+    }
+}
+
+/// Feature flags of the `export_data_to_file` RPC, advertised in
+/// `supported_features.export_data_to_file` of the backend state
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportDataToFileFeatures {
+    /// The support status for this RPC method
+    pub support_status: SupportStatus,
+
+    /// File formats supported
+    pub supported_formats: Vec<ExportFileFormat>,
+}
+
+/// Adds the features of `export_data_to_file` to the supported features of
+/// the backend `state`
+pub fn state_with_export_features(state: BackendState) -> anyhow::Result<serde_json::Value> {
+    let features = ExportDataToFileFeatures {
+        support_status: SupportStatus::Supported,
+        supported_formats: vec![ExportFileFormat::Csv, ExportFileFormat::Parquet],
+    };
+
+    let mut state = serde_json::to_value(state)?;
+    state["supported_features"]["export_data_to_file"] = serde_json::to_value(features)?;
+
+    Ok(state)
+}
+
+// Writes the rows of the data frame to a file, a chunk of rows at a time
+//
+// Arguments:
+// - backend: The backend of the data frame
+// - data: The full data frame to export
+// - view_indices: The 1-based indices of the rows to export, in the order of
+//   the view, i.e. after sorts and filters. All rows are exported when `None`.
+// - num_rows: The number of rows of the full data frame
+// - columns: The 0-based indices of the columns to export
+// - path: The path of the file to write
+// - format: The format of the file (csv and parquet are currently supported).
+//
+// The file is removed if writing any of the chunks fails.
+pub fn export_file(
+    backend: &dyn TableBackend,
+    data: &RObject,
+    view_indices: &Option<Vec<i32>>,
+    num_rows: i32,
+    columns: &[i64],
+    path: &str,
+    format: ExportFileFormat,
+) -> anyhow::Result<ExportedFile> {
+    let view_indices = view_indices.as_deref();
+    let num_rows = match view_indices {
+        Some(indices) => indices.len(),
+        None => num_rows as usize,
+    };
+
+    // Subsetting takes 1-based column indices
+    let columns: Vec<i64> = columns.iter().map(|j| j + 1).collect();
+
+    // The schema of Parquet files is derived from the types of the full
+    // columns rather than from the first chunk of rows
+    let prototype = backend.prototype(data, Some(columns.clone()))?;
+
+    let writer = RFunction::from("export_file_writer")
+        .param("path", path)
+        .param("format", format.to_string())
+        .param("prototype", prototype)
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    if let Err(err) = write_chunks(backend, data, view_indices, num_rows, &columns, &writer) {
+        if let Err(close_err) = RFunction::from("export_file_close")
+            .add(writer)
+            .param("remove", true)
+            .call_in(ARK_ENVS.positron_ns)
+        {
+            log::error!("Can't remove partially exported file '{path}': {close_err}");
+        }
+        return Err(err);
+    }
+
+    RFunction::from("export_file_close")
+        .add(writer)
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    Ok(ExportedFile {
+        path: path.to_string(),
+        num_rows: num_rows as i64,
+        num_columns: columns.len() as i64,
+    })
+}
+
+fn write_chunks(
+    backend: &dyn TableBackend,
+    data: &RObject,
+    view_indices: Option<&[i32]>,
+    num_rows: usize,
+    columns: &[i64],
+    writer: &RObject,
+) -> anyhow::Result<()> {
+    // The row indices of a chunk are only created when the chunk is written,
+    // so that exporting a large table doesn't hold all of them in memory.
+    // An empty table is still written as a single chunk, e.g. so that CSV
+    // files have a header.
+    let mut start = 0;
+    loop {
+        let end = usize::min(start + EXPORT_CHUNK_SIZE, num_rows);
+
+        let rows: Vec<i64> = match view_indices {
+            Some(indices) => indices[start..end].iter().map(|i| *i as i64).collect(),
+            None => (start as i64 + 1..=end as i64).collect(),
+        };
+
+        let x = backend.subset(data, Some(rows), Some(columns.to_vec()))?;
+        RFunction::from("export_file_write")
+            .add(writer.clone())
+            .add(x)
+            .call_in(ARK_ENVS.positron_ns)?;
+
+        start = end;
+        if start >= num_rows {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::object::RObject;
+
+    use super::*;
+    use crate::data_explorer::table_backend::table_backend;
+    use crate::test::r_test;
+
+    fn temp_path(ext: &str) -> String {
+        harp::parse_eval_global(&format!("tempfile(fileext = '.{ext}')"))
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    fn read_lines(path: &str) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_export_csv_file() {
+        r_test(|| {
+            let data: RObject = harp::parse_eval_global(
+                "data.frame(a = 1:3, b = c(4, 5, NA), c = c('x', 'y,z', 'w'))",
+            )
+            .unwrap();
+            let backend = table_backend(&data).unwrap();
+
+            let path = temp_path("csv");
+
+            // All rows and columns
+            let exported = export_file(
+                backend.as_ref(),
+                &data,
+                &None,
+                3,
+                &[0, 1, 2],
+                &path,
+                ExportFileFormat::Csv,
+            )
+            .unwrap();
+            assert_eq!(exported.num_rows, 3);
+            assert_eq!(exported.num_columns, 3);
+            assert_eq!(read_lines(&path), vec![
+                r#""a","b","c""#,
+                r#"1,4,"x""#,
+                r#"2,5,"y,z""#,
+                r#"3,,"w""#,
+            ]);
+
+            // Rows in the order of the view and a subset of the columns
+            export_file(
+                backend.as_ref(),
+                &data,
+                &Some(vec![3, 1]),
+                3,
+                &[2, 0],
+                &path,
+                ExportFileFormat::Csv,
+            )
+            .unwrap();
+            assert_eq!(read_lines(&path), vec![
+                r#""c","a""#,
+                r#""w",3"#,
+                r#""x",1"#,
+            ]);
+
+            // Empty views still have a header
+            export_file(
+                backend.as_ref(),
+                &data,
+                &Some(vec![]),
+                3,
+                &[0],
+                &path,
+                ExportFileFormat::Csv,
+            )
+            .unwrap();
+            assert_eq!(read_lines(&path), vec![r#""a""#]);
+
+            std::fs::remove_file(path).unwrap();
+        })
+    }
+
+    #[test]
+    fn test_export_csv_file_in_chunks() {
+        r_test(|| {
+            let data: RObject = harp::parse_eval_global("data.frame(x = seq_len(25000))").unwrap();
+            let backend = table_backend(&data).unwrap();
+
+            let path = temp_path("csv");
+
+            // The header is only written once
+            export_file(
+                backend.as_ref(),
+                &data,
+                &None,
+                25000,
+                &[0],
+                &path,
+                ExportFileFormat::Csv,
+            )
+            .unwrap();
+            let lines = read_lines(&path);
+            assert_eq!(lines.len(), 25001);
+            assert_eq!(lines[0], r#""x""#);
+            assert_eq!(lines[10001], "10001");
+            assert_eq!(lines[25000], "25000");
+
+            // Rows in the order of the view span several chunks too
+            let view_indices: Vec<i32> = (1..=25000).rev().collect();
+            let exported = export_file(
+                backend.as_ref(),
+                &data,
+                &Some(view_indices),
+                25000,
+                &[0],
+                &path,
+                ExportFileFormat::Csv,
+            )
+            .unwrap();
+            assert_eq!(exported.num_rows, 25000);
+            let lines = read_lines(&path);
+            assert_eq!(lines.len(), 25001);
+            assert_eq!(lines[1], "25000");
+            assert_eq!(lines[10001], "15000");
+            assert_eq!(lines[25000], "1");
+
+            std::fs::remove_file(path).unwrap();
+        })
+    }
+
+    #[test]
+    fn test_export_parquet_file_in_chunks() {
+        r_test(|| {
+            let arrow: bool = harp::parse_eval_global("requireNamespace('arrow', quietly = TRUE)")
+                .unwrap()
+                .try_into()
+                .unwrap();
+            if !arrow {
+                return;
+            }
+
+            // Like database drivers, the backend types the columns of the rows
+            // it fetches by their values, so `y` is logical in the first chunk
+            harp::parse_eval_global(
+                r#".ps.register_table_backend("ark_test_chunked_table", list(
+                    shape = function(x) list(num_rows = nrow(x$data), prototype = x$data[0, , drop = FALSE]),
+                    column = function(x, j, i) x$data[[j]],
+                    subset = function(x, i, j) {
+                        rows <- x$data[i, j, drop = FALSE]
+                        rows[] <- lapply(rows, function(col) if (all(is.na(col))) as.logical(col) else col)
+                        rows
+                    },
+                    sort_rows = function(x, keys) seq_len(nrow(x$data)),
+                    filter_rows = function(x, row_filters) {
+                        list(indices = seq_len(nrow(x$data)), errors = rep(NA_character_, length(row_filters)))
+                    }
+                ))"#,
+            )
+            .unwrap();
+
+            let data: RObject = harp::parse_eval_global(
+                "structure(
+                    list(data = data.frame(x = seq_len(25000), y = c(rep(NA, 10000), seq_len(15000) / 2))),
+                    class = 'ark_test_chunked_table'
+                )",
+            )
+            .unwrap();
+            let backend = table_backend(&data).unwrap();
+
+            let path = temp_path("parquet");
+
+            // The schema of the file is the one of the full columns
+            export_file(
+                backend.as_ref(),
+                &data,
+                &None,
+                25000,
+                &[0, 1],
+                &path,
+                ExportFileFormat::Parquet,
+            )
+            .unwrap();
+
+            let valid: bool = harp::parse_eval_global(&format!(
+                "local({{
+                    x <- arrow::read_parquet('{}')
+                    nrow(x) == 25000 && is.double(x$y) && sum(is.na(x$y)) == 10000 && x$y[[25000]] == 7500
+                }})",
+                path.replace('\\', "/")
+            ))
+            .unwrap()
+            .try_into()
+            .unwrap();
+            assert!(valid);
+
+            std::fs::remove_file(path).unwrap();
+        })
+    }
+}
@@ -16,30 +16,36 @@ use amalthea::comm::data_explorer_comm::TableSelectionKind;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
-use libr::SEXP;
 
+use crate::data_explorer::table_backend::TableBackend;
 use crate::data_explorer::utils::tbl_subset_with_view_indices;
 use crate::modules::ARK_ENVS;
 
 // Returns the data frame exported in the requested format as a string
 //
 // Arguments:
+// - backend: The backend of the data frame
 // - data: The data frame full data frame to export
 // - view_indices: The order of rows, and maybe filtered rows from the data frame to be selected.
 //   Must be applied before the selection rules if selection affects rows.
 // - selection: The selected region of the data frame
-// - format: The format to export the data frame to (csv, tsv and html are currently supported).
+// - format: The format to export the data frame to (csv, tsv, html, markdown, json and excel
+//   are currently supported).
 pub fn export_selection(
-    data: SEXP,
+    backend: &dyn TableBackend,
+    data: &RObject,
     view_indices: &Option<Vec<i32>>,
     selection: TableSelection,
     format: ExportFormat,
 ) -> anyhow::Result<String> {
-    let region = get_selection(data, view_indices, selection.clone())?;
+    let region = get_selection(backend, data, view_indices, selection.clone())?;
     let format_string = match format {
         ExportFormat::Csv => "csv",
         ExportFormat::Tsv => "tsv",
         ExportFormat::Html => "html",
+        ExportFormat::Markdown => "markdown",
+        ExportFormat::Json => "json",
+        ExportFormat::Excel => "excel",
     };
     let include_header = match selection.kind {
         TableSelectionKind::SingleCell => false,
@@ -58,7 +64,8 @@ pub fn export_selection(
 }
 
 fn get_selection(
-    data: SEXP,
+    backend: &dyn TableBackend,
+    data: &RObject,
     view_indices: &Option<Vec<i32>>,
     selection: TableSelection,
 ) -> anyhow::Result<RObject> {
@@ -106,7 +113,7 @@ fn get_selection(
         },
     };
 
-    tbl_subset_with_view_indices(data, view_indices, i, j)
+    tbl_subset_with_view_indices(backend, data, view_indices, i, j)
 }
 
 #[cfg(test)]
@@ -117,6 +124,7 @@ mod tests {
     use harp::object::RObject;
 
     use super::*;
+    use crate::data_explorer::table_backend::table_backend;
     use crate::test::r_test;
 
     fn export_selection_helper(data: RObject, selection: TableSelection) -> String {
@@ -128,7 +136,8 @@ mod tests {
         selection: TableSelection,
         format: ExportFormat,
     ) -> String {
-        export_selection(data.sexp, &None, selection, format).unwrap()
+        let backend = table_backend(&data).unwrap();
+        export_selection(backend.as_ref(), &data, &None, selection, format).unwrap()
     }
 
     fn export_selection_helper_with_view_indices(
@@ -136,7 +145,15 @@ mod tests {
         view_indices: Vec<i32>,
         selection: TableSelection,
     ) -> String {
-        export_selection(data.sexp, &Some(view_indices), selection, ExportFormat::Csv).unwrap()
+        let backend = table_backend(&data).unwrap();
+        export_selection(
+            backend.as_ref(),
+            &data,
+            &Some(view_indices),
+            selection,
+            ExportFormat::Csv,
+        )
+        .unwrap()
     }
 
     fn small_test_data() -> RObject {
@@ -377,6 +394,98 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_export_formats() {
+        r_test(|| {
+            let data = small_test_data();
+
+            let cell_range_selection = |i1, i2, j1, j2| TableSelection {
+                kind: TableSelectionKind::CellRange,
+                selection: Selection::CellRange(DataSelectionCellRange {
+                    first_row_index: i1,
+                    last_row_index: i2,
+                    first_column_index: j1,
+                    last_column_index: j2,
+                }),
+            };
+            let single_cell_selection = |i, j| TableSelection {
+                kind: TableSelectionKind::SingleCell,
+                selection: Selection::SingleCell(DataSelectionSingleCell {
+                    row_index: i,
+                    column_index: j,
+                }),
+            };
+            let export = |selection, format| {
+                export_selection_helper_with_format(data.clone(), selection, format)
+            };
+
+            // Markdown tables, with right-aligned numbers
+            assert_eq!(
+                export(cell_range_selection(0, 1, 0, 1), ExportFormat::Markdown),
+                "| a | b |\n| ---: | ---: |\n| 1 | 4 |\n| 2 | 5 |".to_string()
+            );
+            assert_eq!(
+                export(cell_range_selection(1, 2, 1, 2), ExportFormat::Markdown),
+                "| b | c |\n| ---: | --- |\n| 5 | b |\n|  | c |".to_string()
+            );
+
+            // JSON records, with NA's as null
+            assert_eq!(
+                export(cell_range_selection(1, 2, 1, 2), ExportFormat::Json),
+                "[\n  {\"b\": 5, \"c\": \"b\"},\n  {\"b\": null, \"c\": \"c\"}\n]".to_string()
+            );
+
+            // Excel-compatible TSV
+            assert_eq!(
+                export(cell_range_selection(1, 2, 1, 2), ExportFormat::Excel),
+                "b\tc\n5\tb\n\tc".to_string()
+            );
+
+            // Single cells are copied as values
+            assert_eq!(
+                export(single_cell_selection(0, 2), ExportFormat::Markdown),
+                "a".to_string()
+            );
+            assert_eq!(
+                export(single_cell_selection(0, 2), ExportFormat::Json),
+                "\"a\"".to_string()
+            );
+            assert_eq!(
+                export(single_cell_selection(2, 1), ExportFormat::Json),
+                "null".to_string()
+            );
+
+            // Dates are written in ISO 8601 and special characters are escaped
+            let data = harp::parse_eval_global(
+                r#"data.frame(d = as.Date("2024-01-31"), s = 'say "hi"\tnow', p = "a|b")"#,
+            )
+            .unwrap();
+            let export = |format| {
+                export_selection_helper_with_format(
+                    data.clone(),
+                    cell_range_selection(0, 0, 0, 2),
+                    format,
+                )
+            };
+            assert_eq!(
+                export(ExportFormat::Excel),
+                "d\ts\tp\n2024-01-31\t\"say \"\"hi\"\"\tnow\"\ta|b".to_string()
+            );
+            assert_eq!(
+                export(ExportFormat::Json),
+                r#"[
+  {"d": "2024-01-31", "s": "say \"hi\"\tnow", "p": "a|b"}
+]"#
+                .to_string()
+            );
+            assert_eq!(
+                export(ExportFormat::Markdown),
+                "| d | s | p |\n| --- | --- | --- |\n| 2024-01-31 | say \"hi\"\tnow | a\\|b |"
+                    .to_string()
+            );
+        })
+    }
+
     #[test]
     fn test_view_indices() {
         r_test(|| {
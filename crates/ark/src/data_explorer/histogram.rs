@@ -57,13 +57,24 @@ pub fn profile_histogram(
         ColumnHistogramParamsMethod::Scott => "scott".into(),
     };
 
-    let results: HashMap<String, RObject> = RFunction::from("profile_histogram")
+    let results = RFunction::from("profile_histogram")
         .add(column)
         .add(method)
         .add(num_bins)
         .add(quantiles)
-        .call_in(ARK_ENVS.positron_ns)?
-        .try_into()?;
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    histogram_from(results, params, format_options)
+}
+
+/// A histogram from the output of `profile_histogram()`, which may also be
+/// computed natively by a table backend
+pub fn histogram_from(
+    results: RObject,
+    params: &ColumnHistogramParams,
+    format_options: &FormatOptions,
+) -> anyhow::Result<ColumnHistogram> {
+    let results: HashMap<String, RObject> = results.try_into()?;
 
     // Bin edges are expected to be objects that can be formatted, such as integers vectors,
     // numeric vectors or even dates.
@@ -120,11 +131,22 @@ pub fn profile_frequency_table(
     params: &ColumnFrequencyTableParams,
     format_options: &FormatOptions,
 ) -> anyhow::Result<ColumnFrequencyTable> {
-    let results: HashMap<String, RObject> = RFunction::from("profile_frequency_table")
+    let results = RFunction::from("profile_frequency_table")
         .add(column)
         .add(params.limit as i32)
-        .call_in(ARK_ENVS.positron_ns)?
-        .try_into()?;
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    frequency_table_from(results, params, format_options)
+}
+
+/// A frequency table from the output of `profile_frequency_table()`, which
+/// may also be computed natively by a table backend
+pub fn frequency_table_from(
+    results: RObject,
+    params: &ColumnFrequencyTableParams,
+    format_options: &FormatOptions,
+) -> anyhow::Result<ColumnFrequencyTable> {
+    let results: HashMap<String, RObject> = results.try_into()?;
 
     let values = unwrap!(results.get("values"), None => {
         return Err(anyhow!("Something went wrong when computing `values`"));
@@ -276,6 +298,57 @@ mod tests {
         });
     }
 
+    // Compares the histogram computed from aggregates by `native_histogram()`,
+    // as table backends do, with the one computed in memory
+    fn test_native_histogram(code: &str, method: ColumnHistogramParamsMethod, num_bins: i64) {
+        let params = ColumnHistogramParams {
+            method,
+            num_bins,
+            quantiles: None,
+        };
+        let column = harp::parse_eval_global(code).unwrap();
+
+        let stats = harp::parse_eval_global(&format!(
+            "local({{ x <- {code}; list(n = length(x), min = min(x), max = max(x), stdev = sd(x), iqr = IQR(x)) }})"
+        ))
+        .unwrap();
+        let count_le = harp::parse_eval_global(&format!(
+            "local({{ x <- {code}; function(edges) vapply(edges, function(edge) sum(x <= edge), integer(1)) }})"
+        ))
+        .unwrap();
+        let is_integer = harp::parse_eval_global(&format!("is.integer({code})")).unwrap();
+
+        let native = RFunction::from("native_histogram")
+            .add(stats)
+            .add(RObject::try_from(serde_json::to_value(&params).unwrap()).unwrap())
+            .add(is_integer)
+            .add(RObject::null())
+            .add(count_le)
+            .call_in(ARK_ENVS.positron_ns)
+            .unwrap();
+
+        assert_eq!(
+            histogram_from(native, &params, &default_options()).unwrap(),
+            profile_histogram(column.sexp, &params, &default_options()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_native_histograms() {
+        r_test(|| {
+            for code in [
+                "0:10",
+                "c(1L, 5L, 5L, 20L, 100L)",
+                "c(0.5, 1.2, 3.3, 3.3, 7.9, 10)",
+            ] {
+                test_native_histogram(code, ColumnHistogramParamsMethod::Fixed, 4);
+                test_native_histogram(code, ColumnHistogramParamsMethod::Sturges, 0);
+                test_native_histogram(code, ColumnHistogramParamsMethod::Scott, 0);
+            }
+            test_native_histogram("0:10", ColumnHistogramParamsMethod::FreedmanDiaconis, 0);
+        })
+    }
+
     #[test]
     fn test_basic_histograms() {
         r_test(|| {
@@ -5,11 +5,14 @@
 //
 //
 
+pub mod column_filter;
 pub mod column_profile;
+pub mod export_file;
 pub mod export_selection;
 pub mod format;
 pub mod histogram;
 pub mod r_data_explorer;
 pub mod summary_stats;
 pub mod table;
+pub mod table_backend;
 pub mod utils;
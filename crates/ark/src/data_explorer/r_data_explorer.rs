@@ -6,11 +6,12 @@
 //
 
 use std::cmp;
-use std::collections::HashMap;
+use std::sync::Arc;
 
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::data_explorer_comm::ArraySelection;
 use amalthea::comm::data_explorer_comm::BackendState;
+use amalthea::comm::data_explorer_comm::CodeSyntaxName;
 use amalthea::comm::data_explorer_comm::ColumnDisplayType;
 use amalthea::comm::data_explorer_comm::ColumnFilter;
 use amalthea::comm::data_explorer_comm::ColumnProfileType;
@@ -19,6 +20,9 @@ use amalthea::comm::data_explorer_comm::ColumnSchema;
 use amalthea::comm::data_explorer_comm::ColumnSelection;
 use amalthea::comm::data_explorer_comm::ColumnSortKey;
 use amalthea::comm::data_explorer_comm::ColumnValue;
+use amalthea::comm::data_explorer_comm::ConvertToCodeFeatures;
+use amalthea::comm::data_explorer_comm::ConvertToCodeParams;
+use amalthea::comm::data_explorer_comm::ConvertedCode;
 use amalthea::comm::data_explorer_comm::DataExplorerBackendReply;
 use amalthea::comm::data_explorer_comm::DataExplorerBackendRequest;
 use amalthea::comm::data_explorer_comm::DataExplorerFrontendEvent;
@@ -38,7 +42,10 @@ use amalthea::comm::data_explorer_comm::RowFilterParams;
 use amalthea::comm::data_explorer_comm::RowFilterType;
 use amalthea::comm::data_explorer_comm::RowFilterTypeSupportStatus;
 use amalthea::comm::data_explorer_comm::SearchSchemaFeatures;
+use amalthea::comm::data_explorer_comm::SearchSchemaParams;
+use amalthea::comm::data_explorer_comm::SearchSchemaResult;
 use amalthea::comm::data_explorer_comm::SetColumnFiltersFeatures;
+use amalthea::comm::data_explorer_comm::SetColumnFiltersParams;
 use amalthea::comm::data_explorer_comm::SetRowFiltersFeatures;
 use amalthea::comm::data_explorer_comm::SetRowFiltersParams;
 use amalthea::comm::data_explorer_comm::SetSortColumnsFeatures;
@@ -54,7 +61,6 @@ use amalthea::comm::event::CommManagerEvent;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
 use anyhow::anyhow;
-use anyhow::bail;
 use crossbeam::channel::unbounded;
 use crossbeam::channel::Sender;
 use crossbeam::select;
@@ -62,9 +68,6 @@ use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
 use harp::r_symbol;
-use harp::tbl_get_column;
-use harp::TableInfo;
-use harp::TableKind;
 use itertools::Itertools;
 use libr::*;
 use serde::Deserialize;
@@ -76,20 +79,36 @@ use stdext::unwrap;
 use tracing::Instrument;
 use uuid::Uuid;
 
+use crate::data_explorer::column_filter::filter_columns;
+use crate::data_explorer::column_filter::supported_column_filter_types;
 use crate::data_explorer::column_profile::handle_columns_profiles_requests;
 use crate::data_explorer::column_profile::ProcessColumnsProfilesParams;
+use crate::data_explorer::export_file;
+use crate::data_explorer::export_file::DataExplorerExtensionReply;
+use crate::data_explorer::export_file::DataExplorerExtensionRequest;
+use crate::data_explorer::export_file::DataExplorerReply;
+use crate::data_explorer::export_file::DataExplorerRequest;
+use crate::data_explorer::export_file::ExportDataToFileParams;
+use crate::data_explorer::export_file::ExportFileFormat;
+use crate::data_explorer::export_file::ExportedFile;
 use crate::data_explorer::export_selection;
 use crate::data_explorer::format;
 use crate::data_explorer::format::format_string;
 use crate::data_explorer::table::Table;
-use crate::data_explorer::utils::display_type;
+use crate::data_explorer::table_backend::table_backend;
+use crate::data_explorer::table_backend::DataObjectShape;
+use crate::data_explorer::table_backend::TableBackend;
+use crate::data_explorer::utils::r_list;
 use crate::data_explorer::utils::tbl_subset_with_view_indices;
+use crate::data_explorer::utils::view_row_indices;
 use crate::interface::RMain;
 use crate::lsp::events::EVENTS;
 use crate::modules::ARK_ENVS;
 use crate::r_task;
 use crate::thread::RThreadSafe;
-use crate::variables::variable::WorkspaceVariableDisplayType;
+
+/// The syntaxes of the R code generated by `convert_to_code`
+const CODE_SYNTAXES: &[&str] = &["base", "dplyr", "data.table"];
 
 /// A name/value binding pair in an environment.
 ///
@@ -101,12 +120,6 @@ pub struct DataObjectEnvInfo {
     pub env: RThreadSafe<RObject>,
 }
 
-struct DataObjectShape {
-    pub columns: Vec<ColumnSchema>,
-    pub num_rows: i32,
-    pub kind: TableKind,
-}
-
 /// The R backend for Positron's Data Explorer.
 pub struct RDataExplorer {
     /// The human-readable title of the data viewer.
@@ -115,6 +128,10 @@ pub struct RDataExplorer {
     /// The data object that the data viewer is currently viewing.
     table: Table,
 
+    /// The backend answering requests about the data object, e.g. sorting
+    /// or filtering its rows.
+    backend: Arc<dyn TableBackend>,
+
     /// An optional binding to the environment containing the data object.
     /// This can be omitted for cases wherein the data object isn't in an
     /// environment (e.g. a temporary or unnamed object)
@@ -133,6 +150,11 @@ pub struct RDataExplorer {
     /// A cache containing the current set of column filters
     col_filters: Vec<ColumnFilter>,
 
+    /// The indices of the columns that pass the column filters, if any column
+    /// filters are applied. These are the columns displayed in the data
+    /// viewer; `get_schema` indices are positions in this set.
+    filtered_columns: Option<Vec<usize>>,
+
     /// The set of sorted row indices, if any sorts are applied. This always
     /// includes all row indices.
     sorted_indices: Option<Vec<i32>>,
@@ -189,11 +211,12 @@ impl RDataExplorer {
             let shape = r_task(|| Self::r_get_shape(table.get()?));
             match shape {
                 // shape the columns; start the data viewer
-                Ok(shape) => {
+                Ok((backend, shape)) => {
                     // Create the initial state for the data viewer
                     let viewer = Self {
                         title,
                         table,
+                        backend,
                         binding,
                         shape,
                         sorted_indices: None,
@@ -202,6 +225,7 @@ impl RDataExplorer {
                         sort_keys: vec![],
                         row_filters: vec![],
                         col_filters: vec![],
+                        filtered_columns: None,
                         comm,
                         comm_manager_tx,
                     };
@@ -298,7 +322,7 @@ impl RDataExplorer {
                     }
 
                     let comm = self.comm.clone();
-                    comm.handle_request(msg, |req| self.handle_rpc(req));
+                    comm.handle_request(msg, |req| self.handle_request(req));
                 },
             }
         }
@@ -364,7 +388,12 @@ impl RDataExplorer {
         // Consider: there may be a cheaper way to test the schema for changes
         // than regenerating it, but it'd be a lot more complicated.
         let new_shape = match r_task(|| Self::r_get_shape(self.table.get()?.clone())) {
-            Ok(shape) => shape,
+            Ok((backend, shape)) => {
+                // The object might now be viewed with another backend,
+                // e.g. when a matrix is replaced with a data frame
+                self.backend = backend;
+                shape
+            },
             Err(_) => {
                 // The most likely cause of this error is that the object is no
                 // longer something with a usable shape -- it's been removed or
@@ -384,6 +413,9 @@ impl RDataExplorer {
             // Update row filters to reflect the new schema
             self.row_filters_update()?;
 
+            // Apply the column filters to the new columns
+            self.filtered_columns = self.column_filters_compute(&self.col_filters)?;
+
             // Clear precomputed indices
             self.sorted_indices = None;
             self.filtered_indices = None;
@@ -456,6 +488,29 @@ impl RDataExplorer {
         Ok(())
     }
 
+    /// Handles the requests of the comm schema and the `export_data_to_file`
+    /// request, see `DataExplorerRequest`
+    fn handle_request(&mut self, req: DataExplorerRequest) -> anyhow::Result<DataExplorerReply> {
+        match req {
+            DataExplorerRequest::Backend(req) => match self.handle_rpc(req)? {
+                DataExplorerBackendReply::GetStateReply(state) => Ok(DataExplorerReply::Extension(
+                    DataExplorerExtensionReply::GetStateReply(
+                        export_file::state_with_export_features(state)?,
+                    ),
+                )),
+                reply => Ok(DataExplorerReply::Backend(reply)),
+            },
+
+            DataExplorerRequest::Extension(DataExplorerExtensionRequest::ExportDataToFile(
+                ExportDataToFileParams { path, format },
+            )) => Ok(DataExplorerReply::Extension(
+                DataExplorerExtensionReply::ExportDataToFileReply(
+                    self.r_export_data_to_file(&path, format)?,
+                ),
+            )),
+        }
+    }
+
     fn handle_rpc(
         &mut self,
         req: DataExplorerBackendRequest,
@@ -522,12 +577,19 @@ impl RDataExplorer {
 
             DataExplorerBackendRequest::GetState => r_task(|| self.r_get_state()),
 
-            DataExplorerBackendRequest::SearchSchema(_) => {
-                return Err(anyhow!("Data Explorer: Not yet supported"));
-            },
+            DataExplorerBackendRequest::SearchSchema(SearchSchemaParams {
+                filters,
+                start_index,
+                max_results,
+            }) => self.search_schema(filters, start_index, max_results),
+
+            DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams { filters }) => {
+                // Compute the filtered columns first so that invalid filters
+                // are rejected without changing the current ones
+                self.filtered_columns = self.column_filters_compute(&filters)?;
+                self.col_filters = filters;
 
-            DataExplorerBackendRequest::SetColumnFilters(_) => {
-                return Err(anyhow!("Data Explorer: Not yet supported"));
+                Ok(DataExplorerBackendReply::SetColumnFiltersReply())
             },
 
             DataExplorerBackendRequest::GetRowLabels(req) => {
@@ -549,66 +611,28 @@ impl RDataExplorer {
                     format,
                 },
             )),
+
+            DataExplorerBackendRequest::ConvertToCode(ConvertToCodeParams { code_syntax_name }) => {
+                Ok(DataExplorerBackendReply::ConvertToCodeReply(r_task(
+                    || self.r_convert_to_code(code_syntax_name),
+                )?))
+            },
+
+            DataExplorerBackendRequest::SuggestCodeSyntax => {
+                Ok(DataExplorerBackendReply::SuggestCodeSyntaxReply(r_task(
+                    || self.r_suggest_code_syntax(),
+                )?))
+            },
         }
     }
 }
 
 // Methods that must be run on the main R thread
 impl RDataExplorer {
-    fn r_get_shape(table: RObject) -> anyhow::Result<DataObjectShape> {
-        unsafe {
-            let table = table.clone();
-            let object = *table;
-
-            let info = table_info_or_bail(object)?;
-
-            let harp::TableInfo {
-                kind,
-                dims:
-                    harp::TableDim {
-                        num_rows,
-                        num_cols: total_num_columns,
-                    },
-                col_names: column_names,
-            } = info;
-
-            let mut column_schemas = Vec::<ColumnSchema>::new();
-            for i in 0..(total_num_columns as isize) {
-                let column_name = match column_names.get_unchecked(i) {
-                    Some(name) => name,
-                    None => format!("[, {}]", i + 1),
-                };
-
-                // TODO: handling for nested data frame columns
-
-                let col = match kind {
-                    harp::TableKind::Dataframe => VECTOR_ELT(object, i),
-                    harp::TableKind::Matrix => object,
-                };
-
-                let type_name = WorkspaceVariableDisplayType::from(col, false).display_type;
-                let type_display = display_type(col);
-
-                column_schemas.push(ColumnSchema {
-                    column_name,
-                    column_index: i as i64,
-                    type_name,
-                    type_display,
-                    description: None,
-                    children: None,
-                    precision: None,
-                    scale: None,
-                    timezone: None,
-                    type_size: None,
-                });
-            }
-
-            Ok(DataObjectShape {
-                columns: column_schemas,
-                kind,
-                num_rows,
-            })
-        }
+    fn r_get_shape(table: RObject) -> anyhow::Result<(Arc<dyn TableBackend>, DataObjectShape)> {
+        let backend = table_backend(&table)?;
+        let shape = backend.shape(&table)?;
+        Ok((backend, shape))
     }
 
     fn launch_get_column_profiles_handler(&self, params: GetColumnProfilesParams) {
@@ -617,7 +641,8 @@ impl RDataExplorer {
         let params = ProcessColumnsProfilesParams {
             table: self.table.clone(),
             indices: self.filtered_indices.clone(),
-            kind: self.shape.kind,
+            row_filters: self.row_filters.clone(),
+            backend: self.backend.clone(),
             request: params,
         };
         let comm = self.comm.clone();
@@ -635,29 +660,7 @@ impl RDataExplorer {
     ///
     /// Returns a vector containing the sorted row indices.
     fn r_sort_rows(&self) -> anyhow::Result<Vec<i32>> {
-        let mut order = RFunction::new("base", "order");
-
-        // Allocate a vector to hold the sort order for each column
-        let mut decreasing: Vec<bool> = Vec::new();
-
-        // For each element of self.sort_keys, add an argument to order
-        for key in &self.sort_keys {
-            // Get the column to sort by
-            order.add(tbl_get_column(
-                self.table.get()?.sexp,
-                key.column_index as i32,
-                self.shape.kind,
-            )?);
-            decreasing.push(!key.ascending);
-        }
-        // Add the sort order per column
-        order.param("decreasing", RObject::try_from(&decreasing)?);
-        order.param("method", RObject::from("radix"));
-
-        // Invoke the order function and return the result
-        let result = order.call()?;
-        let indices: Vec<i32> = result.try_into()?;
-        Ok(indices)
+        self.backend.sort_rows(&self.table.get()?, &self.sort_keys)
     }
 
     /// Filter all the rows in the data object according to the row filters in
@@ -666,47 +669,14 @@ impl RDataExplorer {
     /// Returns a tuple containing a vector of all the row indices that pass the filters and
     /// a character vector of errors, where None means no error happened.
     fn r_filter_rows(&self) -> anyhow::Result<(Vec<i32>, Vec<Option<String>>)> {
-        let mut filters: Vec<RObject> = vec![];
-
         // Shortcut: If there are no row filters, the filtered indices include
         // all row indices.
         if self.row_filters.is_empty() {
             return Ok(((1..=self.shape.num_rows).collect(), vec![]));
         }
 
-        // Convert each filter to an R object by marshaling through the JSON
-        // layer.
-        //
-        // This feels a little weird since the filters were *unmarshaled* from
-        // JSON earlier in the RPC stack, but it's the easiest way to create R
-        // objects from the filter data without creating an unnecessary
-        // intermediate representation.
-        for filter in &self.row_filters {
-            let filter = serde_json::to_value(filter)?;
-            let filter = RObject::try_from(filter)?;
-            filters.push(filter);
-        }
-
-        // Pass the row filters to R and get the resulting row indices
-        let filters = RObject::try_from(filters)?;
-        let result: HashMap<String, RObject> = RFunction::new("", ".ps.filter_rows")
-            .param("table", self.table.get()?.sexp)
-            .param("row_filters", filters)
-            .call_in(ARK_ENVS.positron_ns)?
-            .try_into()?;
-
-        // Handle errors that occured in the filters
-        let row_indices = match result.get("indices") {
-            Some(indices) => Vec::<i32>::try_from(indices.clone())?,
-            None => bail!("Unexpected output from .ps.filter_rows. Expected 'indices' field."),
-        };
-
-        let errors = match result.get("errors") {
-            Some(errors) => Vec::<Option<String>>::try_from(errors.clone())?,
-            None => bail!("Unexpected output from .ps.filter_rows. Expected 'errors' field."),
-        };
-
-        Ok((row_indices, errors))
+        self.backend
+            .filter_rows(&self.table.get()?, &self.row_filters)
     }
 
     // Compute filtered indices out of the current `row_filters`.
@@ -764,7 +734,17 @@ impl RDataExplorer {
             RowFilterType::IsTrue | RowFilterType::IsFalse => {
                 Ok(display_type == &ColumnDisplayType::Boolean)
             },
-            RowFilterType::IsNull | RowFilterType::NotNull | RowFilterType::SetMembership => {
+            RowFilterType::SetMembership => {
+                // Values are compared to the numbers of numeric columns and
+                // to the strings of other columns, e.g. factor levels
+                Ok(matches!(
+                    display_type,
+                    ColumnDisplayType::String |
+                        ColumnDisplayType::Number |
+                        ColumnDisplayType::Boolean
+                ))
+            },
+            RowFilterType::IsNull | RowFilterType::NotNull => {
                 // Filters always supported
                 Ok(true)
             },
@@ -791,6 +771,27 @@ impl RDataExplorer {
         return Ok(had_errors);
     }
 
+    // Compute the indices of the columns that pass the column `filters`, or
+    // `None` if there are no column filters.
+    fn column_filters_compute(
+        &self,
+        filters: &[ColumnFilter],
+    ) -> anyhow::Result<Option<Vec<usize>>> {
+        if filters.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(filter_columns(&self.shape.columns, filters)?))
+    }
+
+    /// The number of columns displayed in the data viewer, i.e. after
+    /// applying the column filters.
+    fn num_filtered_columns(&self) -> usize {
+        match self.filtered_columns {
+            Some(ref columns) => columns.len(),
+            None => self.shape.columns.len(),
+        }
+    }
+
     /// Sort the filtered indices according to the sort keys, storing the
     /// result in view_indices.
     fn apply_sorts_and_filters(&mut self) {
@@ -836,10 +837,11 @@ impl RDataExplorer {
 
     /// Get the schema for a vector of columns in the data object.
     ///
-    /// - `column_indices`: The vector of columns in the data object.
+    /// - `column_indices`: The vector of columns in the data object, relative
+    ///   to the columns that pass the column filters.
     fn get_schema(&self, column_indices: Vec<i64>) -> anyhow::Result<DataExplorerBackendReply> {
         // Get the columns length. (Does Rust optimize loop invariants well?)
-        let columns_len = self.shape.columns.len();
+        let columns_len = self.num_filtered_columns();
 
         // Gather the column schemas to return.
         let mut columns: Vec<ColumnSchema> = Vec::new();
@@ -860,6 +862,12 @@ impl RDataExplorer {
                 break;
             }
 
+            // Map the column index to the unfiltered columns.
+            let column_index = match self.filtered_columns {
+                Some(ref filtered_columns) => filtered_columns[column_index],
+                None => column_index,
+            };
+
             // Push the column schema.
             columns.push(self.shape.columns[column_index].clone());
         }
@@ -870,6 +878,34 @@ impl RDataExplorer {
         }))
     }
 
+    /// Search all the columns of the data object, regardless of the current
+    /// column filters, for the ones matching `filters`.
+    ///
+    /// - `start_index`: The index of the first match to return, for paging.
+    /// - `max_results`: The maximum number of matches to return.
+    fn search_schema(
+        &self,
+        filters: Vec<ColumnFilter>,
+        start_index: i64,
+        max_results: i64,
+    ) -> anyhow::Result<DataExplorerBackendReply> {
+        let matches = filter_columns(&self.shape.columns, &filters)?;
+
+        let columns = matches
+            .iter()
+            .skip(cmp::max(start_index, 0) as usize)
+            .take(cmp::max(max_results, 0) as usize)
+            .map(|index| self.shape.columns[*index].clone())
+            .collect();
+
+        Ok(DataExplorerBackendReply::SearchSchemaReply(
+            SearchSchemaResult {
+                matches: TableSchema { columns },
+                total_num_matches: matches.len() as i64,
+            },
+        ))
+    }
+
     fn r_get_state(&self) -> anyhow::Result<DataExplorerBackendReply> {
         let state = BackendState {
             display_name: self.title.clone(),
@@ -878,7 +914,7 @@ impl RDataExplorer {
                     Some(ref indices) => indices.len() as i64,
                     None => self.shape.num_rows as i64,
                 },
-                num_columns: self.shape.columns.len() as i64,
+                num_columns: self.num_filtered_columns() as i64,
             },
             table_unfiltered_shape: TableShape {
                 num_rows: self.shape.num_rows as i64,
@@ -922,8 +958,8 @@ impl RDataExplorer {
                     ],
                 },
                 search_schema: SearchSchemaFeatures {
-                    support_status: SupportStatus::Unsupported,
-                    supported_types: vec![],
+                    support_status: SupportStatus::Supported,
+                    supported_types: supported_column_filter_types(),
                 },
                 set_row_filters: SetRowFiltersFeatures {
                     support_status: SupportStatus::Supported,
@@ -938,6 +974,7 @@ impl RDataExplorer {
                         RowFilterType::NotEmpty,
                         RowFilterType::NotNull,
                         RowFilterType::Search,
+                        RowFilterType::SetMembership,
                     ]
                     .iter()
                     .map(|row_filter_type| RowFilterTypeSupportStatus {
@@ -945,14 +982,11 @@ impl RDataExplorer {
                         support_status: SupportStatus::Supported,
                     })
                     .collect(),
-                    // Temporarily disabled for https://github.com/posit-dev/positron/issues/3489
-                    // on 6/11/2024. This will be enabled again when the UI has been reworked to
-                    // support grouping.
-                    supports_conditions: SupportStatus::Unsupported,
+                    supports_conditions: SupportStatus::Supported,
                 },
                 set_column_filters: SetColumnFiltersFeatures {
-                    support_status: SupportStatus::Unsupported,
-                    supported_types: vec![],
+                    support_status: SupportStatus::Supported,
+                    supported_types: supported_column_filter_types(),
                 },
                 set_sort_columns: SetSortColumnsFeatures {
                     support_status: SupportStatus::Supported,
@@ -963,8 +997,22 @@ impl RDataExplorer {
                         ExportFormat::Csv,
                         ExportFormat::Tsv,
                         ExportFormat::Html,
+                        ExportFormat::Markdown,
+                        ExportFormat::Json,
+                        ExportFormat::Excel,
                     ],
                 },
+                convert_to_code: Some(ConvertToCodeFeatures {
+                    support_status: SupportStatus::Supported,
+                    code_syntaxes: Some(
+                        CODE_SYNTAXES
+                            .iter()
+                            .map(|name| CodeSyntaxName {
+                                code_syntax_name: name.to_string(),
+                            })
+                            .collect(),
+                    ),
+                }),
             },
         };
         Ok(DataExplorerBackendReply::GetStateReply(state))
@@ -976,16 +1024,15 @@ impl RDataExplorer {
         format_options: FormatOptions,
     ) -> anyhow::Result<DataExplorerBackendReply> {
         let mut column_data: Vec<Vec<ColumnValue>> = Vec::with_capacity(columns.len());
+        let table = self.table.get()?;
         for selection in columns {
-            let tbl = tbl_subset_with_view_indices(
-                self.table.get()?.sexp,
+            let rows = view_row_indices(
                 &self.view_indices,
-                Some(self.get_row_selection_indices(selection.spec)),
-                Some(vec![selection.column_index]),
-            )?;
-
-            // The column will be always at index 0 because we already selected a single column above.
-            let column = tbl_get_column(tbl.sexp, 0, self.shape.kind)?;
+                self.get_row_selection_indices(selection.spec),
+            );
+            let column = self
+                .backend
+                .column(&table, selection.column_index, &Some(rows))?;
             let formatted = format::format_column(column.sexp, &format_options);
             column_data.push(formatted.clone());
         }
@@ -1003,7 +1050,8 @@ impl RDataExplorer {
         format_options: &FormatOptions,
     ) -> anyhow::Result<Vec<String>> {
         let tbl = tbl_subset_with_view_indices(
-            self.table.get()?.sexp,
+            self.backend.as_ref(),
+            &self.table.get()?,
             &self.view_indices,
             Some(self.get_row_selection_indices(selection)),
             Some(vec![]), // Use empty vec, because we only need the row names.
@@ -1057,17 +1105,102 @@ impl RDataExplorer {
     ) -> anyhow::Result<String> {
         r_task(|| {
             export_selection::export_selection(
-                self.table.get()?.sexp,
+                self.backend.as_ref(),
+                &self.table.get()?,
                 &self.view_indices,
                 selection,
                 format,
             )
         })
     }
-}
 
-fn table_info_or_bail(x: SEXP) -> anyhow::Result<TableInfo> {
-    harp::table_info(x).ok_or(anyhow!("Unsupported type for data viewer"))
+    /// Export the rows of the view and the columns that pass the column
+    /// filters to a file.
+    fn r_export_data_to_file(
+        &self,
+        path: &str,
+        format: ExportFileFormat,
+    ) -> anyhow::Result<ExportedFile> {
+        let columns: Vec<i64> = match self.filtered_columns {
+            Some(ref columns) => columns.iter().map(|j| *j as i64).collect(),
+            None => (0..self.shape.columns.len() as i64).collect(),
+        };
+
+        r_task(|| {
+            export_file::export_file(
+                self.backend.as_ref(),
+                &self.table.get()?,
+                &self.view_indices,
+                self.shape.num_rows,
+                &columns,
+                path,
+                format,
+            )
+        })
+    }
+
+    /// Convert the row filters, sort keys and column filters to R code
+    /// reproducing the view of the data. The code refers to the data by the
+    /// name of its binding, or by the title of the data viewer.
+    fn r_convert_to_code(&self, syntax: CodeSyntaxName) -> anyhow::Result<ConvertedCode> {
+        if !CODE_SYNTAXES.contains(&syntax.code_syntax_name.as_str()) {
+            return Err(anyhow!(
+                "Unsupported code syntax '{}'",
+                syntax.code_syntax_name
+            ));
+        }
+
+        let column_name = |index: usize| self.shape.columns[index].column_name.clone();
+
+        // Invalid filters aren't applied to the view
+        let row_filters: Vec<RowFilter> = self
+            .row_filters
+            .iter()
+            .filter(|filter| filter.is_valid != Some(false))
+            .cloned()
+            .collect();
+
+        let sort_columns: Vec<String> = self
+            .sort_keys
+            .iter()
+            .map(|key| column_name(key.column_index as usize))
+            .collect();
+        let sort_ascending: Vec<bool> = self.sort_keys.iter().map(|key| key.ascending).collect();
+
+        let (name, is_binding) = match self.binding {
+            Some(ref binding) => (binding.name.clone(), true),
+            None => (self.title.clone(), false),
+        };
+
+        let mut call = RFunction::from(".ps.convert_to_code");
+        call.param("table", self.table.get()?)
+            .param("name", name)
+            .param("is_binding", is_binding)
+            .param("row_filters", r_list(&row_filters)?)
+            .param("sort_columns", sort_columns)
+            .param("sort_ascending", RObject::try_from(&sort_ascending)?)
+            .param("syntax", syntax.code_syntax_name);
+
+        if let Some(ref columns) = self.filtered_columns {
+            let columns: Vec<String> = columns.iter().map(|index| column_name(*index)).collect();
+            call.param("columns", columns);
+        }
+
+        let converted_code = Vec::<String>::try_from(call.call_in(ARK_ENVS.positron_ns)?)?;
+        Ok(ConvertedCode { converted_code })
+    }
+
+    /// Suggest the code syntax matching the class of the data, e.g. dplyr for
+    /// tibbles.
+    fn r_suggest_code_syntax(&self) -> anyhow::Result<CodeSyntaxName> {
+        let code_syntax_name = RFunction::from(".ps.suggest_code_syntax")
+            .add(self.table.get()?)
+            .call_in(ARK_ENVS.positron_ns)?;
+
+        Ok(CodeSyntaxName {
+            code_syntax_name: String::try_from(code_syntax_name)?,
+        })
+    }
 }
 
 /// Open an R object in the data viewer.
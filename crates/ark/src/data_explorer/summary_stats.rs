@@ -32,21 +32,48 @@ pub fn summary_stats(
     column: SEXP,
     display_type: ColumnDisplayType,
     format_options: &FormatOptions,
+) -> anyhow::Result<ColumnSummaryStats> {
+    let function = match display_type {
+        ColumnDisplayType::Number => "summary_stats_number",
+        ColumnDisplayType::String => "summary_stats_string",
+        ColumnDisplayType::Boolean => "summary_stats_boolean",
+        ColumnDisplayType::Date | ColumnDisplayType::Datetime => "summary_stats_date",
+        _ => {
+            return Err(anyhow::anyhow!("Unkown type"));
+        },
+    };
+    let r_stats = call_summary_fn(function, column)?;
+    summary_stats_from(r_stats, column, display_type, format_options)
+}
+
+/// Summary stats from the output of the R function computing the stats of
+/// `display_type`, e.g. `summary_stats_number()`.
+///
+/// The column is only used to look up the timezone of datetimes, so it can
+/// be a prototype without any rows when the stats are computed natively by a
+/// table backend.
+pub fn summary_stats_from(
+    r_stats: RObject,
+    column: SEXP,
+    display_type: ColumnDisplayType,
+    format_options: &FormatOptions,
 ) -> anyhow::Result<ColumnSummaryStats> {
     let mut stats = empty_column_summary_stats();
     stats.type_display = display_type;
     match stats.type_display {
         ColumnDisplayType::Number => {
-            stats.number_stats = Some(summary_stats_number(column, format_options)?);
+            stats.number_stats = Some(summary_stats_number(r_stats, format_options)?);
         },
         ColumnDisplayType::String => {
-            stats.string_stats = Some(summary_stats_string(column)?);
+            stats.string_stats = Some(summary_stats_string(r_stats)?);
         },
         ColumnDisplayType::Boolean => {
-            stats.boolean_stats = Some(summary_stats_boolean(column)?);
+            stats.boolean_stats = Some(summary_stats_boolean(r_stats)?);
+        },
+        ColumnDisplayType::Date => stats.date_stats = Some(summary_stats_date(r_stats)?),
+        ColumnDisplayType::Datetime => {
+            stats.datetime_stats = Some(summary_stats_datetime(r_stats, column)?)
         },
-        ColumnDisplayType::Date => stats.date_stats = Some(summary_stats_date(column)?),
-        ColumnDisplayType::Datetime => stats.datetime_stats = Some(summary_stats_datetime(column)?),
         _ => {
             return Err(anyhow::anyhow!("Unkown type"));
         },
@@ -55,11 +82,9 @@ pub fn summary_stats(
 }
 
 fn summary_stats_number(
-    column: SEXP,
+    r_stats: RObject,
     format_options: &FormatOptions,
 ) -> anyhow::Result<SummaryStatsNumber> {
-    let r_stats = call_summary_fn("summary_stats_number", column)?;
-
     let names = unsafe { CharacterVector::new_unchecked(r_names2(r_stats.sexp)) };
     let values = format_string(r_stats.sexp, format_options);
 
@@ -81,8 +106,7 @@ fn summary_stats_number(
     })
 }
 
-fn summary_stats_string(column: SEXP) -> anyhow::Result<SummaryStatsString> {
-    let stats = call_summary_fn("summary_stats_string", column)?;
+fn summary_stats_string(stats: RObject) -> anyhow::Result<SummaryStatsString> {
     let r_stats: HashMap<String, i32> = stats.try_into()?;
 
     Ok(SummaryStatsString {
@@ -91,8 +115,7 @@ fn summary_stats_string(column: SEXP) -> anyhow::Result<SummaryStatsString> {
     })
 }
 
-fn summary_stats_boolean(column: SEXP) -> anyhow::Result<SummaryStatsBoolean> {
-    let stats = call_summary_fn("summary_stats_boolean", column)?;
+fn summary_stats_boolean(stats: RObject) -> anyhow::Result<SummaryStatsBoolean> {
     let r_stats: HashMap<String, i32> = stats.try_into()?;
 
     Ok(SummaryStatsBoolean {
@@ -101,9 +124,8 @@ fn summary_stats_boolean(column: SEXP) -> anyhow::Result<SummaryStatsBoolean> {
     })
 }
 
-fn summary_stats_date(column: SEXP) -> anyhow::Result<SummaryStatsDate> {
-    let r_stats: HashMap<String, RObject> =
-        call_summary_fn("summary_stats_date", column)?.try_into()?;
+fn summary_stats_date(stats: RObject) -> anyhow::Result<SummaryStatsDate> {
+    let r_stats: HashMap<String, RObject> = stats.try_into()?;
 
     let num_unique: i32 = get_stat(&r_stats, "num_unique")?.try_into()?;
 
@@ -116,11 +138,10 @@ fn summary_stats_date(column: SEXP) -> anyhow::Result<SummaryStatsDate> {
     })
 }
 
-fn summary_stats_datetime(column: SEXP) -> anyhow::Result<SummaryStatsDatetime> {
+fn summary_stats_datetime(stats: RObject, column: SEXP) -> anyhow::Result<SummaryStatsDatetime> {
     // Use the same implementationas the date summary stats
     // but add the timezone.
-    let r_stats: HashMap<String, RObject> =
-        call_summary_fn("summary_stats_date", column)?.try_into()?;
+    let r_stats: HashMap<String, RObject> = stats.try_into()?;
 
     let num_unique: i32 = get_stat(&r_stats, "num_unique")?.try_into()?;
     let timezone: Option<String> = RFunction::from("summary_stats_get_timezone")
@@ -0,0 +1,447 @@
+//
+// table_backend.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use amalthea::comm::data_explorer_comm::ColumnProfileSpec;
+use amalthea::comm::data_explorer_comm::ColumnSchema;
+use amalthea::comm::data_explorer_comm::ColumnSortKey;
+use amalthea::comm::data_explorer_comm::RowFilter;
+use anyhow::anyhow;
+use anyhow::bail;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use harp::tbl_get_column;
+use harp::utils::r_is_data_frame;
+use harp::utils::r_is_null;
+use harp::utils::r_is_object;
+use harp::TableInfo;
+use harp::TableKind;
+use libr::*;
+
+use crate::data_explorer::utils::display_type;
+use crate::data_explorer::utils::r_list;
+use crate::modules::ARK_ENVS;
+use crate::variables::variable::WorkspaceVariableDisplayType;
+
+/// The number of rows and the schema of each column of a table
+pub struct DataObjectShape {
+    pub columns: Vec<ColumnSchema>,
+    pub num_rows: i32,
+}
+
+/// A source of tabular data for the data explorer.
+///
+/// Backends answer the requests of the data explorer with R objects that are
+/// small enough to be formatted and profiled in memory, e.g. the visible rows
+/// of a column. Tables that don't live in R memory, like Arrow tables or
+/// database queries, are sorted and filtered by their backend so that only
+/// these rows are materialized.
+///
+/// Row indices, and the column indices of `subset()`, are 1-based like in R.
+/// Column schemas, sort keys and `column()` use the 0-based column indices of
+/// the frontend.
+///
+/// SAFETY: All methods require access to the R runtime.
+pub trait TableBackend: Send + Sync {
+    /// The number of rows and the column schemas of `table`
+    fn shape(&self, table: &RObject) -> anyhow::Result<DataObjectShape>;
+
+    /// A column of `table` as an R vector, limited to `rows` if supplied
+    fn column(
+        &self,
+        table: &RObject,
+        column_index: i64,
+        rows: &Option<Vec<i32>>,
+    ) -> anyhow::Result<RObject>;
+
+    /// The `rows` and `columns` of `table` as a data frame or a matrix. All
+    /// rows or columns are selected when `None`.
+    fn subset(
+        &self,
+        table: &RObject,
+        rows: Option<Vec<i64>>,
+        columns: Option<Vec<i64>>,
+    ) -> anyhow::Result<RObject>;
+
+    /// The `columns` of `table` without any rows, e.g. to derive the schema of
+    /// exported files from the types of the full columns. All columns are
+    /// selected when `None`.
+    fn prototype(&self, table: &RObject, columns: Option<Vec<i64>>) -> anyhow::Result<RObject> {
+        self.subset(table, Some(vec![]), columns)
+    }
+
+    /// The indices of all the rows of `table`, sorted by `keys`
+    fn sort_rows(&self, table: &RObject, keys: &[ColumnSortKey]) -> anyhow::Result<Vec<i32>>;
+
+    /// The indices of the rows of `table` that pass `filters`, in ascending
+    /// order, along with the error of each filter, if any
+    fn filter_rows(
+        &self,
+        table: &RObject,
+        filters: &[RowFilter],
+    ) -> anyhow::Result<(Vec<i32>, Vec<Option<String>>)>;
+
+    /// A profile of a column computed natively by the backend, e.g. with
+    /// aggregate queries, for the `rows` of `table` that pass `filters`. The
+    /// result has the shape of the output of the R function computing the
+    /// profile in memory, e.g. `profile_histogram()`.
+    ///
+    /// Returns `None` when the backend can't compute the profile, in which
+    /// case it's computed in memory from `column()`.
+    fn column_profile(
+        &self,
+        _table: &RObject,
+        _column_index: i64,
+        _rows: &Option<Vec<i32>>,
+        _filters: &[RowFilter],
+        _profile: &ColumnProfileSpec,
+    ) -> anyhow::Result<Option<RObject>> {
+        Ok(None)
+    }
+}
+
+/// The backend for viewing `table` in the data explorer.
+///
+/// SAFETY: Requires access to the R runtime.
+pub fn table_backend(table: &RObject) -> anyhow::Result<Arc<dyn TableBackend>> {
+    if let Some(info) = harp::table_info(table.sexp) {
+        return Ok(Arc::new(MemoryBackend::new(info.kind)));
+    }
+
+    if has_methods_backend(table.sexp) {
+        return Ok(Arc::new(MethodsBackend));
+    }
+
+    Err(anyhow!("Unsupported type for data viewer"))
+}
+
+/// Is there an R backend registered for the class of `x`? See
+/// `table_backend.R`.
+///
+/// SAFETY: Requires access to the R runtime.
+pub fn has_methods_backend(x: SEXP) -> bool {
+    if !r_is_object(x) {
+        return false;
+    }
+
+    let supported = RFunction::new("", ".ps.table_backend_supported")
+        .add(x)
+        .call_in(ARK_ENVS.positron_ns)
+        .and_then(bool::try_from);
+
+    match supported {
+        Ok(supported) => supported,
+        Err(err) => {
+            log::error!("Can't look up the table backend of an object: {err}");
+            false
+        },
+    }
+}
+
+/// Data frames and matrices, including data frames with extra classes like
+/// tibbles and data.tables. These are sorted and filtered in memory.
+pub struct MemoryBackend {
+    kind: TableKind,
+}
+
+impl MemoryBackend {
+    pub fn new(kind: TableKind) -> Self {
+        Self { kind }
+    }
+}
+
+impl TableBackend for MemoryBackend {
+    fn shape(&self, table: &RObject) -> anyhow::Result<DataObjectShape> {
+        let info =
+            harp::table_info(table.sexp).ok_or(anyhow!("Unsupported type for data viewer"))?;
+
+        Ok(DataObjectShape {
+            columns: column_schemas(table.sexp, &info),
+            num_rows: info.dims.num_rows,
+        })
+    }
+
+    fn column(
+        &self,
+        table: &RObject,
+        column_index: i64,
+        rows: &Option<Vec<i32>>,
+    ) -> anyhow::Result<RObject> {
+        let column = tbl_get_column(table.sexp, column_index as i32, self.kind)?;
+
+        Ok(match rows {
+            Some(rows) => RFunction::from("col_filter_indices")
+                .add(column)
+                .add(RObject::try_from(rows)?)
+                .call_in(ARK_ENVS.positron_ns)?,
+            None => column,
+        })
+    }
+
+    fn subset(
+        &self,
+        table: &RObject,
+        rows: Option<Vec<i64>>,
+        columns: Option<Vec<i64>>,
+    ) -> anyhow::Result<RObject> {
+        let mut call = RFunction::from(".ps.table_subset");
+        call.param("x", table.sexp);
+        if let Some(rows) = rows {
+            call.param("i", &rows);
+        }
+        if let Some(columns) = columns {
+            call.param("j", &columns);
+        }
+
+        Ok(call.call_in(ARK_ENVS.positron_ns)?)
+    }
+
+    fn sort_rows(&self, table: &RObject, keys: &[ColumnSortKey]) -> anyhow::Result<Vec<i32>> {
+        let mut order = RFunction::new("base", "order");
+
+        // Allocate a vector to hold the sort order for each column
+        let mut decreasing: Vec<bool> = Vec::new();
+
+        // For each sort key, add an argument to order
+        for key in keys {
+            // Get the column to sort by
+            order.add(tbl_get_column(
+                table.sexp,
+                key.column_index as i32,
+                self.kind,
+            )?);
+            decreasing.push(!key.ascending);
+        }
+        // Add the sort order per column
+        order.param("decreasing", RObject::try_from(&decreasing)?);
+        order.param("method", RObject::from("radix"));
+
+        // Invoke the order function and return the result
+        let result = order.call()?;
+        let indices: Vec<i32> = result.try_into()?;
+        Ok(indices)
+    }
+
+    fn filter_rows(
+        &self,
+        table: &RObject,
+        filters: &[RowFilter],
+    ) -> anyhow::Result<(Vec<i32>, Vec<Option<String>>)> {
+        // Pass the row filters to R and get the resulting row indices
+        let result = RFunction::new("", ".ps.filter_rows")
+            .param("table", table.sexp)
+            .param("row_filters", r_list(filters)?)
+            .call_in(ARK_ENVS.positron_ns)?;
+
+        filter_result(result)
+    }
+}
+
+/// Tables answering the requests of the data explorer with the R backend
+/// registered for their class, e.g. Arrow tables or dbplyr lazy tables. See
+/// `table_backend.R`.
+pub struct MethodsBackend;
+
+impl MethodsBackend {
+    fn call(table: &RObject, method: &str) -> RFunction {
+        let mut call = RFunction::new("", ".ps.table_backend_call");
+        call.add(table.sexp);
+        call.add(method);
+        call
+    }
+}
+
+impl TableBackend for MethodsBackend {
+    fn shape(&self, table: &RObject) -> anyhow::Result<DataObjectShape> {
+        let result = Self::call(table, "shape").call_in(ARK_ENVS.positron_ns)?;
+        let mut result = HashMap::<String, RObject>::try_from(result)?;
+
+        let mut field = |name: &str| {
+            result
+                .remove(name)
+                .ok_or_else(|| anyhow!("Missing field `{name}` in table shape"))
+        };
+        let num_rows = i32::try_from(field("num_rows")?)?;
+
+        // The schema is derived from a data frame with the columns of the
+        // table but no rows
+        let prototype = field("prototype")?;
+        let info = harp::table_info(prototype.sexp)
+            .ok_or(anyhow!("The prototype of a table must be a data frame"))?;
+
+        Ok(DataObjectShape {
+            columns: column_schemas(prototype.sexp, &info),
+            num_rows,
+        })
+    }
+
+    fn column(
+        &self,
+        table: &RObject,
+        column_index: i64,
+        rows: &Option<Vec<i32>>,
+    ) -> anyhow::Result<RObject> {
+        let rows = match rows {
+            Some(rows) => RObject::try_from(rows)?,
+            None => RObject::null(),
+        };
+
+        Ok(Self::call(table, "column")
+            .param("j", RObject::from(column_index as i32 + 1))
+            .param("i", rows)
+            .call_in(ARK_ENVS.positron_ns)?)
+    }
+
+    fn subset(
+        &self,
+        table: &RObject,
+        rows: Option<Vec<i64>>,
+        columns: Option<Vec<i64>>,
+    ) -> anyhow::Result<RObject> {
+        let rows = match rows {
+            Some(rows) => RObject::from(&rows),
+            None => RObject::null(),
+        };
+        let columns = match columns {
+            Some(columns) => RObject::from(&columns),
+            None => RObject::null(),
+        };
+
+        Ok(Self::call(table, "subset")
+            .param("i", rows)
+            .param("j", columns)
+            .call_in(ARK_ENVS.positron_ns)?)
+    }
+
+    fn prototype(&self, table: &RObject, columns: Option<Vec<i64>>) -> anyhow::Result<RObject> {
+        let columns = match columns {
+            Some(columns) => RObject::from(&columns),
+            None => RObject::null(),
+        };
+
+        // Rows fetched from some backends, e.g. databases, are typed by their
+        // values, so the prototype comes from the shape of the table
+        Ok(RFunction::new("", ".ps.table_backend_prototype")
+            .add(table.sexp)
+            .param("j", columns)
+            .call_in(ARK_ENVS.positron_ns)?)
+    }
+
+    fn sort_rows(&self, table: &RObject, keys: &[ColumnSortKey]) -> anyhow::Result<Vec<i32>> {
+        let result = Self::call(table, "sort_rows")
+            .param("keys", r_list(keys)?)
+            .call_in(ARK_ENVS.positron_ns)?;
+
+        Ok(Vec::<i32>::try_from(result)?)
+    }
+
+    fn filter_rows(
+        &self,
+        table: &RObject,
+        filters: &[RowFilter],
+    ) -> anyhow::Result<(Vec<i32>, Vec<Option<String>>)> {
+        let result = Self::call(table, "filter_rows")
+            .param("row_filters", r_list(filters)?)
+            .call_in(ARK_ENVS.positron_ns)?;
+
+        filter_result(result)
+    }
+
+    fn column_profile(
+        &self,
+        table: &RObject,
+        column_index: i64,
+        rows: &Option<Vec<i32>>,
+        filters: &[RowFilter],
+        profile: &ColumnProfileSpec,
+    ) -> anyhow::Result<Option<RObject>> {
+        let rows = match rows {
+            Some(rows) => RObject::try_from(rows)?,
+            None => RObject::null(),
+        };
+        let profile = RObject::try_from(serde_json::to_value(profile)?)?;
+
+        // Backends without a `profile()` method, and those that can't compute
+        // this profile, return `NULL`
+        let result = RFunction::new("", ".ps.table_backend_profile")
+            .add(table.sexp)
+            .param("j", RObject::from(column_index as i32 + 1))
+            .param("i", rows)
+            .param("row_filters", r_list(filters)?)
+            .param("profile", profile)
+            .call_in(ARK_ENVS.positron_ns)?;
+
+        if r_is_null(result.sexp) {
+            return Ok(None);
+        }
+        Ok(Some(result))
+    }
+}
+
+/// The schema of each column of a data frame or matrix. The columns of nested
+/// data frame columns are the `children` of their schema.
+fn column_schemas(object: SEXP, info: &TableInfo) -> Vec<ColumnSchema> {
+    let mut column_schemas = Vec::<ColumnSchema>::new();
+
+    for i in 0..(info.dims.num_cols as isize) {
+        let column_name = match info.col_names.get_unchecked(i) {
+            Some(name) => name,
+            None => format!("[, {}]", i + 1),
+        };
+
+        let col = match info.kind {
+            TableKind::Dataframe => unsafe { VECTOR_ELT(object, i) },
+            TableKind::Matrix => object,
+        };
+
+        let type_name = WorkspaceVariableDisplayType::from(col, false).display_type;
+        let type_display = display_type(col);
+
+        let children = match info.kind {
+            TableKind::Dataframe if r_is_data_frame(col) => {
+                harp::table_info(col).map(|info| column_schemas(col, &info))
+            },
+            _ => None,
+        };
+
+        column_schemas.push(ColumnSchema {
+            column_name,
+            column_index: i as i64,
+            type_name,
+            type_display,
+            description: None,
+            children,
+            precision: None,
+            scale: None,
+            timezone: None,
+            type_size: None,
+        });
+    }
+
+    column_schemas
+}
+
+/// Reads the result of `.ps.filter_rows()`
+fn filter_result(result: RObject) -> anyhow::Result<(Vec<i32>, Vec<Option<String>>)> {
+    let result = HashMap::<String, RObject>::try_from(result)?;
+
+    // Handle errors that occured in the filters
+    let row_indices = match result.get("indices") {
+        Some(indices) => Vec::<i32>::try_from(indices.clone())?,
+        None => bail!("Unexpected output from .ps.filter_rows. Expected 'indices' field."),
+    };
+
+    let errors = match result.get("errors") {
+        Some(errors) => Vec::<Option<String>>::try_from(errors.clone())?,
+        None => bail!("Unexpected output from .ps.filter_rows. Expected 'errors' field."),
+    };
+
+    Ok((row_indices, errors))
+}
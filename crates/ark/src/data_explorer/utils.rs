@@ -1,49 +1,56 @@
 use amalthea::comm::data_explorer_comm::ColumnDisplayType;
-use harp::exec::RFunction;
-use harp::exec::RFunctionExt;
 use harp::object::RObject;
 use harp::utils::r_inherits;
 use harp::utils::r_is_object;
 use harp::utils::r_is_s4;
 use harp::utils::r_typeof;
 use libr::*;
+use serde::Serialize;
 
-use crate::modules::ARK_ENVS;
+use crate::data_explorer::table_backend::TableBackend;
 
 pub fn tbl_subset_with_view_indices(
-    x: SEXP,
+    backend: &dyn TableBackend,
+    x: &RObject,
     view_indices: &Option<Vec<i32>>,
     i: Option<Vec<i64>>,
     j: Option<Vec<i64>>,
 ) -> anyhow::Result<RObject> {
-    let i = match view_indices {
-        Some(view_indices) => match i {
-            Some(i) => Some(i.iter().map(|i| view_indices[*i as usize] as i64).collect()),
-            None => None,
-        },
-        None => match i {
-            Some(i) => Some(i.iter().map(|i| i + 1).collect()),
-            None => None,
-        },
-    };
+    let i = i.map(|i| {
+        view_row_indices(view_indices, i)
+            .into_iter()
+            .map(i64::from)
+            .collect()
+    });
     let j = match j {
         Some(j) => Some(j.iter().map(|j| j + 1).collect()),
         None => None,
     };
-    tbl_subset(x, i, j)
+    backend.subset(x, i, j)
 }
 
-fn tbl_subset(x: SEXP, i: Option<Vec<i64>>, j: Option<Vec<i64>>) -> anyhow::Result<RObject> {
-    let mut call = RFunction::from(".ps.table_subset");
-    call.param("x", x);
-    if let Some(i) = i {
-        call.param("i", &i);
-    }
-    if let Some(j) = j {
-        call.param("j", &j);
+/// Maps positions in the view to the 1-based indices of the rows of the
+/// table they display.
+pub fn view_row_indices(view_indices: &Option<Vec<i32>>, i: Vec<i64>) -> Vec<i32> {
+    match view_indices {
+        Some(view_indices) => i.iter().map(|i| view_indices[*i as usize]).collect(),
+        None => i.iter().map(|i| *i as i32 + 1).collect(),
     }
+}
 
-    Ok(call.call_in(ARK_ENVS.positron_ns)?)
+/// Converts sort keys or row filters to an R list by marshaling each of them
+/// through the JSON layer.
+///
+/// This feels a little weird since they were *unmarshaled* from JSON earlier
+/// in the RPC stack, but it's the easiest way to create R objects from them
+/// without creating an unnecessary intermediate representation.
+pub fn r_list<T: Serialize>(items: &[T]) -> anyhow::Result<RObject> {
+    let mut list: Vec<RObject> = vec![];
+    for item in items {
+        let item = serde_json::to_value(item)?;
+        list.push(RObject::try_from(item)?);
+    }
+    Ok(RObject::try_from(list)?)
 }
 
 // This returns the type of an _element_ of the column. In R atomic
@@ -89,9 +96,14 @@ pub fn display_type(x: SEXP) -> ColumnDisplayType {
             return ColumnDisplayType::Datetime;
         }
 
-        // TODO: vctrs's list_of
+        // Nested data frames are described by the children of their schema
+        if r_inherits(x, "data.frame") {
+            return ColumnDisplayType::Struct;
+        }
+
+        // List columns, including vctrs's `list_of`
         if r_inherits(x, "list") {
-            return ColumnDisplayType::Unknown;
+            return ColumnDisplayType::Array;
         }
 
         // Catch-all, including for data frame
@@ -102,7 +114,7 @@ pub fn display_type(x: SEXP) -> ColumnDisplayType {
         LGLSXP => return ColumnDisplayType::Boolean,
         INTSXP | REALSXP | CPLXSXP => return ColumnDisplayType::Number,
         STRSXP => return ColumnDisplayType::String,
-        VECSXP => return ColumnDisplayType::Unknown,
+        VECSXP => return ColumnDisplayType::Array,
         _ => return ColumnDisplayType::Unknown,
     }
 }
@@ -99,6 +99,8 @@ use crate::errors;
 use crate::help::message::HelpEvent;
 use crate::help::r_help::RHelp;
 use crate::kernel::Kernel;
+use crate::lsp::diagnostics_calls::console_call_signatures;
+use crate::lsp::diagnostics_calls::ConsoleSignatures;
 use crate::lsp::events::EVENTS;
 use crate::lsp::main_loop::Event;
 use crate::lsp::main_loop::KernelNotification;
@@ -1583,9 +1585,16 @@ pub(crate) fn console_inputs() -> anyhow::Result<ConsoleInputs> {
         .call()?
         .try_into()?;
 
+    // Failing to inspect some function shouldn't prevent updating the scopes
+    let console_signatures = console_call_signatures().unwrap_or_else(|err| {
+        log::error!("Can't look up call signatures: {err:?}");
+        ConsoleSignatures::default()
+    });
+
     Ok(ConsoleInputs {
         console_scopes: scopes,
         installed_packages,
+        console_signatures,
     })
 }
 
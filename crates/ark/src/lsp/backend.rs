@@ -91,9 +91,25 @@ pub(crate) enum LspRequest {
     GotoImplementation(GotoImplementationParams),
     SelectionRange(SelectionRangeParams),
     References(ReferenceParams),
+    PrepareRename(TextDocumentPositionParams),
+    Rename(RenameParams),
+    CodeAction(CodeActionParams),
+    CodeLens(CodeLensParams),
+    PrepareCallHierarchy(CallHierarchyPrepareParams),
+    IncomingCalls(CallHierarchyIncomingCallsParams),
+    OutgoingCalls(CallHierarchyOutgoingCallsParams),
+    InlayHint(InlayHintParams),
+    FoldingRange(FoldingRangeParams),
+    DocumentHighlight(DocumentHighlightParams),
+    LinkedEditingRange(LinkedEditingRangeParams),
+    SemanticTokensFull(SemanticTokensParams),
+    SemanticTokensFullDelta(SemanticTokensDeltaParams),
+    SemanticTokensRange(SemanticTokensRangeParams),
     StatementRange(StatementRangeParams),
     HelpTopic(HelpTopicParams),
     OnTypeFormatting(DocumentOnTypeFormattingParams),
+    Formatting(DocumentFormattingParams),
+    RangeFormatting(DocumentRangeFormattingParams),
     VirtualDocument(VirtualDocumentParams),
     InputBoundaries(InputBoundariesParams),
 }
@@ -113,9 +129,25 @@ pub(crate) enum LspResponse {
     GotoImplementation(Option<GotoImplementationResponse>),
     SelectionRange(Option<Vec<SelectionRange>>),
     References(Option<Vec<Location>>),
+    PrepareRename(Option<PrepareRenameResponse>),
+    Rename(Option<WorkspaceEdit>),
+    CodeAction(Option<CodeActionResponse>),
+    CodeLens(Option<Vec<CodeLens>>),
+    PrepareCallHierarchy(Option<Vec<CallHierarchyItem>>),
+    IncomingCalls(Option<Vec<CallHierarchyIncomingCall>>),
+    OutgoingCalls(Option<Vec<CallHierarchyOutgoingCall>>),
+    InlayHint(Option<Vec<InlayHint>>),
+    FoldingRange(Option<Vec<FoldingRange>>),
+    DocumentHighlight(Option<Vec<DocumentHighlight>>),
+    LinkedEditingRange(Option<LinkedEditingRanges>),
+    SemanticTokensFull(Option<SemanticTokensResult>),
+    SemanticTokensFullDelta(Option<SemanticTokensFullDeltaResult>),
+    SemanticTokensRange(Option<SemanticTokensRangeResult>),
     StatementRange(Option<StatementRangeResponse>),
     HelpTopic(Option<HelpTopicResponse>),
     OnTypeFormatting(Option<Vec<TextEdit>>),
+    Formatting(Option<Vec<TextEdit>>),
+    RangeFormatting(Option<Vec<TextEdit>>),
     VirtualDocument(VirtualDocumentResponse),
     InputBoundaries(InputBoundariesResponse),
 }
@@ -295,6 +327,132 @@ impl LanguageServer for Backend {
         )
     }
 
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        cast_response!(
+            self.request(LspRequest::PrepareRename(params)).await,
+            LspResponse::PrepareRename
+        )
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        cast_response!(
+            self.request(LspRequest::Rename(params)).await,
+            LspResponse::Rename
+        )
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        cast_response!(
+            self.request(LspRequest::CodeAction(params)).await,
+            LspResponse::CodeAction
+        )
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        cast_response!(
+            self.request(LspRequest::PrepareCallHierarchy(params)).await,
+            LspResponse::PrepareCallHierarchy
+        )
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        cast_response!(
+            self.request(LspRequest::IncomingCalls(params)).await,
+            LspResponse::IncomingCalls
+        )
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        cast_response!(
+            self.request(LspRequest::OutgoingCalls(params)).await,
+            LspResponse::OutgoingCalls
+        )
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        cast_response!(
+            self.request(LspRequest::InlayHint(params)).await,
+            LspResponse::InlayHint
+        )
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        cast_response!(
+            self.request(LspRequest::CodeLens(params)).await,
+            LspResponse::CodeLens
+        )
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        cast_response!(
+            self.request(LspRequest::FoldingRange(params)).await,
+            LspResponse::FoldingRange
+        )
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        cast_response!(
+            self.request(LspRequest::DocumentHighlight(params)).await,
+            LspResponse::DocumentHighlight
+        )
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        cast_response!(
+            self.request(LspRequest::LinkedEditingRange(params)).await,
+            LspResponse::LinkedEditingRange
+        )
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        cast_response!(
+            self.request(LspRequest::SemanticTokensFull(params)).await,
+            LspResponse::SemanticTokensFull
+        )
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        cast_response!(
+            self.request(LspRequest::SemanticTokensFullDelta(params))
+                .await,
+            LspResponse::SemanticTokensFullDelta
+        )
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        cast_response!(
+            self.request(LspRequest::SemanticTokensRange(params)).await,
+            LspResponse::SemanticTokensRange
+        )
+    }
+
     async fn on_type_formatting(
         &self,
         params: DocumentOnTypeFormattingParams,
@@ -304,6 +462,23 @@ impl LanguageServer for Backend {
             LspResponse::OnTypeFormatting
         )
     }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        cast_response!(
+            self.request(LspRequest::Formatting(params)).await,
+            LspResponse::Formatting
+        )
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        cast_response!(
+            self.request(LspRequest::RangeFormatting(params)).await,
+            LspResponse::RangeFormatting
+        )
+    }
 }
 
 // Custom methods for the backend.
@@ -0,0 +1,522 @@
+//
+// call_hierarchy.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use ropey::Rope;
+use tower_lsp::lsp_types::CallHierarchyIncomingCall;
+use tower_lsp::lsp_types::CallHierarchyIncomingCallsParams;
+use tower_lsp::lsp_types::CallHierarchyItem;
+use tower_lsp::lsp_types::CallHierarchyOutgoingCall;
+use tower_lsp::lsp_types::CallHierarchyOutgoingCallsParams;
+use tower_lsp::lsp_types::CallHierarchyPrepareParams;
+use tower_lsp::lsp_types::SymbolKind;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::indexer;
+use crate::lsp::references::find_identifier_at_point;
+use crate::lsp::references::for_each_document_in_folder;
+use crate::lsp::references::node_is_symbol_reference;
+use crate::lsp::references::node_local_binding;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::references::resolve_symbol_scope;
+use crate::lsp::state::with_document;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::node::NodeExt;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// A function bound at top level of a document, e.g. `fn <- function() {}`.
+/// Only these functions take part in the call hierarchy, calls from nested
+/// functions are attributed to the enclosing top-level function.
+struct FunctionDefinition<'tree> {
+    name: String,
+
+    /// The assignment node
+    node: Node<'tree>,
+
+    /// The node of the name being assigned
+    name_node: Node<'tree>,
+}
+
+impl FunctionDefinition<'_> {
+    fn item(&self, uri: &Url, contents: &Rope) -> CallHierarchyItem {
+        CallHierarchyItem {
+            name: self.name.clone(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: None,
+            uri: uri.clone(),
+            range: convert_tree_sitter_range_to_lsp_range(contents, self.node.range()),
+            selection_range: convert_tree_sitter_range_to_lsp_range(
+                contents,
+                self.name_node.range(),
+            ),
+            data: None,
+        }
+    }
+}
+
+pub(crate) fn prepare_call_hierarchy(
+    params: CallHierarchyPrepareParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyItem>>> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
+
+    let document = state.get_document(&uri)?;
+    let contents = &document.contents;
+    let point = convert_position_to_point(contents, position);
+
+    let Some(node) = find_identifier_at_point(document.ast.root_node(), point) else {
+        return Ok(None);
+    };
+    if !node_is_symbol_reference(&node, contents) {
+        return Ok(None);
+    }
+    let Some(name) = node_symbol_name(&node, contents) else {
+        return Ok(None);
+    };
+
+    // Locally bound functions are not part of the hierarchy
+    if resolve_symbol_scope(&node, &name, contents).is_function_definition() {
+        return Ok(None);
+    }
+
+    Ok(find_definition_item(&name, &uri, document, state).map(|item| vec![item]))
+}
+
+/// Finds the calls to the function `item` in the open documents and in the
+/// workspace, grouped by calling function. Calls made outside of a function
+/// are attributed to the file they are in.
+pub(crate) fn incoming_calls(
+    params: CallHierarchyIncomingCallsParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+    let item = params.item;
+    let mut calls: Vec<CallHierarchyIncomingCall> = Vec::new();
+
+    for_each_workspace_document(state, |uri, document| {
+        let contents = &document.contents;
+        let root = document.ast.root_node();
+
+        // Calls in other files resolve to their own definition, if any
+        if *uri != item.uri &&
+            top_level_definitions(root, contents)
+                .iter()
+                .any(|definition| definition.name == item.name)
+        {
+            return;
+        }
+
+        let mut document_calls: Vec<CallHierarchyIncomingCall> = Vec::new();
+
+        for callee in function_calls(root, &item.name, contents) {
+            let from = match enclosing_definition(&callee, contents) {
+                Some(definition) => definition.item(uri, contents),
+                None => file_item(uri, root, contents),
+            };
+            let range = convert_tree_sitter_range_to_lsp_range(contents, callee.range());
+
+            match document_calls.iter_mut().find(|call| call.from == from) {
+                Some(call) => call.from_ranges.push(range),
+                None => document_calls.push(CallHierarchyIncomingCall {
+                    from,
+                    from_ranges: vec![range],
+                }),
+            }
+        }
+
+        calls.append(&mut document_calls);
+    });
+
+    if calls.is_empty() {
+        return Ok(None);
+    }
+
+    calls.sort_by(|lhs, rhs| {
+        (&lhs.from.uri, lhs.from.range.start).cmp(&(&rhs.from.uri, rhs.from.range.start))
+    });
+
+    Ok(Some(calls))
+}
+
+/// Finds the functions of the workspace called by the function `item`, in
+/// order of first call. Calls to functions that aren't defined in the
+/// workspace, e.g. from packages, are not included.
+pub(crate) fn outgoing_calls(
+    params: CallHierarchyOutgoingCallsParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+    let item = params.item;
+
+    let calls = with_uri_document(&item.uri, state, |document| {
+        let contents = &document.contents;
+        let root = document.ast.root_node();
+
+        let definitions = top_level_definitions(root, contents);
+        let Some(definition) = definitions.iter().find(|definition| {
+            definition.name == item.name &&
+                convert_tree_sitter_range_to_lsp_range(contents, definition.name_node.range()) ==
+                    item.selection_range
+        }) else {
+            return Ok(Vec::new());
+        };
+
+        let mut calls: Vec<CallHierarchyOutgoingCall> = Vec::new();
+        let mut unresolved: HashSet<String> = HashSet::new();
+
+        let mut cursor = definition.node.walk();
+        cursor.recurse(|node| {
+            let Some((name, callee)) = call_callee(&node, contents) else {
+                return true;
+            };
+            if unresolved.contains(&name) {
+                return true;
+            }
+
+            let range = convert_tree_sitter_range_to_lsp_range(contents, callee.range());
+
+            if let Some(call) = calls.iter_mut().find(|call| call.to.name == name) {
+                call.from_ranges.push(range);
+                return true;
+            }
+
+            match find_definition_item(&name, &item.uri, document, state) {
+                Some(to) => calls.push(CallHierarchyOutgoingCall {
+                    to,
+                    from_ranges: vec![range],
+                }),
+                None => {
+                    unresolved.insert(name);
+                },
+            }
+
+            true
+        });
+
+        Ok(calls)
+    })?;
+
+    if calls.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(calls))
+}
+
+/// Finds the definition of the function `name`, first at top level of
+/// `document`, then in the workspace index.
+fn find_definition_item(
+    name: &str,
+    uri: &Url,
+    document: &Document,
+    state: &WorldState,
+) -> Option<CallHierarchyItem> {
+    let contents = &document.contents;
+    let definitions = top_level_definitions(document.ast.root_node(), contents);
+
+    if let Some(definition) = definitions
+        .iter()
+        .find(|definition| definition.name == name)
+    {
+        return Some(definition.item(uri, contents));
+    }
+
+    let (path, _entry) = indexer::find(name)?;
+    let path = PathBuf::from(path);
+    let uri = Url::from_file_path(&path).ok()?;
+
+    let item = with_document(&path, state, |document| {
+        let contents = &document.contents;
+        top_level_definitions(document.ast.root_node(), contents)
+            .iter()
+            .find(|definition| definition.name == name)
+            .map(|definition| definition.item(&uri, contents))
+            .ok_or(anyhow!(
+                "Can't find definition of `{name}` in {}",
+                path.display()
+            ))
+    });
+
+    item.ok()
+}
+
+fn top_level_definitions<'tree>(
+    root: Node<'tree>,
+    contents: &Rope,
+) -> Vec<FunctionDefinition<'tree>> {
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter_map(|node| function_definition(node, contents))
+        .collect()
+}
+
+fn function_definition<'tree>(
+    node: Node<'tree>,
+    contents: &Rope,
+) -> Option<FunctionDefinition<'tree>> {
+    let name_node = node_local_binding(&node)?;
+
+    let value = match node.node_type() {
+        NodeType::BinaryOperator(BinaryOperatorType::RightAssignment) => {
+            node.child_by_field_name("lhs")
+        },
+        _ => node.child_by_field_name("rhs"),
+    }?;
+    if !value.is_function_definition() {
+        return None;
+    }
+
+    Some(FunctionDefinition {
+        name: node_symbol_name(&name_node, contents)?,
+        node,
+        name_node,
+    })
+}
+
+/// The top-level function definition containing `node`, if any
+fn enclosing_definition<'tree>(
+    node: &Node<'tree>,
+    contents: &Rope,
+) -> Option<FunctionDefinition<'tree>> {
+    let statement = node
+        .ancestors()
+        .find(|node| node.parent().is_some_and(|parent| parent.is_program()))?;
+    function_definition(statement, contents)
+}
+
+/// If `node` is a call to a function bound at top level, returns the name of
+/// the function and the callee node
+fn call_callee<'tree>(node: &Node<'tree>, contents: &Rope) -> Option<(String, Node<'tree>)> {
+    if !node.is_call() {
+        return None;
+    }
+
+    let callee = node.child_by_field_name("function")?;
+    if !callee.is_identifier() {
+        return None;
+    }
+    let name = node_symbol_name(&callee, contents)?;
+
+    if resolve_symbol_scope(&callee, &name, contents).is_function_definition() {
+        return None;
+    }
+
+    Some((name, callee))
+}
+
+/// Callee nodes of the calls to the top-level function `name`
+fn function_calls<'tree>(root: Node<'tree>, name: &str, contents: &Rope) -> Vec<Node<'tree>> {
+    let mut callees = Vec::new();
+
+    let mut cursor = root.walk();
+    cursor.recurse(|node| {
+        if let Some((callee_name, callee)) = call_callee(&node, contents) {
+            if callee_name == name {
+                callees.push(callee);
+            }
+        }
+        true
+    });
+
+    callees
+}
+
+fn file_item(uri: &Url, root: Node, contents: &Rope) -> CallHierarchyItem {
+    let name = uri
+        .path_segments()
+        .and_then(|segments| segments.last())
+        .unwrap_or(uri.as_str())
+        .to_string();
+    let range = convert_tree_sitter_range_to_lsp_range(contents, root.range());
+
+    CallHierarchyItem {
+        name,
+        kind: SymbolKind::FILE,
+        tags: None,
+        detail: None,
+        uri: uri.clone(),
+        range,
+        selection_range: range,
+        data: None,
+    }
+}
+
+/// Calls `callback` with the document at `uri`, preferring the copy managed
+/// by the client
+fn with_uri_document<T>(
+    uri: &Url,
+    state: &WorldState,
+    mut callback: impl FnMut(&Document) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    if let Ok(document) = state.get_document(uri) {
+        return callback(document);
+    }
+
+    let path = uri
+        .to_file_path()
+        .map_err(|_| anyhow!("Can't convert {uri} to a file path"))?;
+    with_document(&path, state, callback)
+}
+
+/// Invokes `callback` on the open documents, then on the other R documents of
+/// the workspace folders
+fn for_each_workspace_document(state: &WorldState, mut callback: impl FnMut(&Url, &Document)) {
+    let mut visited: HashSet<Url> = HashSet::new();
+
+    for (uri, document) in state.documents.iter() {
+        visited.insert(uri.clone());
+        callback(uri, document);
+    }
+
+    for folder in state.workspace.folders.iter() {
+        let Ok(path) = folder.to_file_path() else {
+            continue;
+        };
+
+        for_each_document_in_folder(&path, state, |path, document| {
+            let Ok(uri) = Url::from_file_path(path) else {
+                return;
+            };
+            if visited.insert(uri.clone()) {
+                callback(&uri, document);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::CallHierarchyIncomingCallsParams;
+    use tower_lsp::lsp_types::CallHierarchyItem;
+    use tower_lsp::lsp_types::CallHierarchyOutgoingCallsParams;
+    use tower_lsp::lsp_types::CallHierarchyPrepareParams;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::SymbolKind;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::TextDocumentPositionParams;
+    use tower_lsp::lsp_types::Url;
+    use tree_sitter::Point;
+
+    use crate::lsp::call_hierarchy::incoming_calls;
+    use crate::lsp::call_hierarchy::outgoing_calls;
+    use crate::lsp::call_hierarchy::prepare_call_hierarchy;
+    use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
+    use crate::test::point_from_cursor;
+
+    fn state_with_documents(documents: &[(&str, &str)]) -> WorldState {
+        let mut state = WorldState::default();
+        for (name, text) in documents.iter() {
+            let uri = Url::parse(&format!("file:///{name}")).unwrap();
+            state.documents.insert(uri, Document::new(text, None));
+        }
+        state
+    }
+
+    fn prepare(state: &WorldState, name: &str, point: Point) -> Option<CallHierarchyItem> {
+        let params = CallHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier {
+                    uri: Url::parse(&format!("file:///{name}")).unwrap(),
+                },
+                position: Position::new(point.row as u32, point.column as u32),
+            },
+            work_done_progress_params: Default::default(),
+        };
+        let mut items = prepare_call_hierarchy(params, state).unwrap()?;
+        assert_eq!(items.len(), 1);
+        items.pop()
+    }
+
+    #[test]
+    fn test_call_hierarchy_prepare() {
+        let (text, point) = point_from_cursor("foo <- function() 1\nfo@o()\n");
+        let state = state_with_documents(&[("a.R", &text)]);
+
+        let item = prepare(&state, "a.R", point).unwrap();
+        assert_eq!(item.name, "foo");
+        assert_eq!(item.kind, SymbolKind::FUNCTION);
+        assert_eq!(item.selection_range.start, Position::new(0, 0));
+        assert_eq!(item.range.end, Position::new(0, 19));
+
+        // Local functions are not part of the hierarchy
+        let (text, point) =
+            point_from_cursor("f <- function() {\n  g <- function() 1\n  @g()\n}\n");
+        let state = state_with_documents(&[("a.R", &text)]);
+        assert!(prepare(&state, "a.R", point).is_none());
+    }
+
+    #[test]
+    fn test_call_hierarchy_incoming_calls() {
+        let (a, point) =
+            point_from_cursor("@foo <- function() 1\nbar <- function() foo() + foo()\n");
+        let b = "baz <- function() {\n  helper <- function() foo()\n  helper()\n}\nfoo()\n";
+        // Calls in a file that defines its own `foo` don't count
+        let c = "foo <- function() 2\nqux <- function() foo()\n";
+
+        let state = state_with_documents(&[("a.R", &a), ("b.R", b), ("c.R", c)]);
+        let item = prepare(&state, "a.R", point).unwrap();
+
+        let calls = incoming_calls(
+            CallHierarchyIncomingCallsParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+            &state,
+        )
+        .unwrap()
+        .unwrap();
+
+        let calls: Vec<(String, SymbolKind, usize)> = calls
+            .into_iter()
+            .map(|call| (call.from.name, call.from.kind, call.from_ranges.len()))
+            .collect();
+
+        assert_eq!(calls, vec![
+            (String::from("bar"), SymbolKind::FUNCTION, 2),
+            (String::from("baz"), SymbolKind::FUNCTION, 1),
+            (String::from("b.R"), SymbolKind::FILE, 1),
+        ]);
+    }
+
+    #[test]
+    fn test_call_hierarchy_outgoing_calls() {
+        let (text, point) = point_from_cursor(
+            "helper <- function(x) x\n@main <- function(f) {\n  helper(1)\n  paste(helper(2))\n  f()\n}\n",
+        );
+        let state = state_with_documents(&[("a.R", &text)]);
+        let item = prepare(&state, "a.R", point).unwrap();
+
+        let calls = outgoing_calls(
+            CallHierarchyOutgoingCallsParams {
+                item,
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+            &state,
+        )
+        .unwrap()
+        .unwrap();
+
+        // `paste()` isn't defined in the workspace and `f` is an argument
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].to.name, "helper");
+        assert_eq!(calls[0].to.selection_range.start, Position::new(0, 0));
+        assert_eq!(calls[0].from_ranges.len(), 2);
+    }
+}
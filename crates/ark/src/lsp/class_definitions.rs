@@ -0,0 +1,1041 @@
+//
+// class_definitions.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use ropey::Rope;
+use serde::Deserialize;
+use serde::Serialize;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::indexer;
+use crate::lsp::indexer::IndexEntry;
+use crate::lsp::indexer::IndexEntryData;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::traits::node::NodeExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::ExtractOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Maximum depth of the inheritance chains we follow when collecting members
+const MAX_INHERITANCE_DEPTH: usize = 16;
+
+/// The object systems whose definitions we understand without running code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClassSystem {
+    R6,
+    RefClass,
+    S4,
+    S7,
+}
+
+impl ClassSystem {
+    /// Operator used to access the members of an instance
+    pub fn accessor(&self) -> &'static str {
+        match self {
+            ClassSystem::R6 | ClassSystem::RefClass => "$",
+            ClassSystem::S4 | ClassSystem::S7 => "@",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ClassSystem::R6 => "R6",
+            ClassSystem::RefClass => "Reference",
+            ClassSystem::S4 => "S4",
+            ClassSystem::S7 => "S7",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemberKind {
+    Method,
+    Field,
+}
+
+/// A method or field of a class: R6 and reference class members, S4 slots,
+/// and S7 properties
+#[derive(Clone, Debug)]
+pub(crate) struct ClassMember<'tree> {
+    pub name: String,
+    pub kind: MemberKind,
+    /// Whether the member is only accessible through `private$` (R6)
+    pub private: bool,
+    /// Parameters of methods
+    pub arguments: Vec<String>,
+    pub name_node: Node<'tree>,
+    pub node: Node<'tree>,
+}
+
+/// A call to `R6Class()`, `setRefClass()`, `setClass()`, or `new_class()`
+#[derive(Clone, Debug)]
+pub(crate) struct ClassDefinition<'tree> {
+    /// The class name, e.g. `"Foo"` in `setClass("Foo")`. Falls back to the
+    /// binding for classes defined without one.
+    pub name: String,
+    /// The symbol the generator is assigned to, if any
+    pub binding: Option<String>,
+    pub system: ClassSystem,
+    pub parent: Option<String>,
+    pub members: Vec<ClassMember<'tree>>,
+    /// The binding if any, otherwise the class name string
+    pub name_node: Node<'tree>,
+    pub node: Node<'tree>,
+}
+
+/// A call to `setGeneric()` or `new_generic()`
+#[derive(Clone, Debug)]
+pub(crate) struct GenericDefinition<'tree> {
+    pub name: String,
+    pub arguments: Vec<String>,
+    pub name_node: Node<'tree>,
+    pub node: Node<'tree>,
+}
+
+/// A call to `setMethod()` or an S7 `method(generic, class) <- function()`
+/// assignment
+#[derive(Clone, Debug)]
+pub(crate) struct MethodDefinition<'tree> {
+    pub generic: String,
+    pub class: String,
+    pub arguments: Vec<String>,
+    pub name_node: Node<'tree>,
+    pub node: Node<'tree>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Definition<'tree> {
+    Class(ClassDefinition<'tree>),
+    Generic(GenericDefinition<'tree>),
+    Method(MethodDefinition<'tree>),
+}
+
+struct Argument<'tree> {
+    name: Option<String>,
+    name_node: Option<Node<'tree>>,
+    value: Node<'tree>,
+    node: Node<'tree>,
+}
+
+/// Recognises a class, generic, or method definition in `node`, either a
+/// bare call or the right-hand side of an assignment.
+pub(crate) fn definition<'tree>(node: Node<'tree>, contents: &Rope) -> Option<Definition<'tree>> {
+    if matches!(
+        node.node_type(),
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+    ) {
+        let lhs = node.child_by_field_name("lhs")?;
+        let rhs = node.child_by_field_name("rhs")?;
+
+        if lhs.is_call() {
+            return s7_method(node, lhs, rhs, contents).map(Definition::Method);
+        }
+
+        if !lhs.is_identifier_or_string() || !rhs.is_call() {
+            return None;
+        }
+
+        return call_definition(node, rhs, Some(lhs), contents);
+    }
+
+    if node.is_call() {
+        return call_definition(node, node, None, contents);
+    }
+
+    None
+}
+
+fn call_definition<'tree>(
+    node: Node<'tree>,
+    call: Node<'tree>,
+    binding: Option<Node<'tree>>,
+    contents: &Rope,
+) -> Option<Definition<'tree>> {
+    let function = call.child_by_field_name("function")?;
+    let arguments = call_arguments(&call, contents);
+
+    match function_name(&function, contents)?.as_str() {
+        "R6Class" => r6_class(node, &arguments, binding, contents).map(Definition::Class),
+        "setRefClass" => ref_class(node, &arguments, binding, contents).map(Definition::Class),
+        "setClass" => s4_class(node, &arguments, binding, contents).map(Definition::Class),
+        "new_class" => s7_class(node, &arguments, binding, contents).map(Definition::Class),
+        "setGeneric" => s4_generic(node, &arguments, contents).map(Definition::Generic),
+        "new_generic" => s7_generic(node, &arguments, binding, contents).map(Definition::Generic),
+        "setMethod" => s4_method(node, &arguments, contents).map(Definition::Method),
+        _ => None,
+    }
+}
+
+fn r6_class<'tree>(
+    node: Node<'tree>,
+    arguments: &[Argument<'tree>],
+    binding: Option<Node<'tree>>,
+    contents: &Rope,
+) -> Option<ClassDefinition<'tree>> {
+    let name = argument_value(arguments, "classname", 0);
+    let mut class = class_definition(node, ClassSystem::R6, name, binding, contents)?;
+
+    if let Some(public) = argument_value(arguments, "public", 1) {
+        class.members.extend(members(public, false, contents));
+    }
+
+    if let Some(private) = argument_value(arguments, "private", 2) {
+        let private = members(private, false, contents);
+        class
+            .members
+            .extend(private.into_iter().map(|member| ClassMember {
+                private: true,
+                ..member
+            }));
+    }
+
+    // Active bindings are accessed like fields
+    if let Some(active) = argument_value(arguments, "active", 3) {
+        let active = members(active, false, contents);
+        class
+            .members
+            .extend(active.into_iter().map(|member| ClassMember {
+                kind: MemberKind::Field,
+                arguments: Vec::new(),
+                ..member
+            }));
+    }
+
+    class.parent =
+        argument_value(arguments, "inherit", 4).and_then(|parent| first_name(&parent, contents));
+
+    Some(class)
+}
+
+fn ref_class<'tree>(
+    node: Node<'tree>,
+    arguments: &[Argument<'tree>],
+    binding: Option<Node<'tree>>,
+    contents: &Rope,
+) -> Option<ClassDefinition<'tree>> {
+    let name = argument_value(arguments, "Class", 0);
+    let mut class = class_definition(node, ClassSystem::RefClass, name, binding, contents)?;
+
+    // Fields are either a named list or vector of types, or a character
+    // vector of names
+    if let Some(fields) = argument_value(arguments, "fields", 1) {
+        let fields = members(fields, true, contents);
+        class
+            .members
+            .extend(fields.into_iter().map(|member| ClassMember {
+                kind: MemberKind::Field,
+                arguments: Vec::new(),
+                ..member
+            }));
+    }
+
+    if let Some(methods) = argument_value(arguments, "methods", 3) {
+        class.members.extend(members(methods, false, contents));
+    }
+
+    class.parent =
+        argument_value(arguments, "contains", 2).and_then(|parent| first_name(&parent, contents));
+
+    Some(class)
+}
+
+fn s4_class<'tree>(
+    node: Node<'tree>,
+    arguments: &[Argument<'tree>],
+    binding: Option<Node<'tree>>,
+    contents: &Rope,
+) -> Option<ClassDefinition<'tree>> {
+    let name = argument_value(arguments, "Class", 0);
+    let mut class = class_definition(node, ClassSystem::S4, name, binding, contents)?;
+
+    // The unnamed elements of `representation()` are superclasses, whereas
+    // those of `slots` are slot names
+    let slots = if let Some(slots) = named_argument_value(arguments, "slots") {
+        members(slots, true, contents)
+    } else if let Some(representation) = argument_value(arguments, "representation", 1) {
+        members(representation, false, contents)
+    } else {
+        Vec::new()
+    };
+
+    class
+        .members
+        .extend(slots.into_iter().map(|member| ClassMember {
+            kind: MemberKind::Field,
+            arguments: Vec::new(),
+            ..member
+        }));
+
+    class.parent =
+        argument_value(arguments, "contains", 3).and_then(|parent| first_name(&parent, contents));
+
+    Some(class)
+}
+
+fn s7_class<'tree>(
+    node: Node<'tree>,
+    arguments: &[Argument<'tree>],
+    binding: Option<Node<'tree>>,
+    contents: &Rope,
+) -> Option<ClassDefinition<'tree>> {
+    let name = argument_value(arguments, "name", 0);
+    let mut class = class_definition(node, ClassSystem::S7, name, binding, contents)?;
+
+    if let Some(properties) = argument_value(arguments, "properties", 3) {
+        let properties = members(properties, true, contents);
+        class
+            .members
+            .extend(properties.into_iter().map(|member| ClassMember {
+                kind: MemberKind::Field,
+                arguments: Vec::new(),
+                ..member
+            }));
+    }
+
+    class.parent =
+        argument_value(arguments, "parent", 1).and_then(|parent| first_name(&parent, contents));
+
+    Some(class)
+}
+
+fn s4_generic<'tree>(
+    node: Node<'tree>,
+    arguments: &[Argument<'tree>],
+    contents: &Rope,
+) -> Option<GenericDefinition<'tree>> {
+    let name_node = argument_value(arguments, "name", 0)?;
+    if !name_node.is_string() {
+        return None;
+    }
+    let name = node_symbol_name(&name_node, contents)?;
+
+    let arguments = argument_value(arguments, "def", 1)
+        .map(|def| function_parameters(&def, contents))
+        .unwrap_or_default();
+
+    Some(GenericDefinition {
+        name,
+        arguments,
+        name_node,
+        node,
+    })
+}
+
+fn s7_generic<'tree>(
+    node: Node<'tree>,
+    arguments: &[Argument<'tree>],
+    binding: Option<Node<'tree>>,
+    contents: &Rope,
+) -> Option<GenericDefinition<'tree>> {
+    let name_node = match binding {
+        Some(binding) => binding,
+        None => argument_value(arguments, "name", 0).filter(|name| name.is_string())?,
+    };
+    let name = node_symbol_name(&name_node, contents)?;
+
+    // Prefer the parameters of the generic function, which include the
+    // dispatch arguments
+    let arguments = match argument_value(arguments, "fun", 2) {
+        Some(fun) => function_parameters(&fun, contents),
+        None => argument_value(arguments, "dispatch_args", 1)
+            .map(|dispatch_args| strings(&dispatch_args, contents))
+            .unwrap_or_default(),
+    };
+
+    Some(GenericDefinition {
+        name,
+        arguments,
+        name_node,
+        node,
+    })
+}
+
+fn s4_method<'tree>(
+    node: Node<'tree>,
+    arguments: &[Argument<'tree>],
+    contents: &Rope,
+) -> Option<MethodDefinition<'tree>> {
+    let name_node = argument_value(arguments, "f", 0)?;
+    let generic = node_symbol_name(&name_node, contents)?;
+
+    let signature = argument_value(arguments, "signature", 1)?;
+    let class = first_name(&signature, contents)?;
+
+    let arguments = argument_value(arguments, "definition", 2)
+        .map(|definition| function_parameters(&definition, contents))
+        .unwrap_or_default();
+
+    Some(MethodDefinition {
+        generic,
+        class,
+        arguments,
+        name_node,
+        node,
+    })
+}
+
+fn s7_method<'tree>(
+    node: Node<'tree>,
+    lhs: Node<'tree>,
+    rhs: Node<'tree>,
+    contents: &Rope,
+) -> Option<MethodDefinition<'tree>> {
+    let function = lhs.child_by_field_name("function")?;
+    if function_name(&function, contents)? != "method" {
+        return None;
+    }
+
+    if !rhs.is_function_definition() {
+        return None;
+    }
+
+    let arguments = call_arguments(&lhs, contents);
+
+    let name_node = argument_value(&arguments, "generic", 0)?;
+    let generic = node_symbol_name(&name_node, contents)?;
+
+    let signature = argument_value(&arguments, "signature", 1)?;
+    let class = first_name(&signature, contents)?;
+
+    Some(MethodDefinition {
+        generic,
+        class,
+        arguments: function_parameters(&rhs, contents),
+        name_node,
+        node,
+    })
+}
+
+fn class_definition<'tree>(
+    node: Node<'tree>,
+    system: ClassSystem,
+    name: Option<Node<'tree>>,
+    binding: Option<Node<'tree>>,
+    contents: &Rope,
+) -> Option<ClassDefinition<'tree>> {
+    let name = name.filter(|name| name.is_string());
+    let binding_name = binding.and_then(|binding| node_symbol_name(&binding, contents));
+
+    let (class_name, name_node) = match (name, binding) {
+        (Some(name), binding) => (node_symbol_name(&name, contents)?, binding.unwrap_or(name)),
+        (None, Some(binding)) => (binding_name.clone()?, binding),
+        (None, None) => return None,
+    };
+
+    Some(ClassDefinition {
+        name: class_name,
+        binding: binding_name,
+        system,
+        parent: None,
+        members: Vec::new(),
+        name_node,
+        node,
+    })
+}
+
+/// Collects the members of a `list()` (or `c()`, `representation()`, ...)
+/// call. Named elements are members whose kind depends on whether the value
+/// is a function. Unnamed strings are field names if `unnamed_strings` is
+/// set, as in `fields = c("x", "y")`.
+fn members<'tree>(
+    node: Node<'tree>,
+    unnamed_strings: bool,
+    contents: &Rope,
+) -> Vec<ClassMember<'tree>> {
+    if node.is_string() {
+        if !unnamed_strings {
+            return Vec::new();
+        }
+        let Some(name) = node_symbol_name(&node, contents) else {
+            return Vec::new();
+        };
+        return vec![ClassMember {
+            name,
+            kind: MemberKind::Field,
+            private: false,
+            arguments: Vec::new(),
+            name_node: node,
+            node,
+        }];
+    }
+
+    if !node.is_call() {
+        return Vec::new();
+    }
+
+    let mut members = Vec::new();
+
+    for argument in call_arguments(&node, contents) {
+        match (argument.name, argument.name_node) {
+            (Some(name), Some(name_node)) => {
+                let (kind, arguments) = if argument.value.is_function_definition() {
+                    let arguments = function_parameters(&argument.value, contents);
+                    (MemberKind::Method, arguments)
+                } else {
+                    (MemberKind::Field, Vec::new())
+                };
+                members.push(ClassMember {
+                    name,
+                    kind,
+                    private: false,
+                    arguments,
+                    name_node,
+                    node: argument.node,
+                });
+            },
+            _ => {
+                if !unnamed_strings || !argument.value.is_string() {
+                    continue;
+                }
+                let Some(name) = node_symbol_name(&argument.value, contents) else {
+                    continue;
+                };
+                members.push(ClassMember {
+                    name,
+                    kind: MemberKind::Field,
+                    private: false,
+                    arguments: Vec::new(),
+                    name_node: argument.value,
+                    node: argument.node,
+                });
+            },
+        }
+    }
+
+    members
+}
+
+/// Name of the called function, without any namespace prefix
+fn function_name(function: &Node, contents: &Rope) -> Option<String> {
+    if function.is_namespace_operator() {
+        let name = function.child_by_field_name("rhs")?;
+        return node_symbol_name(&name, contents);
+    }
+
+    if function.is_identifier() {
+        return node_symbol_name(function, contents);
+    }
+
+    None
+}
+
+fn call_arguments<'tree>(call: &Node<'tree>, contents: &Rope) -> Vec<Argument<'tree>> {
+    let Some(arguments) = call.child_by_field_name("arguments") else {
+        return Vec::new();
+    };
+
+    let mut cursor = arguments.walk();
+    arguments
+        .children_by_field_name("argument", &mut cursor)
+        .filter_map(|node| {
+            let value = node.child_by_field_name("value")?;
+            let name_node = node.child_by_field_name("name");
+            let name = name_node.and_then(|name| node_symbol_name(&name, contents));
+            Some(Argument {
+                name,
+                name_node,
+                value,
+                node,
+            })
+        })
+        .collect()
+}
+
+/// Finds an argument by name, or else by its position among the unnamed
+/// arguments, approximating how R matches arguments to formals
+fn argument_value<'tree>(
+    arguments: &[Argument<'tree>],
+    name: &str,
+    position: usize,
+) -> Option<Node<'tree>> {
+    if let Some(value) = named_argument_value(arguments, name) {
+        return Some(value);
+    }
+
+    arguments
+        .iter()
+        .filter(|argument| argument.name.is_none())
+        .nth(position)
+        .map(|argument| argument.value)
+}
+
+fn named_argument_value<'tree>(arguments: &[Argument<'tree>], name: &str) -> Option<Node<'tree>> {
+    arguments
+        .iter()
+        .find(|argument| argument.name.as_deref() == Some(name))
+        .map(|argument| argument.value)
+}
+
+/// Name of a class given as a symbol, a string, or the first element of a
+/// call such as `c("A", "B")` or `signature(x = "A")`
+fn first_name(node: &Node, contents: &Rope) -> Option<String> {
+    if node.is_identifier_or_string() {
+        return node_symbol_name(node, contents);
+    }
+
+    if node.is_call() {
+        let arguments = call_arguments(node, contents);
+        let argument = arguments.first()?;
+        return first_name(&argument.value, contents);
+    }
+
+    None
+}
+
+/// The strings of a string or of a call such as `c("x", "y")`
+fn strings(node: &Node, contents: &Rope) -> Vec<String> {
+    if node.is_string() {
+        return node_symbol_name(node, contents).into_iter().collect();
+    }
+
+    call_arguments(node, contents)
+        .iter()
+        .filter(|argument| argument.value.is_string())
+        .filter_map(|argument| node_symbol_name(&argument.value, contents))
+        .collect()
+}
+
+pub(crate) fn function_parameters(function: &Node, contents: &Rope) -> Vec<String> {
+    if !function.is_function_definition() {
+        return Vec::new();
+    }
+    let Some(parameters) = function.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+
+    let mut cursor = parameters.walk();
+    parameters
+        .children_by_field_name("parameter", &mut cursor)
+        .filter_map(|parameter| parameter.child_by_field_name("name"))
+        .filter_map(|name| contents.node_slice(&name).ok())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Finds the class definition that encloses `node`, e.g. the `R6Class()`
+/// call in which `self` is used
+pub(crate) fn enclosing_class<'tree>(
+    node: &Node<'tree>,
+    contents: &Rope,
+) -> Option<ClassDefinition<'tree>> {
+    let mut parent = node.parent();
+
+    while let Some(node) = parent {
+        if let Some(Definition::Class(class)) = definition(node, contents) {
+            return Some(class);
+        }
+        parent = node.parent();
+    }
+
+    None
+}
+
+/// Infers the class of the object bound to `name` from the last assignment
+/// before `point` that creates an instance: `new("Class")`, `Class$new()`, or
+/// a call to the generator of a known class.
+pub(crate) fn instance_class(
+    root: Node,
+    name: &str,
+    point: Point,
+    contents: &Rope,
+) -> Option<String> {
+    let mut instances = Vec::new();
+    let mut stack = vec![root];
+
+    while let Some(node) = stack.pop() {
+        if node.start_position() >= point {
+            continue;
+        }
+
+        if let Some(instance) = assigned_instance_class(&node, name, contents) {
+            instances.push((node.start_position(), instance));
+        }
+
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+
+    // Keep the assignment closest to the point
+    instances
+        .into_iter()
+        .max_by_key(|(start, _)| *start)
+        .map(|(_, class)| class)
+}
+
+fn assigned_instance_class(node: &Node, name: &str, contents: &Rope) -> Option<String> {
+    if !matches!(
+        node.node_type(),
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+    ) {
+        return None;
+    }
+
+    let lhs = node.child_by_field_name("lhs")?;
+    if !lhs.is_identifier() || node_symbol_name(&lhs, contents)? != name {
+        return None;
+    }
+
+    let rhs = node.child_by_field_name("rhs")?;
+    if !rhs.is_call() {
+        return None;
+    }
+    let function = rhs.child_by_field_name("function")?;
+
+    // `Class$new()`
+    if function.node_type() == NodeType::ExtractOperator(ExtractOperatorType::Dollar) {
+        let generator = function.child_by_field_name("lhs")?;
+        let method = function.child_by_field_name("rhs")?;
+        if node_symbol_name(&method, contents)? != "new" {
+            return None;
+        }
+        return node_symbol_name(&generator, contents);
+    }
+
+    let callee = function_name(&function, contents)?;
+
+    // `new("Class")`
+    if callee == "new" {
+        let arguments = call_arguments(&rhs, contents);
+        let class = argument_value(&arguments, "Class", 0)?;
+        return class
+            .is_string()
+            .then(|| node_symbol_name(&class, contents))
+            .flatten();
+    }
+
+    // `Class()` for S4 and S7 generators
+    workspace_class(&callee).map(|_| callee)
+}
+
+/// Finds a class in the workspace index by class name or by the name of its
+/// generator
+pub(crate) fn workspace_class(name: &str) -> Option<IndexEntry> {
+    let mut class = None;
+
+    indexer::map(|_path, symbol, entry| {
+        if class.is_some() {
+            return;
+        }
+        if let IndexEntryData::Class {
+            name: class_name, ..
+        } = &entry.data
+        {
+            if symbol == name || class_name == name {
+                class = Some(entry.clone());
+            }
+        }
+    });
+
+    class
+}
+
+/// Collects the members of a class defined in the workspace, followed by the
+/// members it inherits. Members of a class are ordered as in its definition.
+pub(crate) fn workspace_members(name: &str) -> Vec<(PathBuf, IndexEntry)> {
+    let mut members: Vec<(PathBuf, IndexEntry)> = Vec::new();
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut next = Some(name.to_string());
+
+    while let Some(name) = next.take() {
+        if visited.len() >= MAX_INHERITANCE_DEPTH {
+            break;
+        }
+
+        let Some(class) = workspace_class(&name) else {
+            break;
+        };
+        let IndexEntryData::Class {
+            name: class_name,
+            parent,
+            ..
+        } = class.data
+        else {
+            break;
+        };
+
+        if !visited.insert(class_name.clone()) {
+            break;
+        }
+
+        let mut class_members = Vec::new();
+        indexer::map(|path, _symbol, entry| {
+            let IndexEntryData::Member { class, name, .. } = &entry.data else {
+                return;
+            };
+            if *class != class_name {
+                return;
+            }
+
+            // Members overridden by a subclass are reported once
+            if members
+                .iter()
+                .any(|(_, member)| member_name(member) == Some(name.as_str()))
+            {
+                return;
+            }
+
+            class_members.push((path.to_path_buf(), entry.clone()));
+        });
+
+        class_members
+            .sort_by_key(|(_, entry)| (entry.range.start.line, entry.range.start.character));
+        members.append(&mut class_members);
+
+        next = parent;
+    }
+
+    members
+}
+
+/// Collects the members accessible from the left-hand side of an `$` or `@`
+/// extractor: `self$` and `private$` within an R6 class, `.self$` within a
+/// reference class, or an object created from a class of the workspace.
+/// Returns `None` if the class of the object is unknown.
+pub(crate) fn extractor_members(
+    extractor: &Node,
+    contents: &Rope,
+) -> Option<Vec<(PathBuf, IndexEntry)>> {
+    let accessor = match extractor.node_type() {
+        NodeType::ExtractOperator(ExtractOperatorType::Dollar) => "$",
+        NodeType::ExtractOperator(ExtractOperatorType::At) => "@",
+        _ => return None,
+    };
+
+    let lhs = extractor.child_by_field_name("lhs")?;
+    if !lhs.is_identifier() {
+        return None;
+    }
+    let object = node_symbol_name(&lhs, contents)?;
+
+    let (class, visible): (String, fn(&IndexEntryData) -> bool) = match object.as_str() {
+        "self" | "private" | "super" | ".self" => {
+            let class = enclosing_class(extractor, contents)?;
+
+            match (class.system, object.as_str()) {
+                (ClassSystem::R6, "self") => (class.name, |data| !member_is_private(data)),
+                (ClassSystem::R6, "private") => (class.name, member_is_private),
+                (ClassSystem::R6, "super") => (class.parent?, member_is_method),
+                (ClassSystem::RefClass, ".self") => (class.name, |_| true),
+                _ => return None,
+            }
+        },
+        _ => {
+            let root = lhs.ancestors().last()?;
+            let class = instance_class(root, &object, extractor.start_position(), contents)?;
+            (class, |data| !member_is_private(data))
+        },
+    };
+
+    let IndexEntryData::Class { system, .. } = workspace_class(&class)?.data else {
+        return None;
+    };
+    if system.accessor() != accessor {
+        return None;
+    }
+
+    let members = workspace_members(&class)
+        .into_iter()
+        .filter(|(_, entry)| visible(&entry.data))
+        .collect();
+
+    Some(members)
+}
+
+fn member_name(entry: &IndexEntry) -> Option<&str> {
+    match &entry.data {
+        IndexEntryData::Member { name, .. } => Some(name.as_str()),
+        _ => None,
+    }
+}
+
+fn member_is_private(data: &IndexEntryData) -> bool {
+    matches!(data, IndexEntryData::Member { private: true, .. })
+}
+
+fn member_is_method(data: &IndexEntryData) -> bool {
+    matches!(data, IndexEntryData::Member {
+        kind: MemberKind::Method,
+        ..
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::class_definitions::definition;
+    use crate::lsp::class_definitions::instance_class;
+    use crate::lsp::class_definitions::ClassSystem;
+    use crate::lsp::class_definitions::Definition;
+    use crate::lsp::class_definitions::MemberKind;
+    use crate::lsp::documents::Document;
+    use crate::test::point_from_cursor;
+
+    fn first_definition(text: &str) -> Option<String> {
+        let document = Document::new(text, None);
+        let root = document.ast.root_node();
+        let node = root.child(0)?;
+
+        let description = match definition(node, &document.contents)? {
+            Definition::Class(class) => {
+                let members: Vec<String> = class
+                    .members
+                    .iter()
+                    .map(|member| {
+                        let kind = match member.kind {
+                            MemberKind::Method => {
+                                format!("{}({})", member.name, member.arguments.join(", "))
+                            },
+                            MemberKind::Field => member.name.clone(),
+                        };
+                        if member.private {
+                            format!("private {kind}")
+                        } else {
+                            kind
+                        }
+                    })
+                    .collect();
+                format!(
+                    "{} class {} ({}) < {}: {}",
+                    class.system.label(),
+                    class.name,
+                    class.binding.unwrap_or_default(),
+                    class.parent.unwrap_or_default(),
+                    members.join(", ")
+                )
+            },
+            Definition::Generic(generic) => {
+                format!("generic {}({})", generic.name, generic.arguments.join(", "))
+            },
+            Definition::Method(method) => format!(
+                "method {} for {}({})",
+                method.generic,
+                method.class,
+                method.arguments.join(", ")
+            ),
+        };
+
+        Some(description)
+    }
+
+    #[test]
+    fn test_r6_class() {
+        let text = r#"
+Dog <- R6::R6Class("Dog",
+  inherit = Animal,
+  public = list(
+    name = NULL,
+    initialize = function(name) {
+      self$name <- name
+    },
+    bark = function(times = 1) private$sound
+  ),
+  private = list(sound = "woof"),
+  active = list(loud = function(value) toupper(private$sound))
+)"#;
+        assert_eq!(
+            first_definition(text.trim()).unwrap(),
+            "R6 class Dog (Dog) < Animal: name, initialize(name), bark(times), private sound, loud"
+        );
+
+        // The class name falls back to the binding
+        assert_eq!(
+            first_definition("Cat <- R6Class(public = list(meow = function() 1))").unwrap(),
+            "R6 class Cat (Cat) < : meow()"
+        );
+    }
+
+    #[test]
+    fn test_ref_class() {
+        let text = r#"
+Account <- setRefClass("Account",
+  fields = list(balance = "numeric"),
+  methods = list(deposit = function(x) {
+    balance <<- balance + x
+  })
+)"#;
+        assert_eq!(
+            first_definition(text.trim()).unwrap(),
+            "Reference class Account (Account) < : balance, deposit(x)"
+        );
+
+        assert_eq!(
+            first_definition("setRefClass('Person', fields = c('name', 'age'), contains = 'Base')")
+                .unwrap(),
+            "Reference class Person () < Base: name, age"
+        );
+    }
+
+    #[test]
+    fn test_s4_definitions() {
+        assert_eq!(
+            first_definition(
+                "setClass('Circle', contains = 'Shape', slots = c(r = 'numeric', 'label'))"
+            )
+            .unwrap(),
+            "S4 class Circle () < Shape: r, label"
+        );
+        assert_eq!(
+            first_definition("setClass('Shape', representation('VIRTUAL', id = 'character'))")
+                .unwrap(),
+            "S4 class Shape () < : id"
+        );
+        assert_eq!(
+            first_definition("setGeneric('area', function(shape, ...) standardGeneric('area'))")
+                .unwrap(),
+            "generic area(shape, ...)"
+        );
+        assert_eq!(
+            first_definition("setMethod('area', signature('Circle'), function(shape, ...) pi)")
+                .unwrap(),
+            "method area for Circle(shape, ...)"
+        );
+    }
+
+    #[test]
+    fn test_s7_definitions() {
+        let text = r#"
+Dog <- new_class("Dog",
+  parent = Animal,
+  properties = list(name = class_character, age = class_numeric)
+)"#;
+        assert_eq!(
+            first_definition(text.trim()).unwrap(),
+            "S7 class Dog (Dog) < Animal: name, age"
+        );
+        assert_eq!(
+            first_definition("speak <- S7::new_generic('speak', 'x')").unwrap(),
+            "generic speak(x)"
+        );
+        assert_eq!(
+            first_definition("method(speak, Dog) <- function(x) 'woof'").unwrap(),
+            "method speak for Dog(x)"
+        );
+
+        assert!(first_definition("foo <- list(a = 1)").is_none());
+        assert!(first_definition("method(speak, Dog)").is_none());
+    }
+
+    #[test]
+    fn test_instance_class() {
+        let (text, point) =
+            point_from_cursor("x <- Dog$new('Rex')\nf <- function() {\n  x <- new('Cat')\n}\nx@");
+        let document = Document::new(&text, None);
+        let root = document.ast.root_node();
+
+        assert_eq!(
+            instance_class(root, "x", point, &document.contents),
+            Some(String::from("Cat"))
+        );
+        assert_eq!(instance_class(root, "y", point, &document.contents), None);
+    }
+}
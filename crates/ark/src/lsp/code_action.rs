@@ -0,0 +1,671 @@
+//
+// code_action.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::CodeAction;
+use tower_lsp::lsp_types::CodeActionKind;
+use tower_lsp::lsp_types::CodeActionOrCommand;
+use tower_lsp::lsp_types::CodeActionParams;
+use tower_lsp::lsp_types::CodeActionResponse;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::Position;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::TextEdit;
+use tower_lsp::lsp_types::Url;
+use tower_lsp::lsp_types::WorkspaceEdit;
+use tree_sitter::Node;
+
+use crate::lsp::diagnostics::DiagnosticData;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::indent::line_indent;
+use crate::lsp::indent::new_line_indent;
+use crate::lsp::package;
+use crate::lsp::references::find_scoped_references;
+use crate::lsp::references::node_is_symbol_reference;
+use crate::lsp::references::node_local_binding;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::references::resolve_symbol_scope;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::treesitter::node_is_call;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Name of the function created when wrapping a selection in a function
+const WRAP_FUNCTION_NAME: &str = "new_function";
+
+pub(crate) fn code_actions(
+    params: CodeActionParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<CodeActionResponse>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    let mut actions: Vec<CodeActionOrCommand> = Vec::new();
+
+    for diagnostic in params.context.diagnostics.iter() {
+        let Some(data) = DiagnosticData::from_diagnostic(diagnostic) else {
+            continue;
+        };
+
+        match data {
+            DiagnosticData::InvalidNaComparison {
+                function,
+                operand,
+                range,
+            } => {
+                let replacement = format!("{function}({operand})");
+                actions.push(quick_fix(
+                    format!("Replace with `{replacement}`"),
+                    &uri,
+                    vec![TextEdit::new(range, replacement)],
+                    diagnostic,
+                    true,
+                ));
+            },
+
+            DiagnosticData::AssignmentInCondition { range } => {
+                actions.push(quick_fix(
+                    String::from("Replace `=` with `==`"),
+                    &uri,
+                    vec![TextEdit::new(range, String::from("=="))],
+                    diagnostic,
+                    true,
+                ));
+            },
+
+            DiagnosticData::SymbolNotInScope { symbol } => {
+                // The packages are looked up in the background when the
+                // diagnostic is published, see `spawn_diagnostics_refresh()`
+                let Some(packages) = package::cached_packages_exporting(&symbol) else {
+                    continue;
+                };
+
+                let position = library_insertion_position(document);
+
+                for package in packages.iter() {
+                    actions.push(quick_fix(
+                        format!("Add `library({package})`"),
+                        &uri,
+                        vec![TextEdit::new(
+                            Range::new(position, position),
+                            format!("library({package})\n"),
+                        )],
+                        diagnostic,
+                        false,
+                    ));
+                    actions.push(quick_fix(
+                        format!("Use `{package}::{symbol}`"),
+                        &uri,
+                        vec![TextEdit::new(
+                            diagnostic.range,
+                            format!("{package}::{symbol}"),
+                        )],
+                        diagnostic,
+                        false,
+                    ));
+                }
+            },
+        }
+    }
+
+    if let Some(action) = wrap_in_function(&uri, document, params.range) {
+        actions.push(action);
+    }
+
+    if actions.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(actions))
+}
+
+fn quick_fix(
+    title: String,
+    uri: &Url,
+    edits: Vec<TextEdit>,
+    diagnostic: &Diagnostic,
+    is_preferred: bool,
+) -> CodeActionOrCommand {
+    let changes = HashMap::from([(uri.clone(), edits)]);
+
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit::new(changes)),
+        is_preferred: Some(is_preferred),
+        ..Default::default()
+    })
+}
+
+/// New `library()` calls are inserted after the last top-level `library()` or
+/// `require()` call at the start of the document, or at the very start if
+/// there are none.
+fn library_insertion_position(document: &Document) -> Position {
+    let root = document.ast.root_node();
+    let contents = &document.contents;
+
+    let mut row = 0;
+
+    let mut cursor = root.walk();
+    for child in root.children(&mut cursor) {
+        if child.is_comment() {
+            continue;
+        }
+        if !node_is_call(&child, "library", contents) && !node_is_call(&child, "require", contents)
+        {
+            break;
+        }
+        row = child.end_position().row + 1;
+    }
+
+    Position::new(row as u32, 0)
+}
+
+/// Offers to wrap the selected statements in a function definition followed
+/// by a call to that function. Variables local to the enclosing function are
+/// passed as arguments. A variable assigned in the selection and used after it
+/// is returned by the new function and reassigned from the call. The action
+/// isn't offered when there are several such variables.
+fn wrap_in_function(uri: &Url, document: &Document, range: Range) -> Option<CodeActionOrCommand> {
+    if range.start == range.end {
+        return None;
+    }
+
+    let contents = &document.contents;
+    let (first, last) = selected_statements(document, range)?;
+
+    let config = &document.config.indent;
+    let start = first.start_position();
+    let (base, _) = line_indent(contents, start.row, config);
+    let base = new_line_indent(config, base);
+    let indent = new_line_indent(config, config.indent_size);
+
+    let statements = statements_between(&first, &last);
+    let assigned = assigned_symbols(&statements, contents);
+
+    let mut returned = symbols_used_outside(&first, &last, &assigned, contents);
+    if returned.len() > 1 {
+        return None;
+    }
+    let returned = returned.pop();
+
+    let parameters = free_local_symbols(&first, &last, &statements, &assigned, contents).join(", ");
+    let name = unused_function_name(document);
+
+    let text = contents
+        .byte_slice(first.start_byte()..last.end_byte())
+        .to_string();
+
+    // Lines starting within a multi-line string are kept as is since
+    // indenting them would change the value of the string
+    let root = document.ast.root_node();
+    let mut offset = first.start_byte();
+    let mut body = Vec::new();
+
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+
+        let line = line.trim_end_matches(['\n', '\r']);
+        if i > 0 && byte_is_in_string(root, line_start) {
+            body.push(line.to_string());
+        } else if line.trim().is_empty() {
+            body.push(String::new());
+        } else if i == 0 {
+            body.push(format!("{base}{indent}{line}"));
+        } else {
+            body.push(format!("{indent}{line}"));
+        }
+    }
+    if let Some(symbol) = &returned {
+        body.push(format!("{base}{indent}{symbol}"));
+    }
+    let body = body.join("\n");
+
+    let call = match &returned {
+        Some(symbol) => format!("{symbol} <- {name}({parameters})"),
+        None => format!("{name}({parameters})"),
+    };
+
+    let new_text = format!("{name} <- function({parameters}) {{\n{body}\n{base}}}\n{base}{call}");
+
+    let range = Range::new(
+        convert_point_to_position(contents, first.start_position()),
+        convert_point_to_position(contents, last.end_position()),
+    );
+    let changes = HashMap::from([(uri.clone(), vec![TextEdit::new(range, new_text)])]);
+
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: String::from("Wrap in function"),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit::new(changes)),
+        ..Default::default()
+    }))
+}
+
+/// Whether the byte at `offset` lies within a string, excluding its opening
+/// quote
+fn byte_is_in_string(root: Node, offset: usize) -> bool {
+    let mut node = root.descendant_for_byte_range(offset, offset);
+    while let Some(candidate) = node {
+        if candidate.is_string() && candidate.start_byte() < offset {
+            return true;
+        }
+        node = candidate.parent();
+    }
+    false
+}
+
+/// Returns the first and last statements covered by the selection. The
+/// selection must span complete statements of the same block, ignoring
+/// surrounding whitespace.
+fn selected_statements<'tree>(
+    document: &'tree Document,
+    range: Range,
+) -> Option<(Node<'tree>, Node<'tree>)> {
+    let contents = &document.contents;
+
+    let start = convert_position_to_point(contents, range.start);
+    let end = convert_position_to_point(contents, range.end);
+    let start = contents.line_to_byte(start.row) + start.column;
+    let end = contents.line_to_byte(end.row) + end.column;
+
+    let text = contents.byte_slice(start..end).to_string();
+    let start = start + (text.len() - text.trim_start().len());
+    let end = end - (text.len() - text.trim_end().len());
+    if start >= end {
+        return None;
+    }
+
+    let root = document.ast.root_node();
+    let first = statement_at(root.descendant_for_byte_range(start, start)?)?;
+    let last = statement_at(root.descendant_for_byte_range(end - 1, end - 1)?)?;
+
+    if first.parent() != last.parent() ||
+        first.start_byte() != start ||
+        last.end_byte() != end ||
+        first.is_comment() ||
+        last.is_comment()
+    {
+        return None;
+    }
+
+    Some((first, last))
+}
+
+/// Returns the statement of a `program` or braced expression containing `node`
+fn statement_at(node: Node) -> Option<Node> {
+    let mut node = node;
+    loop {
+        let parent = node.parent()?;
+        if parent.is_program() || parent.is_braced_expression() {
+            if node.is_named() {
+                return Some(node);
+            }
+            return None;
+        }
+        node = parent;
+    }
+}
+
+/// Returns the sibling statements from `first` to `last`
+fn statements_between<'tree>(first: &Node<'tree>, last: &Node<'tree>) -> Vec<Node<'tree>> {
+    let mut statements = vec![*first];
+    while let Some(next) = statements.last().and_then(|node| node.next_named_sibling()) {
+        if next.start_byte() >= last.end_byte() {
+            break;
+        }
+        statements.push(next);
+    }
+    statements
+}
+
+/// Returns the symbols assigned within `statements` along with the node of
+/// their first assignment. These become local to the new function.
+fn assigned_symbols<'tree>(
+    statements: &[Node<'tree>],
+    contents: &Rope,
+) -> Vec<(String, Node<'tree>)> {
+    let mut assigned: Vec<(String, Node<'tree>)> = Vec::new();
+
+    for statement in statements.iter() {
+        let mut cursor = statement.walk();
+        cursor.recurse(|node| {
+            if let Some(target) = node_local_binding(&node) {
+                if let Some(symbol) = node_symbol_name(&target, contents) {
+                    if !assigned.iter().any(|(seen, _)| *seen == symbol) {
+                        assigned.push((symbol, target));
+                    }
+                }
+            }
+            true
+        });
+    }
+
+    assigned
+}
+
+/// Returns the symbols assigned within the selection that are still used
+/// outside of it, either after the selection or elsewhere in an enclosing
+/// loop, where the next iteration may read them.
+fn symbols_used_outside(
+    first: &Node,
+    last: &Node,
+    assigned: &[(String, Node)],
+    contents: &Rope,
+) -> Vec<String> {
+    let selection = first.start_byte()..last.end_byte();
+    let mut symbols = Vec::new();
+
+    for (symbol, target) in assigned.iter() {
+        let scope = resolve_symbol_scope(target, symbol, contents);
+
+        // The outermost loop enclosing the selection within the scope
+        let mut enclosing_loop = None;
+        let mut node = *first;
+        while let Some(parent) = node.parent() {
+            if parent == scope {
+                break;
+            }
+            if matches!(
+                parent.node_type(),
+                NodeType::ForStatement | NodeType::WhileStatement | NodeType::RepeatStatement
+            ) {
+                enclosing_loop = Some(parent.byte_range());
+            }
+            node = parent;
+        }
+
+        let used = find_scoped_references(scope, symbol, contents)
+            .iter()
+            .map(|node| node.start_byte())
+            .filter(|start| !selection.contains(start))
+            .any(|start| {
+                start >= selection.end ||
+                    enclosing_loop
+                        .as_ref()
+                        .is_some_and(|range| range.contains(&start))
+            });
+
+        if used {
+            symbols.push(symbol.clone());
+        }
+    }
+
+    symbols
+}
+
+/// Returns the symbols referenced in the statements from `first` to `last`
+/// that are bound in an enclosing function outside of the selection. Symbols
+/// bound at top level are still visible from the new function and don't need
+/// to be passed as arguments.
+fn free_local_symbols(
+    first: &Node,
+    last: &Node,
+    statements: &[Node],
+    assigned: &[(String, Node)],
+    contents: &Rope,
+) -> Vec<String> {
+    let selection = first.start_byte()..last.end_byte();
+
+    let mut seen: HashSet<String> = assigned.iter().map(|(symbol, _)| symbol.clone()).collect();
+    let mut symbols: Vec<String> = Vec::new();
+
+    for statement in statements.iter() {
+        let mut cursor = statement.walk();
+        cursor.recurse(|node| {
+            if !node_is_symbol_reference(&node, contents) {
+                return true;
+            }
+            let Some(symbol) = node_symbol_name(&node, contents) else {
+                return true;
+            };
+            if seen.contains(&symbol) {
+                return true;
+            }
+
+            let scope = resolve_symbol_scope(&node, &symbol, contents);
+            if scope.is_function_definition() && !selection.contains(&scope.start_byte()) {
+                seen.insert(symbol.clone());
+                symbols.push(symbol);
+            }
+
+            true
+        });
+    }
+
+    symbols
+}
+
+fn unused_function_name(document: &Document) -> String {
+    let text = document.contents.to_string();
+
+    let mut name = String::from(WRAP_FUNCTION_NAME);
+    let mut i = 1;
+    while text.contains(name.as_str()) {
+        i += 1;
+        name = format!("{WRAP_FUNCTION_NAME}{i}");
+    }
+
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::CodeActionContext;
+    use tower_lsp::lsp_types::CodeActionOrCommand;
+    use tower_lsp::lsp_types::CodeActionParams;
+    use tower_lsp::lsp_types::Diagnostic;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::code_action::code_actions;
+    use crate::lsp::diagnostics::generate_diagnostics;
+    use crate::lsp::documents::Document;
+    use crate::lsp::package::packages_exporting;
+    use crate::lsp::state::WorldState;
+    use crate::test::r_test;
+
+    fn test_actions(
+        text: &str,
+        range: Range,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Vec<(String, String)> {
+        let uri = Url::parse("file:///test.R").unwrap();
+        let mut state = WorldState::default();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(text, None));
+
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            range,
+            context: CodeActionContext {
+                diagnostics,
+                only: None,
+                trigger_kind: None,
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        let actions = code_actions(params, &state).unwrap().unwrap_or_default();
+
+        actions
+            .into_iter()
+            .map(|action| {
+                let CodeActionOrCommand::CodeAction(action) = action else {
+                    panic!("Expected a code action");
+                };
+                let mut changes = action.edit.unwrap().changes.unwrap();
+                let edits = changes.remove(&uri).unwrap();
+                assert_eq!(edits.len(), 1);
+                (action.title, edits[0].new_text.clone())
+            })
+            .collect()
+    }
+
+    fn test_diagnostics(text: &str) -> Vec<Diagnostic> {
+        let document = Document::new(text, None);
+        generate_diagnostics(document, WorldState::default())
+    }
+
+    fn empty_range() -> Range {
+        Range::new(Position::new(0, 0), Position::new(0, 0))
+    }
+
+    #[test]
+    fn test_na_comparison_fix() {
+        r_test(|| {
+            let text = "x <- 1\nif (x == NA) 1";
+            let diagnostics: Vec<Diagnostic> = test_diagnostics(text)
+                .into_iter()
+                .filter(|d| d.message.contains("is.na"))
+                .collect();
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(
+                diagnostics[0].range,
+                Range::new(Position::new(1, 9), Position::new(1, 11))
+            );
+
+            let actions = test_actions(text, empty_range(), diagnostics);
+            assert_eq!(actions, vec![(
+                String::from("Replace with `is.na(x)`"),
+                String::from("is.na(x)")
+            )]);
+        })
+    }
+
+    #[test]
+    fn test_assignment_in_condition_fix() {
+        r_test(|| {
+            let text = "x <- 1\nif (x = 1) 1";
+            let diagnostics: Vec<Diagnostic> = test_diagnostics(text)
+                .into_iter()
+                .filter(|d| d.message.contains("Unexpected '='"))
+                .collect();
+            assert_eq!(diagnostics.len(), 1);
+
+            let actions = test_actions(text, empty_range(), diagnostics);
+            assert_eq!(actions, vec![(
+                String::from("Replace `=` with `==`"),
+                String::from("==")
+            )]);
+        })
+    }
+
+    #[test]
+    fn test_symbol_not_in_scope_fix() {
+        r_test(|| {
+            let text = "fivenum(1:10)";
+            let range = Range::new(Position::new(0, 0), Position::new(0, 7));
+            let mut diagnostic =
+                Diagnostic::new_simple(range, String::from("No symbol named 'fivenum' in scope."));
+            diagnostic.data = Some(serde_json::json!({
+                "kind": "symbol_not_in_scope",
+                "symbol": "fivenum",
+            }));
+
+            // Packages are only offered once they have been looked up in the
+            // background, so that code actions never block on R
+            assert!(test_actions(text, empty_range(), vec![diagnostic.clone()]).is_empty());
+
+            packages_exporting(&[String::from("fivenum")]);
+            let actions = test_actions(text, empty_range(), vec![diagnostic]);
+            assert!(actions.contains(&(
+                String::from("Add `library(stats)`"),
+                String::from("library(stats)\n")
+            )));
+            assert!(actions.contains(&(
+                String::from("Use `stats::fivenum`"),
+                String::from("stats::fivenum")
+            )));
+        })
+    }
+
+    #[test]
+    fn test_wrap_in_function() {
+        let text = "f <- function(a) {\n  b <- a + 1\n  b * 2\n}";
+        let range = Range::new(Position::new(1, 0), Position::new(2, 7));
+
+        let actions = test_actions(text, range, vec![]);
+        assert_eq!(actions, vec![(
+            String::from("Wrap in function"),
+            String::from(
+                "new_function <- function(a) {\n    b <- a + 1\n    b * 2\n  }\n  new_function(a)"
+            )
+        )]);
+    }
+
+    #[test]
+    fn test_wrap_in_function_returns_variable_used_after() {
+        let text = "f <- function(a) {\n  b <- a + 1\n  c <- 2\n  b * c\n}";
+        let range = Range::new(Position::new(1, 0), Position::new(1, 12));
+
+        let actions = test_actions(text, range, vec![]);
+        assert_eq!(actions, vec![(
+            String::from("Wrap in function"),
+            String::from(
+                "new_function <- function(a) {\n    b <- a + 1\n    b\n  }\n  b <- new_function(a)"
+            )
+        )]);
+
+        // Several variables can't be returned
+        let range = Range::new(Position::new(1, 0), Position::new(2, 8));
+        assert!(test_actions(text, range, vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_wrap_in_function_returns_variable_used_in_loop() {
+        let text = "for (i in 1:3) {\n  print(x)\n  x <- i\n}";
+        let range = Range::new(Position::new(2, 0), Position::new(2, 8));
+
+        let actions = test_actions(text, range, vec![]);
+        assert_eq!(actions, vec![(
+            String::from("Wrap in function"),
+            String::from(
+                "new_function <- function() {\n    x <- i\n    x\n  }\n  x <- new_function()"
+            )
+        )]);
+    }
+
+    #[test]
+    fn test_wrap_in_function_keeps_multiline_strings() {
+        let text = "x <- \"a\n  b\"\nprint(x)";
+        let range = Range::new(Position::new(0, 0), Position::new(2, 8));
+
+        let actions = test_actions(text, range, vec![]);
+        assert_eq!(actions, vec![(
+            String::from("Wrap in function"),
+            String::from(
+                "new_function <- function() {\n  x <- \"a\n  b\"\n  print(x)\n}\nnew_function()"
+            )
+        )]);
+    }
+
+    #[test]
+    fn test_wrap_in_function_requires_complete_statements() {
+        let text = "x <- 1\ny <- x + 2";
+        let range = Range::new(Position::new(0, 2), Position::new(1, 3));
+        assert!(test_actions(text, range, vec![]).is_empty());
+
+        // Top-level symbols aren't passed as arguments
+        let range = Range::new(Position::new(1, 0), Position::new(1, 10));
+        assert_eq!(test_actions(text, range, vec![]), vec![(
+            String::from("Wrap in function"),
+            String::from("new_function <- function() {\n  y <- x + 2\n}\nnew_function()")
+        )]);
+    }
+}
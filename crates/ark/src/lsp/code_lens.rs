@@ -0,0 +1,272 @@
+//
+// code_lens.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use ropey::Rope;
+use serde_json::json;
+use serde_json::Value;
+use tower_lsp::lsp_types::CodeLens;
+use tower_lsp::lsp_types::Command;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::folding_range::section_end;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Frontend command run by the code lenses. It submits the code to the R
+/// console like code typed by the user, and is run by the client so that it
+/// doesn't depend on the kernel having an active request.
+pub(crate) const EXECUTE_CODE_COMMAND: &str = "workbench.action.executeCode.console";
+
+/// Computes the code lenses of a document:
+///
+/// - "Run test" above `test_that()` blocks.
+/// - "Run section" above RStudio-style sections like `## Title ----`.
+/// - "Source function" and "Debug" above top-level function definitions.
+///
+/// Only top-level expressions get code lenses since nested code can't
+/// generally be run on its own.
+pub(crate) fn code_lenses(document: &Document) -> Vec<CodeLens> {
+    let contents = &document.contents;
+    let root = document.ast.root_node();
+
+    let mut cursor = root.walk();
+    let children: Vec<Node> = root.named_children(&mut cursor).collect();
+
+    let mut lenses = Vec::new();
+
+    for (i, child) in children.iter().enumerate() {
+        if let Some(end) = section_end(&children, i, contents) {
+            if let Some(code) = code_between(child, &end, contents) {
+                lenses.push(code_lens(child, "Run section", code, contents));
+            }
+            continue;
+        }
+
+        if node_is_test(child, contents) {
+            if let Some(code) = code_between(child, child, contents) {
+                lenses.push(code_lens(child, "Run test", code, contents));
+            }
+            continue;
+        }
+
+        if let Some(name) = function_definition_name(child, contents) {
+            let Some(code) = code_between(child, child, contents) else {
+                continue;
+            };
+            let debug = format!("{code}\ndebugonce({name})");
+
+            lenses.push(code_lens(child, "Source function", code, contents));
+            lenses.push(code_lens(child, "Debug", debug, contents));
+        }
+    }
+
+    lenses
+}
+
+fn code_lens(node: &Node, title: &str, code: String, contents: &Rope) -> CodeLens {
+    let range = convert_tree_sitter_range_to_lsp_range(contents, node.range());
+
+    CodeLens {
+        range,
+        command: Some(Command {
+            title: String::from(title),
+            command: String::from(EXECUTE_CODE_COMMAND),
+            arguments: Some(vec![execute_code_arguments(code)]),
+        }),
+        data: None,
+    }
+}
+
+/// Arguments of `EXECUTE_CODE_COMMAND`
+fn execute_code_arguments(code: String) -> Value {
+    json!({
+        "langId": "r",
+        "code": code,
+        "focus": true,
+    })
+}
+
+/// The code from the start of `first` to the end of `last`
+fn code_between(first: &Node, last: &Node, contents: &Rope) -> Option<String> {
+    let start = contents.try_byte_to_char(first.start_byte()).ok()?;
+    let end = contents.try_byte_to_char(last.end_byte()).ok()?;
+    Some(contents.get_slice(start..end)?.to_string())
+}
+
+/// Is `node` a call to `test_that()` or `testthat::test_that()`?
+fn node_is_test(node: &Node, contents: &Rope) -> bool {
+    if !node.is_call() {
+        return false;
+    }
+    let Some(mut function) = node.child_by_field_name("function") else {
+        return false;
+    };
+
+    if function.is_namespace_operator() {
+        let Some(rhs) = function.child_by_field_name("rhs") else {
+            return false;
+        };
+        function = rhs;
+    }
+
+    node_symbol_name(&function, contents).is_some_and(|name| name == "test_that")
+}
+
+/// The name bound by a function definition like `name <- function() {}`
+fn function_definition_name(node: &Node, contents: &Rope) -> Option<String> {
+    if !matches!(
+        node.node_type(),
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+    ) {
+        return None;
+    }
+
+    let lhs = node.child_by_field_name("lhs")?;
+    let rhs = node.child_by_field_name("rhs")?;
+    if !lhs.is_identifier_or_string() || !rhs.is_function_definition() {
+        return None;
+    }
+
+    // Backquote names defined with strings so that `debugonce()` gets valid
+    // code. Backquoted identifiers are kept as is.
+    if lhs.is_string() {
+        let name = node_symbol_name(&lhs, contents)?;
+        return Some(format!("`{name}`"));
+    }
+    Some(contents.node_slice(&lhs).ok()?.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use serde_json::Value;
+
+    use crate::lsp::code_lens::code_lenses;
+    use crate::lsp::code_lens::EXECUTE_CODE_COMMAND;
+    use crate::lsp::documents::Document;
+
+    fn lenses(text: &str) -> Vec<(u32, String, String)> {
+        let document = Document::new(text, None);
+        code_lenses(&document)
+            .into_iter()
+            .map(|lens| {
+                let command = lens.command.unwrap();
+                let code = match command.arguments.unwrap().into_iter().next() {
+                    Some(Value::Object(arguments)) => {
+                        arguments["code"].as_str().unwrap().to_string()
+                    },
+                    _ => panic!("Expected code argument"),
+                };
+                (lens.range.start.line, command.title, code)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_code_lens_command() {
+        let document = Document::new("test_that(\"it works\", {})", None);
+        let lens = code_lenses(&document).into_iter().next().unwrap();
+
+        // The command is run by the frontend, which executes the code in the
+        // console even when the kernel is idle
+        let command = lens.command.unwrap();
+        assert_eq!(command.command, EXECUTE_CODE_COMMAND);
+        assert_eq!(
+            command.arguments,
+            Some(vec![json!({
+                "langId": "r",
+                "code": "test_that(\"it works\", {})",
+                "focus": true,
+            })])
+        );
+    }
+
+    #[test]
+    fn test_code_lens_tests() {
+        let text = "
+test_that(\"it works\", {
+  expect_true(TRUE)
+})
+testthat::test_that(\"it still works\", {})
+expect_true(TRUE)
+";
+        assert_eq!(lenses(text), vec![
+            (
+                1,
+                String::from("Run test"),
+                String::from("test_that(\"it works\", {\n  expect_true(TRUE)\n})")
+            ),
+            (
+                4,
+                String::from("Run test"),
+                String::from("testthat::test_that(\"it still works\", {})")
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_code_lens_functions() {
+        let text = "
+f <- function(x) x
+\"my fun\" <- function() NULL
+g <- 1
+h <- function() {
+  nested <- function() NULL
+}
+";
+        let titles: Vec<(u32, String)> = lenses(text)
+            .into_iter()
+            .map(|(line, title, _code)| (line, title))
+            .collect();
+
+        assert_eq!(titles, vec![
+            (1, String::from("Source function")),
+            (1, String::from("Debug")),
+            (2, String::from("Source function")),
+            (2, String::from("Debug")),
+            (4, String::from("Source function")),
+            (4, String::from("Debug")),
+        ]);
+
+        let text = "\"my fun\" <- function() NULL";
+        assert_eq!(
+            lenses(text)[1].2,
+            "\"my fun\" <- function() NULL\ndebugonce(`my fun`)"
+        );
+    }
+
+    #[test]
+    fn test_code_lens_sections() {
+        let text = "
+# Section ----
+a <- 1
+
+## Subsection ----
+b <- 2
+
+# Empty section ----
+";
+        assert_eq!(lenses(text), vec![
+            (
+                1,
+                String::from("Run section"),
+                String::from("# Section ----\na <- 1\n\n## Subsection ----\nb <- 2")
+            ),
+            (
+                4,
+                String::from("Run section"),
+                String::from("## Subsection ----\nb <- 2")
+            ),
+        ]);
+    }
+}
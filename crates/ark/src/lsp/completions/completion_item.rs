@@ -40,6 +40,7 @@ use tower_lsp::lsp_types::Range;
 use tower_lsp::lsp_types::TextEdit;
 use tree_sitter::Node;
 
+use crate::lsp::class_definitions::MemberKind;
 use crate::lsp::completions::types::CompletionData;
 use crate::lsp::completions::types::PromiseStrategy;
 use crate::lsp::document_context::DocumentContext;
@@ -192,6 +193,36 @@ pub(super) fn completion_item_from_function<T: AsRef<str>>(
     return Ok(item);
 }
 
+pub(super) fn completion_item_from_class_member(
+    name: &str,
+    class: &str,
+    kind: MemberKind,
+    parameters: &[String],
+) -> Result<CompletionItem> {
+    let mut item = completion_item(name, CompletionData::ClassMember {
+        name: name.to_string(),
+        class: class.to_string(),
+    })?;
+
+    let insert_text = sym_quote_invalid(name);
+
+    match kind {
+        MemberKind::Method => {
+            item.kind = Some(CompletionItemKind::METHOD);
+            item.detail = Some(format!("{class}: {name}({})", parameters.joined(", ")));
+            item.insert_text_format = Some(InsertTextFormat::SNIPPET);
+            item.insert_text = Some(format!("{insert_text}($0)"));
+        },
+        MemberKind::Field => {
+            item.kind = Some(CompletionItemKind::FIELD);
+            item.detail = Some(class.to_string());
+            item.insert_text = Some(insert_text);
+        },
+    }
+
+    Ok(item)
+}
+
 // TODO
 pub(super) unsafe fn completion_item_from_dataset(name: &str) -> Result<CompletionItem> {
     let mut item = completion_item(name.to_string(), CompletionData::Unknown)?;
@@ -26,6 +26,8 @@ pub fn resolve_completion(item: &mut CompletionItem) -> Result<bool> {
     });
 
     match data {
+        CompletionData::ChunkOption { name: _ } => Ok(false),
+        CompletionData::ClassMember { name: _, class: _ } => Ok(false),
         CompletionData::DataVariable { name: _, owner: _ } => Ok(false),
         CompletionData::Directory { path: _ } => Ok(false),
         CompletionData::File { path: _ } => Ok(false),
@@ -69,7 +69,7 @@ pub fn completions_from_composite_sources(
     if is_identifier_like(context.node) {
         completions.append(&mut completions_from_keywords());
         completions.append(&mut completions_from_snippets());
-        completions.append(&mut completions_from_search_path(context)?);
+        completions.append(&mut completions_from_search_path(context, state)?);
 
         if let Some(mut additional_completions) = completions_from_document(context)? {
             completions.append(&mut additional_completions);
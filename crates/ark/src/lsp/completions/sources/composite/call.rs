@@ -259,7 +259,8 @@ fn completions_from_workspace_arguments(
     let mut completions = vec![];
 
     match entry.data {
-        indexer::IndexEntryData::Function { name, arguments } => {
+        indexer::IndexEntryData::Function { name, arguments } |
+        indexer::IndexEntryData::Generic { name, arguments } => {
             for argument in arguments {
                 match completion_item_from_parameter(argument.as_str(), name.as_str(), context) {
                     Ok(item) => completions.push(item),
@@ -267,7 +268,10 @@ fn completions_from_workspace_arguments(
                 }
             }
         },
-        indexer::IndexEntryData::Section { level: _, title: _ } => {
+        indexer::IndexEntryData::Section { .. } |
+        indexer::IndexEntryData::Class { .. } |
+        indexer::IndexEntryData::Member { .. } |
+        indexer::IndexEntryData::Method { .. } => {
             // Not a function
             return Ok(None);
         },
@@ -5,6 +5,8 @@
 //
 //
 
+use std::collections::BTreeMap;
+
 use anyhow::Result;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
@@ -19,15 +21,20 @@ use libr::R_lsInternal;
 use libr::ENCLOS;
 use tower_lsp::lsp_types::CompletionItem;
 
+use crate::lsp::completions::completion_item::completion_item_from_namespace;
 use crate::lsp::completions::completion_item::completion_item_from_package;
 use crate::lsp::completions::completion_item::completion_item_from_symbol;
 use crate::lsp::completions::sources::utils::filter_out_dot_prefixes;
 use crate::lsp::completions::sources::utils::set_sort_text_by_words_first;
 use crate::lsp::completions::types::PromiseStrategy;
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::package::namespace_exports;
+use crate::lsp::package::Package;
+use crate::lsp::state::WorldState;
 
 pub(super) fn completions_from_search_path(
     context: &DocumentContext,
+    state: &WorldState,
 ) -> Result<Vec<CompletionItem>> {
     log::info!("completions_from_search_path()");
 
@@ -112,6 +119,12 @@ pub(super) fn completions_from_search_path(
         }
     }
 
+    // Include the scope of the workspace package, which isn't necessarily
+    // loaded in the session
+    if let Some(package) = &state.workspace.package {
+        completions.append(&mut completions_from_package_scope(package, state));
+    }
+
     filter_out_dot_prefixes(context, &mut completions);
 
     // Push search path completions starting with non-word characters to the
@@ -120,3 +133,50 @@ pub(super) fn completions_from_search_path(
 
     Ok(completions)
 }
+
+/// Completions for the symbols imported in the `NAMESPACE` of the workspace
+/// package, and for the exports of the packages it attaches
+fn completions_from_package_scope(package: &Package, state: &WorldState) -> Vec<CompletionItem> {
+    // Packages that aren't installed can't provide any details
+    let is_installed = |pkg: &str| {
+        state
+            .installed_packages
+            .iter()
+            .any(|installed| installed == pkg)
+    };
+
+    let mut symbols: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (pkg, symbol) in package.namespace.imported_symbols.iter() {
+        if is_installed(pkg) {
+            symbols.entry(pkg.clone()).or_default().push(symbol.clone());
+        }
+    }
+
+    for pkg in package.attached_packages().into_iter() {
+        if is_installed(&pkg) {
+            let exports = namespace_exports(std::slice::from_ref(&pkg));
+            symbols.entry(pkg).or_default().extend(exports);
+        }
+    }
+
+    let mut completions = vec![];
+
+    for (pkg, symbols) in symbols.iter() {
+        let Ok(namespace) = RFunction::new("base", "getNamespace")
+            .add(pkg.as_str())
+            .call()
+        else {
+            continue;
+        };
+
+        for symbol in symbols.iter() {
+            match unsafe { completion_item_from_namespace(symbol, *namespace, pkg) } {
+                Ok(item) => completions.push(item),
+                Err(error) => log::error!("{:?}", error),
+            }
+        }
+    }
+
+    completions
+}
@@ -63,7 +63,8 @@ pub(super) fn completions_from_workspace(
         }
 
         match &entry.data {
-            indexer::IndexEntryData::Function { name, arguments } => {
+            indexer::IndexEntryData::Function { name, arguments } |
+            indexer::IndexEntryData::Generic { name, arguments } => {
                 let mut completion = unwrap!(completion_item_from_function(name, None, arguments), Err(error) => {
                     error!("{:?}", error);
                     return;
@@ -97,6 +98,9 @@ pub(super) fn completions_from_workspace(
             },
 
             indexer::IndexEntryData::Section { level: _, title: _ } => {},
+            indexer::IndexEntryData::Class { .. } => {},
+            indexer::IndexEntryData::Member { .. } => {},
+            indexer::IndexEntryData::Method { .. } => {},
         }
     });
 
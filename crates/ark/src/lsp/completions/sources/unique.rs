@@ -5,6 +5,7 @@
 //
 //
 
+mod chunk_option;
 mod colon;
 mod comment;
 mod custom;
@@ -15,6 +16,7 @@ mod string;
 mod subset;
 
 use anyhow::Result;
+use chunk_option::completions_from_chunk_option;
 use colon::completions_from_single_colon;
 use comment::completions_from_comment;
 use custom::completions_from_custom_source;
@@ -37,6 +39,11 @@ pub fn completions_from_unique_sources(
         return Ok(Some(completions));
     }
 
+    // Try `#|` chunk option completions of Quarto and R Markdown documents
+    if let Some(completions) = completions_from_chunk_option(context)? {
+        return Ok(Some(completions));
+    }
+
     // Try comment / roxygen2 completions
     if let Some(completions) = completions_from_comment(context)? {
         return Ok(Some(completions));
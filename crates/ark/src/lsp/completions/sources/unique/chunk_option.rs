@@ -0,0 +1,242 @@
+//
+// chunk_option.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use anyhow::Result;
+use tower_lsp::lsp_types::CompletionItem;
+use tower_lsp::lsp_types::CompletionItemKind;
+use tower_lsp::lsp_types::Documentation;
+use tower_lsp::lsp_types::MarkupContent;
+use tower_lsp::lsp_types::MarkupKind;
+
+use crate::lsp::completions::completion_item::completion_item;
+use crate::lsp::completions::types::CompletionData;
+use crate::lsp::document_context::DocumentContext;
+use crate::treesitter::NodeTypeExt;
+
+struct ChunkOption {
+    name: &'static str,
+    description: &'static str,
+    logical: bool,
+}
+
+/// Common chunk options understood by both knitr and Quarto
+const CHUNK_OPTIONS: &[ChunkOption] = &[
+    ChunkOption {
+        name: "label",
+        description: "Unique label of the chunk, used to name its outputs and to cross-reference it.",
+        logical: false,
+    },
+    ChunkOption {
+        name: "echo",
+        description: "Whether to include the code of the chunk in the output.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "eval",
+        description: "Whether to evaluate the code of the chunk.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "include",
+        description: "Whether to include the code and the results of the chunk in the output.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "output",
+        description: "Whether to include the results of the chunk in the output, or `asis` to include them as raw markdown.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "warning",
+        description: "Whether to include warnings in the output.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "message",
+        description: "Whether to include messages in the output.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "error",
+        description: "Whether to include errors in the output rather than stopping the rendering.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "cache",
+        description: "Whether to cache the results of the chunk.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "code-fold",
+        description: "Whether to hide the code of the chunk behind a toggle in HTML output.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "collapse",
+        description: "Whether to merge the code and the text output of the chunk into a single block.",
+        logical: true,
+    },
+    ChunkOption {
+        name: "fig-cap",
+        description: "Caption of the figures of the chunk.",
+        logical: false,
+    },
+    ChunkOption {
+        name: "fig-alt",
+        description: "Alternative text of the figures of the chunk.",
+        logical: false,
+    },
+    ChunkOption {
+        name: "fig-width",
+        description: "Width of the figures of the chunk, in inches.",
+        logical: false,
+    },
+    ChunkOption {
+        name: "fig-height",
+        description: "Height of the figures of the chunk, in inches.",
+        logical: false,
+    },
+    ChunkOption {
+        name: "fig-align",
+        description: "Alignment of the figures of the chunk: `default`, `left`, `right`, or `center`.",
+        logical: false,
+    },
+    ChunkOption {
+        name: "out-width",
+        description: "Width of the figures of the chunk in the output document, e.g. `100%`.",
+        logical: false,
+    },
+    ChunkOption {
+        name: "results",
+        description: "How to include the text results of the chunk: `markup`, `asis`, `hold`, or `hide`.",
+        logical: false,
+    },
+];
+
+/// Completes the `#| name: value` option comments of the R chunks of Quarto
+/// and R Markdown documents. Option names are completed at first, and
+/// `true`/`false` are offered once a logical option name is complete.
+pub fn completions_from_chunk_option(
+    context: &DocumentContext,
+) -> Result<Option<Vec<CompletionItem>>> {
+    log::info!("completions_from_chunk_option()");
+
+    if context.document.markdown.is_none() {
+        return Ok(None);
+    }
+
+    let node = context.node;
+    if !node.is_comment() {
+        return Ok(None);
+    }
+
+    // Only consider the text before the cursor. The comment might not include
+    // trailing whitespace so we take the text from the line.
+    let line = context
+        .document
+        .contents
+        .line(context.point.row)
+        .to_string();
+    let start = node.start_position().column;
+    let Some(option) = line
+        .get(start..context.point.column)
+        .and_then(|text| text.strip_prefix("#|"))
+    else {
+        return Ok(None);
+    };
+
+    let mut completions: Vec<CompletionItem> = vec![];
+
+    match option.split_once(':') {
+        Some((name, _value)) => {
+            let name = name.trim();
+            let logical = CHUNK_OPTIONS
+                .iter()
+                .any(|option| option.logical && option.name == name);
+
+            if logical {
+                for value in ["true", "false"] {
+                    let mut item = completion_item(value, CompletionData::ChunkOption {
+                        name: name.to_string(),
+                    })?;
+                    item.kind = Some(CompletionItemKind::VALUE);
+                    completions.push(item);
+                }
+            }
+        },
+        None => {
+            for option in CHUNK_OPTIONS.iter() {
+                completions.push(completion_item_from_chunk_option(option)?);
+            }
+        },
+    }
+
+    Ok(Some(completions))
+}
+
+fn completion_item_from_chunk_option(option: &ChunkOption) -> Result<CompletionItem> {
+    let mut item = completion_item(option.name, CompletionData::ChunkOption {
+        name: option.name.to_string(),
+    })?;
+
+    item.kind = Some(CompletionItemKind::PROPERTY);
+    item.insert_text = Some(format!("{}: ", option.name));
+    item.detail = Some(format!("chunk option {}", option.name));
+
+    let markup = MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: option.description.to_string(),
+    };
+    item.documentation = Some(Documentation::MarkupContent(markup));
+
+    Ok(item)
+}
+
+#[test]
+fn test_chunk_option_names() {
+    use tree_sitter::Point;
+
+    use crate::lsp::documents::Document;
+
+    let document = Document::new_markdown("```{r}\n#| ec\nx <- 1\n```\n", None);
+    let point = Point { row: 1, column: 5 };
+    let context = DocumentContext::new(&document, point, None);
+    let completions = completions_from_chunk_option(&context).unwrap().unwrap();
+
+    let echo = completions
+        .iter()
+        .find(|item| item.label == "echo")
+        .unwrap();
+    assert_eq!(echo.insert_text, Some(String::from("echo: ")));
+
+    // R scripts don't have chunk options
+    let document = Document::new("#| ec\nx <- 1\n", None);
+    let point = Point { row: 0, column: 5 };
+    let context = DocumentContext::new(&document, point, None);
+    assert!(completions_from_chunk_option(&context).unwrap().is_none());
+}
+
+#[test]
+fn test_chunk_option_values() {
+    use tree_sitter::Point;
+
+    use crate::lsp::documents::Document;
+
+    let document = Document::new_markdown("```{r}\n#| echo: \n#| label: \n```\n", None);
+
+    let point = Point { row: 1, column: 9 };
+    let context = DocumentContext::new(&document, point, None);
+    let completions = completions_from_chunk_option(&context).unwrap().unwrap();
+    let labels: Vec<&str> = completions.iter().map(|item| item.label.as_str()).collect();
+    assert_eq!(labels, vec!["true", "false"]);
+
+    // No values for options that aren't logical
+    let point = Point { row: 2, column: 10 };
+    let context = DocumentContext::new(&document, point, None);
+    let completions = completions_from_chunk_option(&context).unwrap().unwrap();
+    assert!(completions.is_empty());
+}
@@ -12,6 +12,7 @@ use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use regex::Regex;
 use tower_lsp::lsp_types::CompletionItem;
+use tower_lsp::lsp_types::CompletionItemKind;
 use tower_lsp::lsp_types::Documentation;
 use tower_lsp::lsp_types::InsertTextFormat;
 use tower_lsp::lsp_types::MarkupContent;
@@ -21,6 +22,12 @@ use yaml_rust::YamlLoader;
 use crate::lsp::completions::completion_item::completion_item;
 use crate::lsp::completions::types::CompletionData;
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::package::namespace_exports;
+use crate::lsp::roxygen::assigned_function;
+use crate::lsp::roxygen::block_comments;
+use crate::lsp::roxygen::documented_node;
+use crate::lsp::roxygen::function_formals;
+use crate::lsp::roxygen::RoxygenBlock;
 use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::NodeTypeExt;
 
@@ -38,6 +45,10 @@ pub fn completions_from_comment(context: &DocumentContext) -> Result<Option<Vec<
     let contents = context.document.contents.node_slice(&node)?.to_string();
     let token = pattern.replace(contents.as_str(), "");
 
+    if let Some(completions) = completions_from_roxygen_tag_arguments(context)? {
+        return Ok(Some(completions));
+    }
+
     let mut completions: Vec<CompletionItem> = vec![];
 
     if !token.starts_with('@') {
@@ -89,6 +100,99 @@ pub fn completions_from_comment(context: &DocumentContext) -> Result<Option<Vec<
     Ok(Some(completions))
 }
 
+/// Completes the arguments of roxygen tags:
+/// - `@param` with the formals of the documented function that aren't
+///   documented yet.
+/// - `@importFrom pkg` with the exports of `pkg`.
+fn completions_from_roxygen_tag_arguments(
+    context: &DocumentContext,
+) -> Result<Option<Vec<CompletionItem>>> {
+    let node = context.node;
+
+    // Only consider the text before the cursor. The comment might not include
+    // trailing whitespace so we take the text from the line.
+    let line = context
+        .document
+        .contents
+        .line(context.point.row)
+        .to_string();
+    let start = node.start_position().column;
+    let Some(line) = line
+        .get(start..context.point.column)
+        .and_then(|text| text.strip_prefix("#'"))
+    else {
+        return Ok(None);
+    };
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some(tag) = words.first() else {
+        return Ok(None);
+    };
+
+    // Index of the argument being typed, the tag being the 0th word
+    let index = if line.ends_with(char::is_whitespace) {
+        words.len()
+    } else {
+        words.len() - 1
+    };
+
+    match *tag {
+        "@param" if index == 1 => {
+            let contents = &context.document.contents;
+            let comments = block_comments(&node, contents);
+
+            let Some(function) =
+                documented_node(&comments).and_then(|node| assigned_function(&node))
+            else {
+                return Ok(Some(vec![]));
+            };
+
+            // Skip the formals that are already documented on other lines
+            let row = node.start_position().row;
+            let documented: Vec<String> = RoxygenBlock::parse(&comments, contents)
+                .params()
+                .into_iter()
+                .filter(|param| param.row != row)
+                .map(|param| param.name)
+                .collect();
+
+            let mut completions = vec![];
+            for (name, _node) in function_formals(&function, contents) {
+                if documented.contains(&name) {
+                    continue;
+                }
+                let mut item = completion_item(name.clone(), CompletionData::ScopeParameter {
+                    name: name.clone(),
+                })?;
+                item.kind = Some(CompletionItemKind::VARIABLE);
+                item.detail = Some(String::from("roxygen @param (R)"));
+                completions.push(item);
+            }
+
+            Ok(Some(completions))
+        },
+        "@importFrom" if index >= 2 => {
+            let package = words[1];
+            let imported = &words[2..index];
+
+            let mut completions = vec![];
+            for name in namespace_exports(&[package.to_string()]) {
+                if imported.contains(&name.as_str()) {
+                    continue;
+                }
+                let item = completion_item(name.clone(), CompletionData::Function {
+                    name,
+                    package: Some(package.to_string()),
+                })?;
+                completions.push(item);
+            }
+
+            Ok(Some(completions))
+        },
+        _ => Ok(None),
+    }
+}
+
 fn completion_item_from_roxygen(
     name: &str,
     template: Option<&str>,
@@ -197,6 +301,25 @@ fn test_roxygen_comment() {
     });
 }
 
+#[test]
+fn test_roxygen_param_completions() {
+    use tree_sitter::Point;
+
+    use crate::lsp::documents::Document;
+
+    let text = "#' Title\n#' @param x A value.\n#' @param \nf <- function(x, y, ...) NULL\n";
+    let document = Document::new(text, None);
+    let point = Point { row: 2, column: 10 };
+    let context = DocumentContext::new(&document, point, None);
+
+    let completions = completions_from_roxygen_tag_arguments(&context)
+        .unwrap()
+        .unwrap();
+
+    let labels: Vec<&str> = completions.iter().map(|item| item.label.as_str()).collect();
+    assert_eq!(labels, vec!["y", "..."]);
+}
+
 #[test]
 fn test_roxygen_completion_item() {
     let name = "aliases";
@@ -5,6 +5,8 @@
 //
 //
 
+use std::path::PathBuf;
+
 use anyhow::Result;
 use harp::eval::RParseEvalOptions;
 use harp::exec::RFunction;
@@ -17,9 +19,13 @@ use libr::STRSXP;
 use tower_lsp::lsp_types::CompletionItem;
 use tree_sitter::Node;
 
+use crate::lsp::class_definitions::extractor_members;
+use crate::lsp::completions::completion_item::completion_item_from_class_member;
 use crate::lsp::completions::completion_item::completion_item_from_data_variable;
 use crate::lsp::completions::sources::utils::set_sort_text_by_first_appearance;
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::indexer::IndexEntry;
+use crate::lsp::indexer::IndexEntryData;
 use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::ExtractOperatorType;
 use crate::treesitter::NodeType;
@@ -62,6 +68,12 @@ fn completions_from_extractor(
     // completion sources from running.
     let mut completions: Vec<CompletionItem> = vec![];
 
+    // Members of classes defined in the workspace, e.g. `self$` in R6 methods
+    if let Some(members) = extractor_members(&node, &context.document.contents) {
+        completions.append(&mut completions_from_class_members(members));
+        return Ok(Some(completions));
+    }
+
     // Get the object to evaluate that we collect completion names for
     let Some(node) = node.child_by_field_name("lhs") else {
         return Ok(Some(completions));
@@ -102,6 +114,33 @@ fn locate_extractor_node(node: Node, node_type: NodeType) -> Option<Node> {
     }
 }
 
+fn completions_from_class_members(members: Vec<(PathBuf, IndexEntry)>) -> Vec<CompletionItem> {
+    let mut completions = vec![];
+
+    for (_path, entry) in members {
+        let IndexEntryData::Member {
+            class,
+            name,
+            kind,
+            arguments,
+            ..
+        } = entry.data
+        else {
+            continue;
+        };
+
+        match completion_item_from_class_member(&name, &class, kind, &arguments) {
+            Ok(item) => completions.push(item),
+            Err(err) => log::error!("{err:?}"),
+        }
+    }
+
+    // Members are listed in the order of the class definitions
+    set_sort_text_by_first_appearance(&mut completions);
+
+    completions
+}
+
 fn completions_from_extractor_object(text: &str, fun: &str) -> Result<Vec<CompletionItem>> {
     log::info!("completions_from_extractor_object({text:?}, {fun:?})");
 
@@ -162,12 +201,17 @@ fn completions_from_extractor_object(text: &str, fun: &str) -> Result<Vec<Comple
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use harp::eval::RParseEvalOptions;
     use harp::object::r_lgl_get;
+    use tree_sitter::Point;
 
+    use crate::lsp::completions::sources::unique::extractor::completions_from_at;
     use crate::lsp::completions::sources::unique::extractor::completions_from_dollar;
     use crate::lsp::document_context::DocumentContext;
     use crate::lsp::documents::Document;
+    use crate::lsp::indexer;
     use crate::test::point_from_cursor;
     use crate::test::r_test;
 
@@ -318,4 +362,51 @@ mod tests {
             harp::parse_eval("remove(foo)", options.clone()).unwrap();
         })
     }
+
+    #[test]
+    fn test_dollar_completions_on_r6_self() {
+        let text = "
+ExtractorCounter <- R6Class('ExtractorCounter',
+  public = list(
+    count = 0,
+    add = function(n = 1) {
+      self$co@
+    }
+  ),
+  private = list(secret = 1)
+)";
+        let (text, point) = point_from_cursor(text);
+        let document = Document::new(text.as_str(), None);
+        indexer::update(&document, Path::new("/extractor_counter.R")).unwrap();
+
+        let context = DocumentContext::new(&document, point, None);
+        let completions = completions_from_dollar(&context).unwrap().unwrap();
+        let labels: Vec<&str> = completions.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["count", "add"]);
+
+        let text = text.replace("self$co", "private$s");
+        let point = Point::new(point.row, point.column + 2);
+        let document = Document::new(text.as_str(), None);
+        let context = DocumentContext::new(&document, point, None);
+        let completions = completions_from_dollar(&context).unwrap().unwrap();
+        let labels: Vec<&str> = completions.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["secret"]);
+    }
+
+    #[test]
+    fn test_at_completions_on_s4_instance() {
+        let definitions = "setClass('ExtractorBase', slots = c(id = 'character'))
+setClass('ExtractorPoint', contains = 'ExtractorBase', slots = c(x = 'numeric', y = 'numeric'))";
+        let document = Document::new(definitions, None);
+        indexer::update(&document, Path::new("/extractor_point.R")).unwrap();
+
+        // Can't use `point_from_cursor()` with `@`
+        let text = "p <- new('ExtractorPoint', x = 1)\np@";
+        let point = Point::new(1, 2);
+        let document = Document::new(text, None);
+        let context = DocumentContext::new(&document, point, None);
+        let completions = completions_from_at(&context).unwrap().unwrap();
+        let labels: Vec<&str> = completions.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["x", "y", "id"]);
+    }
 }
@@ -12,6 +12,13 @@ use serde::Serialize;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub(super) enum CompletionData {
+    ChunkOption {
+        name: String,
+    },
+    ClassMember {
+        name: String,
+        class: String,
+    },
     DataVariable {
         name: String,
         owner: String,
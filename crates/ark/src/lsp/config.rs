@@ -1,14 +1,62 @@
+use std::collections::HashMap;
+use std::path::Path;
+
 use serde::Deserialize;
 use serde::Serialize;
 use struct_field_names_as_array::FieldNamesAsArray;
+use tower_lsp::lsp_types::Url;
 
 use crate::lsp;
 use crate::lsp::diagnostics::DiagnosticsConfig;
+use crate::lsp::diagnostics_rules::DiagnosticRule;
+use crate::lsp::diagnostics_rules::RuleSeverity;
+
+/// Name of the project configuration file at the root of workspace folders
+pub(crate) const PROJECT_CONFIG_FILE: &str = ".ark.toml";
 
 /// Configuration of the LSP
 #[derive(Clone, Debug)]
 pub(crate) struct LspConfig {
+    /// Diagnostics configuration set in the editor settings
     pub(crate) diagnostics: DiagnosticsConfig,
+
+    /// Inlay hints configuration set in the editor settings
+    pub(crate) inlay_hints: InlayHintsConfig,
+
+    /// Configuration set in the `.ark.toml` files of the workspace folders.
+    /// Takes precedence over the editor settings.
+    pub(crate) project: ProjectConfig,
+}
+
+/// Configuration of inlay hints
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct InlayHintsConfig {
+    /// Whether to show the class of objects existing in the global
+    /// environment. Off by default since it requires querying the R session.
+    pub object_class: bool,
+}
+
+/// Configuration of a project, read from `.ark.toml`:
+///
+/// ```toml
+/// [diagnostics]
+/// enable = true
+///
+/// [diagnostics.rules]
+/// symbol-not-in-scope = "off"
+/// invalid-na-comparison = "warning"
+/// ```
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct ProjectConfig {
+    pub diagnostics: ProjectDiagnosticsConfig,
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct ProjectDiagnosticsConfig {
+    pub enable: Option<bool>,
+    pub rules: HashMap<String, RuleSeverity>,
 }
 
 /// Configuration of a document.
@@ -51,6 +99,13 @@ pub(crate) struct VscDocumentConfig {
 pub(crate) struct VscDiagnosticsConfig {
     // DEV NOTE: Update `section_from_key()` method after adding a field
     pub enable: bool,
+    pub rules: Option<HashMap<String, RuleSeverity>>,
+}
+
+#[derive(Serialize, Deserialize, FieldNamesAsArray, Clone, Debug)]
+pub(crate) struct VscInlayHintsConfig {
+    // DEV NOTE: Update `section_from_key()` method after adding a field
+    pub object_class: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -64,10 +119,92 @@ impl Default for LspConfig {
     fn default() -> Self {
         Self {
             diagnostics: Default::default(),
+            inlay_hints: Default::default(),
+            project: Default::default(),
         }
     }
 }
 
+impl LspConfig {
+    /// Diagnostics configuration with the project settings applied on top of
+    /// the editor settings
+    pub(crate) fn diagnostics(&self) -> DiagnosticsConfig {
+        let mut config = self.diagnostics.clone();
+        let project = &self.project.diagnostics;
+
+        if let Some(enable) = project.enable {
+            config.enable = enable;
+        }
+        for (id, severity) in project.rules.iter() {
+            config.rules.insert(id.clone(), *severity);
+        }
+
+        config
+    }
+}
+
+impl ProjectConfig {
+    /// Reads the project configuration of a folder. Returns `None` if the
+    /// folder doesn't contain a configuration file.
+    pub(crate) fn read(folder: &Path) -> anyhow::Result<Option<Self>> {
+        let path = folder.join(PROJECT_CONFIG_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = std::fs::read_to_string(&path)?;
+        let config: ProjectConfig = toml::from_str(&contents)?;
+
+        Ok(Some(config))
+    }
+
+    /// IDs of the configured rules that don't match any known rule
+    pub(crate) fn unknown_rules(&self) -> Vec<&str> {
+        self.diagnostics
+            .rules
+            .keys()
+            .map(|id| id.as_str())
+            .filter(|id| DiagnosticRule::from_id(id).is_none())
+            .collect()
+    }
+
+    /// Reads and merges the project configurations of the workspace folders.
+    /// Folders listed first take precedence.
+    pub(crate) fn from_workspace(folders: &[Url]) -> Self {
+        let mut config = ProjectConfig::default();
+
+        for folder in folders.iter().rev() {
+            let Ok(path) = folder.to_file_path() else {
+                continue;
+            };
+
+            let folder_config = match ProjectConfig::read(&path) {
+                Ok(Some(folder_config)) => folder_config,
+                Ok(None) => continue,
+                Err(err) => {
+                    lsp::log_error!("Can't read project configuration in {path:?}: {err:?}");
+                    continue;
+                },
+            };
+
+            for id in folder_config.unknown_rules() {
+                let file = path.join(PROJECT_CONFIG_FILE);
+                lsp::log_warn!("Unknown diagnostic rule `{id}` in {}", file.display());
+            }
+
+            if folder_config.diagnostics.enable.is_some() {
+                config.diagnostics.enable = folder_config.diagnostics.enable;
+            }
+            config
+                .diagnostics
+                .rules
+                .extend(folder_config.diagnostics.rules);
+        }
+
+        config
+    }
+}
+
 impl Default for IndentationConfig {
     fn default() -> Self {
         Self {
@@ -121,6 +258,7 @@ impl VscDiagnosticsConfig {
     pub(crate) fn section_from_key(key: &str) -> &str {
         match key {
             "enable" => "positron.r.diagnostics.enable",
+            "rules" => "positron.r.diagnostics.rules",
             _ => "unknown", // To be caught via downstream errors
         }
     }
@@ -130,6 +268,24 @@ impl From<VscDiagnosticsConfig> for DiagnosticsConfig {
     fn from(value: VscDiagnosticsConfig) -> Self {
         Self {
             enable: value.enable,
+            rules: value.rules.unwrap_or_default(),
+        }
+    }
+}
+
+impl VscInlayHintsConfig {
+    pub(crate) fn section_from_key(key: &str) -> &str {
+        match key {
+            "object_class" => "positron.r.inlayHints.objectClass",
+            _ => "unknown", // To be caught via downstream errors
+        }
+    }
+}
+
+impl From<VscInlayHintsConfig> for InlayHintsConfig {
+    fn from(value: VscInlayHintsConfig) -> Self {
+        Self {
+            object_class: value.object_class.unwrap_or(false),
         }
     }
 }
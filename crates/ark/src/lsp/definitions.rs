@@ -6,18 +6,24 @@
 //
 
 use anyhow::Result;
+use ropey::Rope;
 use tower_lsp::lsp_types::GotoDefinitionParams;
 use tower_lsp::lsp_types::GotoDefinitionResponse;
 use tower_lsp::lsp_types::LocationLink;
 use tower_lsp::lsp_types::Range;
 use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
 
+use crate::lsp::class_definitions::extractor_members;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
 use crate::lsp::encoding::convert_position_to_point;
 use crate::lsp::indexer;
+use crate::lsp::indexer::IndexEntryData;
 use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::ExtractOperatorType;
+use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
 pub unsafe fn goto_definition<'a>(
@@ -42,6 +48,13 @@ pub unsafe fn goto_definition<'a>(
     let end = convert_point_to_position(contents, node.end_position());
     let range = Range { start, end };
 
+    // search for a class member, e.g. `self$method`
+    if node.is_identifier() {
+        if let Some(links) = member_definitions(&node, contents)? {
+            return Ok(Some(GotoDefinitionResponse::Link(links)));
+        }
+    }
+
     // search for a reference in the document index
     if node.is_identifier() {
         let symbol = document.contents.node_slice(&node)?.to_string();
@@ -75,3 +88,59 @@ pub unsafe fn goto_definition<'a>(
     let response = GotoDefinitionResponse::Link(vec![link]);
     Ok(Some(response))
 }
+
+/// Finds the definitions of the member on the right-hand side of `$` or `@`.
+/// When the class of the object can't be inferred, all members of workspace
+/// classes with that name are candidates.
+fn member_definitions(node: &Node, contents: &Rope) -> Result<Option<Vec<LocationLink>>> {
+    let Some(extractor) = node.parent() else {
+        return Ok(None);
+    };
+    let accessor = match extractor.node_type() {
+        NodeType::ExtractOperator(ExtractOperatorType::Dollar) => "$",
+        NodeType::ExtractOperator(ExtractOperatorType::At) => "@",
+        _ => return Ok(None),
+    };
+    if extractor.child_by_field_name("rhs") != Some(*node) {
+        return Ok(None);
+    }
+
+    let name = contents.node_slice(node)?.to_string();
+
+    let members = match extractor_members(&extractor, contents) {
+        Some(members) => members,
+        None => {
+            let mut members = Vec::new();
+            let suffix = format!("{accessor}{name}");
+            indexer::map(|path, symbol, entry| {
+                if matches!(entry.data, IndexEntryData::Member { .. }) && symbol.ends_with(&suffix)
+                {
+                    members.push((path.to_path_buf(), entry.clone()));
+                }
+            });
+            members
+        },
+    };
+
+    let links: Vec<LocationLink> = members
+        .into_iter()
+        .filter(|(_, entry)| match &entry.data {
+            IndexEntryData::Member { name: member, .. } => *member == name,
+            _ => false,
+        })
+        .filter_map(|(path, entry)| {
+            Some(LocationLink {
+                origin_selection_range: None,
+                target_uri: Url::from_file_path(path).ok()?,
+                target_range: entry.range,
+                target_selection_range: entry.range,
+            })
+        })
+        .collect();
+
+    if links.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(links))
+}
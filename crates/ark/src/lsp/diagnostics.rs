@@ -7,23 +7,37 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::rc::Rc;
 
 use anyhow::bail;
 use anyhow::Result;
 use harp::utils::is_symbol_valid;
 use harp::utils::sym_quote_invalid;
 use ropey::Rope;
+use serde::Deserialize;
+use serde::Serialize;
 use stdext::*;
 use tower_lsp::lsp_types::Diagnostic;
-use tower_lsp::lsp_types::DiagnosticSeverity;
 use tree_sitter::Node;
 use tree_sitter::Range;
 
 use crate::lsp::declarations::top_level_declare;
+use crate::lsp::diagnostics_calls::diagnose_call_arguments;
+use crate::lsp::diagnostics_calls::CallSignatures;
+use crate::lsp::diagnostics_calls::ConsoleSignatures;
+use crate::lsp::diagnostics_roxygen::roxygen_diagnostics;
+use crate::lsp::diagnostics_rules::apply_rules;
+use crate::lsp::diagnostics_rules::DiagnosticRule;
+use crate::lsp::diagnostics_rules::RuleSeverity;
+use crate::lsp::diagnostics_rules::Suppressions;
 use crate::lsp::diagnostics_syntax::syntax_diagnostics;
+use crate::lsp::diagnostics_usage::diagnose_unreachable_code;
+use crate::lsp::diagnostics_usage::diagnose_unused_bindings;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
 use crate::lsp::indexer;
+use crate::lsp::package::cached_namespace_exports;
+use crate::lsp::package::Package;
 use crate::lsp::state::WorldState;
 use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::node_has_error_or_missing;
@@ -35,6 +49,10 @@ use crate::treesitter::UnaryOperatorType;
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DiagnosticsConfig {
     pub enable: bool,
+
+    /// Severity of rules, indexed by rule ID. Rules that are not listed use
+    /// their default severity.
+    pub rules: HashMap<String, RuleSeverity>,
 }
 
 #[derive(Clone)]
@@ -60,11 +78,52 @@ pub struct DiagnosticContext<'a> {
 
     // Whether or not we're inside of a call's arguments
     pub in_call: bool,
+
+    /// The signatures of the functions defined in the document.
+    pub(crate) call_signatures: Rc<CallSignatures>,
+
+    /// The signatures of the functions available in the session.
+    pub(crate) session_signatures: ConsoleSignatures,
+}
+
+/// Structured data attached to diagnostics so that code actions can fix them
+/// without having to parse the message or the document again
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum DiagnosticData {
+    /// Comparison of a value to `NA`, `NaN`, or `NULL` with `==`. The whole
+    /// comparison at `range` can be replaced by `function(operand)`.
+    InvalidNaComparison {
+        function: String,
+        operand: String,
+        range: tower_lsp::lsp_types::Range,
+    },
+
+    /// Use of `=` in an `if` condition. The operator at `range` can be
+    /// replaced by `==`.
+    AssignmentInCondition { range: tower_lsp::lsp_types::Range },
+
+    /// Symbol that isn't defined in the document, workspace, or session
+    SymbolNotInScope { symbol: String },
+}
+
+impl DiagnosticData {
+    pub(crate) fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Self> {
+        let data = diagnostic.data.clone()?;
+        serde_json::from_value(data).ok()
+    }
+
+    fn attach(self, diagnostic: &mut Diagnostic) {
+        diagnostic.data = serde_json::to_value(self).ok();
+    }
 }
 
 impl Default for DiagnosticsConfig {
     fn default() -> Self {
-        Self { enable: true }
+        Self {
+            enable: true,
+            rules: HashMap::new(),
+        }
     }
 }
 
@@ -78,6 +137,78 @@ impl<'a> DiagnosticContext<'a> {
             installed_packages: HashSet::new(),
             in_formula: false,
             in_call: false,
+            call_signatures: Rc::new(CallSignatures::default()),
+            session_signatures: ConsoleSignatures::default(),
+        }
+    }
+
+    /// Creates a context with a root scope for the document and the symbols
+    /// known to the workspace indexer and to the console session.
+    pub fn from_state(contents: &'a Rope, state: &WorldState) -> Self {
+        let mut context = Self::new(contents);
+
+        // Add a 'root' context for the document.
+        context.document_symbols.push(HashMap::new());
+
+        // Add the current workspace symbols.
+        indexer::map(|_path, symbol, entry| match &entry.data {
+            indexer::IndexEntryData::Function { name, arguments: _ } => {
+                context.workspace_symbols.insert(name.to_string());
+            },
+            indexer::IndexEntryData::Generic { name, arguments: _ } => {
+                context.workspace_symbols.insert(name.to_string());
+            },
+            // Classes are indexed under the symbol of their generator
+            indexer::IndexEntryData::Class { .. } => {
+                context.workspace_symbols.insert(symbol.to_string());
+            },
+            _ => {},
+        });
+
+        for scope in state.console_scopes.iter() {
+            for name in scope.iter() {
+                if is_symbol_valid(name.as_str()) {
+                    context.session_symbols.insert(name.clone());
+                } else {
+                    let name = sym_quote_invalid(name.as_str());
+                    context.session_symbols.insert(name.clone());
+                }
+            }
+        }
+
+        for pkg in state.installed_packages.iter() {
+            context.installed_packages.insert(pkg.clone());
+        }
+
+        context.session_signatures = state.console_signatures.clone();
+
+        // When the workspace is a package, its `NAMESPACE` determines the
+        // scope of the package code even if it isn't loaded in the session
+        if let Some(package) = &state.workspace.package {
+            context.add_package_scope(package, state);
+        }
+
+        context
+    }
+
+    fn add_package_scope(&mut self, package: &Package, state: &WorldState) {
+        // Declared dependencies may legitimately be missing from the
+        // library, e.g. suggested packages
+        self.installed_packages
+            .insert(package.description.name.clone());
+        for pkg in package.dependencies() {
+            self.installed_packages.insert(pkg.clone());
+        }
+
+        for (_pkg, symbol) in package.namespace.imported_symbols.iter() {
+            self.workspace_symbols.insert(symbol.clone());
+        }
+
+        // Exports of attached packages are looked up in the session before
+        // diagnostics are refreshed, see `spawn_diagnostics_refresh_all()`
+        let attached = package.installed_attached_packages(&state.installed_packages);
+        for symbol in cached_namespace_exports(&attached) {
+            self.workspace_symbols.insert(symbol);
         }
     }
 
@@ -105,45 +236,50 @@ impl<'a> DiagnosticContext<'a> {
 }
 
 pub(crate) fn generate_diagnostics(doc: Document, state: WorldState) -> Vec<Diagnostic> {
-    let mut diagnostics = Vec::new();
+    let config = state.config.diagnostics();
+    if !config.enable {
+        return Vec::new();
+    }
 
-    if !state.config.diagnostics.enable {
-        return diagnostics;
+    let context = DiagnosticContext::from_state(&doc.contents, &state);
+    document_diagnostics(&doc, context, &config)
+}
+
+/// Generates diagnostics without an R session, e.g. for `ark lint`. Rules that
+/// need to inspect the session are turned off.
+pub(crate) fn headless_diagnostics(doc: &Document, config: &DiagnosticsConfig) -> Vec<Diagnostic> {
+    if !config.enable {
+        return Vec::new();
     }
 
-    // Check that diagnostics are not disabled in top-level declarations for
-    // this document
-    let decls = top_level_declare(&doc.ast, &doc.contents);
-    if !decls.diagnostics {
-        return diagnostics;
+    let mut config = config.clone();
+    for rule in DiagnosticRule::ALL
+        .iter()
+        .filter(|rule| rule.requires_session())
+    {
+        config
+            .rules
+            .insert(rule.id().to_string(), RuleSeverity::Off);
     }
 
     let mut context = DiagnosticContext::new(&doc.contents);
-
-    // Add a 'root' context for the document.
     context.document_symbols.push(HashMap::new());
 
-    // Add the current workspace symbols.
-    indexer::map(|_path, _symbol, entry| match &entry.data {
-        indexer::IndexEntryData::Function { name, arguments: _ } => {
-            context.workspace_symbols.insert(name.to_string());
-        },
-        _ => {},
-    });
+    document_diagnostics(doc, context, &config)
+}
 
-    for scope in state.console_scopes.iter() {
-        for name in scope.iter() {
-            if is_symbol_valid(name.as_str()) {
-                context.session_symbols.insert(name.clone());
-            } else {
-                let name = sym_quote_invalid(name.as_str());
-                context.session_symbols.insert(name.clone());
-            }
-        }
-    }
+fn document_diagnostics(
+    doc: &Document,
+    mut context: DiagnosticContext,
+    config: &DiagnosticsConfig,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
 
-    for pkg in state.installed_packages.iter() {
-        context.installed_packages.insert(pkg.clone());
+    // Check that diagnostics are not disabled in top-level declarations for
+    // this document
+    let decls = top_level_declare(&doc.ast, &doc.contents);
+    if !decls.diagnostics {
+        return diagnostics;
     }
 
     // Start iterating through the nodes.
@@ -155,13 +291,21 @@ pub(crate) fn generate_diagnostics(doc: Document, state: WorldState) -> Vec<Diag
         Err(err) => log::error!("Error while generating syntax diagnostics: {err:?}"),
     }
 
+    // Collect the signatures of functions defined in the document
+    context.call_signatures = Rc::new(CallSignatures::new(root, &context));
+
     // Collect semantic related diagnostics
     match semantic_diagnostics(root, &mut context) {
         Ok(mut semantic_diagnostics) => diagnostics.append(&mut semantic_diagnostics),
         Err(err) => log::error!("Error while generating semantic diagnostics: {err:?}"),
     }
 
-    diagnostics
+    // Check roxygen documentation against the documented functions
+    diagnostics.append(&mut roxygen_diagnostics(root, &doc.contents));
+
+    // Apply the configured severities and `# ark: ignore` comments
+    let suppressions = Suppressions::from_document(root, &doc.contents);
+    apply_rules(diagnostics, &config.rules, &suppressions)
 }
 
 fn semantic_diagnostics(
@@ -270,6 +414,8 @@ fn recurse_function(
         recurse(body, context, diagnostics)?;
     }
 
+    diagnose_unused_bindings(node, context, diagnostics)?;
+
     Ok(())
 }
 
@@ -619,7 +765,7 @@ fn recurse_namespace(
         let range = lhs.range();
         let range = convert_tree_sitter_range_to_lsp_range(context.contents, range);
         let message = format!("Package '{}' is not installed.", package);
-        let diagnostic = Diagnostic::new_simple(range, message);
+        let diagnostic = DiagnosticRule::PackageNotInstalled.diagnostic(range, message);
         diagnostics.push(diagnostic);
     }
 
@@ -675,6 +821,8 @@ fn recurse_braced_expression(
         recurse(child, context, diagnostics)?;
     }
 
+    diagnose_unreachable_code(node, context, diagnostics)?;
+
     ().ok()
 }
 
@@ -814,6 +962,7 @@ fn recurse_call(
 ) -> Result<()> {
     // Run diagnostics on the call itself
     dispatch(node, context, diagnostics);
+    diagnose_call_arguments(node, context, diagnostics)?;
 
     // Recurse into the callee.
     let callee = node.child(0).into_result()?;
@@ -919,22 +1068,45 @@ fn check_invalid_na_comparison(
         return false.ok();
     }
 
+    let lhs = node.child_by_field_name("lhs");
+    let rhs = node.child_by_field_name("rhs");
+
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
         let contents = context.contents.node_slice(&child)?.to_string();
         let contents = contents.as_str();
 
         if matches!(contents, "NA" | "NaN" | "NULL") {
-            let message = match contents {
-                "NA" => "Consider using `is.na()` to check `NA` values",
-                "NaN" => "Consider using `is.nan()` to check `NaN` values.",
-                "NULL" => "Consider using `is.null()` to check `NULL` values.",
+            let (message, function) = match contents {
+                "NA" => ("Consider using `is.na()` to check `NA` values", "is.na"),
+                "NaN" => ("Consider using `is.nan()` to check `NaN` values.", "is.nan"),
+                "NULL" => (
+                    "Consider using `is.null()` to check `NULL` values.",
+                    "is.null",
+                ),
                 _ => continue,
             };
             let range = child.range();
             let range = convert_tree_sitter_range_to_lsp_range(context.contents, range);
-            let mut diagnostic = Diagnostic::new_simple(range, message.into());
-            diagnostic.severity = Some(DiagnosticSeverity::INFORMATION);
+            let mut diagnostic =
+                DiagnosticRule::InvalidNaComparison.diagnostic(range, message.into());
+
+            // The value being compared is the other side of the comparison
+            let operand = if Some(child) == lhs { rhs } else { lhs };
+            if let Some(operand) = operand {
+                let operand = context.contents.node_slice(&operand)?.to_string();
+                if !matches!(operand.as_str(), "NA" | "NaN" | "NULL") {
+                    let range =
+                        convert_tree_sitter_range_to_lsp_range(context.contents, node.range());
+                    DiagnosticData::InvalidNaComparison {
+                        function: function.to_string(),
+                        operand,
+                        range,
+                    }
+                    .attach(&mut diagnostic);
+                }
+            }
+
             diagnostics.push(diagnostic);
         }
     }
@@ -968,7 +1140,13 @@ fn check_unexpected_assignment_in_if_conditional(
     let range = condition.range();
     let range = convert_tree_sitter_range_to_lsp_range(context.contents, range);
     let message = "Unexpected '='; use '==' to compare values for equality.";
-    let diagnostic = Diagnostic::new_simple(range, message.into());
+    let mut diagnostic = DiagnosticRule::AssignmentInCondition.diagnostic(range, message.into());
+
+    if let Some(operator) = condition.child_by_field_name("operator") {
+        let range = convert_tree_sitter_range_to_lsp_range(context.contents, operator.range());
+        DiagnosticData::AssignmentInCondition { range }.attach(&mut diagnostic);
+    }
+
     diagnostics.push(diagnostic);
 
     true.ok()
@@ -1017,8 +1195,8 @@ fn check_symbol_in_scope(
     let range = convert_tree_sitter_range_to_lsp_range(context.contents, range);
     let identifier = context.contents.node_slice(&node)?.to_string();
     let message = format!("No symbol named '{}' in scope.", identifier);
-    let mut diagnostic = Diagnostic::new_simple(range, message);
-    diagnostic.severity = Some(DiagnosticSeverity::WARNING);
+    let mut diagnostic = DiagnosticRule::SymbolNotInScope.diagnostic(range, message);
+    DiagnosticData::SymbolNotInScope { symbol: identifier }.attach(&mut diagnostic);
     diagnostics.push(diagnostic);
 
     true.ok()
@@ -1028,11 +1206,17 @@ fn check_symbol_in_scope(
 mod tests {
     use harp::eval::RParseEvalOptions;
     use once_cell::sync::Lazy;
+    use tower_lsp::lsp_types::DiagnosticSeverity;
+    use tower_lsp::lsp_types::NumberOrString;
     use tower_lsp::lsp_types::Position;
 
     use crate::interface::console_inputs;
     use crate::lsp::diagnostics::generate_diagnostics;
+    use crate::lsp::diagnostics_rules::RuleSeverity;
     use crate::lsp::documents::Document;
+    use crate::lsp::package::Description;
+    use crate::lsp::package::Namespace;
+    use crate::lsp::package::Package;
     use crate::lsp::state::WorldState;
     use crate::test::r_test;
 
@@ -1045,6 +1229,7 @@ mod tests {
         WorldState {
             console_scopes: inputs.console_scopes,
             installed_packages: inputs.installed_packages,
+            console_signatures: inputs.console_signatures,
             ..Default::default()
         }
     }
@@ -1358,4 +1543,76 @@ foo
             insta::assert_snapshot!(diagnostic.message);
         })
     }
+
+    #[test]
+    fn test_rules_configuration_and_suppression() {
+        r_test(|| {
+            let code = "
+                foo # ark: ignore[symbol-not-in-scope]
+                bar
+                if (x == NA) 1
+            ";
+            let document = Document::new(code, None);
+
+            let diagnostics = generate_diagnostics(document.clone(), DEFAULT_STATE.clone());
+            let mut codes: Vec<_> = diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.range.start.line, diagnostic.code.clone()))
+                .collect();
+            codes.sort_by_key(|(line, code)| (*line, format!("{code:?}")));
+
+            let code = |id: &str| Some(NumberOrString::String(String::from(id)));
+            assert_eq!(codes, vec![
+                (2, code("symbol-not-in-scope")),
+                (3, code("invalid-na-comparison")),
+                (3, code("symbol-not-in-scope")),
+            ]);
+
+            let mut state = DEFAULT_STATE.clone();
+            state
+                .config
+                .diagnostics
+                .rules
+                .insert(String::from("symbol-not-in-scope"), RuleSeverity::Off);
+            state
+                .config
+                .project
+                .diagnostics
+                .rules
+                .insert(String::from("invalid-na-comparison"), RuleSeverity::Error);
+
+            let diagnostics = generate_diagnostics(document, state);
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        })
+    }
+
+    #[test]
+    fn test_package_namespace_imports_are_in_scope() {
+        r_test(|| {
+            let code = "
+                abort('oops')
+                mypkg:::helper()
+                notimported()
+            ";
+            let document = Document::new(code, None);
+
+            let mut state = DEFAULT_STATE.clone();
+            state.workspace.package = Some(Package {
+                description: Description {
+                    name: String::from("mypkg"),
+                    ..Default::default()
+                },
+                namespace: Namespace {
+                    imported_symbols: vec![(String::from("rlang"), String::from("abort"))],
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+
+            let diagnostics = generate_diagnostics(document, state);
+            assert_eq!(diagnostics.len(), 1);
+            assert!(diagnostics[0].message.contains("notimported"));
+        })
+    }
 }
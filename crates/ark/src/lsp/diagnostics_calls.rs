@@ -0,0 +1,704 @@
+//
+// diagnostics_calls.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use harp::r_null;
+use lazy_static::lazy_static;
+use ropey::Rope;
+use tower_lsp::lsp_types::Diagnostic;
+use tree_sitter::Node;
+
+use crate::lsp::diagnostics::DiagnosticContext;
+use crate::lsp::diagnostics_rules::DiagnosticRule;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::references::node_local_binding;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+lazy_static! {
+    /// Signatures of the console scopes converted from R, by scope key. Only
+    /// scopes whose version changed are sent again by R.
+    static ref CONSOLE_SCOPES: Mutex<HashMap<String, ScopeSignatures>> = Default::default();
+}
+
+/// Signature of a function, used to validate the arguments of calls
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CallSignature {
+    /// Names of the formal parameters, in order, including `...`
+    pub parameters: Vec<String>,
+
+    /// Parameters without a default value that must be supplied
+    pub required: HashSet<String>,
+}
+
+/// Signatures of the functions defined in a document
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CallSignatures {
+    /// Functions defined once at top level of the document
+    pub document: HashMap<String, CallSignature>,
+}
+
+/// Signatures of the functions visible from the console. Snapshot taken when
+/// the console is idle so that diagnostics don't need to query R, see
+/// `console_call_signatures()`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ConsoleSignatures {
+    /// Scopes from the global environment to the base package
+    scopes: Vec<ScopeSignatures>,
+}
+
+#[derive(Clone, Debug)]
+struct ScopeSignatures {
+    /// Package attached as this scope, if any
+    package: Option<String>,
+    version: i32,
+    signatures: Arc<HashMap<String, CallSignature>>,
+}
+
+impl ConsoleSignatures {
+    /// Looks up the function `name` on the search path, or in the attached
+    /// package `pkg` for names like `pkg::name`
+    pub(crate) fn get(&self, name: &str) -> Option<&CallSignature> {
+        if let Some((package, name)) = name.split_once("::") {
+            // Internal functions accessed with `:::` aren't collected
+            if name.starts_with(':') {
+                return None;
+            }
+            return self
+                .scopes
+                .iter()
+                .find(|scope| scope.package.as_deref() == Some(package))
+                .and_then(|scope| scope.signatures.get(name));
+        }
+
+        // Calls resolve to the first function found on the search path
+        self.scopes
+            .iter()
+            .find_map(|scope| scope.signatures.get(name))
+    }
+}
+
+impl CallSignature {
+    fn from_function_definition(node: &Node, contents: &Rope) -> Option<Self> {
+        let parameters = node.child_by_field_name("parameters")?;
+
+        let mut signature = CallSignature::default();
+
+        let mut cursor = parameters.walk();
+        for parameter in parameters.children_by_field_name("parameter", &mut cursor) {
+            let name = parameter.child_by_field_name("name")?;
+            let name = if name.node_type() == NodeType::Dots {
+                String::from("...")
+            } else {
+                node_symbol_name(&name, contents)?
+            };
+
+            if name != "..." && parameter.child_by_field_name("default").is_none() {
+                signature.required.insert(name.clone());
+            }
+            signature.parameters.push(name);
+        }
+
+        // Arguments checked with `missing()` are optional, and functions
+        // inspecting their call may handle arguments themselves
+        if let Some(body) = node.child_by_field_name("body") {
+            let mut cursor = body.walk();
+            cursor.recurse(|node| {
+                match callee_name(&node, contents).as_deref() {
+                    Some("missing") => {
+                        if let Some(name) = first_argument_symbol(&node, contents) {
+                            signature.required.remove(&name);
+                        }
+                    },
+                    Some("nargs" | "match.call" | "sys.call") => signature.required.clear(),
+                    _ => {},
+                }
+                true
+            });
+        }
+
+        Some(signature)
+    }
+
+    fn dots(&self) -> Option<usize> {
+        self.parameters.iter().position(|name| name == "...")
+    }
+}
+
+impl CallSignatures {
+    /// Collects the signatures of functions defined in the document. The
+    /// signatures of session functions come from the snapshot taken when the
+    /// console was last idle, see `ConsoleSignatures`.
+    pub(crate) fn new(root: Node, context: &DiagnosticContext) -> Self {
+        let contents = context.contents;
+        let mut signatures = Self::default();
+
+        let mut definitions: HashMap<String, Option<CallSignature>> = HashMap::new();
+
+        let mut cursor = root.walk();
+        for child in root.children(&mut cursor) {
+            let Some(target) = node_local_binding(&child) else {
+                continue;
+            };
+            let Some(name) = node_symbol_name(&target, contents) else {
+                continue;
+            };
+
+            let value = match child.node_type() {
+                NodeType::BinaryOperator(BinaryOperatorType::RightAssignment) => {
+                    child.child_by_field_name("lhs")
+                },
+                _ => child.child_by_field_name("rhs"),
+            };
+            let signature = value
+                .filter(|value| value.is_function_definition())
+                .and_then(|value| CallSignature::from_function_definition(&value, contents));
+
+            // Only consider functions that are defined once
+            match definitions.get_mut(&name) {
+                Some(definition) => *definition = None,
+                None => {
+                    definitions.insert(name, signature);
+                },
+            }
+        }
+
+        signatures.document = definitions
+            .into_iter()
+            .filter_map(|(name, signature)| signature.map(|signature| (name, signature)))
+            .collect();
+
+        signatures
+    }
+}
+
+/// Returns the signatures of the functions visible from the global
+/// environment. Must be called on the R thread, typically when the console is
+/// idle. Scopes that didn't change since the last call, e.g. attached
+/// packages, are reused rather than inspected and converted again.
+pub(crate) fn console_call_signatures() -> anyhow::Result<ConsoleSignatures> {
+    let mut cache = CONSOLE_SCOPES.lock().unwrap();
+
+    let (keys, versions): (Vec<String>, Vec<i32>) = cache
+        .iter()
+        .map(|(key, scope)| (key.clone(), scope.version))
+        .unzip();
+
+    let result = RFunction::new("", ".ps.console_call_signatures")
+        .param("keys", keys)
+        .param("versions", RObject::try_from(&versions)?)
+        .call()?;
+    let result = Vec::<RObject>::try_from(result)?;
+
+    let mut scopes = Vec::new();
+    let mut keys = HashSet::new();
+
+    for scope in result.into_iter() {
+        let mut scope = HashMap::<String, RObject>::try_from(scope)?;
+        let (Some(key), Some(package), Some(version), Some(signatures)) = (
+            scope.remove("key"),
+            scope.remove("package"),
+            scope.remove("version"),
+            scope.remove("signatures"),
+        ) else {
+            continue;
+        };
+        let key = Option::<String>::try_from(key)?;
+
+        let scope = if signatures.sexp == r_null() {
+            // R only omits the signatures of the versions we already have
+            let Some(scope) = key.as_ref().and_then(|key| cache.get(key)) else {
+                continue;
+            };
+            scope.clone()
+        } else {
+            ScopeSignatures {
+                package: Option::<String>::try_from(package)?,
+                version: i32::try_from(version)?,
+                signatures: Arc::new(scope_signatures(signatures)?),
+            }
+        };
+
+        if let Some(key) = key {
+            cache.insert(key.clone(), scope.clone());
+            keys.insert(key);
+        }
+        scopes.push(scope);
+    }
+
+    // Forget scopes that were detached
+    cache.retain(|key, _| keys.contains(key));
+
+    Ok(ConsoleSignatures { scopes })
+}
+
+fn scope_signatures(signatures: RObject) -> anyhow::Result<HashMap<String, CallSignature>> {
+    let signatures = HashMap::<String, RObject>::try_from(signatures)?;
+
+    let mut out = HashMap::new();
+
+    for (name, signature) in signatures.into_iter() {
+        if signature.sexp == r_null() {
+            continue;
+        }
+
+        let mut signature = HashMap::<String, RObject>::try_from(signature)?;
+        let (Some(parameters), Some(required)) =
+            (signature.remove("names"), signature.remove("required"))
+        else {
+            continue;
+        };
+
+        out.insert(name, CallSignature {
+            parameters: Vec::<String>::try_from(parameters)?,
+            required: Vec::<String>::try_from(required)?.into_iter().collect(),
+        });
+    }
+
+    Ok(out)
+}
+
+/// Returns the name of the function called by `node` if it's a simple
+/// identifier or a namespaced function like `pkg::fn`
+fn callee_name(node: &Node, contents: &Rope) -> Option<String> {
+    if !node.is_call() {
+        return None;
+    }
+    let function = node.child_by_field_name("function")?;
+
+    if function.is_identifier() {
+        return node_symbol_name(&function, contents);
+    }
+
+    if function.is_namespace_operator() {
+        let package = function.child_by_field_name("lhs")?;
+        let name = function.child_by_field_name("rhs")?;
+        let package = contents.node_slice(&package).ok()?.to_string();
+        let operator = function.child_by_field_name("operator")?;
+        let operator = contents.node_slice(&operator).ok()?.to_string();
+        let name = node_symbol_name(&name, contents)?;
+        return Some(format!("{package}{operator}{name}"));
+    }
+
+    None
+}
+
+fn first_argument_symbol(node: &Node, contents: &Rope) -> Option<String> {
+    let arguments = node.child_by_field_name("arguments")?;
+    let mut cursor = arguments.walk();
+    let argument = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .next()?;
+    let value = argument.child_by_field_name("value")?;
+    node_symbol_name(&value, contents)
+}
+
+/// Checks the arguments of a call against the signature of the callee:
+/// unknown named arguments, duplicate arguments, too many positional
+/// arguments, and missing required arguments.
+pub(crate) fn diagnose_call_arguments(
+    node: Node,
+    context: &DiagnosticContext,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> anyhow::Result<()> {
+    let contents = context.contents;
+
+    let Some(callee) = callee_name(&node, contents) else {
+        return Ok(());
+    };
+    let Some(signature) = lookup_signature(&callee, context) else {
+        return Ok(());
+    };
+    let Some(arguments) = node.child_by_field_name("arguments") else {
+        return Ok(());
+    };
+
+    let dots = signature.dots();
+
+    // Parameters that can be matched by position or partially by name
+    let matchable: Vec<&String> = match dots {
+        Some(dots) => signature.parameters[..dots].iter().collect(),
+        None => signature.parameters.iter().collect(),
+    };
+
+    let mut matched: HashSet<&str> = HashSet::new();
+    let mut supplied: HashSet<String> = HashSet::new();
+    let mut positional: Vec<Node> = Vec::new();
+    let mut forwards_dots = false;
+
+    let mut named: Vec<(String, Node)> = Vec::new();
+
+    let mut cursor = arguments.walk();
+    for argument in arguments.children_by_field_name("argument", &mut cursor) {
+        let value = argument.child_by_field_name("value");
+
+        // Arguments forwarded with `...` may fill any parameter
+        if value.is_some_and(|value| value.node_type() == NodeType::Dots) {
+            forwards_dots = true;
+        }
+
+        match argument.child_by_field_name("name") {
+            Some(name) => {
+                let Some(symbol) = node_symbol_name(&name, contents) else {
+                    continue;
+                };
+                named.push((symbol, name));
+            },
+            None => positional.push(argument),
+        }
+    }
+
+    // Exact matching first, then partial matching
+    for (symbol, name) in named.iter() {
+        let is_parameter = signature.parameters.contains(symbol);
+
+        if !supplied.insert(symbol.clone()) && (is_parameter || dots.is_none()) {
+            let message = format!("Argument '{symbol}' is supplied more than once.");
+            diagnostics.push(call_diagnostic(
+                DiagnosticRule::DuplicateArgument,
+                name,
+                message,
+                contents,
+            ));
+            continue;
+        }
+
+        if is_parameter {
+            matched.insert(symbol.as_str());
+        }
+    }
+
+    for (symbol, name) in named.iter() {
+        if signature.parameters.contains(symbol) {
+            continue;
+        }
+
+        let candidates: Vec<&&String> = matchable
+            .iter()
+            .filter(|parameter| {
+                parameter.starts_with(symbol.as_str()) && !matched.contains(parameter.as_str())
+            })
+            .collect();
+
+        match candidates.as_slice() {
+            [parameter] => {
+                matched.insert(parameter.as_str());
+            },
+            [] if dots.is_none() => {
+                let message =
+                    format!("Argument '{symbol}' doesn't match any parameter of '{callee}()'.");
+                diagnostics.push(call_diagnostic(
+                    DiagnosticRule::UnknownArgument,
+                    name,
+                    message,
+                    contents,
+                ));
+            },
+            // Ambiguous partial matches, or arguments collected by `...`
+            _ => {},
+        }
+    }
+
+    if forwards_dots {
+        return Ok(());
+    }
+
+    // The lhs of a pipe is passed as first argument
+    let n_implicit = usize::from(piped_placeholder(&node, contents).is_some());
+
+    let available: Vec<&String> = matchable
+        .iter()
+        .filter(|parameter| !matched.contains(parameter.as_str()))
+        .copied()
+        .collect();
+
+    let n_positional = positional.len() + n_implicit;
+
+    if dots.is_none() && n_positional > available.len() {
+        let extra = available.len().saturating_sub(n_implicit);
+        if let Some(argument) = positional.get(extra) {
+            let message = format!(
+                "Too many arguments in call to '{callee}()': expected at most {}.",
+                signature.parameters.len()
+            );
+            diagnostics.push(call_diagnostic(
+                DiagnosticRule::TooManyArguments,
+                argument,
+                message,
+                contents,
+            ));
+        }
+    }
+
+    for parameter in available.iter().skip(n_positional) {
+        if !signature.required.contains(parameter.as_str()) {
+            continue;
+        }
+
+        let Some(function) = node.child_by_field_name("function") else {
+            continue;
+        };
+        let message = format!("Missing required argument '{parameter}' in call to '{callee}()'.");
+        diagnostics.push(call_diagnostic(
+            DiagnosticRule::MissingArgument,
+            &function,
+            message,
+            contents,
+        ));
+    }
+
+    Ok(())
+}
+
+fn lookup_signature<'a>(name: &str, context: &'a DiagnosticContext) -> Option<&'a CallSignature> {
+    let signatures = context.call_signatures.as_ref();
+
+    if name.contains("::") {
+        return context.session_signatures.get(name);
+    }
+
+    // Functions bound in a local scope shadow global definitions, and we don't
+    // know their signature
+    if context
+        .document_symbols
+        .iter()
+        .skip(1)
+        .any(|symbols| symbols.contains_key(name))
+    {
+        return None;
+    }
+
+    if let Some(signature) = signatures.document.get(name) {
+        return Some(signature);
+    }
+
+    let defined_in_document = context
+        .document_symbols
+        .first()
+        .is_some_and(|symbols| symbols.contains_key(name));
+    if defined_in_document || context.workspace_symbols.contains(name) {
+        return None;
+    }
+
+    context.session_signatures.get(name)
+}
+
+/// If the call is the right-hand side of a pipe that inserts its left-hand
+/// side as first argument, returns the placeholder of that pipe: `_` for `|>`
+/// and `.` for `%>%`.
+pub(crate) fn piped_placeholder(node: &Node, contents: &Rope) -> Option<&'static str> {
+    let parent = node.parent()?;
+    if parent.child_by_field_name("rhs") != Some(*node) {
+        return None;
+    }
+
+    let placeholder = if parent.is_native_pipe_operator() {
+        "_"
+    } else if parent.is_magrittr_pipe_operator(contents).unwrap_or(false) {
+        "."
+    } else {
+        return None;
+    };
+
+    // The lhs is passed in place of the placeholder instead, if any
+    let Some(arguments) = node.child_by_field_name("arguments") else {
+        return Some(placeholder);
+    };
+    let mut cursor = arguments.walk();
+    let has_placeholder = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .filter_map(|argument| argument.child_by_field_name("value"))
+        .any(|value| {
+            value.is_identifier() &&
+                contents
+                    .node_slice(&value)
+                    .is_ok_and(|text| text.to_string() == placeholder)
+        });
+
+    (!has_placeholder).then_some(placeholder)
+}
+
+fn call_diagnostic(
+    rule: DiagnosticRule,
+    node: &Node,
+    message: String,
+    contents: &Rope,
+) -> Diagnostic {
+    let range = convert_tree_sitter_range_to_lsp_range(contents, node.range());
+    rule.diagnostic(range, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use harp::eval::parse_eval_global;
+    use tower_lsp::lsp_types::NumberOrString;
+
+    use crate::interface::console_inputs;
+    use crate::lsp::diagnostics::generate_diagnostics;
+    use crate::lsp::diagnostics_calls::console_call_signatures;
+    use crate::lsp::diagnostics_calls::ConsoleSignatures;
+    use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
+    use crate::test::r_test;
+
+    fn call_diagnostics(text: &str) -> Vec<(u32, String)> {
+        let inputs = console_inputs().unwrap();
+        let state = WorldState {
+            console_scopes: inputs.console_scopes,
+            installed_packages: inputs.installed_packages,
+            console_signatures: inputs.console_signatures,
+            ..Default::default()
+        };
+
+        let document = Document::new(text, None);
+
+        generate_diagnostics(document, state)
+            .into_iter()
+            .filter_map(|diagnostic| {
+                let Some(NumberOrString::String(code)) = diagnostic.code else {
+                    return None;
+                };
+                if !code.ends_with("-argument") && code != "too-many-arguments" {
+                    return None;
+                }
+                Some((diagnostic.range.start.line, code))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_call_arguments_document_function() {
+        r_test(|| {
+            let text = "
+f <- function(x, y = 1) x + y
+f(1, 2, 3)
+f(z = 1)
+f(x = 1, x = 2)
+f(1)
+f(y = 2, 1)
+1 |> f(2)
+1 |> f(2, 3)
+";
+            assert_eq!(call_diagnostics(text), vec![
+                (2, String::from("too-many-arguments")),
+                (3, String::from("unknown-argument")),
+                (3, String::from("missing-argument")),
+                (4, String::from("duplicate-argument")),
+                (8, String::from("too-many-arguments")),
+            ]);
+        })
+    }
+
+    #[test]
+    fn test_call_arguments_partial_matching_and_dots() {
+        r_test(|| {
+            let text = "
+f <- function(value, ...) value
+f(val = 1)
+f(1, 2, 3, other = 4)
+g <- function(x) x
+g(...)
+";
+            assert!(call_diagnostics(text).is_empty());
+        })
+    }
+
+    #[test]
+    fn test_call_arguments_missing_checks() {
+        r_test(|| {
+            let text = "
+f <- function(x, y) if (missing(y)) x else x + y
+f(1)
+g <- function(x) nargs()
+g()
+";
+            assert!(call_diagnostics(text).is_empty());
+        })
+    }
+
+    #[test]
+    fn test_call_arguments_session_function() {
+        r_test(|| {
+            let text = "
+identity(1, 2)
+nchar()
+paste(1, sepp = '')
+base::identity(1, 2)
+";
+            assert_eq!(call_diagnostics(text), vec![
+                (1, String::from("too-many-arguments")),
+                (2, String::from("missing-argument")),
+                (4, String::from("too-many-arguments")),
+            ]);
+        })
+    }
+
+    #[test]
+    fn test_call_arguments_console_snapshot() {
+        r_test(|| {
+            // Functions defined in the console are picked up by the snapshot
+            parse_eval_global("ark_test_console_fn <- function(x) x").unwrap();
+            let diagnostics = call_diagnostics("ark_test_console_fn(1, 2)");
+
+            parse_eval_global("ark_test_console_fn <- function(x, y) x").unwrap();
+            let redefined = call_diagnostics("ark_test_console_fn(1, 2)");
+
+            parse_eval_global("remove(ark_test_console_fn)").unwrap();
+
+            assert_eq!(diagnostics, vec![(0, String::from("too-many-arguments"))]);
+            assert!(redefined.is_empty());
+        })
+    }
+
+    #[test]
+    fn test_console_call_signatures_reuses_scopes() {
+        r_test(|| {
+            let base = |signatures: &ConsoleSignatures| {
+                let scope = signatures
+                    .scopes
+                    .iter()
+                    .find(|scope| scope.package.as_deref() == Some("base"))
+                    .unwrap();
+                scope.signatures.clone()
+            };
+
+            // Unchanged scopes are neither inspected nor converted again
+            let first = console_call_signatures().unwrap();
+            let second = console_call_signatures().unwrap();
+            assert!(Arc::ptr_eq(&base(&first), &base(&second)));
+
+            assert!(second.get("paste").is_some());
+            assert!(second.get("base::paste").is_some());
+            assert!(second.get("base:::paste").is_none());
+        })
+    }
+
+    #[test]
+    fn test_call_arguments_redefined_function() {
+        r_test(|| {
+            let text = "
+f <- function(x) x
+f <- function(x, y) x + y
+f(1, 2)
+";
+            assert!(call_diagnostics(text).is_empty());
+        })
+    }
+}
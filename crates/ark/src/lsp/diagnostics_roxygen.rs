@@ -0,0 +1,211 @@
+//
+// diagnostics_roxygen.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashSet;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::Range;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::diagnostics_rules::DiagnosticRule;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::roxygen::assigned_function;
+use crate::lsp::roxygen::function_formals;
+use crate::lsp::roxygen::RoxygenBlock;
+use crate::treesitter::node_has_error_or_missing;
+use crate::treesitter::NodeTypeExt;
+
+/// Checks the `@param` tags of the roxygen blocks documenting top-level
+/// functions against the formals of these functions:
+///
+/// - `@param` tags for arguments the function doesn't have are flagged, unless
+///   the topic is shared with other functions with `@rdname` or `@describeIn`.
+///   The parameters of all functions of the topic may then be documented in
+///   any of their blocks.
+/// - Formals without a `@param` tag are flagged, unless the block doesn't
+///   generate documentation or documents its parameters in another way, e.g.
+///   with `@inheritParams`.
+pub(crate) fn roxygen_diagnostics(root: Node, contents: &Rope) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut documented: Vec<(Option<String>, RoxygenBlock, Node)> = Vec::new();
+
+    let mut cursor = root.walk();
+    for node in root.named_children(&mut cursor) {
+        if node.is_comment() || node_has_error_or_missing(&node) {
+            continue;
+        }
+
+        let Some(function) = assigned_function(&node) else {
+            continue;
+        };
+        let Some(block) = RoxygenBlock::documenting(&node, contents) else {
+            continue;
+        };
+
+        let name = node
+            .child_by_field_name("lhs")
+            .and_then(|lhs| node_symbol_name(&lhs, contents));
+        documented.push((name, block, function));
+    }
+
+    // Topics other blocks are merged into
+    let shared_topics: HashSet<&str> = documented
+        .iter()
+        .filter_map(|(_, block, _)| block.shared_topic())
+        .collect();
+
+    for (name, block, function) in documented.iter() {
+        let is_shared = block.documents_params_elsewhere() ||
+            name.as_deref()
+                .is_some_and(|name| shared_topics.contains(name));
+
+        diagnose_params(block, function, is_shared, contents, &mut diagnostics);
+    }
+
+    diagnostics
+}
+
+/// `is_shared` tells whether the topic of the block is shared with other
+/// functions, whose parameters may be documented in the block
+fn diagnose_params(
+    block: &RoxygenBlock,
+    function: &Node,
+    is_shared: bool,
+    contents: &Rope,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let formals = function_formals(function, contents);
+    let params = block.params();
+
+    for param in params.iter().filter(|_| !is_shared) {
+        if formals.iter().any(|(formal, _node)| *formal == param.name) {
+            continue;
+        }
+
+        let start = Point::new(param.row, param.column);
+        let end = Point::new(param.row, param.column + param.name.len());
+        let range = Range::new(
+            convert_point_to_position(contents, start),
+            convert_point_to_position(contents, end),
+        );
+
+        let message = format!(
+            "Documented parameter `{}` is not an argument of the function.",
+            param.name
+        );
+        diagnostics.push(DiagnosticRule::UnknownRoxygenParam.diagnostic(range, message));
+    }
+
+    if !block.requires_params() {
+        return;
+    }
+
+    for (formal, node) in formals.iter() {
+        if params.iter().any(|param| param.name == *formal) {
+            continue;
+        }
+
+        let range = convert_tree_sitter_range_to_lsp_range(contents, node.range());
+        let message = format!("Argument `{formal}` is not documented with `@param`.");
+        diagnostics.push(DiagnosticRule::UndocumentedParam.diagnostic(range, message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::NumberOrString;
+
+    use crate::lsp::diagnostics::headless_diagnostics;
+    use crate::lsp::diagnostics::DiagnosticsConfig;
+    use crate::lsp::documents::Document;
+
+    fn roxygen_diagnostics(text: &str) -> Vec<(u32, u32, String)> {
+        let document = Document::new(text, None);
+
+        headless_diagnostics(&document, &DiagnosticsConfig::default())
+            .into_iter()
+            .filter_map(|diagnostic| {
+                let Some(NumberOrString::String(code)) = diagnostic.code else {
+                    return None;
+                };
+                if !code.ends_with("-param") {
+                    return None;
+                }
+                let start = diagnostic.range.start;
+                Some((start.line, start.character, code))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_roxygen_unknown_and_undocumented_params() {
+        let text = "
+#' Title
+#' @param x,z Values.
+#' @param ... Passed on.
+f <- function(x, y, ...) NULL
+";
+        assert_eq!(roxygen_diagnostics(text), vec![
+            (2, 12, String::from("unknown-roxygen-param")),
+            (4, 17, String::from("undocumented-param")),
+        ]);
+    }
+
+    #[test]
+    fn test_roxygen_params_documented_elsewhere() {
+        // Parameters are inherited
+        let text = "
+#' Title
+#' @inheritParams g
+f <- function(x) NULL
+";
+        assert_eq!(roxygen_diagnostics(text), vec![]);
+
+        // Topics shared with other functions document their parameters in
+        // any of their blocks
+        let text = "
+#' Title
+#' @param x A value.
+#' @param y Another value.
+f <- function(x) NULL
+
+#' @rdname f
+#' @param z A third value.
+g <- function(x, y, z) NULL
+
+#' @describeIn f Variant.
+#' @param w A fourth value.
+h <- function(w) NULL
+
+#' Title
+#' @param y Unknown.
+k <- function(x) NULL
+";
+        assert_eq!(roxygen_diagnostics(text), vec![
+            (15, 10, String::from("unknown-roxygen-param")),
+            (16, 14, String::from("undocumented-param")),
+        ]);
+
+        // No documentation is generated, but unknown parameters are still flagged
+        let text = "
+#' @param y A value.
+f <- function(x) NULL
+
+# Not roxygen
+g <- function(x) NULL
+";
+        assert_eq!(roxygen_diagnostics(text), vec![(
+            1,
+            10,
+            String::from("unknown-roxygen-param")
+        )]);
+    }
+}
@@ -0,0 +1,443 @@
+//
+// diagnostics_rules.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use ropey::Rope;
+use serde::Deserialize;
+use serde::Serialize;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+use tower_lsp::lsp_types::NumberOrString;
+use tower_lsp::lsp_types::Range;
+use tree_sitter::Node;
+
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::NodeTypeExt;
+
+/// Semantic checks performed by the diagnostics engine. Each rule has a
+/// stable ID that is used in the configuration and in suppression comments.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub(crate) enum DiagnosticRule {
+    InvalidNaComparison,
+    AssignmentInCondition,
+    SymbolNotInScope,
+    PackageNotInstalled,
+    UnusedVariable,
+    UnusedArgument,
+    UnreachableCode,
+    UnknownArgument,
+    DuplicateArgument,
+    TooManyArguments,
+    MissingArgument,
+    UnknownRoxygenParam,
+    UndocumentedParam,
+}
+
+/// Severity of a rule as set in the configuration
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSeverity {
+    Off,
+    Hint,
+    Information,
+    Warning,
+    Error,
+}
+
+impl DiagnosticRule {
+    pub(crate) const ALL: &'static [DiagnosticRule] = &[
+        DiagnosticRule::InvalidNaComparison,
+        DiagnosticRule::AssignmentInCondition,
+        DiagnosticRule::SymbolNotInScope,
+        DiagnosticRule::PackageNotInstalled,
+        DiagnosticRule::UnusedVariable,
+        DiagnosticRule::UnusedArgument,
+        DiagnosticRule::UnreachableCode,
+        DiagnosticRule::UnknownArgument,
+        DiagnosticRule::DuplicateArgument,
+        DiagnosticRule::TooManyArguments,
+        DiagnosticRule::MissingArgument,
+        DiagnosticRule::UnknownRoxygenParam,
+        DiagnosticRule::UndocumentedParam,
+    ];
+
+    pub(crate) fn id(&self) -> &'static str {
+        match self {
+            DiagnosticRule::InvalidNaComparison => "invalid-na-comparison",
+            DiagnosticRule::AssignmentInCondition => "assignment-in-condition",
+            DiagnosticRule::SymbolNotInScope => "symbol-not-in-scope",
+            DiagnosticRule::PackageNotInstalled => "package-not-installed",
+            DiagnosticRule::UnusedVariable => "unused-variable",
+            DiagnosticRule::UnusedArgument => "unused-argument",
+            DiagnosticRule::UnreachableCode => "unreachable-code",
+            DiagnosticRule::UnknownArgument => "unknown-argument",
+            DiagnosticRule::DuplicateArgument => "duplicate-argument",
+            DiagnosticRule::TooManyArguments => "too-many-arguments",
+            DiagnosticRule::MissingArgument => "missing-argument",
+            DiagnosticRule::UnknownRoxygenParam => "unknown-roxygen-param",
+            DiagnosticRule::UndocumentedParam => "undocumented-param",
+        }
+    }
+
+    pub(crate) fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.iter().find(|rule| rule.id() == id).copied()
+    }
+
+    pub(crate) fn default_severity(&self) -> RuleSeverity {
+        match self {
+            DiagnosticRule::InvalidNaComparison => RuleSeverity::Information,
+            DiagnosticRule::AssignmentInCondition => RuleSeverity::Error,
+            DiagnosticRule::SymbolNotInScope => RuleSeverity::Warning,
+            DiagnosticRule::PackageNotInstalled => RuleSeverity::Error,
+            DiagnosticRule::UnusedVariable => RuleSeverity::Warning,
+            DiagnosticRule::UnusedArgument => RuleSeverity::Warning,
+            DiagnosticRule::UnreachableCode => RuleSeverity::Warning,
+            DiagnosticRule::UnknownArgument => RuleSeverity::Warning,
+            DiagnosticRule::DuplicateArgument => RuleSeverity::Warning,
+            DiagnosticRule::TooManyArguments => RuleSeverity::Warning,
+            DiagnosticRule::MissingArgument => RuleSeverity::Warning,
+            DiagnosticRule::UnknownRoxygenParam => RuleSeverity::Warning,
+            DiagnosticRule::UndocumentedParam => RuleSeverity::Information,
+        }
+    }
+
+    /// Whether the rule inspects the R session, e.g. to find the symbols
+    /// defined in the global environment or the installed packages
+    pub(crate) fn requires_session(&self) -> bool {
+        matches!(
+            self,
+            DiagnosticRule::SymbolNotInScope | DiagnosticRule::PackageNotInstalled
+        )
+    }
+
+    /// Creates a diagnostic tagged with the ID of the rule, with the default
+    /// severity of the rule. The configured severity is applied later on by
+    /// `apply_rules()`.
+    pub(crate) fn diagnostic(&self, range: Range, message: String) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new_simple(range, message);
+        diagnostic.code = Some(NumberOrString::String(self.id().to_string()));
+        diagnostic.severity = self.default_severity().into_lsp();
+        diagnostic
+    }
+
+    fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Self> {
+        match &diagnostic.code {
+            Some(NumberOrString::String(id)) => Self::from_id(id),
+            _ => None,
+        }
+    }
+}
+
+impl RuleSeverity {
+    pub(crate) fn into_lsp(self) -> Option<DiagnosticSeverity> {
+        match self {
+            RuleSeverity::Off => None,
+            RuleSeverity::Hint => Some(DiagnosticSeverity::HINT),
+            RuleSeverity::Information => Some(DiagnosticSeverity::INFORMATION),
+            RuleSeverity::Warning => Some(DiagnosticSeverity::WARNING),
+            RuleSeverity::Error => Some(DiagnosticSeverity::ERROR),
+        }
+    }
+}
+
+/// Set of rules disabled by a suppression comment
+#[derive(Clone, Debug, PartialEq)]
+enum SuppressedRules {
+    All,
+    Some(HashSet<String>),
+}
+
+impl SuppressedRules {
+    fn contains(&self, id: &str) -> bool {
+        match self {
+            SuppressedRules::All => true,
+            SuppressedRules::Some(ids) => ids.contains(id),
+        }
+    }
+
+    fn merge(&mut self, other: SuppressedRules) {
+        match (self, other) {
+            (SuppressedRules::All, _) => {},
+            (this, SuppressedRules::All) => *this = SuppressedRules::All,
+            (SuppressedRules::Some(ids), SuppressedRules::Some(other)) => ids.extend(other),
+        }
+    }
+}
+
+/// Suppression comments of a document.
+///
+/// - `# ark: ignore[rule-a, rule-b]` disables the listed rules. When the
+///   comment trails an expression, it applies to the line of the comment.
+///   When it stands on its own line, it applies to the whole expression that
+///   follows.
+///
+/// - `# ark: ignore` without a list of rules disables all rules.
+///
+/// - `# ark: ignore-file[rule-a]` disables the listed rules in the whole file.
+#[derive(Debug, Default)]
+pub(crate) struct Suppressions {
+    file: Option<SuppressedRules>,
+    lines: HashMap<usize, SuppressedRules>,
+}
+
+impl Suppressions {
+    pub(crate) fn from_document(root: Node, contents: &Rope) -> Self {
+        let mut suppressions = Self::default();
+        suppressions.collect(root, contents);
+        suppressions
+    }
+
+    fn collect(&mut self, node: Node, contents: &Rope) {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.is_comment() {
+                self.collect_comment(child, contents);
+            } else {
+                self.collect(child, contents);
+            }
+        }
+    }
+
+    fn collect_comment(&mut self, comment: Node, contents: &Rope) {
+        let Ok(text) = contents.node_slice(&comment) else {
+            return;
+        };
+        let Some((directive, rules)) = parse_suppression(&text.to_string()) else {
+            return;
+        };
+
+        if directive == Directive::IgnoreFile {
+            match &mut self.file {
+                Some(file) => file.merge(rules),
+                None => self.file = Some(rules),
+            }
+            return;
+        }
+
+        let row = comment.start_position().row;
+
+        // A comment trailing an expression applies to its own line
+        let trailing = comment
+            .prev_sibling()
+            .is_some_and(|prev| prev.end_position().row == row);
+
+        let rows = if trailing {
+            row..=row
+        } else {
+            match comment.next_named_sibling() {
+                Some(next) if !next.is_comment() => {
+                    next.start_position().row..=next.end_position().row
+                },
+                _ => row + 1..=row + 1,
+            }
+        };
+
+        for row in rows {
+            match self.lines.get_mut(&row) {
+                Some(suppressed) => suppressed.merge(rules.clone()),
+                None => {
+                    self.lines.insert(row, rules.clone());
+                },
+            }
+        }
+    }
+
+    pub(crate) fn is_suppressed(&self, id: &str, row: usize) -> bool {
+        if let Some(file) = &self.file {
+            if file.contains(id) {
+                return true;
+            }
+        }
+        match self.lines.get(&row) {
+            Some(suppressed) => suppressed.contains(id),
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Directive {
+    Ignore,
+    IgnoreFile,
+}
+
+fn parse_suppression(comment: &str) -> Option<(Directive, SuppressedRules)> {
+    let text = comment.trim_start_matches('#').trim_start();
+    let text = text.strip_prefix("ark:")?.trim_start();
+
+    let (directive, rest) = if let Some(rest) = text.strip_prefix("ignore-file") {
+        (Directive::IgnoreFile, rest)
+    } else if let Some(rest) = text.strip_prefix("ignore") {
+        (Directive::Ignore, rest)
+    } else {
+        return None;
+    };
+
+    let Some(rest) = rest.strip_prefix('[') else {
+        // Directives without a list of rules apply to all rules. Make sure
+        // we don't match words like `ignored`.
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        return Some((directive, SuppressedRules::All));
+    };
+
+    let (ids, _) = rest.split_once(']')?;
+    let ids: HashSet<String> = ids
+        .split(',')
+        .map(|id| id.trim())
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect();
+
+    Some((directive, SuppressedRules::Some(ids)))
+}
+
+/// Applies the configured severity of each rule to the diagnostics, and
+/// removes the diagnostics of disabled or suppressed rules. Diagnostics that
+/// don't belong to a rule, such as syntax errors, are left untouched.
+pub(crate) fn apply_rules(
+    diagnostics: Vec<Diagnostic>,
+    rules: &HashMap<String, RuleSeverity>,
+    suppressions: &Suppressions,
+) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .filter_map(|mut diagnostic| {
+            let Some(rule) = DiagnosticRule::from_diagnostic(&diagnostic) else {
+                return Some(diagnostic);
+            };
+
+            let row = diagnostic.range.start.line as usize;
+            if suppressions.is_suppressed(rule.id(), row) {
+                return None;
+            }
+
+            let severity = rules
+                .get(rule.id())
+                .copied()
+                .unwrap_or(rule.default_severity());
+            if severity == RuleSeverity::Off {
+                return None;
+            }
+            diagnostic.severity = severity.into_lsp();
+
+            Some(diagnostic)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tower_lsp::lsp_types::DiagnosticSeverity;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+
+    use crate::lsp::diagnostics_rules::apply_rules;
+    use crate::lsp::diagnostics_rules::parse_suppression;
+    use crate::lsp::diagnostics_rules::DiagnosticRule;
+    use crate::lsp::diagnostics_rules::Directive;
+    use crate::lsp::diagnostics_rules::RuleSeverity;
+    use crate::lsp::diagnostics_rules::SuppressedRules;
+    use crate::lsp::diagnostics_rules::Suppressions;
+    use crate::lsp::documents::Document;
+
+    fn suppressions(text: &str) -> Suppressions {
+        let document = Document::new(text, None);
+        Suppressions::from_document(document.ast.root_node(), &document.contents)
+    }
+
+    fn line_range(line: u32) -> Range {
+        Range::new(Position::new(line, 0), Position::new(line, 1))
+    }
+
+    #[test]
+    fn test_parse_suppression() {
+        assert_eq!(
+            parse_suppression("# ark: ignore"),
+            Some((Directive::Ignore, SuppressedRules::All))
+        );
+        assert_eq!(
+            parse_suppression("#ark: ignore[symbol-not-in-scope, package-not-installed]"),
+            Some((
+                Directive::Ignore,
+                SuppressedRules::Some(
+                    ["symbol-not-in-scope", "package-not-installed"]
+                        .map(String::from)
+                        .into()
+                )
+            ))
+        );
+        assert_eq!(
+            parse_suppression("# ark: ignore-file[symbol-not-in-scope]"),
+            Some((
+                Directive::IgnoreFile,
+                SuppressedRules::Some(["symbol-not-in-scope"].map(String::from).into())
+            ))
+        );
+        assert_eq!(parse_suppression("# ark: ignored"), None);
+        assert_eq!(parse_suppression("# ignore[symbol-not-in-scope]"), None);
+    }
+
+    #[test]
+    fn test_suppressions_trailing_and_leading() {
+        let text = "
+foo(x) # ark: ignore[symbol-not-in-scope]
+bar(y)
+# ark: ignore[symbol-not-in-scope]
+baz(
+  z
+)
+qux
+";
+        let suppressions = suppressions(text);
+        let id = DiagnosticRule::SymbolNotInScope.id();
+
+        assert!(suppressions.is_suppressed(id, 1));
+        assert!(!suppressions.is_suppressed(id, 2));
+        assert!(!suppressions.is_suppressed(DiagnosticRule::PackageNotInstalled.id(), 1));
+
+        // Leading comments apply to the whole next expression
+        assert!(suppressions.is_suppressed(id, 4));
+        assert!(suppressions.is_suppressed(id, 5));
+        assert!(suppressions.is_suppressed(id, 6));
+        assert!(!suppressions.is_suppressed(id, 7));
+    }
+
+    #[test]
+    fn test_suppressions_file() {
+        let suppressions = suppressions("# ark: ignore-file[symbol-not-in-scope]\nfoo\n\nbar");
+        assert!(suppressions.is_suppressed(DiagnosticRule::SymbolNotInScope.id(), 3));
+        assert!(!suppressions.is_suppressed(DiagnosticRule::PackageNotInstalled.id(), 3));
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let diagnostics = vec![
+            DiagnosticRule::SymbolNotInScope.diagnostic(line_range(0), String::from("a")),
+            DiagnosticRule::InvalidNaComparison.diagnostic(line_range(1), String::from("b")),
+            DiagnosticRule::SymbolNotInScope.diagnostic(line_range(2), String::from("c")),
+        ];
+
+        let rules = HashMap::from([(String::from("invalid-na-comparison"), RuleSeverity::Error)]);
+        let suppressions = suppressions("\n\nfoo # ark: ignore");
+
+        let diagnostics = apply_rules(diagnostics, &rules, &suppressions);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::ERROR));
+
+        let rules = HashMap::from([(String::from("symbol-not-in-scope"), RuleSeverity::Off)]);
+        let diagnostics =
+            vec![DiagnosticRule::SymbolNotInScope.diagnostic(line_range(0), String::from("a"))];
+        assert!(apply_rules(diagnostics, &rules, &Suppressions::default()).is_empty());
+    }
+}
@@ -0,0 +1,479 @@
+//
+// diagnostics_usage.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::DiagnosticTag;
+use tree_sitter::Node;
+use tree_sitter::Range;
+
+use crate::lsp::diagnostics::DiagnosticContext;
+use crate::lsp::diagnostics_rules::DiagnosticRule;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::references::find_scoped_references;
+use crate::lsp::references::node_local_binding;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Functions that inspect or capture the calling environment. We can't tell
+/// which local variables or arguments they use, so we don't report unused
+/// bindings in functions calling them.
+const ENVIRONMENT_FUNCTIONS: &[&str] = &[
+    "browser",
+    "environment",
+    "eval",
+    "evalq",
+    "ls",
+    "match.call",
+    "mget",
+    "NextMethod",
+    "standardGeneric",
+    "sys.call",
+    "sys.frame",
+    "sys.function",
+    "UseMethod",
+];
+
+/// Functions that never return to their caller
+const NON_RETURNING_FUNCTIONS: &[&str] = &[
+    "abort",
+    "cli_abort",
+    "invokeRestart",
+    "q",
+    "quit",
+    "return",
+    "stop",
+];
+
+/// Reports local variables that are assigned but never read, and arguments
+/// of named functions that are never used. `node` is a function definition.
+pub(crate) fn diagnose_unused_bindings(
+    node: Node,
+    context: &mut DiagnosticContext,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> anyhow::Result<()> {
+    let Some(body) = node.child_by_field_name("body") else {
+        return Ok(());
+    };
+
+    if calls_any(&node, ENVIRONMENT_FUNCTIONS, context.contents) {
+        return Ok(());
+    }
+
+    diagnose_unused_variables(node, body, context, diagnostics)?;
+
+    if let Some(name) = function_name(&node, context.contents) {
+        if !is_s3_method(&name, context) {
+            diagnose_unused_arguments(node, context, diagnostics)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn diagnose_unused_variables(
+    node: Node,
+    body: Node,
+    context: &DiagnosticContext,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> anyhow::Result<()> {
+    let contents = context.contents;
+
+    // The value of the last expression is returned, even when it's an
+    // assignment
+    let returned = if body.is_braced_expression() {
+        let mut cursor = body.walk();
+        let last = body.children_by_field_name("body", &mut cursor).last();
+        last
+    } else {
+        Some(body)
+    };
+
+    let mut bindings: HashMap<String, Vec<Node>> = HashMap::new();
+
+    let mut cursor = body.walk();
+    cursor.recurse(|child| {
+        // Nested functions have their own scope
+        if child.is_function_definition() {
+            return false;
+        }
+
+        // Loop variables are required by the syntax even when they're not used
+        if child.node_type() == NodeType::ForStatement || Some(child) == returned {
+            return true;
+        }
+
+        if let Some(target) = node_local_binding(&child) {
+            if let Some(symbol) = node_symbol_name(&target, contents) {
+                bindings.entry(symbol).or_default().push(target);
+            }
+        }
+
+        true
+    });
+
+    for (symbol, targets) in bindings.into_iter() {
+        if symbol.starts_with('.') || is_used(&node, &symbol, contents) {
+            continue;
+        }
+
+        for target in targets {
+            let range = convert_tree_sitter_range_to_lsp_range(contents, target.range());
+            let message = format!("Local variable '{symbol}' is assigned but never used.");
+            let mut diagnostic = DiagnosticRule::UnusedVariable.diagnostic(range, message);
+            diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    Ok(())
+}
+
+fn diagnose_unused_arguments(
+    node: Node,
+    context: &DiagnosticContext,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> anyhow::Result<()> {
+    let contents = context.contents;
+
+    let Some(parameters) = node.child_by_field_name("parameters") else {
+        return Ok(());
+    };
+
+    let mut cursor = parameters.walk();
+    for parameter in parameters.children_by_field_name("parameter", &mut cursor) {
+        let Some(name) = parameter.child_by_field_name("name") else {
+            continue;
+        };
+        let Some(symbol) = node_symbol_name(&name, contents) else {
+            continue;
+        };
+
+        if symbol == "..." || is_used(&node, &symbol, contents) {
+            continue;
+        }
+
+        let range = convert_tree_sitter_range_to_lsp_range(contents, name.range());
+        let message = format!("Argument '{symbol}' is never used.");
+        let mut diagnostic = DiagnosticRule::UnusedArgument.diagnostic(range, message);
+        diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+        diagnostics.push(diagnostic);
+    }
+
+    Ok(())
+}
+
+/// Reports statements following an unconditional `return()`, `stop()`,
+/// `break`, or `next` in a braced expression.
+pub(crate) fn diagnose_unreachable_code(
+    node: Node,
+    context: &DiagnosticContext,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> anyhow::Result<()> {
+    let mut cursor = node.walk();
+    let statements: Vec<Node> = node
+        .children_by_field_name("body", &mut cursor)
+        .filter(|child| !child.is_comment())
+        .collect();
+
+    let Some(exit) = statements
+        .iter()
+        .position(|statement| never_returns(statement, context.contents))
+    else {
+        return Ok(());
+    };
+
+    let (Some(first), Some(last)) = (statements.get(exit + 1), statements.last()) else {
+        return Ok(());
+    };
+
+    let range = Range {
+        start_byte: first.start_byte(),
+        start_point: first.start_position(),
+        end_byte: last.end_byte(),
+        end_point: last.end_position(),
+    };
+    let range = convert_tree_sitter_range_to_lsp_range(context.contents, range);
+
+    let message = String::from("Unreachable code.");
+    let mut diagnostic = DiagnosticRule::UnreachableCode.diagnostic(range, message);
+    diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+    diagnostics.push(diagnostic);
+
+    Ok(())
+}
+
+fn never_returns(node: &Node, contents: &Rope) -> bool {
+    match node.node_type() {
+        NodeType::Break | NodeType::Next => true,
+        NodeType::Call => call_name(node, contents)
+            .is_some_and(|name| NON_RETURNING_FUNCTIONS.contains(&name.as_str())),
+        _ => false,
+    }
+}
+
+/// Is `symbol`, bound in the function `scope`, read anywhere in that scope?
+fn is_used(scope: &Node, symbol: &str, contents: &Rope) -> bool {
+    let read = find_scoped_references(*scope, symbol, contents)
+        .iter()
+        .any(|reference| !is_binding_target(reference));
+
+    if read {
+        return true;
+    }
+
+    // Variables may be interpolated in strings by glue and friends
+    mentioned_in_template(scope, symbol, contents)
+}
+
+fn is_binding_target(node: &Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+
+    if parent.node_type() == NodeType::Parameter {
+        return parent.child_by_field_name("name") == Some(*node);
+    }
+
+    node_local_binding(&parent) == Some(*node)
+}
+
+fn mentioned_in_template(scope: &Node, symbol: &str, contents: &Rope) -> bool {
+    let mut found = false;
+
+    let mut cursor = scope.walk();
+    cursor.recurse(|node| {
+        if found {
+            return false;
+        }
+        if !node.is_string() {
+            return true;
+        }
+
+        if let Ok(text) = contents.node_slice(&node) {
+            let text = text.to_string();
+            found = text.contains('{') && contains_word(&text, symbol);
+        }
+        false
+    });
+
+    found
+}
+
+/// Does `text` contain `word` delimited by characters that can't be part of
+/// an R symbol?
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_symbol_char = |c: char| c.is_alphanumeric() || c == '.' || c == '_';
+
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.is_some_and(is_symbol_char) && !after.is_some_and(is_symbol_char)
+    })
+}
+
+/// Does `node` contain a call to any of `functions`?
+fn calls_any(node: &Node, functions: &[&str], contents: &Rope) -> bool {
+    let mut found = false;
+
+    let mut cursor = node.walk();
+    cursor.recurse(|node| {
+        if found {
+            return false;
+        }
+        if node.is_call() {
+            found =
+                call_name(&node, contents).is_some_and(|name| functions.contains(&name.as_str()));
+        }
+        true
+    });
+
+    found
+}
+
+/// Returns the name of the function called by `node`, without the namespace
+/// prefix in case of `pkg::fn()`
+fn call_name(node: &Node, contents: &Rope) -> Option<String> {
+    let mut function = node.child_by_field_name("function")?;
+
+    if function.is_namespace_operator() {
+        function = function.child_by_field_name("rhs")?;
+    }
+
+    node_symbol_name(&function, contents)
+}
+
+/// Returns the name of a function definition assigned to a symbol
+fn function_name(node: &Node, contents: &Rope) -> Option<String> {
+    let parent = node.parent()?;
+    let target = node_local_binding(&parent)?;
+
+    if target == *node {
+        return None;
+    }
+
+    node_symbol_name(&target, contents)
+}
+
+/// S3 methods must accept the arguments of their generic even when they don't
+/// use them. A function named `generic.class` is considered a method when
+/// `generic` is a known function.
+fn is_s3_method(name: &str, context: &mut DiagnosticContext) -> bool {
+    name.match_indices('.')
+        .map(|(i, _)| &name[..i])
+        .filter(|generic| !generic.is_empty())
+        .any(|generic| context.has_definition(generic))
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Diagnostic;
+    use tower_lsp::lsp_types::DiagnosticTag;
+    use tower_lsp::lsp_types::NumberOrString;
+
+    use crate::interface::console_inputs;
+    use crate::lsp::diagnostics::generate_diagnostics;
+    use crate::lsp::diagnostics_usage::contains_word;
+    use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
+    use crate::test::r_test;
+
+    fn usage_diagnostics(text: &str) -> Vec<(String, String)> {
+        // Console scopes are needed to detect S3 generics such as `print()`
+        let inputs = console_inputs().unwrap();
+        let state = WorldState {
+            console_scopes: inputs.console_scopes,
+            installed_packages: inputs.installed_packages,
+            ..Default::default()
+        };
+
+        let document = Document::new(text, None);
+        let diagnostics: Vec<Diagnostic> = generate_diagnostics(document, state);
+
+        diagnostics
+            .into_iter()
+            .filter_map(|diagnostic| {
+                let Some(NumberOrString::String(code)) = diagnostic.code else {
+                    return None;
+                };
+                if !matches!(
+                    code.as_str(),
+                    "unused-variable" | "unused-argument" | "unreachable-code"
+                ) {
+                    return None;
+                }
+                assert_eq!(diagnostic.tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+                Some((code, diagnostic.message))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unused_variable() {
+        r_test(|| {
+            let text = "
+f <- function() {
+  x <- 1
+  y <- 2
+  y
+}
+";
+            assert_eq!(usage_diagnostics(text), vec![(
+                String::from("unused-variable"),
+                String::from("Local variable 'x' is assigned but never used.")
+            )]);
+
+            // Used in a nested function, in a glue string, or returned
+            let text = "
+f <- function() {
+  x <- 1
+  y <- 2
+  g <- function() x
+  print(glue('{y}'))
+  g()
+  z <- 3
+}
+";
+            assert!(usage_diagnostics(text).is_empty());
+
+            // Loop variables and top-level variables are never reported
+            let text = "
+x <- 1
+f <- function() {
+  for (i in 1:2) print('hi')
+}
+";
+            assert!(usage_diagnostics(text).is_empty());
+        })
+    }
+
+    #[test]
+    fn test_unused_argument() {
+        r_test(|| {
+            let text = "
+f <- function(x, y, ...) {
+  y
+}
+";
+            assert_eq!(usage_diagnostics(text), vec![(
+                String::from("unused-argument"),
+                String::from("Argument 'x' is never used.")
+            )]);
+
+            // Arguments used in other defaults count as used
+            let text = "f <- function(x, y = x) y";
+            assert!(usage_diagnostics(text).is_empty());
+
+            // S3 methods, generics, and anonymous functions are never reported
+            let text = "
+print.foo <- function(x, ...) cat('foo')
+my_generic <- function(x, ...) UseMethod('my_generic')
+lapply(1:2, function(x) 1)
+";
+            assert!(usage_diagnostics(text).is_empty());
+        })
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        r_test(|| {
+            let text = "
+f <- function(x) {
+  if (x) return(1)
+  stop('oh no')
+  x
+  x + 1
+}
+";
+            assert_eq!(usage_diagnostics(text), vec![(
+                String::from("unreachable-code"),
+                String::from("Unreachable code.")
+            )]);
+
+            let text = "
+for (i in 1:2) {
+  next
+  # Comments don't count
+}
+";
+            assert!(usage_diagnostics(text).is_empty());
+        })
+    }
+
+    #[test]
+    fn test_contains_word() {
+        assert!(contains_word("{x}", "x"));
+        assert!(contains_word("{x + 1}", "x"));
+        assert!(!contains_word("{x.y}", "x"));
+        assert!(!contains_word("{max}", "x"));
+    }
+}
@@ -0,0 +1,349 @@
+//
+// document_highlight.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use ropey::Rope;
+use tower_lsp::lsp_types::DocumentHighlight;
+use tower_lsp::lsp_types::DocumentHighlightKind;
+use tower_lsp::lsp_types::DocumentHighlightParams;
+use tower_lsp::lsp_types::LinkedEditingRangeParams;
+use tower_lsp::lsp_types::LinkedEditingRanges;
+use tree_sitter::Node;
+
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::references::find_identifier_at_point;
+use crate::lsp::references::find_scoped_references;
+use crate::lsp::references::find_symbol_at_point;
+use crate::lsp::references::node_is_super_assignment_target;
+use crate::lsp::references::node_local_binding;
+use crate::lsp::references::node_reference_kind;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::references::resolve_symbol_scope;
+use crate::lsp::references::ReferenceKind;
+use crate::lsp::rename::function_has_formal;
+use crate::lsp::rename::rename_range;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Highlights the uses of the symbol under the cursor within the scope it
+/// resolves in. Bindings are highlighted as writes and other uses as reads.
+/// For `$` and `@` names, which aren't scoped, all the uses of the same name
+/// in the document are highlighted.
+pub(crate) fn document_highlights(
+    params: DocumentHighlightParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<DocumentHighlight>>> {
+    let params = params.text_document_position_params;
+    let document = state.get_document(&params.text_document.uri)?;
+    let contents = &document.contents;
+    let root = document.ast.root_node();
+    let point = convert_position_to_point(contents, params.position);
+
+    if let Some(node) = find_identifier_at_point(root, point) {
+        let kind = node_reference_kind(&node);
+        if kind != ReferenceKind::SymbolName {
+            return Ok(Some(member_highlights(root, &node, kind, contents)));
+        }
+    }
+
+    let Some(node) = find_symbol_at_point(root, point, contents) else {
+        return Ok(None);
+    };
+    let Some(symbol) = node_symbol_name(&node, contents) else {
+        return Ok(None);
+    };
+
+    let scope = resolve_symbol_scope(&node, &symbol, contents);
+
+    let highlights = find_scoped_references(scope, &symbol, contents)
+        .iter()
+        .map(|node| {
+            let kind = if node_is_binding(node) {
+                DocumentHighlightKind::WRITE
+            } else {
+                DocumentHighlightKind::READ
+            };
+            DocumentHighlight {
+                range: rename_range(node, contents),
+                kind: Some(kind),
+            }
+        })
+        .collect();
+
+    Ok(Some(highlights))
+}
+
+/// Links the formal of a function with its uses in the function body so they
+/// can be edited together
+pub(crate) fn linked_editing_ranges(
+    params: LinkedEditingRangeParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<LinkedEditingRanges>> {
+    let params = params.text_document_position_params;
+    let document = state.get_document(&params.text_document.uri)?;
+    let contents = &document.contents;
+    let root = document.ast.root_node();
+    let point = convert_position_to_point(contents, params.position);
+
+    let Some(node) = find_symbol_at_point(root, point, contents) else {
+        return Ok(None);
+    };
+    let Some(symbol) = node_symbol_name(&node, contents) else {
+        return Ok(None);
+    };
+
+    let scope = resolve_symbol_scope(&node, &symbol, contents);
+    if !scope.is_function_definition() || !function_has_formal(&scope, &symbol, contents) {
+        return Ok(None);
+    }
+
+    let nodes = find_scoped_references(scope, &symbol, contents);
+
+    // Linked ranges must have identical contents, which isn't the case when
+    // a non-syntactic name is backquoted in some places and quoted in others
+    let mut texts = nodes.iter().map(|node| {
+        if node.is_string() {
+            node_symbol_name(node, contents)
+        } else {
+            contents.node_slice(node).ok().map(|text| text.to_string())
+        }
+    });
+    let first = texts.next();
+    if texts.any(|text| Some(text) != first) {
+        return Ok(None);
+    }
+
+    let ranges = nodes
+        .iter()
+        .map(|node| rename_range(node, contents))
+        .collect();
+
+    Ok(Some(LinkedEditingRanges {
+        ranges,
+        word_pattern: None,
+    }))
+}
+
+/// Is `node` bound by an assignment, a `for` loop, or as a function formal?
+fn node_is_binding(node: &Node) -> bool {
+    if node_is_super_assignment_target(node) {
+        return true;
+    }
+
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+
+    if parent.node_type() == NodeType::Parameter {
+        return parent.child_by_field_name("name") == Some(*node);
+    }
+
+    node_local_binding(&parent) == Some(*node)
+}
+
+fn member_highlights(
+    root: Node,
+    node: &Node,
+    kind: ReferenceKind,
+    contents: &Rope,
+) -> Vec<DocumentHighlight> {
+    let Ok(name) = contents.node_slice(node) else {
+        return Vec::new();
+    };
+    let name = name.to_string();
+
+    let mut highlights = Vec::new();
+
+    let mut cursor = root.walk();
+    cursor.recurse(|node| {
+        if !node.is_identifier() || node_reference_kind(&node) != kind {
+            return true;
+        }
+        if !contents
+            .node_slice(&node)
+            .is_ok_and(|text| text == name.as_str())
+        {
+            return true;
+        }
+
+        let kind = if node_is_member_assignment(&node) {
+            DocumentHighlightKind::WRITE
+        } else {
+            DocumentHighlightKind::READ
+        };
+        highlights.push(DocumentHighlight {
+            range: convert_tree_sitter_range_to_lsp_range(contents, node.range()),
+            kind: Some(kind),
+        });
+
+        true
+    });
+
+    highlights
+}
+
+/// Is `node`, the name of a `$` or `@` extraction, being assigned to as in
+/// `x$name <- value`?
+fn node_is_member_assignment(node: &Node) -> bool {
+    let Some(extract) = node.parent() else {
+        return false;
+    };
+    let Some(assignment) = extract.parent() else {
+        return false;
+    };
+
+    let target = match assignment.node_type() {
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+        NodeType::BinaryOperator(BinaryOperatorType::LeftSuperAssignment) |
+        NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment) => {
+            assignment.child_by_field_name("lhs")
+        },
+        NodeType::BinaryOperator(BinaryOperatorType::RightAssignment) |
+        NodeType::BinaryOperator(BinaryOperatorType::RightSuperAssignment) => {
+            assignment.child_by_field_name("rhs")
+        },
+        _ => None,
+    };
+
+    target == Some(extract)
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::DocumentHighlightKind;
+    use tower_lsp::lsp_types::DocumentHighlightParams;
+    use tower_lsp::lsp_types::LinkedEditingRangeParams;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::TextDocumentPositionParams;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::document_highlight::document_highlights;
+    use crate::lsp::document_highlight::linked_editing_ranges;
+    use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
+    use crate::test::point_from_cursor;
+
+    fn position_params(text: &str) -> (WorldState, TextDocumentPositionParams) {
+        let (text, point) = point_from_cursor(text);
+        let uri = Url::parse("file:///highlight.R").unwrap();
+
+        let mut state = WorldState::default();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(&text, None));
+
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position::new(point.row as u32, point.column as u32),
+        };
+
+        (state, params)
+    }
+
+    fn test_highlights(text: &str) -> Vec<(u32, u32, DocumentHighlightKind)> {
+        let (state, params) = position_params(text);
+        let params = DocumentHighlightParams {
+            text_document_position_params: params,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        document_highlights(params, &state)
+            .unwrap()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|highlight| {
+                let start = highlight.range.start;
+                (start.line, start.character, highlight.kind.unwrap())
+            })
+            .collect()
+    }
+
+    fn test_linked_ranges(text: &str) -> Option<Vec<(u32, u32)>> {
+        let (state, params) = position_params(text);
+        let params = LinkedEditingRangeParams {
+            text_document_position_params: params,
+            work_done_progress_params: Default::default(),
+        };
+
+        let ranges = linked_editing_ranges(params, &state).unwrap()?;
+        Some(
+            ranges
+                .ranges
+                .into_iter()
+                .map(|range| (range.start.line, range.start.character))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_highlight_scoped_reads_and_writes() {
+        let text = "
+x <- 1
+f <- function() {
+  x <- 2
+  x + 1
+}
+@x
+";
+        assert_eq!(test_highlights(text), vec![
+            (1, 0, DocumentHighlightKind::WRITE),
+            (6, 0, DocumentHighlightKind::READ),
+        ]);
+    }
+
+    #[test]
+    fn test_highlight_function_formals() {
+        let text = "
+f <- function(x) {
+  for (i in x) print(i)
+  @x <- x + 1
+}
+";
+        assert_eq!(test_highlights(text), vec![
+            (1, 14, DocumentHighlightKind::WRITE),
+            (2, 12, DocumentHighlightKind::READ),
+            (3, 2, DocumentHighlightKind::WRITE),
+            (3, 7, DocumentHighlightKind::READ),
+        ]);
+    }
+
+    #[test]
+    fn test_highlight_dollar_names() {
+        let text = "
+x$foo <- 1
+foo <- x$f@oo
+";
+        assert_eq!(test_highlights(text), vec![
+            (1, 2, DocumentHighlightKind::WRITE),
+            (2, 9, DocumentHighlightKind::READ),
+        ]);
+    }
+
+    #[test]
+    fn test_linked_editing_formals() {
+        let text = "
+f <- function(@x, y) {
+  g <- function(x) x
+  x + y
+}
+x
+";
+        assert_eq!(test_linked_ranges(text), Some(vec![(1, 14), (3, 2)]));
+
+        let text = "
+x <- 1
+@x
+";
+        assert_eq!(test_linked_ranges(text), None);
+    }
+}
@@ -8,6 +8,7 @@
 use anyhow::*;
 use ropey::Rope;
 use tower_lsp::lsp_types::DidChangeTextDocumentParams;
+use tower_lsp::lsp_types::Range;
 use tower_lsp::lsp_types::TextDocumentContentChangeEvent;
 use tree_sitter::InputEdit;
 use tree_sitter::Parser;
@@ -16,6 +17,8 @@ use tree_sitter::Tree;
 
 use crate::lsp::config::DocumentConfig;
 use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::quarto;
+use crate::lsp::quarto::Chunk;
 use crate::lsp::traits::rope::RopeExt;
 
 fn compute_point(point: Point, text: &str) -> Point {
@@ -49,6 +52,15 @@ pub struct Document {
 
     // Configuration of the document, such as indentation settings.
     pub config: DocumentConfig,
+
+    // The full text of Quarto and R Markdown documents, of which `contents`
+    // only holds the code of the R chunks. The other lines are blank in
+    // `contents` so that positions are the same in both. None for R scripts.
+    pub markdown: Option<Rope>,
+
+    // The R chunks of `markdown`, kept in sync with edits so that they
+    // don't have to be scanned again on each change. Empty for R scripts.
+    chunks: Vec<Chunk>,
 }
 
 impl std::fmt::Debug for Document {
@@ -81,9 +93,42 @@ impl Document {
             version,
             ast,
             config: Default::default(),
+            markdown: None,
+            chunks: Vec::new(),
         }
     }
 
+    /// Creates a document from the text of a Quarto or R Markdown document.
+    /// Only the code of its R chunks is parsed.
+    pub fn new_markdown(contents: &str, version: Option<i32>) -> Self {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_r::LANGUAGE.into())
+            .unwrap();
+
+        Self::new_markdown_with_parser(contents, &mut parser, version)
+    }
+
+    pub fn new_markdown_with_parser(
+        contents: &str,
+        parser: &mut Parser,
+        version: Option<i32>,
+    ) -> Self {
+        let chunks = quarto::r_chunks(contents);
+        let code = quarto::r_code(contents, &chunks);
+
+        let mut document = Self::new_with_parser(&code, parser, version);
+        document.markdown = Some(Rope::from(contents));
+        document.chunks = chunks;
+
+        document
+    }
+
+    /// The R chunks of Quarto and R Markdown documents. Empty for R scripts.
+    pub(crate) fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
     pub fn on_did_change(&mut self, parser: &mut Parser, params: &DidChangeTextDocumentParams) {
         let new_version = params.text_document.version;
 
@@ -121,6 +166,14 @@ impl Document {
             None => return Ok(()),
         };
 
+        if self.markdown.is_some() {
+            return self.update_markdown(parser, range, &change.text);
+        }
+
+        self.update_code(parser, range, &change.text)
+    }
+
+    fn update_code(&mut self, parser: &mut Parser, range: Range, text: &str) -> Result<()> {
         // Update the AST. We do this before updating the underlying document
         // contents, because edit computations need to be done using the current
         // state of the document (prior to the edit being applied) so that byte
@@ -133,8 +186,8 @@ impl Document {
         let old_end_point = convert_position_to_point(&self.contents, range.end);
         let old_end_byte = self.contents.point_to_byte(old_end_point);
 
-        let new_end_point = compute_point(start_point, &change.text);
-        let new_end_byte = start_byte + change.text.as_bytes().len();
+        let new_end_point = compute_point(start_point, text);
+        let new_end_byte = start_byte + text.as_bytes().len();
 
         // Confusing tree sitter names, the `start_position` is really a `Point`
         let edit = InputEdit {
@@ -155,7 +208,7 @@ impl Document {
 
         // Remove the old slice of text, and insert the new slice of text.
         self.contents.remove(start_character..old_end_character);
-        self.contents.insert(start_character, change.text.as_str());
+        self.contents.insert(start_character, text);
 
         // We've edited the AST, and updated the document. We can now re-parse.
         let contents = &self.contents;
@@ -167,6 +220,77 @@ impl Document {
         Ok(())
     }
 
+    /// Edits in Quarto and R Markdown documents are applied to the full text.
+    /// Edits within the code of a single chunk are applied to the R code too,
+    /// which is then reparsed incrementally. Other edits can change the chunk
+    /// boundaries, so the chunks are scanned again and the R code is parsed
+    /// from scratch.
+    fn update_markdown(&mut self, parser: &mut Parser, range: Range, text: &str) -> Result<()> {
+        let markdown = self.markdown.as_mut().unwrap();
+
+        let start_point = convert_position_to_point(markdown, range.start);
+        let start_character = markdown.byte_to_char(markdown.point_to_byte(start_point));
+
+        let end_point = convert_position_to_point(markdown, range.end);
+        let end_character = markdown.byte_to_char(markdown.point_to_byte(end_point));
+
+        markdown.remove(start_character..end_character);
+        markdown.insert(start_character, text);
+
+        if let Some(index) = self.edited_chunk(range, text) {
+            // Shift the chunks by the number of lines added or removed
+            let added = text.matches('\n').count();
+            let removed = (range.end.line - range.start.line) as usize;
+
+            for (i, chunk) in self.chunks.iter_mut().enumerate().skip(index) {
+                if i > index {
+                    chunk.start = chunk.start + added - removed;
+                }
+                chunk.end = chunk.end + added - removed;
+            }
+
+            return self.update_code(parser, range, text);
+        }
+
+        let markdown = self.markdown.as_ref().unwrap().to_string();
+        self.chunks = quarto::r_chunks(&markdown);
+
+        let code = quarto::r_code(&markdown, &self.chunks);
+        self.ast = parser
+            .parse(&code, None)
+            .ok_or(anyhow!("Failed to parse R chunks"))?;
+        self.contents = Rope::from(code);
+
+        Ok(())
+    }
+
+    /// The index of the chunk whose code contains the edit `range`, provided
+    /// that none of the edited lines closes the chunk. Must be called once the
+    /// edit has been applied to `markdown`.
+    fn edited_chunk(&self, range: Range, text: &str) -> Option<usize> {
+        let markdown = self.markdown.as_ref()?;
+
+        let start_row = range.start.line as usize;
+        let end_row = range.end.line as usize;
+
+        let index = self.chunks.iter().position(|chunk| {
+            let lines = chunk.code_lines();
+            lines.contains(&start_row) && lines.contains(&end_row)
+        })?;
+
+        let opening = markdown.get_line(self.chunks[index].start)?.to_string();
+
+        let edited_rows = start_row..=(start_row + text.matches('\n').count());
+        for row in edited_rows {
+            let line = markdown.get_line(row)?.to_string();
+            if quarto::closes_chunk(&opening, &line) {
+                return None;
+            }
+        }
+
+        Some(index)
+    }
+
     /// A tree-sitter `parse_with()` callback to efficiently return a slice of the
     /// document in the `Rope` that tree-sitter can reparse with.
     ///
@@ -203,6 +327,10 @@ impl Document {
 
 #[cfg(test)]
 mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Url;
+    use tower_lsp::lsp_types::VersionedTextDocumentIdentifier;
+
     use super::*;
 
     #[test]
@@ -232,4 +360,116 @@ mod tests {
         let root = document.ast.root_node();
         assert_eq!(root.start_position(), Point::new(0, 0));
     }
+
+    #[test]
+    fn test_markdown_document_edits() {
+        let text = "Some text\n\n```{r}\nx <- 1\n```\n";
+        let mut document = Document::new_markdown(text, Some(0));
+        assert_eq!(document.contents.to_string(), "\n\n\nx <- 1\n\n");
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_r::LANGUAGE.into())
+            .unwrap();
+
+        // Insert a new chunk before the existing one
+        let params = DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: Url::parse("file:///test.qmd").unwrap(),
+                version: 1,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(1, 0), Position::new(1, 0))),
+                range_length: None,
+                text: String::from("```{r}\ny <- 2\n```\n"),
+            }],
+        };
+        document.on_did_change(&mut parser, &params);
+
+        assert_eq!(
+            document.markdown.as_ref().unwrap().to_string(),
+            "Some text\n```{r}\ny <- 2\n```\n\n```{r}\nx <- 1\n```\n"
+        );
+        assert_eq!(
+            document.contents.to_string(),
+            "\n\ny <- 2\n\n\n\nx <- 1\n\n"
+        );
+        assert_eq!(document.chunks().len(), 2);
+        assert!(!document.ast.root_node().has_error());
+    }
+
+    fn markdown_change(range: Range, text: &str, version: i32) -> DidChangeTextDocumentParams {
+        DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: Url::parse("file:///test.qmd").unwrap(),
+                version,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: Some(range),
+                range_length: None,
+                text: String::from(text),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_markdown_document_chunk_edits() {
+        let text = "```{r}\nx <- 1\n```\n\nText\n\n```{r}\ny <- 2\n```\n";
+        let mut document = Document::new_markdown(text, Some(0));
+        let chunks = vec![Chunk { start: 0, end: 2 }, Chunk { start: 6, end: 8 }];
+        assert_eq!(document.chunks(), chunks);
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_r::LANGUAGE.into())
+            .unwrap();
+
+        // Add a line of code to the first chunk. The chunks after it are shifted.
+        let range = Range::new(Position::new(1, 6), Position::new(1, 6));
+        document.on_did_change(&mut parser, &markdown_change(range, "\nz <- 3", 1));
+
+        let expected = Document::new_markdown(
+            "```{r}\nx <- 1\nz <- 3\n```\n\nText\n\n```{r}\ny <- 2\n```\n",
+            None,
+        );
+        let chunks = vec![Chunk { start: 0, end: 3 }, Chunk { start: 7, end: 9 }];
+        assert_eq!(document.chunks(), chunks);
+        assert_eq!(document.chunks(), expected.chunks());
+        assert_eq!(document.contents.to_string(), expected.contents.to_string());
+        assert_eq!(
+            document.ast.root_node().to_sexp(),
+            expected.ast.root_node().to_sexp()
+        );
+
+        // Remove that line again
+        let range = Range::new(Position::new(1, 6), Position::new(2, 6));
+        document.on_did_change(&mut parser, &markdown_change(range, "", 2));
+
+        let expected = Document::new_markdown(text, None);
+        assert_eq!(document.chunks(), expected.chunks());
+        assert_eq!(document.contents.to_string(), expected.contents.to_string());
+        assert_eq!(
+            document.ast.root_node().to_sexp(),
+            expected.ast.root_node().to_sexp()
+        );
+    }
+
+    #[test]
+    fn test_markdown_document_closing_fence_edits() {
+        let text = "```{r}\nx <- 1\ny <- 2\n```\n";
+        let mut document = Document::new_markdown(text, Some(0));
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_r::LANGUAGE.into())
+            .unwrap();
+
+        // Closing the chunk early moves the rest of its code out of it
+        let range = Range::new(Position::new(1, 6), Position::new(1, 6));
+        document.on_did_change(&mut parser, &markdown_change(range, "\n```", 1));
+
+        assert_eq!(document.chunks(), vec![Chunk { start: 0, end: 2 }]);
+        assert_eq!(document.contents.to_string(), "\nx <- 1\n\n\n\n");
+        assert!(!document.ast.root_node().has_error());
+    }
 }
@@ -0,0 +1,257 @@
+//
+// folding_range.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::cmp::Reverse;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::FoldingRange;
+use tower_lsp::lsp_types::FoldingRangeKind;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::lsp::indexer::comment_section;
+use crate::lsp::roxygen::is_roxygen_comment;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Computes the line-based folding ranges of a document:
+///
+/// - The contents of multi-line braces, such as function bodies and the
+///   branches of `if`/`else` chains.
+/// - The arguments of multi-line calls and the parameters of multi-line
+///   function definitions.
+/// - Consecutive roxygen comments.
+/// - RStudio-style sections like `## Title ----`, which extend until the next
+///   section of the same or a higher level within the same block.
+///
+/// Delimited ranges stop before the line of the closing delimiter so that it
+/// remains visible when folded.
+pub(crate) fn folding_ranges(document: &Document) -> Vec<FoldingRange> {
+    let contents = &document.contents;
+    let mut ranges: Vec<FoldingRange> = Vec::new();
+
+    let mut cursor = document.ast.walk();
+    cursor.recurse(|node| {
+        match node.node_type() {
+            NodeType::Program => {
+                block_ranges(&node, contents, &mut ranges);
+            },
+            NodeType::BracedExpression => {
+                delimited_range(&node, &mut ranges);
+                block_ranges(&node, contents, &mut ranges);
+            },
+            NodeType::Arguments | NodeType::Parameters => {
+                delimited_range(&node, &mut ranges);
+            },
+            _ => {},
+        }
+        true
+    });
+
+    // Clients only keep one range per start line. Prefer the outermost one,
+    // e.g. the arguments of `lapply(x, function(i) {` rather than the braces.
+    ranges.sort_by_key(|range| (range.start_line, Reverse(range.end_line)));
+    ranges.dedup_by_key(|range| range.start_line);
+
+    ranges
+}
+
+fn delimited_range(node: &Node, ranges: &mut Vec<FoldingRange>) {
+    let start_line = node.start_position().row as u32;
+    let end_line = node.end_position().row as u32;
+
+    // Keep the closing delimiter visible
+    if end_line <= start_line + 1 {
+        return;
+    }
+    push_range(ranges, start_line, end_line - 1, None);
+}
+
+/// Folds the roxygen blocks and sections among the children of a block
+fn block_ranges(node: &Node, contents: &Rope, ranges: &mut Vec<FoldingRange>) {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.named_children(&mut cursor).collect();
+
+    roxygen_ranges(&children, contents, ranges);
+    section_ranges(&children, contents, ranges);
+}
+
+fn roxygen_ranges(children: &[Node], contents: &Rope, ranges: &mut Vec<FoldingRange>) {
+    let mut block: Option<(u32, u32)> = None;
+
+    for child in children.iter() {
+        if !is_roxygen_comment(child, contents) {
+            if let Some((start, end)) = block.take() {
+                push_range(ranges, start, end, Some(FoldingRangeKind::Comment));
+            }
+            continue;
+        }
+
+        let row = child.start_position().row as u32;
+        block = match block {
+            Some((start, end)) if row == end + 1 => Some((start, row)),
+            Some((start, end)) => {
+                push_range(ranges, start, end, Some(FoldingRangeKind::Comment));
+                Some((row, row))
+            },
+            None => Some((row, row)),
+        };
+    }
+
+    if let Some((start, end)) = block {
+        push_range(ranges, start, end, Some(FoldingRangeKind::Comment));
+    }
+}
+
+fn section_ranges(children: &[Node], contents: &Rope, ranges: &mut Vec<FoldingRange>) {
+    for (i, child) in children.iter().enumerate() {
+        let Some(end) = section_end(children, i, contents) else {
+            continue;
+        };
+
+        let start_line = child.start_position().row as u32;
+        let end_line = end.end_position().row as u32;
+        push_range(ranges, start_line, end_line, Some(FoldingRangeKind::Region));
+    }
+}
+
+/// The level of a section header comment like `## Title ----`
+pub(crate) fn section_level(node: &Node, contents: &Rope) -> Option<usize> {
+    if !node.is_comment() {
+        return None;
+    }
+    let text = contents.node_slice(node).ok()?.to_string();
+    let (level, _title) = comment_section(&text)?;
+    Some(level)
+}
+
+/// The last node of the section whose header is `children[index]`. The
+/// section ends with the last node before the next header of the same or a
+/// higher level, so that trailing blank lines aren't included. Returns `None`
+/// if `children[index]` isn't a section header or if the section is empty.
+pub(crate) fn section_end<'tree>(
+    children: &[Node<'tree>],
+    index: usize,
+    contents: &Rope,
+) -> Option<Node<'tree>> {
+    let header_level = section_level(&children[index], contents)?;
+
+    children[index + 1..]
+        .iter()
+        .take_while(
+            |node| !matches!(section_level(node, contents), Some(level) if level <= header_level),
+        )
+        .last()
+        .copied()
+}
+
+fn push_range(
+    ranges: &mut Vec<FoldingRange>,
+    start_line: u32,
+    end_line: u32,
+    kind: Option<FoldingRangeKind>,
+) {
+    if end_line <= start_line {
+        return;
+    }
+
+    ranges.push(FoldingRange {
+        start_line,
+        start_character: None,
+        end_line,
+        end_character: None,
+        kind,
+        collapsed_text: None,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::FoldingRangeKind;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::folding_range::folding_ranges;
+
+    fn ranges(text: &str) -> Vec<(u32, u32, Option<FoldingRangeKind>)> {
+        let document = Document::new(text, None);
+        folding_ranges(&document)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line, range.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_folding_braces_and_calls() {
+        let text = "
+f <- function(x) {
+  if (x) {
+    1
+  } else {
+    2
+  }
+}
+
+list(
+  a = 1,
+  b = 2
+)
+";
+        assert_eq!(ranges(text), vec![
+            (1, 6, None),
+            (2, 3, None),
+            (4, 5, None),
+            (9, 11, None),
+        ]);
+    }
+
+    #[test]
+    fn test_folding_single_line_nodes_are_skipped() {
+        assert_eq!(ranges("f <- function(x) { x }\nlist(a = 1)\n"), vec![]);
+    }
+
+    #[test]
+    fn test_folding_roxygen_blocks() {
+        let text = "
+#' Title
+#'
+#' @param x A value
+f <- function(x) x
+
+# Not roxygen
+# Neither
+#' Single line
+g <- 1
+";
+        assert_eq!(ranges(text), vec![(1, 3, Some(FoldingRangeKind::Comment))]);
+    }
+
+    #[test]
+    fn test_folding_nested_sections() {
+        let text = "
+# Section ----
+a <- 1
+
+## Subsection ----
+b <- 2
+
+## Another subsection ----
+c <- 3
+
+# Next section ----
+d <- 4
+
+";
+        assert_eq!(ranges(text), vec![
+            (1, 8, Some(FoldingRangeKind::Region)),
+            (4, 5, Some(FoldingRangeKind::Region)),
+            (7, 8, Some(FoldingRangeKind::Region)),
+            (10, 11, Some(FoldingRangeKind::Region)),
+        ]);
+    }
+}
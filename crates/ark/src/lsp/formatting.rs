@@ -0,0 +1,1004 @@
+//
+// formatting.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use anyhow::anyhow;
+use ropey::Rope;
+use tree_sitter::Node;
+use tree_sitter::Parser;
+use tree_sitter::Tree;
+
+use crate::lsp::config::IndentationConfig;
+use crate::lsp::documents::Document;
+use crate::lsp::indent::new_line_indent;
+use crate::lsp::offset::ArkPoint;
+use crate::lsp::offset::ArkRange;
+use crate::lsp::offset::ArkTextEdit;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::treesitter::node_has_error_or_missing;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Calls and function signatures that don't fit within this width when
+/// flattened are broken with one argument per line
+const LINE_WIDTH: usize = 80;
+
+/// Formats a whole document
+///
+/// The formatter only ever changes whitespace. It normalises spacing around
+/// operators and commas, puts each expression on its own line, indents
+/// braced expressions and broken calls according to the indentation config,
+/// and moves binary operators (e.g. pipes) to the end of lines. Line breaks
+/// between call arguments are respected: a call broken by the user is
+/// formatted with one argument per line.
+///
+/// The output only depends on the syntax tree and the indentation config, so
+/// formatting is deterministic and idempotent. Documents with syntax errors
+/// are not formatted.
+pub fn format_document(doc: &Document) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    if doc.markdown.is_some() {
+        return format_chunks(doc, 0, usize::MAX);
+    }
+
+    let text = doc.contents.to_string();
+
+    let Some(formatted) = format_source(&text, &doc.ast, &doc.config.indent)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(text_edits(&doc.contents, &text, &formatted)))
+}
+
+/// Formats the top-level expressions overlapping the rows `start..=end`
+///
+/// Expressions are always formatted as a whole, so the edited region may
+/// extend beyond the requested range.
+pub fn format_range(
+    doc: &Document,
+    start: usize,
+    end: usize,
+) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    if doc.markdown.is_some() {
+        return format_chunks(doc, start, end);
+    }
+
+    format_rows(doc, start, end)
+}
+
+/// Formats the R chunks of Quarto and R Markdown documents overlapping the
+/// rows `start..=end`. Each chunk is formatted on its own so that the
+/// markdown lines between chunks are left untouched.
+fn format_chunks(
+    doc: &Document,
+    start: usize,
+    end: usize,
+) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let mut edits = Vec::new();
+
+    for chunk in doc.chunks() {
+        let lines = chunk.code_lines();
+        if lines.is_empty() {
+            continue;
+        }
+
+        let chunk_start = usize::max(start, lines.start);
+        let chunk_end = usize::min(end, lines.end - 1);
+        if chunk_start > chunk_end {
+            continue;
+        }
+
+        if let Some(mut chunk_edits) = format_rows(doc, chunk_start, chunk_end)? {
+            edits.append(&mut chunk_edits);
+        }
+    }
+
+    Ok(Some(edits))
+}
+
+fn format_rows(
+    doc: &Document,
+    start: usize,
+    end: usize,
+) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let root = doc.ast.root_node();
+    if node_has_error_or_missing(&root) {
+        return Ok(None);
+    }
+
+    let mut cursor = root.walk();
+    let nodes: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|node| node.end_position().row >= start && node.start_position().row <= end)
+        .collect();
+
+    let (Some(first), Some(last)) = (nodes.first(), nodes.last()) else {
+        return Ok(None);
+    };
+
+    let text = doc.contents.to_string();
+    let formatter = Formatter::new(&text, &doc.config.indent);
+
+    let mut lines = Lines::new();
+    formatter.format_statements(&mut lines, nodes.iter().copied(), None, 0);
+    let snippet = lines.render(&doc.config.indent);
+
+    let mut formatted = String::with_capacity(text.len());
+    formatted.push_str(&text[..first.start_byte()]);
+    formatted.push_str(&snippet);
+    formatted.push_str(&text[last.end_byte()..]);
+
+    check_equivalent(&doc.ast, &text, &formatted)?;
+
+    Ok(Some(text_edits(&doc.contents, &text, &formatted)))
+}
+
+/// Formats R source code
+///
+/// Returns `None` if the code can't be formatted because of syntax errors.
+pub fn format_source(
+    text: &str,
+    ast: &Tree,
+    config: &IndentationConfig,
+) -> anyhow::Result<Option<String>> {
+    let root = ast.root_node();
+    if node_has_error_or_missing(&root) {
+        return Ok(None);
+    }
+
+    let formatter = Formatter::new(text, config);
+
+    let mut lines = Lines::new();
+    let mut cursor = root.walk();
+    let n = formatter.format_statements(&mut lines, root.named_children(&mut cursor), None, 0);
+
+    let mut formatted = lines.render(config);
+    if n > 0 {
+        formatted.push('\n');
+    }
+
+    check_equivalent(ast, text, &formatted)?;
+
+    Ok(Some(formatted))
+}
+
+/// Make sure the formatted code has the same syntax tree and tokens as the
+/// original code. Formatting should only ever affect whitespace, so this
+/// indicates a bug in the formatter.
+fn check_equivalent(ast: &Tree, text: &str, formatted: &str) -> anyhow::Result<()> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_r::LANGUAGE.into())
+        .unwrap();
+
+    let new_ast = parser
+        .parse(formatted, None)
+        .ok_or(anyhow!("Can't parse formatted code"))?;
+
+    if node_has_error_or_missing(&new_ast.root_node()) {
+        return Err(anyhow!("Formatted code has syntax errors"));
+    }
+
+    // Comments may be moved around operators and commas, so they are
+    // compared separately from the other tokens
+    if tree_signature(ast, text) != tree_signature(&new_ast, formatted) {
+        return Err(anyhow!("Formatted code doesn't match the original code"));
+    }
+
+    Ok(())
+}
+
+fn tree_signature(ast: &Tree, text: &str) -> (String, Vec<String>, Vec<String>) {
+    let root = ast.root_node();
+
+    let sexp = root.to_sexp().replace("(comment)", "");
+    let sexp = sexp.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let mut tokens = Vec::new();
+    let mut comments = Vec::new();
+
+    let mut cursor = root.walk();
+    cursor.recurse(|node| {
+        if node.child_count() == 0 {
+            let token = text[node.byte_range()].to_string();
+            if node.is_comment() {
+                comments.push(token);
+            } else {
+                tokens.push(token);
+            }
+        }
+        true
+    });
+
+    (sexp, tokens, comments)
+}
+
+/// Computes the edit transforming `old` into `new`, spanning the region in
+/// between their common prefix and suffix
+fn text_edits(contents: &Rope, old: &str, new: &str) -> Vec<ArkTextEdit> {
+    if old == new {
+        return Vec::new();
+    }
+
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(old, new)| old == new)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(old, new)| old == new)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+
+    let byte_to_point = |byte: usize| {
+        let row = contents.byte_to_line(byte);
+        let column = byte - contents.line_to_byte(row);
+        ArkPoint { row, column }
+    };
+
+    vec![ArkTextEdit {
+        range: ArkRange {
+            start: byte_to_point(prefix),
+            end: byte_to_point(old.len() - suffix),
+        },
+        new_text: new[prefix..new.len() - suffix].to_string(),
+    }]
+}
+
+#[derive(Clone, Debug, Default)]
+struct Line {
+    /// Indentation in columns, relative to the first line of the block
+    indent: usize,
+    text: String,
+
+    /// Continuation of a multiline token such as a string, printed as is
+    raw: bool,
+}
+
+/// A block of formatted code. The indentation of lines is relative to the
+/// first line so that blocks can be nested at any indentation level.
+#[derive(Clone, Debug)]
+struct Lines(Vec<Line>);
+
+impl Lines {
+    fn new() -> Self {
+        Self(vec![Line::default()])
+    }
+
+    fn from_text(text: &str) -> Self {
+        let mut lines = Self::new();
+        lines.push_str(text);
+        lines
+    }
+
+    /// Creates a block from source code that is kept as is, including the
+    /// indentation of continuation lines
+    fn verbatim(text: &str) -> Self {
+        let mut split = text.split('\n');
+
+        let mut lines = Self::from_text(split.next().unwrap_or(""));
+        for line in split {
+            lines.0.push(Line {
+                indent: 0,
+                text: line.to_string(),
+                raw: true,
+            });
+        }
+
+        lines
+    }
+
+    fn push_str(&mut self, text: &str) {
+        self.0.last_mut().unwrap().text.push_str(text);
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.0.push(Line {
+            indent,
+            text: String::new(),
+            raw: false,
+        });
+    }
+
+    /// Appends `other` to the last line. The continuation lines of `other`
+    /// are indented relative to that line.
+    fn append(&mut self, other: Lines) {
+        let base = self.0.last().unwrap().indent;
+
+        let mut other = other.0.into_iter();
+        if let Some(first) = other.next() {
+            self.push_str(&first.text);
+        }
+        for mut line in other {
+            if !line.raw {
+                line.indent += base;
+            }
+            self.0.push(line);
+        }
+    }
+
+    fn is_multiline(&self) -> bool {
+        self.0.len() > 1
+    }
+
+    fn width(&self) -> usize {
+        self.0[0].text.chars().count()
+    }
+
+    fn last_width(&self) -> usize {
+        self.0.last().unwrap().text.chars().count()
+    }
+
+    fn render(&self, config: &IndentationConfig) -> String {
+        self.0
+            .iter()
+            .map(|line| {
+                if line.raw || line.text.is_empty() {
+                    line.text.clone()
+                } else {
+                    new_line_indent(config, line.indent) + &line.text
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// An element of an argument or parameter list, along with the comments
+/// attached to it
+#[derive(Default)]
+struct ListItem {
+    leading: Vec<String>,
+    lines: Option<Lines>,
+    trailing: Vec<(String, bool)>,
+}
+
+impl ListItem {
+    fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.lines.is_none() && self.trailing.is_empty()
+    }
+
+    fn has_comments(&self) -> bool {
+        !self.leading.is_empty() || !self.trailing.is_empty()
+    }
+}
+
+struct Formatter<'a> {
+    text: &'a str,
+    config: &'a IndentationConfig,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(text: &'a str, config: &'a IndentationConfig) -> Self {
+        Self { text, config }
+    }
+
+    fn text(&self, node: &Node) -> &'a str {
+        &self.text[node.byte_range()]
+    }
+
+    fn indent(&self) -> usize {
+        self.config.indent_size
+    }
+
+    fn format(&self, node: Node) -> Lines {
+        // Comments may appear anywhere in the tree. We only handle them in
+        // the constructs below and keep other nodes containing comments as
+        // they are.
+        let handles_comments = matches!(
+            node.node_type(),
+            NodeType::BracedExpression |
+                NodeType::Arguments |
+                NodeType::Parameters |
+                NodeType::BinaryOperator(_)
+        );
+        if !handles_comments && node_has_comment_child(&node) {
+            return Lines::verbatim(self.text(&node));
+        }
+
+        match node.node_type() {
+            NodeType::BracedExpression => self.format_braced(node),
+            NodeType::ParenthesizedExpression => self.format_parenthesized(node),
+            NodeType::FunctionDefinition => self.format_function(node),
+            NodeType::IfStatement => self.format_if(node),
+            NodeType::ForStatement => self.format_for(node),
+            NodeType::WhileStatement => self.format_while(node),
+            NodeType::RepeatStatement => self.format_repeat(node),
+            NodeType::Call | NodeType::Subset | NodeType::Subset2 => self.format_call(node),
+            NodeType::Argument => self.format_argument(node, "value"),
+            NodeType::Parameter => self.format_argument(node, "default"),
+            NodeType::UnaryOperator(_) => self.format_unary(node),
+            NodeType::BinaryOperator(_) => self.format_binary(node),
+            NodeType::ExtractOperator(_) | NodeType::NamespaceOperator(_) => {
+                self.format_tight(node)
+            },
+            _ => Lines::verbatim(self.text(&node)),
+        }
+    }
+
+    fn format_field(&self, node: &Node, field: &str) -> Option<Lines> {
+        node.child_by_field_name(field)
+            .map(|child| self.format(child))
+    }
+
+    /// Formats a sequence of expressions and comments, each on their own
+    /// line. A single blank line is kept between expressions that were
+    /// separated by blank lines. Returns the number of nodes formatted.
+    fn format_statements<'tree>(
+        &self,
+        lines: &mut Lines,
+        nodes: impl Iterator<Item = Node<'tree>>,
+        open: Option<Node<'tree>>,
+        indent: usize,
+    ) -> usize {
+        let mut prev = open;
+        let mut n = 0;
+
+        for node in nodes {
+            let prev_row = prev.map(|prev| prev.end_position().row);
+            let row = node.start_position().row;
+
+            if node.is_comment() && prev_row == Some(row) {
+                lines.push_str(" ");
+                lines.push_str(self.text(&node));
+            } else {
+                if let Some(prev_row) = prev_row {
+                    // Keep a single blank line between expressions, but not
+                    // after an opening brace
+                    if n > 0 && row > prev_row + 1 {
+                        lines.newline(indent);
+                    }
+                    lines.newline(indent);
+                }
+                lines.append(self.format(node));
+            }
+
+            prev = Some(node);
+            n += 1;
+        }
+
+        n
+    }
+
+    fn format_braced(&self, node: Node) -> Lines {
+        let mut lines = Lines::from_text("{");
+
+        let mut cursor = node.walk();
+        let body: Vec<Node> = node.named_children(&mut cursor).collect();
+
+        if body.is_empty() {
+            lines.push_str("}");
+            return lines;
+        }
+
+        let open = node.child_by_field_name("open");
+        self.format_statements(&mut lines, body.into_iter(), open, self.indent());
+
+        lines.newline(0);
+        lines.push_str("}");
+        lines
+    }
+
+    fn format_parenthesized(&self, node: Node) -> Lines {
+        let mut lines = Lines::from_text("(");
+        if let Some(body) = self.format_field(&node, "body") {
+            lines.append(body);
+        }
+        lines.push_str(")");
+        lines
+    }
+
+    fn format_function(&self, node: Node) -> Lines {
+        let keyword = node.child_by_field_name("name").or(node.child(0));
+        let mut lines = Lines::from_text(keyword.map_or("function", |keyword| self.text(&keyword)));
+
+        if let Some(parameters) = node.child_by_field_name("parameters") {
+            let width = lines.last_width();
+            lines.append(self.format_list(parameters, width));
+        }
+
+        if let Some(body) = self.format_field(&node, "body") {
+            lines.push_str(" ");
+            lines.append(body);
+        }
+
+        lines
+    }
+
+    fn format_if(&self, node: Node) -> Lines {
+        let mut lines = Lines::from_text("if (");
+
+        if let Some(condition) = self.format_field(&node, "condition") {
+            lines.append(condition);
+        }
+        lines.push_str(") ");
+
+        if let Some(consequence) = self.format_field(&node, "consequence") {
+            lines.append(consequence);
+        }
+
+        if let Some(alternative) = self.format_field(&node, "alternative") {
+            lines.push_str(" else ");
+            lines.append(alternative);
+        }
+
+        lines
+    }
+
+    fn format_for(&self, node: Node) -> Lines {
+        let mut lines = Lines::from_text("for (");
+
+        if let Some(variable) = self.format_field(&node, "variable") {
+            lines.append(variable);
+        }
+        lines.push_str(" in ");
+
+        if let Some(sequence) = self.format_field(&node, "sequence") {
+            lines.append(sequence);
+        }
+        lines.push_str(") ");
+
+        if let Some(body) = self.format_field(&node, "body") {
+            lines.append(body);
+        }
+
+        lines
+    }
+
+    fn format_while(&self, node: Node) -> Lines {
+        let mut lines = Lines::from_text("while (");
+
+        if let Some(condition) = self.format_field(&node, "condition") {
+            lines.append(condition);
+        }
+        lines.push_str(") ");
+
+        if let Some(body) = self.format_field(&node, "body") {
+            lines.append(body);
+        }
+
+        lines
+    }
+
+    fn format_repeat(&self, node: Node) -> Lines {
+        let mut lines = Lines::from_text("repeat ");
+
+        if let Some(body) = self.format_field(&node, "body") {
+            lines.append(body);
+        }
+
+        lines
+    }
+
+    fn format_call(&self, node: Node) -> Lines {
+        let mut lines = match self.format_field(&node, "function") {
+            Some(function) => function,
+            None => Lines::new(),
+        };
+
+        if let Some(arguments) = node.child_by_field_name("arguments") {
+            let width = lines.last_width();
+            lines.append(self.format_list(arguments, width));
+        }
+
+        lines
+    }
+
+    /// Formats arguments and parameters, e.g. `name = value`
+    fn format_argument(&self, node: Node, value_field: &str) -> Lines {
+        let Some(name) = node.child_by_field_name("name") else {
+            return match self.format_field(&node, value_field) {
+                Some(value) => value,
+                None => Lines::verbatim(self.text(&node)),
+            };
+        };
+
+        let mut lines = self.format(name);
+
+        let mut cursor = node.walk();
+        let has_equals = node.children(&mut cursor).any(|child| child.kind() == "=");
+        if has_equals {
+            lines.push_str(" =");
+        }
+
+        if let Some(value) = self.format_field(&node, value_field) {
+            lines.push_str(" ");
+            lines.append(value);
+        }
+
+        lines
+    }
+
+    /// Formats the delimited list of `arguments` or `parameters` nodes
+    ///
+    /// The list is formatted on a single line, unless the user broke it over
+    /// several lines, it contains comments, or it doesn't fit within the line
+    /// width. A multiline last argument, typically a function or a braced
+    /// expression, is kept hugging the delimiters.
+    fn format_list(&self, node: Node, prefix_width: usize) -> Lines {
+        let (Some(open), Some(close)) = (
+            node.child_by_field_name("open"),
+            node.child_by_field_name("close"),
+        ) else {
+            return Lines::verbatim(self.text(&node));
+        };
+
+        let mut items: Vec<ListItem> = Vec::new();
+        let mut current = ListItem::default();
+        let mut has_comma = false;
+
+        // Whether the user broke the list over several lines
+        let mut broken = false;
+
+        let mut prev = open;
+        let mut cursor = node.walk();
+
+        for child in node.children(&mut cursor) {
+            if child == open || child == close {
+                continue;
+            }
+
+            let same_row = prev.end_position().row == child.start_position().row;
+            broken = broken || !same_row;
+
+            if child.is_comment() {
+                let text = self.text(&child).to_string();
+                match items.last_mut() {
+                    Some(last) if same_row && prev.node_type() == NodeType::Comma => {
+                        last.trailing.push((text, true))
+                    },
+                    _ if current.lines.is_none() => current.leading.push(text),
+                    _ => current.trailing.push((text, same_row)),
+                }
+            } else if child.node_type() == NodeType::Comma {
+                items.push(std::mem::take(&mut current));
+                has_comma = true;
+            } else {
+                current.lines = Some(self.format(child));
+            }
+
+            prev = child;
+        }
+        broken = broken || prev.end_position().row != close.start_position().row;
+
+        if has_comma || !current.is_empty() {
+            items.push(current);
+        }
+
+        let open = self.text(&open);
+        let close = self.text(&close);
+
+        if items.is_empty() {
+            return Lines::from_text(&format!("{open}{close}"));
+        }
+
+        let has_comments = items.iter().any(|item| item.has_comments());
+        if !broken && !has_comments {
+            if let Some(lines) = self.format_list_flat(&items, open, close, prefix_width) {
+                return lines;
+            }
+        }
+
+        self.format_list_broken(items, open, close)
+    }
+
+    fn format_list_flat(
+        &self,
+        items: &[ListItem],
+        open: &str,
+        close: &str,
+        prefix_width: usize,
+    ) -> Option<Lines> {
+        // Only the last item may span multiple lines
+        let n = items.len();
+        let multiline = items[..n - 1].iter().any(|item| {
+            item.lines
+                .as_ref()
+                .map_or(false, |lines| lines.is_multiline())
+        });
+        if multiline {
+            return None;
+        }
+
+        let mut lines = Lines::from_text(open);
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                lines.push_str(", ");
+            }
+            if let Some(item) = &item.lines {
+                lines.append(item.clone());
+            }
+        }
+        lines.push_str(close);
+
+        if prefix_width + lines.width() > LINE_WIDTH {
+            return None;
+        }
+
+        Some(lines)
+    }
+
+    fn format_list_broken(&self, items: Vec<ListItem>, open: &str, close: &str) -> Lines {
+        let indent = self.indent();
+        let n = items.len();
+
+        let mut lines = Lines::from_text(open);
+
+        for (i, item) in items.into_iter().enumerate() {
+            for comment in item.leading {
+                lines.newline(indent);
+                lines.push_str(&comment);
+            }
+
+            if item.lines.is_some() || i < n - 1 {
+                lines.newline(indent);
+                if let Some(item) = item.lines {
+                    lines.append(item);
+                }
+                if i < n - 1 {
+                    lines.push_str(",");
+                }
+            }
+
+            for (comment, inline) in item.trailing {
+                if !inline {
+                    lines.newline(indent);
+                } else {
+                    lines.push_str(" ");
+                }
+                lines.push_str(&comment);
+            }
+        }
+
+        lines.newline(0);
+        lines.push_str(close);
+        lines
+    }
+
+    fn format_unary(&self, node: Node) -> Lines {
+        let mut lines = match node.child_by_field_name("operator") {
+            Some(operator) => Lines::from_text(self.text(&operator)),
+            None => Lines::new(),
+        };
+
+        let mut cursor = node.walk();
+        let operand = node
+            .child_by_field_name("rhs")
+            .or_else(|| node.named_children(&mut cursor).last());
+        if let Some(operand) = operand {
+            lines.append(self.format(operand));
+        }
+
+        lines
+    }
+
+    /// Formats nodes that don't take spaces around their operator, like `$`
+    /// and `::`
+    fn format_tight(&self, node: Node) -> Lines {
+        let mut lines = match self.format_field(&node, "lhs") {
+            Some(lhs) => lhs,
+            None => Lines::new(),
+        };
+
+        if let Some(operator) = node.child_by_field_name("operator") {
+            lines.push_str(self.text(&operator));
+        }
+
+        if let Some(rhs) = self.format_field(&node, "rhs") {
+            lines.append(rhs);
+        }
+
+        lines
+    }
+
+    fn format_binary(&self, node: Node) -> Lines {
+        let (Some(lhs), Some(operator), Some(rhs)) = (
+            node.child_by_field_name("lhs"),
+            node.child_by_field_name("operator"),
+            node.child_by_field_name("rhs"),
+        ) else {
+            return Lines::verbatim(self.text(&node));
+        };
+
+        let tight = matches!(
+            node.node_type(),
+            NodeType::BinaryOperator(BinaryOperatorType::Exponentiate) |
+                NodeType::BinaryOperator(BinaryOperatorType::Colon)
+        );
+
+        let mut cursor = node.walk();
+        let comments: Vec<&str> = node
+            .children(&mut cursor)
+            .filter(|child| child.is_comment())
+            .map(|child| self.text(&child))
+            .collect();
+
+        let mut lines = self.format(lhs);
+        if !tight {
+            lines.push_str(" ");
+        }
+        lines.push_str(self.text(&operator));
+
+        // Keep the line break if the user split the operands over several
+        // lines, but always leave the operator at the end of the line
+        let broken = !comments.is_empty() || rhs.start_position().row > lhs.end_position().row;
+
+        if broken {
+            for (i, comment) in comments.into_iter().enumerate() {
+                if i > 0 {
+                    lines.newline(self.indent());
+                } else {
+                    lines.push_str(" ");
+                }
+                lines.push_str(comment);
+            }
+            lines.newline(self.indent());
+        } else if !tight {
+            lines.push_str(" ");
+        }
+
+        lines.append(self.format(rhs));
+        lines
+    }
+}
+
+fn node_has_comment_child(node: &Node) -> bool {
+    let mut cursor = node.walk();
+    let has_comment = node.children(&mut cursor).any(|child| child.is_comment());
+    has_comment
+}
+
+#[cfg(test)]
+mod tests {
+    use stdext::assert_match;
+
+    use crate::lsp::config::IndentStyle;
+    use crate::lsp::config::IndentationConfig;
+    use crate::lsp::documents::Document;
+    use crate::lsp::formatting::format_document;
+    use crate::lsp::formatting::format_range;
+    use crate::lsp::offset::apply_text_edits;
+
+    fn format(text: &str) -> String {
+        let doc = Document::new(text, None);
+        format_doc(&doc, text)
+    }
+
+    fn format_doc(doc: &Document, text: &str) -> String {
+        let edits = format_document(doc).unwrap().unwrap();
+        let mut text = text.to_string();
+        apply_text_edits(edits, &mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_format_spacing() {
+        assert_eq!(format("x<-1+2*3"), "x <- 1 + 2 * 3\n");
+        assert_eq!(format("y=c(1,2 ,3)"), "y = c(1, 2, 3)\n");
+        assert_eq!(format("x[1:10]^2"), "x[1:10]^2\n");
+        assert_eq!(format("pkg::fn(a$b,d@e)"), "pkg::fn(a$b, d@e)\n");
+        assert_eq!(format("- x\n! y"), "-x\n!y\n");
+        assert_eq!(format("x[,1]\nx[1,]"), "x[, 1]\nx[1, ]\n");
+        assert_eq!(format("f(a=1,b=)"), "f(a = 1, b =)\n");
+    }
+
+    #[test]
+    fn test_format_braces() {
+        assert_eq!(
+            format("f<-function(x){\nif(x){1}else{2}}"),
+            "f <- function(x) {\n  if (x) {\n    1\n  } else {\n    2\n  }\n}\n"
+        );
+        assert_eq!(format("if(a)b else c"), "if (a) b else c\n");
+        assert_eq!(format("for(i in xs){}"), "for (i in xs) {}\n");
+        assert_eq!(format("{\n\n  x\n\n\n  y\n\n}"), "{\n  x\n\n  y\n}\n");
+    }
+
+    #[test]
+    fn test_format_calls() {
+        // User line breaks are respected
+        assert_eq!(format("foo(a,\nb = 2)"), "foo(\n  a,\n  b = 2\n)\n");
+
+        // Multiline last arguments hug the parentheses
+        assert_eq!(
+            format("lapply(xs, function(x) {\nx\n})"),
+            "lapply(xs, function(x) {\n  x\n})\n"
+        );
+
+        // Long calls are broken
+        let text = "foo(aaaaaaaaaa, bbbbbbbbbb, cccccccccc, dddddddddd, eeeeeeeeee, ffffffffff, gggggggggg)";
+        let expected = "foo(\n  aaaaaaaaaa,\n  bbbbbbbbbb,\n  cccccccccc,\n  dddddddddd,\n  eeeeeeeeee,\n  ffffffffff,\n  gggggggggg\n)\n";
+        assert_eq!(format(text), expected);
+    }
+
+    #[test]
+    fn test_format_pipes() {
+        assert_eq!(
+            format("x |>\nf() |>\n        g()"),
+            "x |>\n  f() |>\n  g()\n"
+        );
+
+        // Operators are moved to the end of lines
+        assert_eq!(format("foo(x\n|> bar())"), "foo(x |>\n  bar())\n");
+    }
+
+    #[test]
+    fn test_format_comments() {
+        assert_eq!(
+            format("# header\n\n\nx <- 1 # trailing\n\n\n\ny <- 2\n"),
+            "# header\n\nx <- 1 # trailing\n\ny <- 2\n"
+        );
+
+        let text = "foo(\n  a, # first\n  # own line\n  b\n)\n";
+        assert_eq!(format(text), text);
+    }
+
+    #[test]
+    fn test_format_idempotent() {
+        let text = "f<-function(x,y=2){\n  # Comment\n  x|>\n  g(y,\n z = 3)  # Trailing\n\n\n  lapply(x,function(i){i})}\n";
+        let formatted = format(text);
+        assert_eq!(format(&formatted), formatted);
+
+        let doc = Document::new(&formatted, None);
+        assert!(format_document(&doc).unwrap().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_format_indentation_config() {
+        let text = "f <- function() {\nx\n}";
+        let mut doc = Document::new(text, None);
+        doc.config.indent = IndentationConfig {
+            indent_style: IndentStyle::Tab,
+            indent_size: 4,
+            tab_width: 4,
+        };
+        assert_eq!(format_doc(&doc, text), "f <- function() {\n\tx\n}\n");
+    }
+
+    #[test]
+    fn test_format_syntax_error() {
+        let doc = Document::new("foo(", None);
+        assert_match!(format_document(&doc), Ok(None));
+    }
+
+    #[test]
+    fn test_format_range() {
+        let text = "x<-1\ny<-2\nz<-3";
+        let doc = Document::new(text, None);
+
+        let edits = format_range(&doc, 1, 1).unwrap().unwrap();
+        let mut text = text.to_string();
+        apply_text_edits(edits, &mut text).unwrap();
+        assert_eq!(text, "x<-1\ny <- 2\nz<-3");
+    }
+
+    #[test]
+    fn test_format_markdown_chunks() {
+        let text = "# Title\n\n```{r}\nx<-1\n```\n\nSome   text\n\n```{r}\ny<-2\n```\n";
+        let doc = Document::new_markdown(text, None);
+
+        // Edits are relative to the original text, apply them from the bottom
+        let mut edits = format_document(&doc).unwrap().unwrap();
+        edits.reverse();
+
+        let mut text = text.to_string();
+        apply_text_edits(edits, &mut text).unwrap();
+        assert_eq!(
+            text,
+            "# Title\n\n```{r}\nx <- 1\n```\n\nSome   text\n\n```{r}\ny <- 2\n```\n"
+        );
+    }
+}
@@ -11,26 +11,60 @@ use once_cell::sync::Lazy;
 use serde_json::Value;
 use stdext::unwrap;
 use struct_field_names_as_array::FieldNamesAsArray;
+use tower_lsp::lsp_types::request::GotoImplementationParams;
+use tower_lsp::lsp_types::request::GotoImplementationResponse;
+use tower_lsp::lsp_types::CallHierarchyIncomingCall;
+use tower_lsp::lsp_types::CallHierarchyIncomingCallsParams;
+use tower_lsp::lsp_types::CallHierarchyItem;
+use tower_lsp::lsp_types::CallHierarchyOutgoingCall;
+use tower_lsp::lsp_types::CallHierarchyOutgoingCallsParams;
+use tower_lsp::lsp_types::CallHierarchyPrepareParams;
+use tower_lsp::lsp_types::CodeActionParams;
+use tower_lsp::lsp_types::CodeActionResponse;
+use tower_lsp::lsp_types::CodeLens;
+use tower_lsp::lsp_types::CodeLensParams;
 use tower_lsp::lsp_types::CompletionItem;
 use tower_lsp::lsp_types::CompletionParams;
 use tower_lsp::lsp_types::CompletionResponse;
+use tower_lsp::lsp_types::DidChangeWatchedFilesRegistrationOptions;
+use tower_lsp::lsp_types::DocumentFormattingParams;
+use tower_lsp::lsp_types::DocumentHighlight;
+use tower_lsp::lsp_types::DocumentHighlightParams;
 use tower_lsp::lsp_types::DocumentOnTypeFormattingParams;
+use tower_lsp::lsp_types::DocumentRangeFormattingParams;
 use tower_lsp::lsp_types::DocumentSymbolParams;
 use tower_lsp::lsp_types::DocumentSymbolResponse;
+use tower_lsp::lsp_types::FileSystemWatcher;
+use tower_lsp::lsp_types::FoldingRange;
+use tower_lsp::lsp_types::FoldingRangeParams;
+use tower_lsp::lsp_types::GlobPattern;
 use tower_lsp::lsp_types::GotoDefinitionParams;
 use tower_lsp::lsp_types::GotoDefinitionResponse;
 use tower_lsp::lsp_types::Hover;
 use tower_lsp::lsp_types::HoverContents;
 use tower_lsp::lsp_types::HoverParams;
+use tower_lsp::lsp_types::InlayHint;
+use tower_lsp::lsp_types::InlayHintParams;
+use tower_lsp::lsp_types::LinkedEditingRangeParams;
+use tower_lsp::lsp_types::LinkedEditingRanges;
 use tower_lsp::lsp_types::Location;
 use tower_lsp::lsp_types::MessageType;
+use tower_lsp::lsp_types::PrepareRenameResponse;
 use tower_lsp::lsp_types::ReferenceParams;
 use tower_lsp::lsp_types::Registration;
+use tower_lsp::lsp_types::RenameParams;
 use tower_lsp::lsp_types::SelectionRange;
 use tower_lsp::lsp_types::SelectionRangeParams;
+use tower_lsp::lsp_types::SemanticTokensDeltaParams;
+use tower_lsp::lsp_types::SemanticTokensFullDeltaResult;
+use tower_lsp::lsp_types::SemanticTokensParams;
+use tower_lsp::lsp_types::SemanticTokensRangeParams;
+use tower_lsp::lsp_types::SemanticTokensRangeResult;
+use tower_lsp::lsp_types::SemanticTokensResult;
 use tower_lsp::lsp_types::SignatureHelp;
 use tower_lsp::lsp_types::SignatureHelpParams;
 use tower_lsp::lsp_types::SymbolInformation;
+use tower_lsp::lsp_types::TextDocumentPositionParams;
 use tower_lsp::lsp_types::TextEdit;
 use tower_lsp::lsp_types::WorkspaceEdit;
 use tower_lsp::lsp_types::WorkspaceSymbolParams;
@@ -40,26 +74,45 @@ use tree_sitter::Point;
 
 use crate::analysis::input_boundaries::input_boundaries;
 use crate::lsp;
+use crate::lsp::call_hierarchy::incoming_calls;
+use crate::lsp::call_hierarchy::outgoing_calls;
+use crate::lsp::call_hierarchy::prepare_call_hierarchy;
+use crate::lsp::code_action::code_actions;
+use crate::lsp::code_lens::code_lenses;
 use crate::lsp::completions::provide_completions;
 use crate::lsp::completions::resolve_completion;
 use crate::lsp::config::VscDiagnosticsConfig;
 use crate::lsp::config::VscDocumentConfig;
 use crate::lsp::definitions::goto_definition;
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::document_highlight::document_highlights;
+use crate::lsp::document_highlight::linked_editing_ranges;
 use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::folding_range::folding_ranges;
+use crate::lsp::formatting::format_document;
+use crate::lsp::formatting::format_range;
 use crate::lsp::help_topic::help_topic;
 use crate::lsp::help_topic::HelpTopicParams;
 use crate::lsp::help_topic::HelpTopicResponse;
 use crate::lsp::hover::r_hover;
+use crate::lsp::hover::workspace_hover;
+use crate::lsp::implementation::goto_implementation;
 use crate::lsp::indent::indent_edit;
+use crate::lsp::inlay_hints::inlay_hints;
 use crate::lsp::input_boundaries::InputBoundariesParams;
 use crate::lsp::input_boundaries::InputBoundariesResponse;
 use crate::lsp::main_loop::LspState;
 use crate::lsp::offset::IntoLspOffset;
 use crate::lsp::references::find_references;
+use crate::lsp::rename::prepare_rename;
+use crate::lsp::rename::rename;
 use crate::lsp::selection_range::convert_selection_range_from_tree_sitter_to_lsp;
 use crate::lsp::selection_range::selection_range;
+use crate::lsp::semantic_tokens::semantic_tokens_full;
+use crate::lsp::semantic_tokens::semantic_tokens_full_delta;
+use crate::lsp::semantic_tokens::semantic_tokens_range;
 use crate::lsp::signature_help::r_signature_help;
+use crate::lsp::signature_help::workspace_signature_help;
 use crate::lsp::state::WorldState;
 use crate::lsp::statement_range::statement_range;
 use crate::lsp::statement_range::StatementRangeParams;
@@ -109,6 +162,30 @@ pub(crate) async fn handle_initialized(
         regs.append(&mut config_diagnostics_regs);
     }
 
+    if lsp_state.needs_registration.did_change_watched_files {
+        // Ask the client to notify us of R files changed outside the editor
+        // so we can keep the workspace index current, and of package and
+        // project files rewritten by tools like `roxygen2::roxygenise()`
+        let watchers = [
+            "**/*.{r,R}",
+            "**/DESCRIPTION",
+            "**/NAMESPACE",
+            "**/.ark.toml",
+        ]
+        .into_iter()
+        .map(|glob| FileSystemWatcher {
+            glob_pattern: GlobPattern::String(String::from(glob)),
+            kind: None,
+        })
+        .collect();
+        let options = DidChangeWatchedFilesRegistrationOptions { watchers };
+        regs.push(Registration {
+            id: uuid::Uuid::new_v4().to_string(),
+            method: String::from("workspace/didChangeWatchedFiles"),
+            register_options: Some(serde_json::to_value(options)?),
+        });
+    }
+
     client
         .register_capability(regs)
         .instrument(span.exit())
@@ -221,6 +298,16 @@ pub(crate) fn handle_hover(
         return Ok(None);
     });
 
+    // fall back to the roxygen documentation of workspace functions, which
+    // might not be installed
+    let result = match result {
+        Some(result) => Some(result),
+        None => workspace_hover(&context, state).unwrap_or_else(|err| {
+            lsp::log_error!("{err:?}");
+            None
+        }),
+    };
+
     // unwrap empty options
     let result = unwrap!(result, None => {
         return Ok(None);
@@ -249,6 +336,13 @@ pub(crate) fn handle_signature_help(
     // request signature help
     let result = r_task(|| r_signature_help(&context));
 
+    // fall back to the functions defined in the workspace, which might not be
+    // loaded. These are looked up outside of the R thread.
+    let result = match result {
+        Ok(None) => workspace_signature_help(&context, state),
+        result => result,
+    };
+
     // unwrap errors
     let result = unwrap!(result, Err(err) => {
         lsp::log_error!("{err:?}");
@@ -281,6 +375,26 @@ pub(crate) fn handle_goto_definition(
     Ok(result)
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_goto_implementation(
+    params: GotoImplementationParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<GotoImplementationResponse>> {
+    let locations = match goto_implementation(params, state) {
+        Ok(locations) => locations,
+        Err(err) => {
+            lsp::log_error!("{err:?}");
+            return Ok(None);
+        },
+    };
+
+    if locations.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(GotoImplementationResponse::Array(locations)))
+    }
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn handle_selection_range(
     params: SelectionRangeParams,
@@ -331,6 +445,122 @@ pub(crate) fn handle_references(
     }
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_prepare_rename(
+    params: TextDocumentPositionParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<PrepareRenameResponse>> {
+    prepare_rename(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_rename(
+    params: RenameParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    rename(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_code_action(
+    params: CodeActionParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<CodeActionResponse>> {
+    code_actions(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_prepare_call_hierarchy(
+    params: CallHierarchyPrepareParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyItem>>> {
+    prepare_call_hierarchy(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_incoming_calls(
+    params: CallHierarchyIncomingCallsParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+    incoming_calls(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_outgoing_calls(
+    params: CallHierarchyOutgoingCallsParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+    outgoing_calls(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_inlay_hint(
+    params: InlayHintParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<InlayHint>>> {
+    inlay_hints(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_code_lens(
+    params: CodeLensParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CodeLens>>> {
+    let document = state.get_document(&params.text_document.uri)?;
+    Ok(Some(code_lenses(document)))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_folding_range(
+    params: FoldingRangeParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<FoldingRange>>> {
+    let document = state.get_document(&params.text_document.uri)?;
+    Ok(Some(folding_ranges(document)))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_document_highlight(
+    params: DocumentHighlightParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<DocumentHighlight>>> {
+    document_highlights(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_linked_editing_range(
+    params: LinkedEditingRangeParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<LinkedEditingRanges>> {
+    linked_editing_ranges(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_semantic_tokens_full(
+    params: SemanticTokensParams,
+    lsp_state: &mut LspState,
+    state: &WorldState,
+) -> anyhow::Result<Option<SemanticTokensResult>> {
+    semantic_tokens_full(params, &mut lsp_state.semantic_tokens, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_semantic_tokens_full_delta(
+    params: SemanticTokensDeltaParams,
+    lsp_state: &mut LspState,
+    state: &WorldState,
+) -> anyhow::Result<Option<SemanticTokensFullDeltaResult>> {
+    semantic_tokens_full_delta(params, &mut lsp_state.semantic_tokens, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_semantic_tokens_range(
+    params: SemanticTokensRangeParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<SemanticTokensRangeResult>> {
+    semantic_tokens_range(params, state)
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn handle_statement_range(
     params: StatementRangeParams,
@@ -384,6 +614,37 @@ pub(crate) fn handle_indent(
     })
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_formatting(
+    params: DocumentFormattingParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let doc = state.get_document(&params.text_document.uri)?;
+
+    let res = format_document(doc);
+
+    Result::map(res, |opt| {
+        Option::map(opt, |edits| edits.into_lsp_offset(&doc.contents))
+    })
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_range_formatting(
+    params: DocumentRangeFormattingParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let doc = state.get_document(&params.text_document.uri)?;
+
+    let start = convert_position_to_point(&doc.contents, params.range.start);
+    let end = convert_position_to_point(&doc.contents, params.range.end);
+
+    let res = format_range(doc, start.row, end.row);
+
+    Result::map(res, |opt| {
+        Option::map(opt, |edits| edits.into_lsp_offset(&doc.contents))
+    })
+}
+
 // TODO: Should be in WorldState and updated via message passing
 pub static mut ARK_VDOCS: Lazy<DashMap<String, String>> = Lazy::new(|| DashMap::new());
 
@@ -14,7 +14,9 @@ use tree_sitter::Node;
 
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::help::RHtmlHelp;
+use crate::lsp::state::WorldState;
 use crate::lsp::traits::rope::RopeExt;
+use crate::lsp::workspace_function::WorkspaceFunction;
 use crate::treesitter::NodeTypeExt;
 
 enum HoverContext {
@@ -100,3 +102,30 @@ pub(crate) fn r_hover(context: &DocumentContext) -> anyhow::Result<Option<Markup
         value: markdown,
     }))
 }
+
+/// Hover documentation of functions defined in the workspace: their
+/// signature and roxygen block. This covers functions of packages under
+/// development that aren't installed yet, for which `r_hover()` finds no help
+/// page.
+pub(crate) fn workspace_hover(
+    context: &DocumentContext,
+    state: &WorldState,
+) -> anyhow::Result<Option<MarkupContent>> {
+    let node = &context.node;
+    if !node.is_identifier_or_string() {
+        return Ok(None);
+    }
+
+    let Some(HoverContext::Topic { topic }) = hover_context(*node, context)? else {
+        return Ok(None);
+    };
+
+    let Some(function) = WorkspaceFunction::find(&topic, state) else {
+        return Ok(None);
+    };
+
+    Ok(Some(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: function.markdown(),
+    }))
+}
@@ -0,0 +1,237 @@
+//
+// implementation.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::RObject;
+use ropey::Rope;
+use tower_lsp::lsp_types::request::GotoImplementationParams;
+use tower_lsp::lsp_types::Location;
+use tower_lsp::lsp_types::Position;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::indexer;
+use crate::lsp::indexer::IndexEntryData;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::state::WorldState;
+use crate::r_task;
+use crate::treesitter::node_find_string;
+use crate::treesitter::NodeTypeExt;
+
+/// Calls whose first argument names the generic they dispatch on
+const DISPATCH_FUNCTIONS: &[&str] = &["UseMethod", "standardGeneric"];
+
+/// Finds the methods of the S3 or S4 generic at the cursor. The generic is
+/// either named at the cursor, e.g. `print` or `setGeneric("area")`, or
+/// dispatched by the `UseMethod()` or `standardGeneric()` call at the cursor.
+/// Methods are collected from:
+///
+/// - Functions of the workspace named `generic.class`.
+/// - `setMethod()` definitions of the workspace.
+/// - Functions registered with `S3method()` in the `NAMESPACE` of the
+///   workspace package, which can have any name.
+/// - S3 methods exported or registered by loaded namespaces.
+pub(crate) fn goto_implementation(
+    params: GotoImplementationParams,
+    state: &WorldState,
+) -> anyhow::Result<Vec<Location>> {
+    let uri = &params.text_document_position_params.text_document.uri;
+    let document = state.get_document(uri)?;
+
+    let position = params.text_document_position_params.position;
+    let point = convert_position_to_point(&document.contents, position);
+
+    let Some(generic) = generic_at_point(document.ast.root_node(), point, &document.contents)
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut locations = workspace_methods(&generic, state);
+
+    match r_task(|| session_methods(&generic)) {
+        Ok(session) => locations.extend(session),
+        Err(err) => lsp::log_error!("Can't look up methods of `{generic}`: {err:?}"),
+    }
+
+    // Methods of packages loaded with `load_all()` are found both in the
+    // workspace and in the session
+    let mut seen: Vec<(Url, u32)> = Vec::new();
+    locations.retain(|location| {
+        let key = (location.uri.clone(), location.range.start.line);
+        if seen.contains(&key) {
+            return false;
+        }
+        seen.push(key);
+        true
+    });
+
+    Ok(locations)
+}
+
+/// The name of the generic at `point`
+fn generic_at_point(root: Node, point: Point, contents: &Rope) -> Option<String> {
+    let node = root.descendant_for_point_range(point, point)?;
+    let node = node_find_string(&node).unwrap_or(node);
+
+    let name = node_symbol_name(&node, contents)?;
+    if !DISPATCH_FUNCTIONS.contains(&name.as_str()) {
+        return Some(name);
+    }
+
+    // On `UseMethod` itself, use the generic it dispatches on
+    let call = node.parent().filter(|parent| parent.is_call())?;
+    if call.child_by_field_name("function") != Some(node) {
+        return Some(name);
+    }
+
+    let arguments = call.child_by_field_name("arguments")?;
+    let mut cursor = arguments.walk();
+    let value = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .next()?
+        .child_by_field_name("value")?;
+
+    node_symbol_name(&value, contents)
+}
+
+fn workspace_methods(generic: &str, state: &WorldState) -> Vec<Location> {
+    let prefix = format!("{generic}.");
+
+    // Functions registered under another name than `generic.class`
+    let registered: Vec<&String> = match &state.workspace.package {
+        Some(package) => package
+            .namespace
+            .s3_methods
+            .iter()
+            .filter(|(name, _function)| name == generic)
+            .map(|(_name, function)| function)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut locations = Vec::new();
+
+    indexer::map(|path, symbol, entry| {
+        let is_method = match &entry.data {
+            IndexEntryData::Function { .. } => {
+                (symbol.starts_with(&prefix) && symbol.len() > prefix.len()) ||
+                    registered.contains(&symbol)
+            },
+            IndexEntryData::Method { generic: name, .. } => name == generic,
+            _ => false,
+        };
+
+        if is_method {
+            if let Some(location) = location(path, entry.range) {
+                locations.push(location);
+            }
+        }
+    });
+
+    // The index is unordered
+    locations.sort_by(|a, b| {
+        (a.uri.as_str(), a.range.start.line).cmp(&(b.uri.as_str(), b.range.start.line))
+    });
+
+    locations
+}
+
+fn location(path: &Path, range: Range) -> Option<Location> {
+    let uri = Url::from_file_path(path).ok()?;
+    Some(Location::new(uri, range))
+}
+
+/// S3 methods of loaded namespaces, located with their source references.
+/// These point to the virtual documents of the namespaces unless packages
+/// were installed with their sources.
+///
+/// SAFETY: Requires access to the R runtime.
+fn session_methods(generic: &str) -> anyhow::Result<Vec<Location>> {
+    let result = RFunction::new("", ".ps.s3.methodSources")
+        .add(generic)
+        .call()?;
+    let mut result = HashMap::<String, RObject>::try_from(result)?;
+
+    let mut field = |name: &str| {
+        result
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("Missing field `{name}`"))
+    };
+    let files = Vec::<String>::try_from(field("file")?)?;
+    let lines = Vec::<i32>::try_from(field("line")?)?;
+    let end_lines = Vec::<i32>::try_from(field("end_line")?)?;
+
+    let mut locations = Vec::new();
+
+    for ((file, line), end_line) in files.iter().zip(lines).zip(end_lines) {
+        // Virtual documents have an `ark:` URI, other files are paths
+        let uri = if file.starts_with("ark:") {
+            Url::parse(file).ok()
+        } else {
+            Url::from_file_path(file).ok()
+        };
+        let Some(uri) = uri else {
+            continue;
+        };
+
+        // Source references are 1-based
+        let start = Position::new((line - 1).max(0) as u32, 0);
+        let end = Position::new((end_line - 1).max(0) as u32, 0);
+        locations.push(Location::new(uri, Range::new(start, end)));
+    }
+
+    Ok(locations)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::implementation::generic_at_point;
+
+    fn generic(text: &str, point: Point) -> Option<String> {
+        let document = Document::new(text, None);
+        generic_at_point(document.ast.root_node(), point, &document.contents)
+    }
+
+    #[test]
+    fn test_generic_at_point() {
+        let text = "summary <- function(object, ...) UseMethod(\"summary\")";
+
+        // On the name of the generic
+        assert_eq!(
+            generic(text, Point::new(0, 2)),
+            Some(String::from("summary"))
+        );
+
+        // On `UseMethod` and on its argument
+        assert_eq!(
+            generic(text, Point::new(0, 36)),
+            Some(String::from("summary"))
+        );
+        assert_eq!(
+            generic(text, Point::new(0, 46)),
+            Some(String::from("summary"))
+        );
+
+        let text = "setGeneric('area', function(shape) standardGeneric('area'))";
+        assert_eq!(generic(text, Point::new(0, 13)), Some(String::from("area")));
+        assert_eq!(generic(text, Point::new(0, 40)), Some(String::from("area")));
+
+        // Not on a symbol
+        assert_eq!(generic("f(1)", Point::new(0, 2)), None);
+    }
+}
@@ -0,0 +1,243 @@
+//
+// index_cache.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use rustc_hash::FxHasher;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::lsp;
+use crate::lsp::indexer::IndexEntry;
+
+/// Bump this whenever the serialized format of the index changes, e.g. when
+/// a variant is added to `IndexEntryData`. Caches written with another
+/// version are discarded.
+const INDEX_CACHE_VERSION: u32 = 1;
+
+/// Identifies the state of a file on disk. The modification time and size
+/// are checked first so that unchanged files don't need to be read. The
+/// content hash catches files that were touched without being modified,
+/// e.g. by a `git checkout` round trip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub modified: u64,
+    pub size: u64,
+    pub hash: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CachedDocument {
+    pub stamp: FileStamp,
+    pub symbols: HashMap<String, IndexEntry>,
+}
+
+/// The on-disk index of a workspace folder, keyed by file path
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IndexCache {
+    version: u32,
+    pub documents: HashMap<String, CachedDocument>,
+}
+
+impl IndexCache {
+    pub fn new(documents: HashMap<String, CachedDocument>) -> Self {
+        Self {
+            version: INDEX_CACHE_VERSION,
+            documents,
+        }
+    }
+
+    /// Loads the cache of a workspace folder. Missing, unreadable, or
+    /// outdated caches result in an empty cache so that the folder is
+    /// indexed from scratch.
+    pub fn load(folder: &str) -> Self {
+        let Some(path) = cache_path(folder) else {
+            return Self::default();
+        };
+
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match Self::read(&path) {
+            Ok(cache) if cache.version == INDEX_CACHE_VERSION => cache,
+            Ok(_) => {
+                lsp::log_info!("Discarding outdated index cache {path:?}");
+                Self::default()
+            },
+            Err(err) => {
+                lsp::log_error!("Can't read index cache {path:?}: {err:?}");
+                Self::default()
+            },
+        }
+    }
+
+    pub fn save(&self, folder: &str) -> anyhow::Result<()> {
+        let Some(path) = cache_path(folder) else {
+            return Ok(());
+        };
+        self.write(&path)
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    fn write(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first so that concurrent sessions never
+        // read a partially written cache. The name is unique so that
+        // concurrent writers don't clobber each other's temporary file.
+        let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+
+        if let Err(err) = std::fs::rename(&tmp, path) {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(err.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the modification time in milliseconds and the size of a file
+pub fn file_metadata(path: &Path) -> anyhow::Result<(u64, u64)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok((modified.as_millis() as u64, metadata.len()))
+}
+
+/// A fast hash that is stable across sessions, unlike the randomly seeded
+/// `DefaultHasher`
+pub fn content_hash(contents: &[u8]) -> u64 {
+    let mut hasher = FxHasher::default();
+    hasher.write(contents);
+    hasher.finish()
+}
+
+/// The cache file of a workspace folder. Folders are identified by a hash of
+/// their path so that the cache directory stays flat.
+fn cache_path(folder: &str) -> Option<PathBuf> {
+    let dir = cache_dir()?;
+    let name = format!("{:016x}.json", content_hash(folder.as_bytes()));
+    Some(dir.join("ark").join("index").join(name))
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("ARK_INDEX_CACHE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+
+    if cfg!(target_os = "windows") {
+        return std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    }
+
+    if cfg!(target_os = "macos") {
+        return home::home_dir().map(|home| home.join("Library").join("Caches"));
+    }
+
+    match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => home::home_dir().map(|home| home.join(".cache")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tower_lsp::lsp_types::Range;
+
+    use crate::lsp::index_cache::content_hash;
+    use crate::lsp::index_cache::CachedDocument;
+    use crate::lsp::index_cache::FileStamp;
+    use crate::lsp::index_cache::IndexCache;
+    use crate::lsp::indexer::IndexEntry;
+    use crate::lsp::indexer::IndexEntryData;
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash(b"foo <- 1"), content_hash(b"foo <- 1"));
+        assert_ne!(content_hash(b"foo <- 1"), content_hash(b"foo <- 2"));
+    }
+
+    #[test]
+    fn test_index_cache_round_trip() {
+        let entry = IndexEntry {
+            key: String::from("foo"),
+            range: Range::default(),
+            data: IndexEntryData::Function {
+                name: String::from("foo"),
+                arguments: vec![String::from("x")],
+            },
+        };
+
+        let stamp = FileStamp {
+            modified: 1,
+            size: 2,
+            hash: 3,
+        };
+
+        let cache = IndexCache::new(HashMap::from([(String::from("/foo.R"), CachedDocument {
+            stamp,
+            symbols: HashMap::from([(String::from("foo"), entry)]),
+        })]));
+
+        let dir = std::env::temp_dir().join(format!("ark-index-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("index.json");
+        cache.write(&path).unwrap();
+
+        let cache = IndexCache::read(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let document = cache.documents.get("/foo.R").unwrap();
+        assert_eq!(document.stamp, stamp);
+
+        let entry = document.symbols.get("foo").unwrap();
+        assert!(matches!(
+            &entry.data,
+            IndexEntryData::Function { name, arguments } if name == "foo" && arguments == &vec![String::from("x")]
+        ));
+    }
+
+    #[test]
+    fn test_index_cache_concurrent_writes() {
+        let dir = std::env::temp_dir().join(format!("ark-index-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("index.json");
+
+        // Writers of the same cache don't share temporary files. Replacing
+        // the cache may still fail on Windows while another writer replaces
+        // it, but no temporary file is left behind.
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || IndexCache::new(HashMap::new()).write(&path))
+            })
+            .collect();
+        for handle in handles {
+            let result = handle.join().unwrap();
+            if !cfg!(windows) {
+                result.unwrap();
+            }
+        }
+
+        let cache = IndexCache::read(&path).unwrap();
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(cache.documents.is_empty());
+        assert_eq!(files, 1);
+    }
+}
@@ -8,13 +8,18 @@
 use std::collections::HashMap;
 use std::path::Path;
 use std::result::Result::Ok;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use lazy_static::lazy_static;
 use regex::Regex;
 use ropey::Rope;
+use serde::Deserialize;
+use serde::Serialize;
 use stdext::unwrap;
 use stdext::unwrap::IntoResult;
 use tower_lsp::lsp_types::Range;
@@ -23,14 +28,23 @@ use walkdir::DirEntry;
 use walkdir::WalkDir;
 
 use crate::lsp;
+use crate::lsp::class_definitions;
+use crate::lsp::class_definitions::ClassSystem;
+use crate::lsp::class_definitions::Definition;
+use crate::lsp::class_definitions::MemberKind;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::index_cache;
+use crate::lsp::index_cache::CachedDocument;
+use crate::lsp::index_cache::FileStamp;
+use crate::lsp::index_cache::IndexCache;
 use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::BinaryOperatorType;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum IndexEntryData {
     Function {
         name: String,
@@ -40,9 +54,36 @@ pub enum IndexEntryData {
         level: usize,
         title: String,
     },
+    /// An R6, reference, S4, or S7 class. Indexed under the symbol its
+    /// generator is assigned to, or under its name if unassigned.
+    Class {
+        name: String,
+        system: ClassSystem,
+        parent: Option<String>,
+    },
+    /// A method or field of a class. Indexed as `Class$name` or
+    /// `Class@name`, depending on how members are accessed.
+    Member {
+        class: String,
+        name: String,
+        kind: MemberKind,
+        private: bool,
+        arguments: Vec<String>,
+    },
+    /// An S4 or S7 generic
+    Generic {
+        name: String,
+        arguments: Vec<String>,
+    },
+    /// A method of a generic for a class. Indexed as `generic,Class`.
+    Method {
+        generic: String,
+        class: String,
+        arguments: Vec<String>,
+    },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IndexEntry {
     pub key: String,
     pub range: Range,
@@ -52,11 +93,31 @@ pub struct IndexEntry {
 type DocumentPath = String;
 type DocumentSymbol = String;
 type DocumentSymbolIndex = HashMap<DocumentSymbol, IndexEntry>;
-type WorkspaceIndex = Arc<Mutex<HashMap<DocumentPath, DocumentSymbolIndex>>>;
+type WorkspaceIndex = Arc<Mutex<HashMap<DocumentPath, DocumentIndex>>>;
+
+#[derive(Clone, Debug, Default)]
+struct DocumentIndex {
+    /// State of the file on disk the symbols were indexed from. This is
+    /// `None` for documents indexed from an editor buffer, which might not
+    /// match the file on disk and are not persisted.
+    stamp: Option<FileStamp>,
+    symbols: DocumentSymbolIndex,
+}
+
+/// Delay without new requests after which the index is written to the
+/// on-disk cache
+const PERSIST_DELAY: Duration = Duration::from_secs(2);
 
 lazy_static! {
     static ref WORKSPACE_INDEX: WorkspaceIndex = Default::default();
     static ref RE_COMMENT_SECTION: Regex = Regex::new(r"^\s*(#+)\s*(.*?)\s*[#=-]{4,}\s*$").unwrap();
+
+    /// Requests to persist workspace folders, handled by a single writer thread
+    static ref PERSIST_TX: Mutex<mpsc::Sender<Vec<String>>> = {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || persist_thread(rx));
+        Mutex::new(tx)
+    };
 }
 
 #[tracing::instrument(level = "info", skip_all)]
@@ -64,12 +125,16 @@ pub fn start(folders: Vec<String>) {
     let now = std::time::Instant::now();
     lsp::log_info!("Initial indexing started");
 
-    for folder in folders {
+    for folder in folders.iter() {
+        // Files that haven't changed since the last session are restored
+        // from the on-disk cache instead of being parsed again
+        let cache = IndexCache::load(folder);
+
         let walker = WalkDir::new(folder);
         for entry in walker.into_iter().filter_entry(|e| filter_entry(e)) {
             if let Ok(entry) = entry {
                 if entry.file_type().is_file() {
-                    if let Err(err) = index_file(entry.path()) {
+                    if let Err(err) = index_file(entry.path(), &cache) {
                         lsp::log_error!("Can't index file {:?}: {err:?}", entry.path());
                     }
                 }
@@ -81,13 +146,81 @@ pub fn start(folders: Vec<String>) {
         "Initial indexing finished after {}ms",
         now.elapsed().as_millis()
     );
+
+    persist(&folders);
+}
+
+/// Schedules writing the index of each workspace folder to the on-disk
+/// cache. Requests are debounced so that bursts of file changes, e.g. from a
+/// `git checkout`, result in a single write once they settle.
+pub fn persist(folders: &[String]) {
+    if let Err(err) = PERSIST_TX.lock().unwrap().send(folders.to_vec()) {
+        lsp::log_error!("Can't schedule writing the index cache: {err:?}");
+    }
+}
+
+fn persist_thread(rx: mpsc::Receiver<Vec<String>>) {
+    let mut pending: Vec<String> = Vec::new();
+
+    loop {
+        let request = if pending.is_empty() {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            rx.recv_timeout(PERSIST_DELAY)
+        };
+
+        match request {
+            Ok(folders) => {
+                for folder in folders.into_iter() {
+                    if !pending.contains(&folder) {
+                        pending.push(folder);
+                    }
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => {
+                write_caches(&pending);
+                pending.clear();
+            },
+            Err(RecvTimeoutError::Disconnected) => {
+                write_caches(&pending);
+                return;
+            },
+        }
+    }
+}
+
+/// Writes the index of each workspace folder to the on-disk cache. Only
+/// documents indexed from disk are persisted.
+#[tracing::instrument(level = "info", skip_all)]
+fn write_caches(folders: &[String]) {
+    for folder in folders.iter() {
+        // Snapshot the documents so the index isn't locked while writing
+        let documents = {
+            let index = WORKSPACE_INDEX.lock().unwrap();
+            index
+                .iter()
+                .filter(|(path, _)| Path::new(path).starts_with(folder))
+                .filter_map(|(path, document)| {
+                    let document = CachedDocument {
+                        stamp: document.stamp?,
+                        symbols: document.symbols.clone(),
+                    };
+                    Some((path.clone(), document))
+                })
+                .collect()
+        };
+
+        if let Err(err) = IndexCache::new(documents).save(folder) {
+            lsp::log_error!("Can't write index cache for {folder}: {err:?}");
+        }
+    }
 }
 
 pub fn find(symbol: &str) -> Option<(String, IndexEntry)> {
     let index = WORKSPACE_INDEX.lock().unwrap();
 
-    for (path, index) in index.iter() {
-        if let Some(entry) = index.get(symbol) {
+    for (path, document) in index.iter() {
+        if let Some(entry) = document.symbols.get(symbol) {
             return Some((path.clone(), entry.clone()));
         }
     }
@@ -98,8 +231,8 @@ pub fn find(symbol: &str) -> Option<(String, IndexEntry)> {
 pub fn map(mut callback: impl FnMut(&Path, &String, &IndexEntry)) {
     let index = WORKSPACE_INDEX.lock().unwrap();
 
-    for (path, index) in index.iter() {
-        for (symbol, entry) in index.iter() {
+    for (path, document) in index.iter() {
+        for (symbol, entry) in document.symbols.iter() {
             let path = Path::new(path);
             callback(path, symbol, entry);
         }
@@ -113,12 +246,44 @@ pub fn update(document: &Document, path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Re-indexes a file from disk, e.g. after it was changed outside the editor
+#[tracing::instrument(level = "trace", skip_all, fields(path = ?path))]
+pub fn refresh(path: &Path) -> anyhow::Result<()> {
+    index_file(path, &IndexCache::default())
+}
+
+/// Removes a deleted file from the index. If `path` is a directory, all the
+/// files it contained are removed.
+pub fn remove(path: &Path) {
+    let mut index = WORKSPACE_INDEX.lock().unwrap();
+    index.retain(|document_path, _| !Path::new(document_path).starts_with(path));
+}
+
 fn insert(path: &Path, entry: IndexEntry) -> anyhow::Result<()> {
     let mut index = WORKSPACE_INDEX.lock().unwrap();
     let path = str_from_path(path)?;
 
-    let index = index.entry(path.to_string()).or_default();
-    index.insert(entry.key.clone(), entry);
+    let document = index.entry(path.to_string()).or_default();
+    document.symbols.insert(entry.key.clone(), entry);
+
+    Ok(())
+}
+
+fn insert_document(path: &Path, document: DocumentIndex) -> anyhow::Result<()> {
+    let mut index = WORKSPACE_INDEX.lock().unwrap();
+    let path = str_from_path(path)?;
+
+    index.insert(path.to_string(), document);
+
+    Ok(())
+}
+
+fn set_stamp(path: &Path, stamp: FileStamp) -> anyhow::Result<()> {
+    let mut index = WORKSPACE_INDEX.lock().unwrap();
+    let path = str_from_path(path)?;
+
+    let document = index.entry(path.to_string()).or_default();
+    document.stamp = Some(stamp);
 
     Ok(())
 }
@@ -128,8 +293,8 @@ fn clear(path: &Path) -> anyhow::Result<()> {
     let path = str_from_path(path)?;
 
     // Only clears if the `path` was an existing key
-    index.entry(path.into()).and_modify(|index| {
-        index.clear();
+    index.entry(path.into()).and_modify(|document| {
+        *document = DocumentIndex::default();
     });
 
     Ok(())
@@ -166,22 +331,53 @@ pub fn filter_entry(entry: &DirEntry) -> bool {
     true
 }
 
-fn index_file(path: &Path) -> anyhow::Result<()> {
+fn index_file(path: &Path, cache: &IndexCache) -> anyhow::Result<()> {
     // only index R files
     let ext = path.extension().unwrap_or_default();
     if ext != "r" && ext != "R" {
         return Ok(());
     }
 
+    let cached = cache.documents.get(str_from_path(path)?);
+    let (modified, size) = index_cache::file_metadata(path)?;
+
+    if let Some(cached) = cached {
+        if cached.stamp.modified == modified && cached.stamp.size == size {
+            return restore(path, cached);
+        }
+    }
+
     // TODO: Handle document encodings here.
     // TODO: Check if there's an up-to-date buffer to be used.
     let contents = std::fs::read(path)?;
+    let stamp = FileStamp {
+        modified,
+        size,
+        hash: index_cache::content_hash(&contents),
+    };
+
+    if let Some(cached) = cached {
+        if cached.stamp.hash == stamp.hash {
+            return restore(path, &CachedDocument {
+                stamp,
+                symbols: cached.symbols.clone(),
+            });
+        }
+    }
+
     let contents = String::from_utf8(contents)?;
     let document = Document::new(contents.as_str(), None);
 
+    clear(path)?;
     index_document(&document, path);
+    set_stamp(path, stamp)
+}
 
-    Ok(())
+fn restore(path: &Path, cached: &CachedDocument) -> anyhow::Result<()> {
+    insert_document(path, DocumentIndex {
+        stamp: Some(cached.stamp),
+        symbols: cached.symbols.clone(),
+    })
 }
 
 fn index_document(document: &Document, path: &Path) {
@@ -191,26 +387,29 @@ fn index_document(document: &Document, path: &Path) {
     let root = ast.root_node();
     let mut cursor = root.walk();
     for node in root.children(&mut cursor) {
-        if let Err(err) = match index_node(path, contents, &node) {
-            Ok(Some(entry)) => insert(path, entry),
-            Ok(None) => Ok(()),
-            Err(err) => Err(err),
-        } {
-            lsp::log_error!("Can't index document: {err:?}");
+        let entries = index_node(path, contents, &node);
+        for entry in entries {
+            if let Err(err) = insert(path, entry) {
+                lsp::log_error!("Can't index document: {err:?}");
+            }
         }
     }
 }
 
-fn index_node(path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<Option<IndexEntry>> {
+fn index_node(path: &Path, contents: &Rope, node: &Node) -> Vec<IndexEntry> {
     if let Ok(Some(entry)) = index_function(path, contents, node) {
-        return Ok(Some(entry));
+        return vec![entry];
     }
 
     if let Ok(Some(entry)) = index_comment(path, contents, node) {
-        return Ok(Some(entry));
+        return vec![entry];
+    }
+
+    if let Some(entries) = index_definition(path, contents, node) {
+        return entries;
     }
 
-    Ok(None)
+    Vec::new()
 }
 
 fn index_function(
@@ -269,20 +468,7 @@ fn index_comment(_path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<O
 
     // see if it looks like a section
     let comment = contents.node_slice(node)?.to_string();
-    let matches = RE_COMMENT_SECTION
-        .captures(comment.as_str())
-        .into_result()?;
-
-    let level = matches.get(1).into_result()?;
-    let title = matches.get(2).into_result()?;
-
-    let level = level.as_str().len();
-    let title = title.as_str().to_string();
-
-    // skip things that look like knitr output
-    if title.starts_with("----") {
-        return Ok(None);
-    }
+    let (level, title) = comment_section(&comment).into_result()?;
 
     let start = convert_point_to_position(contents, node.start_position());
     let end = convert_point_to_position(contents, node.end_position());
@@ -293,3 +479,74 @@ fn index_comment(_path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<O
         data: IndexEntryData::Section { level, title },
     }))
 }
+
+/// Parses RStudio-style section headers such as `## Title ----`, returning
+/// the level of the section (the number of leading `#`) and its title
+pub(crate) fn comment_section(comment: &str) -> Option<(usize, String)> {
+    let matches = RE_COMMENT_SECTION.captures(comment)?;
+
+    let level = matches.get(1)?.as_str().len();
+    let title = matches.get(2)?.as_str().to_string();
+
+    // skip things that look like knitr output
+    if title.starts_with("----") {
+        return None;
+    }
+
+    Some((level, title))
+}
+
+fn index_definition(_path: &Path, contents: &Rope, node: &Node) -> Option<Vec<IndexEntry>> {
+    let range = |node: &Node| convert_tree_sitter_range_to_lsp_range(contents, node.range());
+
+    let entries = match class_definitions::definition(*node, contents)? {
+        Definition::Class(class) => {
+            let mut entries = vec![IndexEntry {
+                key: class.binding.clone().unwrap_or_else(|| class.name.clone()),
+                range: range(&class.name_node),
+                data: IndexEntryData::Class {
+                    name: class.name.clone(),
+                    system: class.system,
+                    parent: class.parent.clone(),
+                },
+            }];
+
+            for member in class.members {
+                entries.push(IndexEntry {
+                    key: format!("{}{}{}", class.name, class.system.accessor(), member.name),
+                    range: range(&member.name_node),
+                    data: IndexEntryData::Member {
+                        class: class.name.clone(),
+                        name: member.name,
+                        kind: member.kind,
+                        private: member.private,
+                        arguments: member.arguments,
+                    },
+                });
+            }
+
+            entries
+        },
+
+        Definition::Generic(generic) => vec![IndexEntry {
+            key: generic.name.clone(),
+            range: range(&generic.name_node),
+            data: IndexEntryData::Generic {
+                name: generic.name,
+                arguments: generic.arguments,
+            },
+        }],
+
+        Definition::Method(method) => vec![IndexEntry {
+            key: format!("{},{}", method.generic, method.class),
+            range: range(&method.name_node),
+            data: IndexEntryData::Method {
+                generic: method.generic,
+                class: method.class,
+                arguments: method.arguments,
+            },
+        }],
+    };
+
+    Some(entries)
+}
@@ -0,0 +1,516 @@
+//
+// inlay_hints.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use harp::environment::Environment;
+use harp::environment::R_ENVS;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::utils::r_typeof;
+use lazy_static::lazy_static;
+use libr::PROMSXP;
+use ropey::Rope;
+use tower_lsp::lsp_types::InlayHint;
+use tower_lsp::lsp_types::InlayHintKind;
+use tower_lsp::lsp_types::InlayHintLabel;
+use tower_lsp::lsp_types::InlayHintParams;
+use tower_lsp::lsp_types::InlayHintTooltip;
+use tree_sitter::Node;
+
+use crate::lsp::diagnostics_calls::piped_placeholder;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::references::node_local_binding;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::signature_help::callee_formals;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::r_task;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Formals of callees, indexed by the code of the callee. `None` if the callee
+/// couldn't be resolved to a function.
+type FormalsCache = HashMap<String, Option<Vec<String>>>;
+
+lazy_static! {
+    /// Formals of the callees seen in inlay hint requests, looked up until the
+    /// console scopes change
+    static ref CALLEE_FORMALS: Mutex<FormalsCache> = Default::default();
+}
+
+/// Computes the inlay hints of the calls and top-level assignments within the
+/// requested range:
+///
+/// - The names of the parameters matched by positional arguments, in calls
+///   with more than one positional argument.
+/// - The parameter receiving the left-hand side of `|>` and `%>%` pipes.
+/// - The class of objects that exist in the global environment, after the
+///   name they're assigned to. Only when enabled in the settings.
+pub(crate) fn inlay_hints(
+    params: InlayHintParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<InlayHint>>> {
+    let document = state.get_document(&params.text_document.uri)?;
+    let contents = &document.contents;
+    let root = document.ast.root_node();
+
+    let start = convert_position_to_point(contents, params.range.start);
+    let end = convert_position_to_point(contents, params.range.end);
+
+    let mut calls: Vec<(Node, String)> = Vec::new();
+
+    let mut cursor = root.walk();
+    cursor.recurse(|node| {
+        if node.end_position() < start || node.start_position() > end {
+            return false;
+        }
+        if node.is_call() {
+            if let Some(callee) = callee_code(&node, contents) {
+                calls.push((node, callee));
+            }
+        }
+        true
+    });
+
+    let mut bindings: Vec<(String, Node)> = Vec::new();
+
+    if state.config.inlay_hints.object_class {
+        let mut cursor = root.walk();
+        for child in root.children(&mut cursor) {
+            if child.end_position() < start || child.start_position() > end {
+                continue;
+            }
+            if child.node_type() == NodeType::ForStatement {
+                continue;
+            }
+            let Some(target) = node_local_binding(&child) else {
+                continue;
+            };
+            if let Some(name) = node_symbol_name(&target, contents) {
+                bindings.push((name, target));
+            }
+        }
+    }
+
+    if calls.is_empty() && bindings.is_empty() {
+        return Ok(None);
+    }
+
+    // Only query R for the callees we haven't seen yet, and for the classes
+    // of objects if enabled
+    let mut formals = FormalsCache::new();
+    let mut missing: Vec<String> = Vec::new();
+    {
+        let cache = CALLEE_FORMALS.lock().unwrap();
+        for (_, callee) in calls.iter() {
+            match cache.get(callee) {
+                Some(callee_formals) => {
+                    formals.insert(callee.clone(), callee_formals.clone());
+                },
+                None if !missing.contains(callee) => missing.push(callee.clone()),
+                None => {},
+            }
+        }
+    }
+
+    let mut classes: Vec<Option<String>> = Vec::new();
+
+    if !missing.is_empty() || !bindings.is_empty() {
+        let (looked_up, looked_up_classes) =
+            r_task(|| -> anyhow::Result<(FormalsCache, Vec<Option<String>>)> {
+                let mut looked_up = FormalsCache::new();
+                for callee in missing.iter() {
+                    let formals = callee_formals(callee)?
+                        .map(|formals| formals.into_iter().map(|formal| formal.name).collect());
+                    looked_up.insert(callee.clone(), formals);
+                }
+
+                let mut classes = Vec::new();
+                for (name, _) in bindings.iter() {
+                    classes.push(object_class(name)?);
+                }
+
+                Ok((looked_up, classes))
+            })?;
+
+        CALLEE_FORMALS.lock().unwrap().extend(looked_up.clone());
+        formals.extend(looked_up);
+        classes = looked_up_classes;
+    }
+
+    let mut hints = Vec::new();
+
+    for (call, callee) in calls.iter() {
+        let callee_formals = formals.get(callee).cloned().flatten();
+        call_hints(call, callee_formals, contents, &mut hints)?;
+    }
+
+    for ((_, target), class) in bindings.iter().zip(classes) {
+        if let Some(class) = class {
+            hints.push(InlayHint {
+                position: convert_point_to_position(contents, target.end_position()),
+                label: InlayHintLabel::String(format!(": {class}")),
+                kind: Some(InlayHintKind::TYPE),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: None,
+                data: None,
+            });
+        }
+    }
+
+    if hints.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(hints))
+}
+
+/// Forgets the formals of callees, e.g. after functions are redefined in the
+/// console
+pub(crate) fn clear_callee_formals() {
+    CALLEE_FORMALS.lock().unwrap().clear();
+}
+
+fn callee_code(call: &Node, contents: &Rope) -> Option<String> {
+    let callee = call.child_by_field_name("function")?;
+    Some(contents.node_slice(&callee).ok()?.to_string())
+}
+
+fn call_hints(
+    call: &Node,
+    formals: Option<Vec<String>>,
+    contents: &Rope,
+    hints: &mut Vec<InlayHint>,
+) -> anyhow::Result<()> {
+    let Some(arguments) = call.child_by_field_name("arguments") else {
+        return Ok(());
+    };
+
+    let mut named: Vec<String> = Vec::new();
+    let mut positional: Vec<Node> = Vec::new();
+
+    let mut cursor = arguments.walk();
+    for argument in arguments.children_by_field_name("argument", &mut cursor) {
+        match argument.child_by_field_name("name") {
+            Some(name) => named.extend(node_symbol_name(&name, contents)),
+            None => positional.push(argument),
+        }
+    }
+
+    // Parameters receiving positional arguments, in order
+    let matched = formals
+        .as_ref()
+        .map(|formals| positional_parameters(formals, &named))
+        .unwrap_or_default();
+    let mut matched = matched.into_iter();
+
+    if let Some(placeholder) = piped_placeholder(call, contents) {
+        let Some(open) = arguments.child_by_field_name("open") else {
+            return Ok(());
+        };
+
+        let mut label = match matched.next() {
+            Some(parameter) => format!("{parameter} = {placeholder}"),
+            None => placeholder.to_string(),
+        };
+        let has_arguments = !named.is_empty() || !positional.is_empty();
+        if has_arguments {
+            label.push(',');
+        }
+
+        hints.push(InlayHint {
+            position: convert_point_to_position(contents, open.end_position()),
+            label: InlayHintLabel::String(label),
+            kind: Some(InlayHintKind::PARAMETER),
+            text_edits: None,
+            tooltip: Some(InlayHintTooltip::String(String::from(
+                "Left-hand side of the pipe",
+            ))),
+            padding_left: None,
+            padding_right: Some(has_arguments),
+            data: None,
+        });
+
+        // No positional arguments besides the implicit one
+        if positional.is_empty() {
+            return Ok(());
+        }
+    } else if positional.len() < 2 {
+        return Ok(());
+    }
+
+    for (argument, parameter) in positional.iter().zip(matched) {
+        let Some(value) = argument.child_by_field_name("value") else {
+            continue;
+        };
+
+        // Arguments forwarded with `...` make positions unknowable
+        if value.node_type() == NodeType::Dots {
+            break;
+        }
+
+        // Don't repeat the name of the parameter, e.g. `substr(x, 1, 2)`
+        if node_symbol_name(&value, contents).as_deref() == Some(parameter.as_str()) {
+            continue;
+        }
+
+        hints.push(InlayHint {
+            position: convert_point_to_position(contents, argument.start_position()),
+            label: InlayHintLabel::String(format!("{parameter} =")),
+            kind: Some(InlayHintKind::PARAMETER),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: Some(true),
+            data: None,
+        });
+    }
+
+    Ok(())
+}
+
+/// Matches named arguments to `formals` exactly, then partially, following
+/// R's argument matching rules. Returns the remaining parameters that receive
+/// positional arguments, i.e. the unmatched parameters before `...`.
+fn positional_parameters(formals: &[String], named: &[String]) -> Vec<String> {
+    let dots = formals
+        .iter()
+        .position(|formal| formal == "...")
+        .unwrap_or(formals.len());
+
+    let mut remaining: Vec<&String> = formals.iter().collect();
+    let mut partial: Vec<&String> = Vec::new();
+
+    for name in named.iter() {
+        match remaining.iter().position(|formal| *formal == name) {
+            Some(index) => {
+                remaining.remove(index);
+            },
+            None => partial.push(name),
+        }
+    }
+
+    // Only parameters before `...` can be partially matched
+    for name in partial.iter() {
+        let candidates: Vec<usize> = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, formal)| {
+                formals[..dots].contains(formal) && formal.starts_with(name.as_str())
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        if let [index] = candidates.as_slice() {
+            remaining.remove(*index);
+        }
+    }
+
+    remaining
+        .into_iter()
+        .take_while(|formal| *formal != "...")
+        .cloned()
+        .collect()
+}
+
+/// Class of the object bound to `name` in the global environment, if any.
+/// Promises and active bindings are not evaluated.
+///
+/// SAFETY: Requires access to the R runtime.
+fn object_class(name: &str) -> anyhow::Result<Option<String>> {
+    let env = Environment::new(R_ENVS.global.into());
+
+    if !env.exists(name) || env.is_active(name.into())? {
+        return Ok(None);
+    }
+
+    let value = env.find(name)?;
+    if r_typeof(value) == PROMSXP {
+        return Ok(None);
+    }
+
+    let class = RFunction::new("base", "class").add(value).call()?;
+    let class = Vec::<String>::try_from(class)?;
+
+    Ok(class.into_iter().next())
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::eval::RParseEvalOptions;
+    use tower_lsp::lsp_types::InlayHintKind;
+    use tower_lsp::lsp_types::InlayHintLabel;
+    use tower_lsp::lsp_types::InlayHintParams;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::inlay_hints::clear_callee_formals;
+    use crate::lsp::inlay_hints::inlay_hints;
+    use crate::lsp::inlay_hints::positional_parameters;
+    use crate::lsp::state::WorldState;
+    use crate::test::r_test;
+
+    fn hints(text: &str) -> Vec<(u32, u32, String)> {
+        hints_with_classes(text, false)
+    }
+
+    fn hints_with_classes(text: &str, object_class: bool) -> Vec<(u32, u32, String)> {
+        let uri = Url::parse("file:///hints.R").unwrap();
+
+        let mut state = WorldState::default();
+        state.config.inlay_hints.object_class = object_class;
+        state
+            .documents
+            .insert(uri.clone(), Document::new(text, None));
+
+        let params = InlayHintParams {
+            work_done_progress_params: Default::default(),
+            text_document: TextDocumentIdentifier { uri },
+            range: Range::new(Position::new(0, 0), Position::new(100, 0)),
+        };
+
+        inlay_hints(params, &state)
+            .unwrap()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|hint| {
+                let InlayHintLabel::String(label) = hint.label else {
+                    panic!("Unexpected label parts");
+                };
+                if label.starts_with(':') {
+                    assert_eq!(hint.kind, Some(InlayHintKind::TYPE));
+                }
+                (hint.position.line, hint.position.character, label)
+            })
+            .collect()
+    }
+
+    fn strings(x: &[&str]) -> Vec<String> {
+        x.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_positional_parameters() {
+        let formals = strings(&["x", "start", "stop"]);
+        assert_eq!(positional_parameters(&formals, &[]), formals);
+        assert_eq!(
+            positional_parameters(&formals, &strings(&["sta"])),
+            strings(&["x", "stop"])
+        );
+
+        // No partial matching after `...`
+        let formals = strings(&["...", "sep", "collapse"]);
+        assert_eq!(
+            positional_parameters(&formals, &strings(&["se"])),
+            Vec::<String>::new()
+        );
+
+        let formals = strings(&["x", "...", "na.rm"]);
+        assert_eq!(
+            positional_parameters(&formals, &strings(&["x"])),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_inlay_hints_parameter_names() {
+        r_test(|| {
+            assert_eq!(hints("substr(x, 2, 5)\n"), vec![
+                (0, 10, String::from("start =")),
+                (0, 13, String::from("stop =")),
+            ]);
+
+            // Single positional arguments are not hinted
+            assert_eq!(hints("identity(1)\n"), vec![]);
+
+            // Named arguments shift positional ones
+            assert_eq!(hints("substr(start = 2, 'abc', 5)\n"), vec![
+                (0, 18, String::from("x =")),
+                (0, 25, String::from("stop =")),
+            ]);
+        })
+    }
+
+    #[test]
+    fn test_inlay_hints_pipes() {
+        r_test(|| {
+            assert_eq!(hints("'abc' |> substr(2, 5)\n"), vec![
+                (0, 16, String::from("x = _,")),
+                (0, 16, String::from("start =")),
+                (0, 19, String::from("stop =")),
+            ]);
+
+            // Parameters are unknown but the implicit argument is still shown
+            assert_eq!(hints("1 |> unknown_function()\n"), vec![(
+                0,
+                22,
+                String::from("_")
+            )]);
+        })
+    }
+
+    #[test]
+    fn test_inlay_hints_object_class() {
+        r_test(|| {
+            let options = RParseEvalOptions {
+                forbid_function_calls: false,
+                ..Default::default()
+            };
+            harp::parse_eval("inlay_df <- data.frame()", options.clone()).unwrap();
+
+            assert_eq!(
+                hints_with_classes("inlay_df <- data.frame()\n", true),
+                vec![(0, 8, String::from(": data.frame"))]
+            );
+
+            // Classes are only shown when enabled in the settings
+            assert_eq!(hints("inlay_df <- data.frame()\n"), vec![]);
+
+            harp::parse_eval("rm(inlay_df)", options.clone()).unwrap();
+        })
+    }
+
+    #[test]
+    fn test_inlay_hints_callee_formals_cache() {
+        r_test(|| {
+            let options = RParseEvalOptions {
+                forbid_function_calls: false,
+                ..Default::default()
+            };
+            harp::parse_eval("inlay_fn <- function(a, b) NULL", options.clone()).unwrap();
+
+            assert_eq!(hints("inlay_fn(1, 2)\n"), vec![
+                (0, 9, String::from("a =")),
+                (0, 12, String::from("b =")),
+            ]);
+
+            // Formals are cached until the console scopes change
+            harp::parse_eval("inlay_fn <- function(c, d) NULL", options.clone()).unwrap();
+            assert_eq!(hints("inlay_fn(1, 2)\n"), vec![
+                (0, 9, String::from("a =")),
+                (0, 12, String::from("b =")),
+            ]);
+
+            clear_callee_formals();
+            assert_eq!(hints("inlay_fn(1, 2)\n"), vec![
+                (0, 9, String::from("c =")),
+                (0, 12, String::from("d =")),
+            ]);
+
+            harp::parse_eval("rm(inlay_fn)", options.clone()).unwrap();
+        })
+    }
+}
@@ -0,0 +1,403 @@
+//
+// lint.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde_json::json;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+use tower_lsp::lsp_types::NumberOrString;
+use walkdir::WalkDir;
+
+use crate::lsp::config::LspConfig;
+use crate::lsp::config::ProjectConfig;
+use crate::lsp::config::PROJECT_CONFIG_FILE;
+use crate::lsp::diagnostics::headless_diagnostics;
+use crate::lsp::diagnostics::DiagnosticsConfig;
+use crate::lsp::diagnostics_rules::DiagnosticRule;
+use crate::lsp::documents::Document;
+use crate::lsp::indexer::filter_entry;
+
+/// Exit code when no errors or warnings were found
+const EXIT_SUCCESS: i32 = 0;
+
+/// Exit code when errors or warnings were found
+const EXIT_DIAGNOSTICS: i32 = 1;
+
+/// Exit code when the command couldn't run, e.g. because of invalid arguments
+const EXIT_FAILURE: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Text,
+    Json,
+    Sarif,
+}
+
+/// Diagnostics found in a file
+struct FileDiagnostics {
+    path: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn print_usage() {
+    println!(
+        r#"
+Usage: ark lint [OPTIONS] [PATHS]...
+
+Reports the diagnostics of R files without starting R. Directories are searched
+recursively for R files. Defaults to the current directory.
+
+Rules that need an R session, such as `symbol-not-in-scope`, are not checked.
+Rules are configured in the `.ark.toml` file of the current directory.
+
+Available options:
+
+--format FORMAT          Output format: text (default), json, or sarif
+--help                   Print this help message
+
+Exits with status 1 if errors or warnings were found, and 2 if the files
+couldn't be checked.
+"#
+    );
+}
+
+/// Runs `ark lint` (also available as `ark check`) with the arguments that
+/// follow the command. Returns the exit code of the process.
+pub fn run(args: Vec<String>) -> i32 {
+    let mut format = OutputFormat::Text;
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("text") => OutputFormat::Text,
+                    Some("json") => OutputFormat::Json,
+                    Some("sarif") => OutputFormat::Sarif,
+                    _ => {
+                        eprintln!("The --format argument must be one of text, json, or sarif.");
+                        return EXIT_FAILURE;
+                    },
+                };
+            },
+            "--help" => {
+                print_usage();
+                return EXIT_SUCCESS;
+            },
+            other if other.starts_with("--") => {
+                eprintln!("Argument '{other}' unknown");
+                return EXIT_FAILURE;
+            },
+            path => paths.push(PathBuf::from(path)),
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+
+    let config = match diagnostics_config(Path::new(".")) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Can't read {PROJECT_CONFIG_FILE}: {err}");
+            return EXIT_FAILURE;
+        },
+    };
+
+    let files = match collect_files(&paths) {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_FAILURE;
+        },
+    };
+
+    let mut results = Vec::new();
+
+    for path in files {
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("Can't read {}: {err}", path.display());
+                return EXIT_FAILURE;
+            },
+        };
+
+        let document = Document::new(&contents, None);
+        let diagnostics = headless_diagnostics(&document, &config);
+
+        results.push(FileDiagnostics {
+            path: display_path(&path),
+            diagnostics,
+        });
+    }
+
+    let output = match format {
+        OutputFormat::Text => format_text(&results),
+        OutputFormat::Json => format_json(&results),
+        OutputFormat::Sarif => format_sarif(&results),
+    };
+    print!("{output}");
+
+    let failed = results
+        .iter()
+        .flat_map(|file| file.diagnostics.iter())
+        .any(|diagnostic| {
+            diagnostic.severity == Some(DiagnosticSeverity::ERROR) ||
+                diagnostic.severity == Some(DiagnosticSeverity::WARNING)
+        });
+
+    if failed {
+        EXIT_DIAGNOSTICS
+    } else {
+        EXIT_SUCCESS
+    }
+}
+
+/// Diagnostics configuration from the `.ark.toml` file of `folder`, if any
+fn diagnostics_config(folder: &Path) -> anyhow::Result<DiagnosticsConfig> {
+    let project = ProjectConfig::read(folder)?.unwrap_or_default();
+
+    for id in project.unknown_rules() {
+        eprintln!("Unknown diagnostic rule `{id}` in {PROJECT_CONFIG_FILE}");
+    }
+
+    let config = LspConfig {
+        project,
+        ..Default::default()
+    };
+    Ok(config.diagnostics())
+}
+
+/// Expands directories into the R files they contain, using the same
+/// exclusions as the workspace indexer. Files passed explicitly are always
+/// included.
+fn collect_files(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in paths.iter() {
+        if path.is_file() {
+            files.push(path.clone());
+            continue;
+        }
+
+        if !path.is_dir() {
+            return Err(anyhow::anyhow!("Can't find {}", path.display()));
+        }
+
+        let mut folder_files = Vec::new();
+
+        let walker = WalkDir::new(path);
+        for entry in walker.into_iter().filter_entry(|e| filter_entry(e)) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let ext = entry.path().extension().unwrap_or_default();
+            if ext == "r" || ext == "R" {
+                folder_files.push(entry.into_path());
+            }
+        }
+
+        folder_files.sort();
+        files.append(&mut folder_files);
+    }
+
+    Ok(files)
+}
+
+fn display_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn severity_name(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "information",
+        _ => "hint",
+    }
+}
+
+fn rule_id(diagnostic: &Diagnostic) -> Option<&str> {
+    match &diagnostic.code {
+        Some(NumberOrString::String(id)) => Some(id.as_str()),
+        _ => None,
+    }
+}
+
+/// One line per diagnostic, with 1-based positions:
+/// `path:line:column: severity: message [rule]`
+fn format_text(results: &[FileDiagnostics]) -> String {
+    let mut output = String::new();
+    let mut n = 0;
+
+    for file in results.iter() {
+        for diagnostic in file.diagnostics.iter() {
+            let start = diagnostic.range.start;
+            output.push_str(&format!(
+                "{}:{}:{}: {}: {}",
+                file.path,
+                start.line + 1,
+                start.character + 1,
+                severity_name(diagnostic.severity),
+                diagnostic.message
+            ));
+            if let Some(id) = rule_id(diagnostic) {
+                output.push_str(&format!(" [{id}]"));
+            }
+            output.push('\n');
+            n += 1;
+        }
+    }
+
+    let files = results.len();
+    output.push_str(&format!("Found {n} diagnostic(s) in {files} file(s).\n"));
+
+    output
+}
+
+fn format_json(results: &[FileDiagnostics]) -> String {
+    let diagnostics: Vec<serde_json::Value> = results
+        .iter()
+        .flat_map(|file| {
+            file.diagnostics.iter().map(|diagnostic| {
+                let range = diagnostic.range;
+                json!({
+                    "path": file.path,
+                    "line": range.start.line + 1,
+                    "column": range.start.character + 1,
+                    "end_line": range.end.line + 1,
+                    "end_column": range.end.character + 1,
+                    "severity": severity_name(diagnostic.severity),
+                    "rule": rule_id(diagnostic),
+                    "message": diagnostic.message,
+                })
+            })
+        })
+        .collect();
+
+    format!("{}\n", serde_json::Value::Array(diagnostics))
+}
+
+/// Static Analysis Results Interchange Format, understood by code scanning
+/// services
+fn format_sarif(results: &[FileDiagnostics]) -> String {
+    let rules: Vec<serde_json::Value> = DiagnosticRule::ALL
+        .iter()
+        .map(|rule| json!({ "id": rule.id() }))
+        .collect();
+
+    let sarif_results: Vec<serde_json::Value> = results
+        .iter()
+        .flat_map(|file| {
+            file.diagnostics.iter().map(|diagnostic| {
+                let level = match diagnostic.severity {
+                    Some(DiagnosticSeverity::ERROR) => "error",
+                    Some(DiagnosticSeverity::WARNING) => "warning",
+                    _ => "note",
+                };
+                let range = diagnostic.range;
+
+                let mut result = json!({
+                    "level": level,
+                    "message": { "text": diagnostic.message },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": file.path },
+                            "region": {
+                                "startLine": range.start.line + 1,
+                                "startColumn": range.start.character + 1,
+                                "endLine": range.end.line + 1,
+                                "endColumn": range.end.character + 1,
+                            }
+                        }
+                    }]
+                });
+                if let Some(id) = rule_id(diagnostic) {
+                    result["ruleId"] = json!(id);
+                }
+                result
+            })
+        })
+        .collect();
+
+    let sarif = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "ark",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": sarif_results,
+        }]
+    });
+
+    format!("{sarif}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::diagnostics::headless_diagnostics;
+    use crate::lsp::diagnostics::DiagnosticsConfig;
+    use crate::lsp::documents::Document;
+    use crate::lsp::lint::format_sarif;
+    use crate::lsp::lint::format_text;
+    use crate::lsp::lint::FileDiagnostics;
+
+    fn lint(path: &str, text: &str) -> FileDiagnostics {
+        let document = Document::new(text, None);
+        FileDiagnostics {
+            path: String::from(path),
+            diagnostics: headless_diagnostics(&document, &DiagnosticsConfig::default()),
+        }
+    }
+
+    #[test]
+    fn test_lint_skips_session_rules() {
+        // `foo` and `pkg` can't be resolved without a session
+        let file = lint("R/foo.R", "f <- function(x) x\nf(1, 2)\nfoo\npkg::fn()\n");
+
+        assert_eq!(
+            format_text(&[file]),
+            "R/foo.R:2:6: warning: Too many arguments in call to 'f()': expected at most 1. [too-many-arguments]\n\
+             Found 1 diagnostic(s) in 1 file(s).\n"
+        );
+    }
+
+    #[test]
+    fn test_lint_sarif() {
+        let file = lint("R/foo.R", "if (x = 1) 2\n1 }\n");
+        let sarif: serde_json::Value = serde_json::from_str(&format_sarif(&[file])).unwrap();
+
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Syntax errors have no rule
+        let syntax = results.iter().find(|result| result["ruleId"].is_null());
+        assert!(syntax.is_some());
+
+        let assignment = results
+            .iter()
+            .find(|result| result["ruleId"] == "assignment-in-condition")
+            .unwrap();
+        assert_eq!(assignment["level"], "error");
+        assert_eq!(
+            assignment["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "R/foo.R"
+        );
+    }
+}
@@ -25,8 +25,11 @@ use crate::lsp::backend::LspNotification;
 use crate::lsp::backend::LspRequest;
 use crate::lsp::backend::LspResponse;
 use crate::lsp::diagnostics;
+use crate::lsp::diagnostics::DiagnosticData;
 use crate::lsp::documents::Document;
 use crate::lsp::handlers;
+use crate::lsp::package;
+use crate::lsp::semantic_tokens::SemanticTokensCache;
 use crate::lsp::state::WorldState;
 use crate::lsp::state_handlers;
 use crate::lsp::state_handlers::ConsoleInputs;
@@ -112,11 +115,15 @@ pub(crate) struct LspState {
     /// List of capabilities for which we need to send a registration request
     /// when we get the `Initialized` notification.
     pub(crate) needs_registration: ClientCaps,
+
+    /// Semantic tokens last sent for each document, used to compute deltas.
+    pub(crate) semantic_tokens: SemanticTokensCache,
 }
 
 #[derive(Debug, Default)]
 pub(crate) struct ClientCaps {
     pub(crate) did_change_configuration: bool,
+    pub(crate) did_change_watched_files: bool,
 }
 
 /// State for the auxiliary loop
@@ -227,8 +234,8 @@ impl GlobalState {
                         LspNotification::DidChangeConfiguration(params) => {
                             state_handlers::did_change_configuration(params, &self.client, &mut self.world).await?;
                         },
-                        LspNotification::DidChangeWatchedFiles(_params) => {
-                            // TODO: Re-index the changed files.
+                        LspNotification::DidChangeWatchedFiles(params) => {
+                            state_handlers::did_change_watched_files(params, &mut self.world)?;
                         },
                         LspNotification::DidOpenTextDocument(params) => {
                             state_handlers::did_open(params, &mut self.lsp_state, &mut self.world)?;
@@ -280,9 +287,8 @@ impl GlobalState {
                         LspRequest::GotoDefinition(params) => {
                             respond(tx, handlers::handle_goto_definition(params, &self.world), LspResponse::GotoDefinition)?;
                         },
-                        LspRequest::GotoImplementation(_params) => {
-                            // TODO
-                            respond(tx, Ok(None), LspResponse::GotoImplementation)?;
+                        LspRequest::GotoImplementation(params) => {
+                            respond(tx, handlers::handle_goto_implementation(params, &self.world), LspResponse::GotoImplementation)?;
                         },
                         LspRequest::SelectionRange(params) => {
                             respond(tx, handlers::handle_selection_range(params, &self.world), LspResponse::SelectionRange)?;
@@ -290,6 +296,48 @@ impl GlobalState {
                         LspRequest::References(params) => {
                             respond(tx, handlers::handle_references(params, &self.world), LspResponse::References)?;
                         },
+                        LspRequest::PrepareRename(params) => {
+                            respond(tx, handlers::handle_prepare_rename(params, &self.world), LspResponse::PrepareRename)?;
+                        },
+                        LspRequest::Rename(params) => {
+                            respond(tx, handlers::handle_rename(params, &self.world), LspResponse::Rename)?;
+                        },
+                        LspRequest::CodeAction(params) => {
+                            respond(tx, handlers::handle_code_action(params, &self.world), LspResponse::CodeAction)?;
+                        },
+                        LspRequest::CodeLens(params) => {
+                            respond(tx, handlers::handle_code_lens(params, &self.world), LspResponse::CodeLens)?;
+                        },
+                        LspRequest::PrepareCallHierarchy(params) => {
+                            respond(tx, handlers::handle_prepare_call_hierarchy(params, &self.world), LspResponse::PrepareCallHierarchy)?;
+                        },
+                        LspRequest::IncomingCalls(params) => {
+                            respond(tx, handlers::handle_incoming_calls(params, &self.world), LspResponse::IncomingCalls)?;
+                        },
+                        LspRequest::OutgoingCalls(params) => {
+                            respond(tx, handlers::handle_outgoing_calls(params, &self.world), LspResponse::OutgoingCalls)?;
+                        },
+                        LspRequest::InlayHint(params) => {
+                            respond(tx, handlers::handle_inlay_hint(params, &self.world), LspResponse::InlayHint)?;
+                        },
+                        LspRequest::FoldingRange(params) => {
+                            respond(tx, handlers::handle_folding_range(params, &self.world), LspResponse::FoldingRange)?;
+                        },
+                        LspRequest::DocumentHighlight(params) => {
+                            respond(tx, handlers::handle_document_highlight(params, &self.world), LspResponse::DocumentHighlight)?;
+                        },
+                        LspRequest::LinkedEditingRange(params) => {
+                            respond(tx, handlers::handle_linked_editing_range(params, &self.world), LspResponse::LinkedEditingRange)?;
+                        },
+                        LspRequest::SemanticTokensFull(params) => {
+                            respond(tx, handlers::handle_semantic_tokens_full(params, &mut self.lsp_state, &self.world), LspResponse::SemanticTokensFull)?;
+                        },
+                        LspRequest::SemanticTokensFullDelta(params) => {
+                            respond(tx, handlers::handle_semantic_tokens_full_delta(params, &mut self.lsp_state, &self.world), LspResponse::SemanticTokensFullDelta)?;
+                        },
+                        LspRequest::SemanticTokensRange(params) => {
+                            respond(tx, handlers::handle_semantic_tokens_range(params, &self.world), LspResponse::SemanticTokensRange)?;
+                        },
                         LspRequest::StatementRange(params) => {
                             respond(tx, handlers::handle_statement_range(params, &self.world), LspResponse::StatementRange)?;
                         },
@@ -300,6 +348,14 @@ impl GlobalState {
                             state_handlers::did_change_formatting_options(&params.text_document_position.text_document.uri, &params.options, &mut self.world);
                             respond(tx, handlers::handle_indent(params, &self.world), LspResponse::OnTypeFormatting)?;
                         },
+                        LspRequest::Formatting(params) => {
+                            state_handlers::did_change_formatting_options(&params.text_document.uri, &params.options, &mut self.world);
+                            respond(tx, handlers::handle_formatting(params, &self.world), LspResponse::Formatting)?;
+                        },
+                        LspRequest::RangeFormatting(params) => {
+                            state_handlers::did_change_formatting_options(&params.text_document.uri, &params.options, &mut self.world);
+                            respond(tx, handlers::handle_range_formatting(params, &self.world), LspResponse::RangeFormatting)?;
+                        },
                         LspRequest::VirtualDocument(params) => {
                             respond(tx, handlers::handle_virtual_document(params), LspResponse::VirtualDocument)?;
                         },
@@ -532,6 +588,7 @@ pub(crate) fn spawn_diagnostics_refresh(uri: Url, document: Document, state: Wor
 
         let version = document.version;
         let diagnostics = diagnostics::generate_diagnostics(document, state);
+        spawn_packages_exporting_lookup(&diagnostics);
 
         Ok(Some(AuxiliaryEvent::PublishDiagnostics(
             uri,
@@ -541,7 +598,53 @@ pub(crate) fn spawn_diagnostics_refresh(uri: Url, document: Document, state: Wor
     })
 }
 
+/// Looks up the packages exporting the symbols that are not in scope, which
+/// the quick fixes of these diagnostics offer to attach. The code action
+/// handler only reads them from the cache so it never blocks on R.
+fn spawn_packages_exporting_lookup(diagnostics: &[Diagnostic]) {
+    let symbols: Vec<String> = diagnostics
+        .iter()
+        .filter_map(
+            |diagnostic| match DiagnosticData::from_diagnostic(diagnostic) {
+                Some(DiagnosticData::SymbolNotInScope { symbol }) => Some(symbol),
+                _ => None,
+            },
+        )
+        .collect();
+
+    let missing = package::uncached_symbols(&symbols);
+    if missing.is_empty() {
+        return;
+    }
+
+    lsp::spawn_blocking(move || {
+        package::packages_exporting(&missing);
+        Ok(None)
+    });
+}
+
 pub(crate) fn spawn_diagnostics_refresh_all(state: WorldState) {
+    // Package code is diagnosed with the exports of the packages it attaches.
+    // Look these up in the session first so that diagnostics never block on
+    // the R thread. Packages that can't be found are cached too, so the
+    // refresh doesn't loop.
+    let missing = match &state.workspace.package {
+        Some(pkg) => {
+            let attached = pkg.installed_attached_packages(&state.installed_packages);
+            package::uncached_namespaces(&attached)
+        },
+        None => Vec::new(),
+    };
+
+    if !missing.is_empty() {
+        lsp::spawn_blocking(move || {
+            package::namespace_exports(&missing);
+            spawn_diagnostics_refresh_all(state);
+            Ok(None)
+        });
+        return;
+    }
+
     for (url, document) in state.documents.iter() {
         spawn_diagnostics_refresh(url.clone(), document.clone(), state.clone())
     }
@@ -6,30 +6,50 @@
 //
 
 pub mod backend;
+pub mod call_hierarchy;
+pub mod class_definitions;
+pub mod code_action;
+pub mod code_lens;
 pub mod comm;
 pub mod completions;
 mod config;
 mod declarations;
 pub mod definitions;
 pub mod diagnostics;
+pub mod diagnostics_calls;
+pub mod diagnostics_roxygen;
+pub mod diagnostics_rules;
 pub mod diagnostics_syntax;
+pub mod diagnostics_usage;
 pub mod document_context;
+pub mod document_highlight;
 pub mod documents;
 pub mod encoding;
 pub mod events;
+pub mod folding_range;
+pub mod formatting;
 pub mod handler;
 pub mod handlers;
 pub mod help;
 pub mod help_topic;
 pub mod hover;
+pub mod implementation;
 pub mod indent;
+pub mod index_cache;
 pub mod indexer;
+pub mod inlay_hints;
 pub mod input_boundaries;
+pub mod lint;
 pub mod main_loop;
 pub mod markdown;
 pub mod offset;
+pub mod package;
+pub mod quarto;
 pub mod references;
+pub mod rename;
+pub mod roxygen;
 pub mod selection_range;
+pub mod semantic_tokens;
 pub mod signature_help;
 pub mod state;
 pub mod state_handlers;
@@ -37,6 +57,7 @@ pub mod statement_range;
 pub mod symbols;
 pub mod traits;
 pub mod util;
+pub mod workspace_function;
 
 // These send LSP messages in a non-async and non-blocking way.
 // The LOG level is not timestamped so we're not using it.
@@ -0,0 +1,503 @@
+//
+// package.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::RObject;
+use lazy_static::lazy_static;
+use tree_sitter::Node;
+use url::Url;
+
+use crate::lsp;
+use crate::lsp::documents::Document;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::r_task;
+use crate::treesitter::NodeTypeExt;
+
+lazy_static! {
+    /// Exports of installed packages, looked up once per session
+    static ref NAMESPACE_EXPORTS: Mutex<HashMap<String, Vec<String>>> = Default::default();
+
+    /// Installed packages exporting a symbol, looked up until the installed
+    /// packages change
+    static ref PACKAGES_EXPORTING: Mutex<HashMap<String, Vec<String>>> = Default::default();
+}
+
+/// An R package developed in the workspace, as described by its
+/// `DESCRIPTION` and `NAMESPACE` files. This determines the scope of the
+/// package code, whether or not the package is loaded in the session.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Package {
+    /// Root folder of the package
+    pub(crate) path: PathBuf,
+    pub(crate) description: Description,
+    pub(crate) namespace: Namespace,
+}
+
+/// The fields of a `DESCRIPTION` file that determine the package scope
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Description {
+    pub(crate) name: String,
+    pub(crate) depends: Vec<String>,
+    pub(crate) imports: Vec<String>,
+    pub(crate) suggests: Vec<String>,
+}
+
+/// The directives of a `NAMESPACE` file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Namespace {
+    /// Packages imported as a whole with `import()`
+    pub(crate) imports: Vec<String>,
+    /// Symbols imported with `importFrom()` and friends, as `(package, symbol)`
+    pub(crate) imported_symbols: Vec<(String, String)>,
+    pub(crate) exports: Vec<String>,
+    pub(crate) export_patterns: Vec<String>,
+    /// Methods registered with `S3method()`, as `(generic, function)`. The
+    /// function is `generic.class` unless the directive names another one.
+    pub(crate) s3_methods: Vec<(String, String)>,
+}
+
+impl Package {
+    /// Reads the package at the root of `folder`. Returns `None` if the
+    /// folder doesn't contain a `DESCRIPTION` file with a `Package` field.
+    pub(crate) fn read(folder: &Path) -> anyhow::Result<Option<Self>> {
+        let description_path = folder.join("DESCRIPTION");
+        if !description_path.exists() {
+            return Ok(None);
+        }
+
+        let contents = std::fs::read_to_string(&description_path)?;
+        let description = Description::parse(&contents);
+        if description.name.is_empty() {
+            return Ok(None);
+        }
+
+        // Packages don't need a `NAMESPACE` file while they're being written
+        let namespace_path = folder.join("NAMESPACE");
+        let namespace = if namespace_path.exists() {
+            let contents = std::fs::read_to_string(&namespace_path)?;
+            Namespace::parse(&contents)
+        } else {
+            Namespace::default()
+        };
+
+        Ok(Some(Package {
+            path: folder.to_path_buf(),
+            description,
+            namespace,
+        }))
+    }
+
+    /// Reads the package of the first workspace folder that contains one
+    pub(crate) fn from_workspace(folders: &[Url]) -> Option<Self> {
+        for folder in folders.iter() {
+            let Ok(path) = folder.to_file_path() else {
+                continue;
+            };
+
+            match Package::read(&path) {
+                Ok(Some(package)) => return Some(package),
+                Ok(None) => continue,
+                Err(err) => {
+                    lsp::log_error!("Can't read package in {path:?}: {err:?}");
+                    continue;
+                },
+            }
+        }
+
+        None
+    }
+
+    /// Packages listed in `Depends`, `Imports`, or `Suggests`
+    pub(crate) fn dependencies(&self) -> impl Iterator<Item = &String> {
+        self.description
+            .depends
+            .iter()
+            .chain(self.description.imports.iter())
+            .chain(self.description.suggests.iter())
+    }
+
+    /// Packages whose exports are in scope in the package code: those
+    /// attached through `Depends` and those imported with `import()`
+    pub(crate) fn attached_packages(&self) -> Vec<String> {
+        let mut packages: Vec<String> = Vec::new();
+
+        for package in self
+            .description
+            .depends
+            .iter()
+            .chain(self.namespace.imports.iter())
+        {
+            if !packages.contains(package) {
+                packages.push(package.clone());
+            }
+        }
+
+        packages
+    }
+
+    /// Attached packages that are installed, whose exports can be looked up
+    pub(crate) fn installed_attached_packages(&self, installed: &[String]) -> Vec<String> {
+        self.attached_packages()
+            .into_iter()
+            .filter(|package| installed.contains(package))
+            .collect()
+    }
+}
+
+impl Description {
+    /// Parses the Debian Control File format of `DESCRIPTION` files. Fields
+    /// may be continued on following lines indented with whitespace.
+    pub(crate) fn parse(contents: &str) -> Self {
+        let mut fields: HashMap<String, String> = HashMap::new();
+        let mut current: Option<String> = None;
+
+        for line in contents.lines() {
+            if line.starts_with([' ', '\t']) {
+                if let Some(value) = current.as_ref().and_then(|field| fields.get_mut(field)) {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                continue;
+            }
+
+            current = None;
+
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            let field = field.trim().to_string();
+            fields.insert(field.clone(), value.trim().to_string());
+            current = Some(field);
+        }
+
+        let packages = |field: &str| {
+            fields
+                .get(field)
+                .map(|value| parse_package_list(value))
+                .unwrap_or_default()
+        };
+
+        Description {
+            name: fields.get("Package").cloned().unwrap_or_default(),
+            depends: packages("Depends"),
+            imports: packages("Imports"),
+            suggests: packages("Suggests"),
+        }
+    }
+}
+
+/// Parses a list of packages like `rlang (>= 1.0.0), vctrs`, dropping the
+/// version requirements and the dependency on R itself
+fn parse_package_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|package| match package.split_once('(') {
+            Some((package, _version)) => package.trim(),
+            None => package.trim(),
+        })
+        .filter(|package| !package.is_empty() && *package != "R")
+        .map(String::from)
+        .collect()
+}
+
+impl Namespace {
+    /// Parses the directives of a `NAMESPACE` file, which is R code. Named
+    /// arguments such as `except` are ignored.
+    pub(crate) fn parse(contents: &str) -> Self {
+        let document = Document::new(contents, None);
+        let contents = &document.contents;
+
+        let mut namespace = Namespace::default();
+
+        let mut cursor = document.ast.walk();
+        cursor.recurse(|node| {
+            if !node.is_call() {
+                return true;
+            }
+
+            let Some(directive) = node
+                .child_by_field_name("function")
+                .and_then(|function| node_symbol_name(&function, contents))
+            else {
+                return true;
+            };
+
+            let arguments = directive_arguments(&node, contents);
+
+            match directive.as_str() {
+                "import" => namespace.imports.extend(arguments),
+                "importFrom" | "importClassesFrom" | "importMethodsFrom" | "useDynLib" => {
+                    let mut arguments = arguments.into_iter();
+                    if let Some(package) = arguments.next() {
+                        namespace
+                            .imported_symbols
+                            .extend(arguments.map(|symbol| (package.clone(), symbol)));
+                    }
+                },
+                "export" => namespace.exports.extend(arguments),
+                "exportPattern" => namespace.export_patterns.extend(arguments),
+                "S3method" => {
+                    if let [generic, class, rest @ ..] = arguments.as_slice() {
+                        let function = match rest.first() {
+                            Some(function) => function.clone(),
+                            None => format!("{generic}.{class}"),
+                        };
+                        namespace.s3_methods.push((generic.clone(), function));
+                    }
+                },
+                _ => {},
+            }
+
+            // Directives can't be nested, but they can be wrapped in `if`
+            false
+        });
+
+        namespace
+    }
+}
+
+/// The unnamed arguments of a `NAMESPACE` directive, which are either
+/// symbols or strings
+fn directive_arguments(node: &Node, contents: &ropey::Rope) -> Vec<String> {
+    let Some(arguments) = node.child_by_field_name("arguments") else {
+        return Vec::new();
+    };
+
+    let mut cursor = arguments.walk();
+    arguments
+        .children_by_field_name("argument", &mut cursor)
+        .filter(|argument| argument.child_by_field_name("name").is_none())
+        .filter_map(|argument| argument.child_by_field_name("value"))
+        .filter_map(|value| node_symbol_name(&value, contents))
+        .collect()
+}
+
+/// Looks up the exports of installed packages without attaching them. Results
+/// are cached for the session, including packages that can't be found so
+/// they are only looked up once.
+///
+/// Blocks on the R thread when some packages aren't cached yet. Diagnostics
+/// must not block on R and use `cached_namespace_exports()` instead.
+pub(crate) fn namespace_exports(packages: &[String]) -> Vec<String> {
+    let missing = uncached_namespaces(packages);
+
+    // The cache is not locked while waiting on the R thread, which may need
+    // it, e.g. to compute completions
+    if !missing.is_empty() {
+        let mut exports = match r_task(|| session_namespace_exports(&missing)) {
+            Ok(exports) => exports,
+            Err(err) => {
+                lsp::log_error!("Can't look up namespace exports: {err:?}");
+                HashMap::new()
+            },
+        };
+
+        let mut cache = NAMESPACE_EXPORTS.lock().unwrap();
+        for package in missing.into_iter() {
+            let package_exports = exports.remove(&package).unwrap_or_default();
+            cache.insert(package, package_exports);
+        }
+    }
+
+    cached_namespace_exports(packages)
+}
+
+/// Exports of the packages that have already been looked up with
+/// `namespace_exports()`. Never blocks on the R thread.
+pub(crate) fn cached_namespace_exports(packages: &[String]) -> Vec<String> {
+    let cache = NAMESPACE_EXPORTS.lock().unwrap();
+
+    packages
+        .iter()
+        .filter_map(|package| cache.get(package))
+        .flatten()
+        .cloned()
+        .collect()
+}
+
+/// Packages whose exports haven't been looked up yet
+pub(crate) fn uncached_namespaces(packages: &[String]) -> Vec<String> {
+    let cache = NAMESPACE_EXPORTS.lock().unwrap();
+
+    packages
+        .iter()
+        .filter(|package| !cache.contains_key(*package))
+        .cloned()
+        .collect()
+}
+
+/// Looks up the installed packages exporting each of `symbols` in the
+/// session and caches them. Blocks on the R thread for the symbols that
+/// haven't been looked up yet, so handlers of the main loop use
+/// `cached_packages_exporting()` instead.
+pub(crate) fn packages_exporting(symbols: &[String]) {
+    let missing = uncached_symbols(symbols);
+
+    for symbol in missing.into_iter() {
+        // The cache is not locked while waiting on the R thread
+        let packages = match r_task(|| session_packages_exporting(&symbol)) {
+            Ok(packages) => packages,
+            Err(err) => {
+                lsp::log_error!("Can't find packages exporting `{symbol}`: {err:?}");
+                Vec::new()
+            },
+        };
+        PACKAGES_EXPORTING.lock().unwrap().insert(symbol, packages);
+    }
+}
+
+/// The installed packages exporting `symbol`, if they have already been
+/// looked up with `packages_exporting()`. Never blocks on the R thread.
+pub(crate) fn cached_packages_exporting(symbol: &str) -> Option<Vec<String>> {
+    PACKAGES_EXPORTING.lock().unwrap().get(symbol).cloned()
+}
+
+/// Symbols whose exporting packages haven't been looked up yet
+pub(crate) fn uncached_symbols(symbols: &[String]) -> Vec<String> {
+    let cache = PACKAGES_EXPORTING.lock().unwrap();
+
+    let mut missing: Vec<String> = Vec::new();
+    for symbol in symbols.iter() {
+        if !cache.contains_key(symbol) && !missing.contains(symbol) {
+            missing.push(symbol.clone());
+        }
+    }
+    missing
+}
+
+/// Forgets the packages exporting symbols, e.g. after packages are installed
+pub(crate) fn clear_packages_exporting() {
+    PACKAGES_EXPORTING.lock().unwrap().clear();
+}
+
+fn session_packages_exporting(symbol: &str) -> anyhow::Result<Vec<String>> {
+    let packages = RFunction::new("", ".ps.packages_exporting")
+        .add(symbol)
+        .call()?;
+    Ok(Vec::<String>::try_from(packages)?)
+}
+
+fn session_namespace_exports(packages: &[String]) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    let result = RFunction::new("", ".ps.namespace_exports")
+        .add(packages.to_vec())
+        .call()?;
+    let result = HashMap::<String, RObject>::try_from(result)?;
+
+    let mut exports = HashMap::new();
+    for (package, names) in result.into_iter() {
+        exports.insert(package, Vec::<String>::try_from(names)?);
+    }
+
+    Ok(exports)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::package::cached_namespace_exports;
+    use crate::lsp::package::cached_packages_exporting;
+    use crate::lsp::package::namespace_exports;
+    use crate::lsp::package::packages_exporting;
+    use crate::lsp::package::uncached_namespaces;
+    use crate::lsp::package::uncached_symbols;
+    use crate::lsp::package::Description;
+    use crate::lsp::package::Namespace;
+    use crate::test::r_test;
+
+    #[test]
+    fn test_parse_description() {
+        let description = Description::parse(
+            "Package: mypkg
+Title: My Package
+Version: 0.1.0
+Depends:
+    R (>= 4.1.0),
+    methods
+Imports: cli (>= 3.0.0), rlang,
+    vctrs
+Suggests: testthat (>= 3.0.0)
+Config/testthat/edition: 3
+",
+        );
+
+        assert_eq!(description.name, "mypkg");
+        assert_eq!(description.depends, vec!["methods"]);
+        assert_eq!(description.imports, vec!["cli", "rlang", "vctrs"]);
+        assert_eq!(description.suggests, vec!["testthat"]);
+    }
+
+    #[test]
+    fn test_parse_namespace() {
+        let namespace = Namespace::parse(
+            "# Generated by roxygen2: do not edit by hand
+
+S3method(print,foo)
+S3method(format,foo,format_foo)
+export(foo)
+export(\"bar\")
+exportPattern(\"^[[:alpha:]]+\")
+import(vctrs, except = c(data_frame))
+importFrom(rlang,\"%||%\")
+importFrom(rlang,abort)
+useDynLib(mypkg, .registration = TRUE)
+if (getRversion() >= \"4.0.0\") {
+  importFrom(tools,R_user_dir)
+}
+",
+        );
+
+        assert_eq!(namespace.imports, vec!["vctrs"]);
+        assert_eq!(namespace.exports, vec!["foo", "bar"]);
+        assert_eq!(namespace.export_patterns, vec!["^[[:alpha:]]+"]);
+        assert_eq!(namespace.imported_symbols, vec![
+            (String::from("rlang"), String::from("%||%")),
+            (String::from("rlang"), String::from("abort")),
+            (String::from("tools"), String::from("R_user_dir")),
+        ]);
+        assert_eq!(namespace.s3_methods, vec![
+            (String::from("print"), String::from("print.foo")),
+            (String::from("format"), String::from("format_foo")),
+        ]);
+    }
+
+    #[test]
+    fn test_namespace_exports_cache() {
+        r_test(|| {
+            let packages = vec![String::from("utils"), String::from("notapackage")];
+            assert!(uncached_namespaces(&packages).contains(&String::from("notapackage")));
+
+            let exports = namespace_exports(&packages);
+            assert!(exports.contains(&String::from("head")));
+
+            // Packages that can't be found are cached too
+            assert!(uncached_namespaces(&packages).is_empty());
+            assert_eq!(cached_namespace_exports(&packages), exports);
+        })
+    }
+
+    #[test]
+    fn test_packages_exporting_cache() {
+        r_test(|| {
+            let symbols = vec![String::from("median"), String::from("notasymbol")];
+            packages_exporting(&symbols);
+            assert!(uncached_symbols(&symbols).is_empty());
+
+            // Symbols that no package exports are cached too
+            assert!(cached_packages_exporting("median")
+                .unwrap()
+                .contains(&String::from("stats")));
+            assert_eq!(cached_packages_exporting("notasymbol"), Some(vec![]));
+        })
+    }
+}
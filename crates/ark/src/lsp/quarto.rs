@@ -0,0 +1,204 @@
+//
+// quarto.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::ops::Range;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use url::Url;
+
+lazy_static! {
+    /// Opening fence of an R chunk: three or more backticks or tildes followed
+    /// by a header like `{r}` or `{r setup, echo=FALSE}`
+    static ref RE_R_CHUNK_START: Regex = Regex::new(r"^\s*(`{3,}|~{3,})\s*\{[rR]([\s,}].*)?$").unwrap();
+}
+
+/// A fenced R chunk of a Quarto or R Markdown document
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Chunk {
+    /// Line of the opening fence
+    pub(crate) start: usize,
+
+    /// Line of the closing fence. For unterminated chunks, this is the number
+    /// of lines of the document.
+    pub(crate) end: usize,
+}
+
+impl Chunk {
+    /// The lines of code between the fences, including chunk options
+    pub(crate) fn code_lines(&self) -> Range<usize> {
+        (self.start + 1)..self.end
+    }
+}
+
+/// Is the document a Quarto or R Markdown document? The language ID sent by
+/// the client takes precedence over the file extension.
+pub(crate) fn is_markdown_document(uri: &Url, language_id: &str) -> bool {
+    match language_id {
+        "quarto" | "rmd" | "rmarkdown" => return true,
+        "r" => return false,
+        _ => {},
+    }
+
+    let path = uri.path().to_lowercase();
+    path.ends_with(".qmd") || path.ends_with(".rmd")
+}
+
+/// Finds the R chunks of a Quarto or R Markdown document
+pub(crate) fn r_chunks(text: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+
+    // The opening line and fence of the chunk we're in, if any
+    let mut current: Option<(usize, &str)> = None;
+    let mut n_lines = 0;
+
+    for (i, line) in text.split('\n').enumerate() {
+        n_lines = i + 1;
+
+        match current {
+            Some((start, fence)) => {
+                if is_closing_fence(line, fence) {
+                    chunks.push(Chunk { start, end: i });
+                    current = None;
+                }
+            },
+            None => {
+                if let Some(captures) = RE_R_CHUNK_START.captures(line) {
+                    let fence = captures.get(1).unwrap().as_str();
+                    current = Some((i, fence));
+                }
+            },
+        }
+    }
+
+    if let Some((start, _fence)) = current {
+        chunks.push(Chunk {
+            start,
+            end: n_lines,
+        });
+    }
+
+    chunks
+}
+
+/// A closing fence uses the same character as the opening fence, at least as
+/// many times, and nothing else
+fn is_closing_fence(line: &str, fence: &str) -> bool {
+    let line = line.trim();
+    let Some(delimiter) = fence.chars().next() else {
+        return false;
+    };
+
+    line.len() >= fence.len() && line.chars().all(|char| char == delimiter)
+}
+
+/// Does `line` close the chunk whose opening fence is on the line `opening`?
+pub(crate) fn closes_chunk(opening: &str, line: &str) -> bool {
+    let opening = opening.trim_end_matches(['\n', '\r']);
+    let Some(captures) = RE_R_CHUNK_START.captures(opening) else {
+        return false;
+    };
+    let fence = captures.get(1).unwrap().as_str();
+
+    is_closing_fence(line, fence)
+}
+
+/// Extracts the R code of a Quarto or R Markdown document. Lines outside of R
+/// chunks, including the fences, are blanked out so that the code has the
+/// same positions as in the original document. `chunks` are the R chunks
+/// found by `r_chunks()`.
+pub(crate) fn r_code(text: &str, chunks: &[Chunk]) -> String {
+    let mut chunks = chunks.iter().peekable();
+
+    let mut lines: Vec<&str> = Vec::new();
+
+    for (i, line) in text.split('\n').enumerate() {
+        while chunks.next_if(|chunk| chunk.end < i).is_some() {}
+
+        let in_chunk = chunks
+            .peek()
+            .is_some_and(|chunk| chunk.code_lines().contains(&i));
+
+        lines.push(if in_chunk { line } else { "" });
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use crate::lsp::quarto::closes_chunk;
+    use crate::lsp::quarto::is_markdown_document;
+    use crate::lsp::quarto::r_chunks;
+    use crate::lsp::quarto::r_code;
+    use crate::lsp::quarto::Chunk;
+
+    #[test]
+    fn test_r_chunks() {
+        let text = "---
+title: Test
+---
+
+```{r setup, include=FALSE}
+library(ggplot2)
+```
+
+```{python}
+x = 1
+```
+
+````{r}
+#| label: plot
+```
+ggplot()
+````
+
+~~~{r}
+unterminated";
+
+        assert_eq!(r_chunks(text), vec![
+            Chunk { start: 4, end: 6 },
+            Chunk { start: 12, end: 16 },
+            Chunk { start: 18, end: 20 },
+        ]);
+    }
+
+    #[test]
+    fn test_r_code_preserves_positions() {
+        let text = "# Title\n\nSome `r 1 + 1` text\n\n```{r}\nx <- 1\n```\n\nMore text\n";
+        let code = r_code(text, &r_chunks(text));
+
+        assert_eq!(code, "\n\n\n\n\nx <- 1\n\n\n\n");
+        assert_eq!(code.split('\n').count(), text.split('\n').count());
+    }
+
+    #[test]
+    fn test_closes_chunk() {
+        assert!(closes_chunk("```{r}\n", "```"));
+        assert!(closes_chunk("```{r}", "  ````  \n"));
+        assert!(!closes_chunk("````{r}", "```"));
+        assert!(!closes_chunk("~~~{r}", "```"));
+        assert!(!closes_chunk("```{python}", "```"));
+    }
+
+    #[test]
+    fn test_is_markdown_document() {
+        let uri = Url::parse("file:///report.qmd").unwrap();
+        assert!(is_markdown_document(&uri, ""));
+
+        let uri = Url::parse("file:///report.Rmd").unwrap();
+        assert!(is_markdown_document(&uri, "rmd"));
+
+        let uri = Url::parse("file:///script.R").unwrap();
+        assert!(!is_markdown_document(&uri, "r"));
+
+        let uri = Url::parse("untitled:Untitled-1").unwrap();
+        assert!(is_markdown_document(&uri, "quarto"));
+    }
+}
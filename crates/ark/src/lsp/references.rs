@@ -9,7 +9,6 @@ use std::path::Path;
 
 use anyhow::anyhow;
 use ropey::Rope;
-use stdext::unwrap::IntoResult;
 use stdext::*;
 use tower_lsp::lsp_types::Location;
 use tower_lsp::lsp_types::Position;
@@ -28,21 +27,24 @@ use crate::lsp::indexer::filter_entry;
 use crate::lsp::state::with_document;
 use crate::lsp::state::WorldState;
 use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::rope::RopeExt;
 use crate::lsp::traits::url::UrlExt;
+use crate::treesitter::node_is_call;
+use crate::treesitter::BinaryOperatorType;
 use crate::treesitter::ExtractOperatorType;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
 #[derive(Debug, PartialEq)]
-enum ReferenceKind {
+pub(crate) enum ReferenceKind {
     SymbolName, // a regular R symbol
     DollarName, // a dollar name, following '$'
     AtName,     // a slot name, following '@'
 }
 
 // Assuming `x` is an `identifier`, is it the RHS of a `$` or `@`?
-fn node_reference_kind(x: &Node) -> ReferenceKind {
+pub(crate) fn node_reference_kind(x: &Node) -> ReferenceKind {
     let Some(parent) = x.parent() else {
         // No `parent`, must be a regular symbol
         return ReferenceKind::SymbolName;
@@ -99,6 +101,35 @@ fn found_match(node: &Node, contents: &Rope, context: &Context) -> bool {
     context.kind == node_reference_kind(node)
 }
 
+pub(crate) fn find_identifier_at_point<'tree>(
+    root: Node<'tree>,
+    point: Point,
+) -> Option<Node<'tree>> {
+    let node = root.descendant_for_point_range(point, point)?;
+
+    // Check and see if we got an identifier. If we didn't, we might need to use
+    // some heuristics to look around. Unfortunately, it seems like if you double-click
+    // to select an identifier, and then use Right Click -> Find All References, the
+    // position received by the LSP maps to the _end_ of the selected range, which
+    // is technically not part of the associated identifier's range. In addition, we
+    // can't just subtract 1 from the position column since that would then fail to
+    // resolve the correct identifier when the cursor is located at the start of the
+    // identifier.
+    if node.is_identifier() {
+        return Some(node);
+    }
+
+    if point.column == 0 {
+        return None;
+    }
+
+    let point = Point::new(point.row, point.column - 1);
+    let node = root.descendant_for_point_range(point, point)?;
+
+    // double check that we found an identifier
+    node.is_identifier().then_some(node)
+}
+
 fn build_context(uri: &Url, position: Position, state: &WorldState) -> anyhow::Result<Context> {
     // Unwrap the URL.
     let path = uri.file_path()?;
@@ -109,33 +140,11 @@ fn build_context(uri: &Url, position: Position, state: &WorldState) -> anyhow::R
         let contents = &document.contents;
         let point = convert_position_to_point(contents, position);
 
-        let mut node = ast
-            .root_node()
-            .descendant_for_point_range(point, point)
-            .into_result()?;
-
-        // Check and see if we got an identifier. If we didn't, we might need to use
-        // some heuristics to look around. Unfortunately, it seems like if you double-click
-        // to select an identifier, and then use Right Click -> Find All References, the
-        // position received by the LSP maps to the _end_ of the selected range, which
-        // is technically not part of the associated identifier's range. In addition, we
-        // can't just subtract 1 from the position column since that would then fail to
-        // resolve the correct identifier when the cursor is located at the start of the
-        // identifier.
-        if !node.is_identifier() && point.column > 0 {
-            let point = Point::new(point.row, point.column - 1);
-            node = ast
-                .root_node()
-                .descendant_for_point_range(point, point)
-                .into_result()?;
-        }
-
-        // double check that we found an identifier
-        if !node.is_identifier() {
+        let Some(node) = find_identifier_at_point(ast.root_node(), point) else {
             return Err(anyhow!(
                 "couldn't find an identifier associated with point {point:?}",
             ));
-        }
+        };
 
         let kind = node_reference_kind(&node);
 
@@ -153,6 +162,19 @@ fn find_references_in_folder(
     path: &Path,
     locations: &mut Vec<Location>,
     state: &WorldState,
+) {
+    for_each_document_in_folder(path, state, |path, document| {
+        find_references_in_document(context, path, document, locations);
+    });
+}
+
+/// Invoke `callback` on every R document found in the workspace folder at
+/// `path`. Documents managed by the client are used if available, otherwise
+/// they are read from disk.
+pub(crate) fn for_each_document_in_folder(
+    path: &Path,
+    state: &WorldState,
+    mut callback: impl FnMut(&Path, &Document),
 ) {
     let walker = WalkDir::new(path);
     for entry in walker.into_iter().filter_entry(|entry| filter_entry(entry)) {
@@ -165,7 +187,7 @@ fn find_references_in_folder(
 
         lsp::log_info!("found R file {}", path.display());
         let result = with_document(path, state, |document| {
-            find_references_in_document(context, path, document, locations);
+            callback(path, document);
             return Ok(());
         });
 
@@ -224,3 +246,272 @@ pub(crate) fn find_references(
 
     return Ok(locations);
 }
+
+// Lexical scoping
+//
+// The helpers below resolve symbols to the scope that binds them, following
+// the same rules as the scope stack of semantic diagnostics: a function binds
+// its formals and the targets of `<-`, `=`, `->` and `for` loops found in its
+// body (excluding nested functions). Super-assignments with `<<-` and `->>`
+// never bind in the current function, they target an enclosing scope. Symbols
+// that aren't bound by any enclosing function resolve to the top-level scope of
+// the document, i.e. the `program` node.
+
+/// Functions taking a quoted symbol name as first argument
+const QUOTED_SYMBOL_FUNCTIONS: &[&str] = &[
+    "assign",
+    "do.call",
+    "exists",
+    "get",
+    "get0",
+    "match.fun",
+    "remove",
+    "rm",
+];
+
+/// Returns the symbol name of an identifier or quoted symbol, without
+/// backticks or quotes.
+pub(crate) fn node_symbol_name(node: &Node, contents: &Rope) -> Option<String> {
+    let text = contents.node_slice(node).ok()?.to_string();
+
+    let delimiters: &[char] = match node.node_type() {
+        NodeType::Identifier => &['`'],
+        NodeType::String => &['"', '\''],
+        _ => return None,
+    };
+
+    for delimiter in delimiters {
+        if let Some(inner) = text
+            .strip_prefix(*delimiter)
+            .and_then(|text| text.strip_suffix(*delimiter))
+        {
+            return Some(inner.to_string());
+        }
+    }
+
+    match node.node_type() {
+        NodeType::Identifier => Some(text),
+        // Raw strings are never considered symbols
+        _ => None,
+    }
+}
+
+/// Is `node` the name of an argument, as in `fn(name = value)`? These refer to
+/// the formals of the callee and not to a binding in the current scope.
+pub(crate) fn node_is_argument_name(node: &Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    parent.is_argument() && parent.child_by_field_name("name") == Some(*node)
+}
+
+/// If `node` is a local assignment or a `for` loop, returns the node it binds.
+pub(crate) fn node_local_binding<'tree>(node: &Node<'tree>) -> Option<Node<'tree>> {
+    let target = match node.node_type() {
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+        NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment) => {
+            node.child_by_field_name("lhs")
+        },
+        NodeType::BinaryOperator(BinaryOperatorType::RightAssignment) => {
+            node.child_by_field_name("rhs")
+        },
+        NodeType::ForStatement => node.child_by_field_name("variable"),
+        _ => None,
+    }?;
+
+    target.is_identifier_or_string().then_some(target)
+}
+
+/// Is `node` the target of a `<<-` or `->>` super-assignment?
+pub(crate) fn node_is_super_assignment_target(node: &Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+
+    let target = match parent.node_type() {
+        NodeType::BinaryOperator(BinaryOperatorType::LeftSuperAssignment) => {
+            parent.child_by_field_name("lhs")
+        },
+        NodeType::BinaryOperator(BinaryOperatorType::RightSuperAssignment) => {
+            parent.child_by_field_name("rhs")
+        },
+        _ => None,
+    };
+
+    target == Some(*node)
+}
+
+/// Is `node` a string standing for a symbol, e.g. `"foo" <- function() {}` or
+/// `get("foo")`?
+pub(crate) fn node_is_quoted_symbol(node: &Node, contents: &Rope) -> bool {
+    if !node.is_string() {
+        return false;
+    }
+
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+
+    if node_local_binding(&parent) == Some(*node) || node_is_super_assignment_target(node) {
+        return true;
+    }
+
+    // Otherwise must be the first argument of a known function
+    if !parent.is_argument() || parent.child_by_field_name("name").is_some() {
+        return false;
+    }
+    let Some(arguments) = parent.parent() else {
+        return false;
+    };
+    let mut cursor = arguments.walk();
+    let first = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .next();
+    if first != Some(parent) {
+        return false;
+    }
+
+    let Some(call) = arguments.parent() else {
+        return false;
+    };
+    QUOTED_SYMBOL_FUNCTIONS
+        .iter()
+        .any(|fun| node_is_call(&call, fun, contents))
+}
+
+/// Is `node` an identifier or a quoted symbol referring to a variable, as
+/// opposed to a `$` or `@` name or the name of an argument?
+pub(crate) fn node_is_symbol_reference(node: &Node, contents: &Rope) -> bool {
+    if node.is_identifier() {
+        return node_reference_kind(node) == ReferenceKind::SymbolName &&
+            !node_is_argument_name(node);
+    }
+    node_is_quoted_symbol(node, contents)
+}
+
+/// Find the identifier or quoted symbol at `point` that refers to a variable
+pub(crate) fn find_symbol_at_point<'tree>(
+    root: Node<'tree>,
+    point: Point,
+    contents: &Rope,
+) -> Option<Node<'tree>> {
+    if let Some(node) = find_identifier_at_point(root, point) {
+        return node_is_symbol_reference(&node, contents).then_some(node);
+    }
+
+    // Might be within a quoted symbol like `get("foo")`
+    let node = root.descendant_for_point_range(point, point)?;
+    let node = node.ancestors().find(|node| node.is_string())?;
+
+    node_is_quoted_symbol(&node, contents).then_some(node)
+}
+
+/// Does `scope`, a function definition or the `program` node, bind `symbol`?
+pub(crate) fn scope_binds_symbol(scope: &Node, symbol: &str, contents: &Rope) -> bool {
+    if !scope.is_function_definition() {
+        return node_binds_symbol(scope, symbol, contents);
+    }
+
+    if let Some(parameters) = scope.child_by_field_name("parameters") {
+        let mut cursor = parameters.walk();
+        for parameter in parameters.children_by_field_name("parameter", &mut cursor) {
+            let Some(name) = parameter.child_by_field_name("name") else {
+                continue;
+            };
+            if node_symbol_name(&name, contents).as_deref() == Some(symbol) {
+                return true;
+            }
+        }
+    }
+
+    match scope.child_by_field_name("body") {
+        Some(body) => node_binds_symbol(&body, symbol, contents),
+        None => false,
+    }
+}
+
+fn node_binds_symbol(node: &Node, symbol: &str, contents: &Rope) -> bool {
+    let mut found = false;
+
+    let mut cursor = node.walk();
+    cursor.recurse(|node| {
+        // Nested functions have their own scope
+        if found || node.is_function_definition() {
+            return false;
+        }
+
+        if let Some(target) = node_local_binding(&node) {
+            if node_symbol_name(&target, contents).as_deref() == Some(symbol) {
+                found = true;
+                return false;
+            }
+        }
+
+        true
+    });
+
+    found
+}
+
+/// Returns the scope in which the symbol `node` resolves. This is either the
+/// closest enclosing function definition that binds the symbol, or the
+/// `program` node of the document.
+pub(crate) fn resolve_symbol_scope<'tree>(
+    node: &Node<'tree>,
+    symbol: &str,
+    contents: &Rope,
+) -> Node<'tree> {
+    // Super-assignments skip the scope of the function they are called from
+    let mut skip = node_is_super_assignment_target(node);
+
+    let mut scope = *node;
+    while let Some(parent) = scope.parent() {
+        scope = parent;
+
+        if !scope.is_function_definition() {
+            continue;
+        }
+        if skip {
+            skip = false;
+            continue;
+        }
+        if scope_binds_symbol(&scope, symbol, contents) {
+            return scope;
+        }
+    }
+
+    scope
+}
+
+/// Find all identifiers and quoted symbols within `scope` that resolve to
+/// `symbol` in that same scope, i.e. excluding shadowed uses in nested
+/// functions.
+pub(crate) fn find_scoped_references<'tree>(
+    scope: Node<'tree>,
+    symbol: &str,
+    contents: &Rope,
+) -> Vec<Node<'tree>> {
+    let mut nodes = Vec::new();
+    collect_scoped_references(scope, scope, symbol, contents, &mut nodes);
+    nodes
+}
+
+fn collect_scoped_references<'tree>(
+    node: Node<'tree>,
+    scope: Node<'tree>,
+    symbol: &str,
+    contents: &Rope,
+    nodes: &mut Vec<Node<'tree>>,
+) {
+    if node_is_symbol_reference(&node, contents) &&
+        node_symbol_name(&node, contents).as_deref() == Some(symbol) &&
+        resolve_symbol_scope(&node, symbol, contents) == scope
+    {
+        nodes.push(node);
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_scoped_references(child, scope, symbol, contents, nodes);
+    }
+}
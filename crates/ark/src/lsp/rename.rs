@@ -0,0 +1,745 @@
+//
+// rename.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use harp::utils::is_symbol_valid;
+use ropey::Rope;
+use tower_lsp::lsp_types::PrepareRenameResponse;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::RenameParams;
+use tower_lsp::lsp_types::TextDocumentPositionParams;
+use tower_lsp::lsp_types::TextEdit;
+use tower_lsp::lsp_types::Url;
+use tower_lsp::lsp_types::WorkspaceEdit;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::indexer;
+use crate::lsp::references::find_scoped_references;
+use crate::lsp::references::find_symbol_at_point;
+use crate::lsp::references::for_each_document_in_folder;
+use crate::lsp::references::node_is_super_assignment_target;
+use crate::lsp::references::node_local_binding;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::references::resolve_symbol_scope;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::node_is_call;
+use crate::treesitter::NodeTypeExt;
+
+/// Reserved words that must be backquoted to be used as symbols
+const R_RESERVED_WORDS: &[&str] = &[
+    "if",
+    "else",
+    "repeat",
+    "while",
+    "function",
+    "for",
+    "in",
+    "next",
+    "break",
+    "TRUE",
+    "FALSE",
+    "NULL",
+    "Inf",
+    "NaN",
+    "NA",
+    "NA_integer_",
+    "NA_real_",
+    "NA_character_",
+    "NA_complex_",
+];
+
+struct RenameTarget<'tree> {
+    /// The identifier or quoted symbol under the cursor
+    node: Node<'tree>,
+
+    /// The symbol name, without backticks or quotes
+    symbol: String,
+
+    /// The scope binding the symbol. Either a function definition or the
+    /// `program` node of the document, in which case the rename also applies
+    /// to the files sharing the top-level scope of the document, see
+    /// `shares_top_level_scope()`.
+    scope: Node<'tree>,
+}
+
+impl RenameTarget<'_> {
+    fn is_local(&self) -> bool {
+        self.scope.is_function_definition()
+    }
+}
+
+pub(crate) fn prepare_rename(
+    params: TextDocumentPositionParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<PrepareRenameResponse>> {
+    let document = state.get_document(&params.text_document.uri)?;
+    let contents = &document.contents;
+    let point = convert_position_to_point(contents, params.position);
+
+    let Some(target) = rename_target(document.ast.root_node(), point, contents)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(PrepareRenameResponse::Range(rename_range(
+        &target.node,
+        contents,
+    ))))
+}
+
+pub(crate) fn rename(
+    params: RenameParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    let uri = params.text_document_position.text_document.uri;
+    let position = params.text_document_position.position;
+
+    let document = state.get_document(&uri)?;
+    let contents = &document.contents;
+    let root = document.ast.root_node();
+    let point = convert_position_to_point(contents, position);
+
+    let Some(target) = rename_target(root, point, contents)? else {
+        return Ok(None);
+    };
+
+    // Allow users to supply the new name with backticks
+    let new_name = params.new_name.as_str();
+    let new_name = new_name
+        .strip_prefix('`')
+        .and_then(|name| name.strip_suffix('`'))
+        .unwrap_or(new_name);
+
+    if new_name.is_empty() {
+        return Err(anyhow!(
+            "Can't rename `{}` to an empty name.",
+            target.symbol
+        ));
+    }
+
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+
+    let mut edits = rename_edits(target.scope, &target.symbol, new_name, contents);
+    edits.append(&mut formal_argument_edits(
+        target.scope,
+        root,
+        &target.symbol,
+        new_name,
+        contents,
+    ));
+
+    // Symbols bound at top level are also renamed in the files where they
+    // resolve to the same definition. Other files of the workspace may bind
+    // an unrelated symbol of the same name.
+    let document_path = uri.to_file_path().ok().filter(|_| !target.is_local());
+    changes.insert(uri, edits);
+
+    if let Some(document_path) = document_path {
+        let document_path = normalize_path(&document_path);
+        let sourced = sourced_files(&document_path, root, contents, state);
+
+        for folder in state.workspace.folders.iter() {
+            let Ok(path) = folder.to_file_path() else {
+                continue;
+            };
+
+            for_each_document_in_folder(&path, state, |path, document| {
+                let Ok(uri) = Url::from_file_path(path) else {
+                    return;
+                };
+                if changes.contains_key(&uri) {
+                    return;
+                }
+                if !shares_top_level_scope(&document_path, &sourced, path, document, state) {
+                    return;
+                }
+
+                let root = document.ast.root_node();
+                let edits = rename_edits(root, &target.symbol, new_name, &document.contents);
+
+                if !edits.is_empty() {
+                    changes.insert(uri, edits);
+                }
+            });
+        }
+    }
+
+    Ok(Some(WorkspaceEdit::new(changes)))
+}
+
+/// Whether the document at `path` shares the top-level scope of the document
+/// being renamed in, at the normalized `document_path`. This is the case for
+/// the files of a package, which are evaluated in the package namespace, and
+/// for files connected by `source()` calls in either direction.
+fn shares_top_level_scope(
+    document_path: &Path,
+    sourced: &[PathBuf],
+    path: &Path,
+    document: &Document,
+    state: &WorldState,
+) -> bool {
+    let path = normalize_path(path);
+    if path == document_path {
+        return false;
+    }
+
+    if let Some(package) = &state.workspace.package {
+        let code = normalize_path(&package.path.join("R"));
+        if document_path.starts_with(&code) && path.starts_with(&code) {
+            return true;
+        }
+    }
+
+    if sourced.contains(&path) {
+        return true;
+    }
+
+    let root = document.ast.root_node();
+    sourced_files(&path, root, &document.contents, state).contains(&document_path.to_path_buf())
+}
+
+/// Files sourced with `source("file.R")` or `sys.source("file.R")` from the
+/// document at `path`. Relative paths are resolved against the directory of
+/// the document and the workspace folders.
+fn sourced_files(path: &Path, root: Node, contents: &Rope, state: &WorldState) -> Vec<PathBuf> {
+    let mut directories: Vec<PathBuf> = path.parent().map(Path::to_path_buf).into_iter().collect();
+    for folder in state.workspace.folders.iter() {
+        if let Ok(folder) = folder.to_file_path() {
+            directories.push(folder);
+        }
+    }
+
+    let mut files = Vec::new();
+
+    let mut cursor = root.walk();
+    cursor.recurse(|node| {
+        if !node_is_call(&node, "source", contents) && !node_is_call(&node, "sys.source", contents)
+        {
+            return true;
+        }
+        let Some(file) = sourced_file_argument(&node, contents) else {
+            return true;
+        };
+
+        let file = Path::new(&file);
+        let candidates = if file.is_absolute() {
+            vec![file.to_path_buf()]
+        } else {
+            directories.iter().map(|dir| dir.join(file)).collect()
+        };
+        for candidate in candidates.into_iter().filter(|path| path.is_file()) {
+            let candidate = normalize_path(&candidate);
+            if !files.contains(&candidate) {
+                files.push(candidate);
+            }
+        }
+
+        false
+    });
+
+    files
+}
+
+/// The file passed as a string literal to `source()`, either as first
+/// positional argument or as `file`
+fn sourced_file_argument(call: &Node, contents: &Rope) -> Option<String> {
+    let arguments = call.child_by_field_name("arguments")?;
+
+    let mut cursor = arguments.walk();
+    let argument = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .find(|argument| match argument.child_by_field_name("name") {
+            Some(name) => node_symbol_name(&name, contents).as_deref() == Some("file"),
+            None => true,
+        })?;
+
+    let value = argument.child_by_field_name("value")?;
+    if !value.is_string() {
+        return None;
+    }
+    let text = contents.node_slice(&value).ok()?.to_string();
+    let text = text.get(1..text.len().checked_sub(1)?)?;
+
+    Some(text.to_string())
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn rename_target<'tree>(
+    root: Node<'tree>,
+    point: Point,
+    contents: &Rope,
+) -> anyhow::Result<Option<RenameTarget<'tree>>> {
+    let Some(node) = find_symbol_at_point(root, point, contents) else {
+        return Ok(None);
+    };
+    let Some(symbol) = node_symbol_name(&node, contents) else {
+        return Ok(None);
+    };
+
+    let scope = resolve_symbol_scope(&node, &symbol, contents);
+    let target = RenameTarget {
+        node,
+        symbol,
+        scope,
+    };
+
+    // Renaming a symbol that isn't defined in the workspace, such as a
+    // function from an attached package, would break the code
+    if !target.is_local() && !is_workspace_symbol(root, &target.symbol, contents) {
+        return Err(anyhow!(
+            "Can't rename `{}`: no definition found in the document or workspace.",
+            target.symbol
+        ));
+    }
+
+    Ok(Some(target))
+}
+
+/// Is `symbol` defined at top level in the document, or indexed as a function
+/// of the workspace?
+fn is_workspace_symbol(root: Node, symbol: &str, contents: &Rope) -> bool {
+    if indexer::find(symbol).is_some() {
+        return true;
+    }
+
+    find_scoped_references(root, symbol, contents)
+        .iter()
+        .any(|node| {
+            let binding = node.parent().and_then(|parent| node_local_binding(&parent));
+            binding == Some(*node) || node_is_super_assignment_target(node)
+        })
+}
+
+fn rename_edits(scope: Node, symbol: &str, new_name: &str, contents: &Rope) -> Vec<TextEdit> {
+    find_scoped_references(scope, symbol, contents)
+        .iter()
+        .map(|node| rename_edit(node, new_name, contents))
+        .collect()
+}
+
+/// When renaming the formal of a function bound to a name, e.g.
+/// `fn <- function(x) x`, also rename the matching named arguments in calls to
+/// that function within the document, e.g. `fn(x = 1)`.
+fn formal_argument_edits(
+    scope: Node,
+    root: Node,
+    symbol: &str,
+    new_name: &str,
+    contents: &Rope,
+) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+
+    if !scope.is_function_definition() || !function_has_formal(&scope, symbol, contents) {
+        return edits;
+    }
+
+    let Some(binding) = scope
+        .parent()
+        .and_then(|parent| node_local_binding(&parent))
+    else {
+        return edits;
+    };
+    if binding == scope {
+        return edits;
+    }
+    let Some(fun) = node_symbol_name(&binding, contents) else {
+        return edits;
+    };
+
+    let mut cursor = root.walk();
+    cursor.recurse(|node| {
+        if !node_is_call(&node, &fun, contents) {
+            return true;
+        }
+
+        let Some(arguments) = node.child_by_field_name("arguments") else {
+            return true;
+        };
+
+        let mut cursor = arguments.walk();
+        for argument in arguments.children_by_field_name("argument", &mut cursor) {
+            let Some(name) = argument.child_by_field_name("name") else {
+                continue;
+            };
+            if node_symbol_name(&name, contents).as_deref() == Some(symbol) {
+                edits.push(rename_edit(&name, new_name, contents));
+            }
+        }
+
+        true
+    });
+
+    edits
+}
+
+pub(crate) fn function_has_formal(function: &Node, symbol: &str, contents: &Rope) -> bool {
+    let Some(parameters) = function.child_by_field_name("parameters") else {
+        return false;
+    };
+
+    let mut cursor = parameters.walk();
+    let mut parameters = parameters.children_by_field_name("parameter", &mut cursor);
+
+    parameters.any(|parameter| {
+        parameter
+            .child_by_field_name("name")
+            .and_then(|name| node_symbol_name(&name, contents))
+            .is_some_and(|name| name == symbol)
+    })
+}
+
+fn rename_edit(node: &Node, new_name: &str, contents: &Rope) -> TextEdit {
+    let range = rename_range(node, contents);
+
+    let new_text = if node.is_string() {
+        escape_string(new_name, string_quote(node, contents))
+    } else if is_symbol_valid(new_name) && !R_RESERVED_WORDS.contains(&new_name) {
+        new_name.to_string()
+    } else {
+        format!("`{}`", escape_string(new_name, '`'))
+    };
+
+    TextEdit { range, new_text }
+}
+
+/// The quote character delimiting the string `node`
+fn string_quote(node: &Node, contents: &Rope) -> char {
+    let start = contents.byte_to_char(node.start_byte());
+    match contents.get_char(start) {
+        Some('\'') => '\'',
+        _ => '"',
+    }
+}
+
+/// Escapes backslashes and `quote` so that `text` can be inserted between
+/// quotes or backticks
+fn escape_string(text: &str, quote: char) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        if char == '\\' || char == quote {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+/// The range of text to replace: the whole identifier, including backticks,
+/// or the contents of a quoted symbol.
+pub(crate) fn rename_range(node: &Node, contents: &Rope) -> Range {
+    if !node.is_string() {
+        return convert_tree_sitter_range_to_lsp_range(contents, node.range());
+    }
+
+    let start = node.start_position();
+    let end = node.end_position();
+
+    let start = Point::new(start.row, start.column + 1);
+    let end = Point::new(end.row, end.column - 1);
+
+    Range::new(
+        convert_point_to_position(contents, start),
+        convert_point_to_position(contents, end),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::RenameParams;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::TextDocumentPositionParams;
+    use tower_lsp::lsp_types::TextEdit;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::package::Package;
+    use crate::lsp::rename::prepare_rename;
+    use crate::lsp::rename::rename;
+    use crate::lsp::state::WorldState;
+    use crate::test::point_from_cursor;
+
+    fn test_rename(text: &str, new_name: &str) -> anyhow::Result<Option<(String, Vec<TextEdit>)>> {
+        let (text, point) = point_from_cursor(text);
+        let uri = Url::parse("file:///rename.R").unwrap();
+
+        let mut state = WorldState::default();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(&text, None));
+
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position: Position::new(point.row as u32, point.column as u32),
+            },
+            new_name: new_name.to_string(),
+            work_done_progress_params: Default::default(),
+        };
+
+        let Some(edit) = rename(params, &state)? else {
+            return Ok(None);
+        };
+        let mut changes = edit.changes.unwrap();
+        let mut edits = changes.remove(&uri).unwrap();
+
+        let text = apply_edits(&text, &mut edits);
+        Ok(Some((text, edits)))
+    }
+
+    fn apply_edits(text: &str, edits: &mut [TextEdit]) -> String {
+        // Apply edits from bottom to top
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+
+        let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+        for edit in edits.iter() {
+            let line = &mut lines[edit.range.start.line as usize];
+            let start = edit.range.start.character as usize;
+            let end = edit.range.end.character as usize;
+            line.replace_range(start..end, &edit.new_text);
+        }
+
+        lines.join("\n")
+    }
+
+    /// Renames the symbol at the cursor of `file` in a workspace containing
+    /// `files`. Returns the renamed contents of the files that were edited.
+    fn test_rename_in_workspace(
+        files: &[(&str, &str)],
+        file: &str,
+        new_name: &str,
+        package: bool,
+    ) -> HashMap<String, String> {
+        let dir = std::env::temp_dir().join(format!("ark-rename-{}", uuid::Uuid::new_v4()));
+
+        let mut state = WorldState::default();
+        state.workspace.folders = vec![Url::from_directory_path(&dir).unwrap()];
+        if package {
+            state.workspace.package = Some(Package {
+                path: dir.clone(),
+                ..Default::default()
+            });
+        }
+
+        let mut texts: HashMap<PathBuf, (String, String)> = HashMap::new();
+        let mut position = None;
+
+        for (name, text) in files.iter() {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+            let text = if *name == file {
+                let (text, point) = point_from_cursor(text);
+                position = Some(Position::new(point.row as u32, point.column as u32));
+                text
+            } else {
+                text.to_string()
+            };
+
+            std::fs::write(&path, &text).unwrap();
+            texts.insert(path.canonicalize().unwrap(), (name.to_string(), text));
+        }
+
+        let path = dir.join(file).canonicalize().unwrap();
+        let uri = Url::from_file_path(&path).unwrap();
+        let text = &texts.get(&path).unwrap().1;
+        state
+            .documents
+            .insert(uri.clone(), Document::new(text, None));
+
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: position.unwrap(),
+            },
+            new_name: new_name.to_string(),
+            work_done_progress_params: Default::default(),
+        };
+        let edit = rename(params, &state).unwrap().unwrap();
+
+        let mut renamed = HashMap::new();
+        for (uri, mut edits) in edit.changes.unwrap().into_iter() {
+            let path = uri.to_file_path().unwrap().canonicalize().unwrap();
+            let (name, text) = texts.get(&path).unwrap();
+            renamed.insert(name.clone(), apply_edits(text, &mut edits));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        renamed
+    }
+
+    #[test]
+    fn test_rename_function_local() {
+        let text = "
+x <- 1
+f <- function() {
+  @x <- 2
+  x + 1
+}
+x
+";
+        let (text, edits) = test_rename(text, "y").unwrap().unwrap();
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            text,
+            "\nx <- 1\nf <- function() {\n  y <- 2\n  y + 1\n}\nx\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_top_level_skips_shadowed() {
+        let text = "
+@x <- 1
+f <- function(x) x
+g <- function() x
+";
+        let (text, _) = test_rename(text, "y").unwrap().unwrap();
+        assert_eq!(text, "\ny <- 1\nf <- function(x) x\ng <- function() y\n");
+    }
+
+    #[test]
+    fn test_rename_super_assignment() {
+        let text = "
+count <- 0
+f <- function() {
+  @count <<- count + 1
+}
+";
+        let (text, _) = test_rename(text, "n").unwrap().unwrap();
+        assert_eq!(text, "\nn <- 0\nf <- function() {\n  n <<- n + 1\n}\n");
+    }
+
+    #[test]
+    fn test_rename_formals_and_call_arguments() {
+        let text = "
+f <- function(@x, y = x) x + y
+f(x = 1)
+";
+        let (text, _) = test_rename(text, "z").unwrap().unwrap();
+        assert_eq!(text, "\nf <- function(z, y = z) z + y\nf(z = 1)\n");
+    }
+
+    #[test]
+    fn test_rename_quoted_symbols() {
+        let text = "
+@foo <- function() 1
+get(\"foo\")
+list(\"foo\")
+";
+        let (text, _) = test_rename(text, "bar").unwrap().unwrap();
+        assert_eq!(text, "\nbar <- function() 1\nget(\"bar\")\nlist(\"foo\")\n");
+    }
+
+    #[test]
+    fn test_rename_quoted_symbols_escapes() {
+        let text = "
+@foo <- 1
+get(\"foo\")
+assign('foo', 2)
+";
+        let (text, _) = test_rename(text, "a\"b'c\\d").unwrap().unwrap();
+        assert_eq!(
+            text,
+            "\n`a\"b'c\\\\d` <- 1\nget(\"a\\\"b'c\\\\d\")\nassign('a\"b\\'c\\\\d', 2)\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_non_syntactic_name() {
+        let text = "
+@foo <- 1
+foo + 1
+";
+        let (text, _) = test_rename(text, "my var").unwrap().unwrap();
+        assert_eq!(text, "\n`my var` <- 1\n`my var` + 1\n");
+    }
+
+    #[test]
+    fn test_rename_sourced_files() {
+        let files = [
+            ("main.R", "source(\"helpers.R\")\n@foo <- 1\nfoo\n"),
+            ("helpers.R", "bar <- function() foo + 1\n"),
+            ("other.R", "foo <- \"unrelated\"\nfoo\n"),
+        ];
+
+        let renamed = test_rename_in_workspace(&files, "main.R", "baz", false);
+        assert_eq!(renamed.len(), 2);
+        assert_eq!(renamed["main.R"], "source(\"helpers.R\")\nbaz <- 1\nbaz\n");
+        assert_eq!(renamed["helpers.R"], "bar <- function() baz + 1\n");
+
+        // Files sourcing the document are renamed too
+        let files = [
+            ("main.R", "source(file = \"helpers.R\")\nfoo()\n"),
+            ("helpers.R", "@foo <- function() 1\n"),
+            ("other.R", "foo <- \"unrelated\"\nfoo\n"),
+        ];
+
+        let renamed = test_rename_in_workspace(&files, "helpers.R", "baz", false);
+        assert_eq!(renamed.len(), 2);
+        assert_eq!(renamed["main.R"], "source(file = \"helpers.R\")\nbaz()\n");
+        assert_eq!(renamed["helpers.R"], "baz <- function() 1\n");
+    }
+
+    #[test]
+    fn test_rename_package_files() {
+        let files = [
+            ("R/a.R", "@foo <- function() 1\n"),
+            ("R/b.R", "bar <- function() foo()\n"),
+            ("scripts/c.R", "foo <- 2\nfoo\n"),
+        ];
+
+        let renamed = test_rename_in_workspace(&files, "R/a.R", "baz", true);
+        assert_eq!(renamed.len(), 2);
+        assert_eq!(renamed["R/a.R"], "baz <- function() 1\n");
+        assert_eq!(renamed["R/b.R"], "bar <- function() baz()\n");
+
+        // Scripts outside of the package code don't share its scope
+        let renamed = test_rename_in_workspace(&files, "scripts/c.R", "baz", true);
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(renamed["scripts/c.R"], "baz <- 2\nbaz\n");
+    }
+
+    #[test]
+    fn test_rename_undefined_symbol() {
+        assert!(test_rename("@undefined_symbol(1)", "foo").is_err());
+        assert!(test_rename("x$@foo", "bar").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_prepare_rename() {
+        let (text, point) = point_from_cursor("x <- 1; @x");
+        let uri = Url::parse("file:///rename.R").unwrap();
+
+        let mut state = WorldState::default();
+        state
+            .documents
+            .insert(uri.clone(), Document::new(&text, None));
+
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position::new(point.row as u32, point.column as u32),
+        };
+        let response = prepare_rename(params, &state).unwrap();
+        assert!(response.is_some());
+    }
+}
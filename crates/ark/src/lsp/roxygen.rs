@@ -0,0 +1,447 @@
+//
+// roxygen.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use ropey::Rope;
+use tree_sitter::Node;
+
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Tags that document the parameters of a function in another way than with
+/// `@param`, or that prevent the block from generating documentation
+const PARAMS_ELSEWHERE_TAGS: &[&str] = &[
+    "describeIn",
+    "eval",
+    "evalRd",
+    "inherit",
+    "inheritParams",
+    "noRd",
+    "rdname",
+    "template",
+];
+
+/// A roxygen block: consecutive `#'` comments documenting the expression that
+/// follows them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RoxygenBlock {
+    /// Text before the first tag, i.e. the title and the description
+    pub(crate) intro: String,
+    pub(crate) tags: Vec<RoxygenTag>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RoxygenTag {
+    /// Name of the tag, without the `@`
+    pub(crate) name: String,
+
+    /// Text following the tag, including continuation lines
+    pub(crate) value: String,
+
+    /// Row of the tag in the document
+    pub(crate) row: usize,
+
+    /// Column at which `value` starts on the row of the tag
+    pub(crate) column: usize,
+}
+
+/// A name documented with `@param`. `@param x,y` documents several names.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RoxygenParam {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) row: usize,
+    pub(crate) column: usize,
+}
+
+impl RoxygenBlock {
+    /// Parses the comments of a block, as returned by `block_comments()`
+    pub(crate) fn parse(comments: &[Node], contents: &Rope) -> Self {
+        let mut block = RoxygenBlock::default();
+        let mut intro: Vec<String> = Vec::new();
+
+        for comment in comments.iter() {
+            let Ok(text) = contents.node_slice(comment) else {
+                continue;
+            };
+            let text = text.to_string();
+
+            // Strip the `#'` prefix and the space following it
+            let Some(line) = text.strip_prefix("#'") else {
+                continue;
+            };
+            let prefix = if line.starts_with(' ') { 3 } else { 2 };
+            let line = &text[prefix..];
+
+            if let Some(tag) = line.strip_prefix('@') {
+                let name: String = tag
+                    .chars()
+                    .take_while(|char| char.is_alphanumeric() || *char == '_')
+                    .collect();
+
+                if !name.is_empty() {
+                    let rest = &tag[name.len()..];
+                    let value = rest.trim_start();
+                    let offset = prefix + 1 + name.len() + (rest.len() - value.len());
+
+                    block.tags.push(RoxygenTag {
+                        name,
+                        value: value.trim_end().to_string(),
+                        row: comment.start_position().row,
+                        column: comment.start_position().column + offset,
+                    });
+                    continue;
+                }
+            }
+
+            match block.tags.last_mut() {
+                Some(tag) => {
+                    tag.value.push('\n');
+                    tag.value.push_str(line.trim_end());
+                },
+                None => intro.push(line.trim_end().to_string()),
+            }
+        }
+
+        block.intro = intro.join("\n").trim().to_string();
+        block
+    }
+
+    /// Parses the roxygen block documenting `node`, if any
+    pub(crate) fn documenting(node: &Node, contents: &Rope) -> Option<Self> {
+        let comments = comments_before(node, contents);
+        if comments.is_empty() {
+            return None;
+        }
+        Some(Self::parse(&comments, contents))
+    }
+
+    pub(crate) fn has_tag(&self, name: &str) -> bool {
+        self.tags.iter().any(|tag| tag.name == name)
+    }
+
+    pub(crate) fn params(&self) -> Vec<RoxygenParam> {
+        let mut params = Vec::new();
+
+        for tag in self.tags.iter().filter(|tag| tag.name == "param") {
+            let names = tag
+                .value
+                .split(char::is_whitespace)
+                .next()
+                .unwrap_or_default();
+            let description = tag.value[names.len()..].trim().to_string();
+
+            let mut column = tag.column;
+            for name in names.split(',') {
+                if !name.is_empty() {
+                    params.push(RoxygenParam {
+                        name: name.to_string(),
+                        description: description.clone(),
+                        row: tag.row,
+                        column,
+                    });
+                }
+                column += name.len() + 1;
+            }
+        }
+
+        params
+    }
+
+    /// Does the block generate documentation in which the parameters must be
+    /// documented with `@param`? Blocks without a title don't generate
+    /// documentation, and some tags document parameters in other ways.
+    pub(crate) fn requires_params(&self) -> bool {
+        if self.intro.is_empty() && !self.has_tag("title") {
+            return false;
+        }
+        !self.documents_params_elsewhere()
+    }
+
+    /// Does the block document parameters in another way than with `@param`?
+    /// Blocks sharing a topic, e.g. with `@rdname` or `@describeIn`, may also
+    /// document the parameters of the other functions of the topic.
+    pub(crate) fn documents_params_elsewhere(&self) -> bool {
+        PARAMS_ELSEWHERE_TAGS.iter().any(|name| self.has_tag(name))
+    }
+
+    /// The topic the block is merged into with `@rdname` or `@describeIn`
+    pub(crate) fn shared_topic(&self) -> Option<&str> {
+        self.tags
+            .iter()
+            .filter(|tag| tag.name == "rdname" || tag.name == "describeIn")
+            .find_map(|tag| tag.value.split_whitespace().next())
+    }
+
+    /// Renders the block as markdown, e.g. for hover documentation. Roxygen
+    /// text is often written in markdown already so it is included as is.
+    pub(crate) fn markdown(&self) -> String {
+        let mut sections: Vec<String> = Vec::new();
+
+        if let Some(title) = self.title() {
+            sections.push(format!("**{}**", title.replace('\n', " ")));
+        }
+
+        // The rest of the intro is the description
+        let paragraphs = self.intro.split("\n\n").map(str::trim);
+        let skip = if self.has_tag("title") { 0 } else { 1 };
+        sections.extend(
+            paragraphs
+                .skip(skip)
+                .filter(|paragraph| !paragraph.is_empty())
+                .map(String::from),
+        );
+
+        for name in ["description", "details"] {
+            if let Some(value) = self.tag_value(name) {
+                sections.push(value.to_string());
+            }
+        }
+
+        let params = self.params();
+        if !params.is_empty() {
+            let items: Vec<String> = params
+                .iter()
+                .map(|param| {
+                    let description = param.description.replace('\n', " ");
+                    format!("- `{}`: {}", param.name, description)
+                })
+                .collect();
+            sections.push(format!("**Parameters**\n\n{}", items.join("\n")));
+        }
+
+        if let Some(value) = self.tag_value("return").or(self.tag_value("returns")) {
+            sections.push(format!("**Value**\n\n{value}"));
+        }
+
+        if let Some(examples) = self.tag_value("examples") {
+            sections.push(format!("**Examples**\n\n```r\n{examples}\n```"));
+        }
+
+        sections.join("\n\n")
+    }
+
+    /// The first paragraph of the intro, unless there's a `@title` tag
+    pub(crate) fn title(&self) -> Option<&str> {
+        self.tag_value("title").or_else(|| {
+            let title = self.intro.split("\n\n").next()?.trim();
+            (!title.is_empty()).then_some(title)
+        })
+    }
+
+    /// The description of a parameter documented with `@param`
+    pub(crate) fn param_description(&self, name: &str) -> Option<String> {
+        self.params()
+            .into_iter()
+            .find(|param| param.name == name)
+            .map(|param| param.description)
+    }
+
+    fn tag_value(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.name == name)
+            .map(|tag| tag.value.trim())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+pub(crate) fn is_roxygen_comment(node: &Node, contents: &Rope) -> bool {
+    node.is_comment() &&
+        contents
+            .node_slice(node)
+            .is_ok_and(|text| text.to_string().starts_with("#'"))
+}
+
+/// The roxygen comments right above `node`, in document order
+pub(crate) fn comments_before<'tree>(node: &Node<'tree>, contents: &Rope) -> Vec<Node<'tree>> {
+    let mut comments = Vec::new();
+
+    let mut row = node.start_position().row;
+    let mut current = node.prev_sibling();
+
+    while let Some(sibling) = current {
+        if !is_roxygen_comment(&sibling, contents) || sibling.end_position().row + 1 != row {
+            break;
+        }
+        row = sibling.start_position().row;
+        comments.push(sibling);
+        current = sibling.prev_sibling();
+    }
+
+    comments.reverse();
+    comments
+}
+
+/// The roxygen comments of the block that `comment` belongs to
+pub(crate) fn block_comments<'tree>(comment: &Node<'tree>, contents: &Rope) -> Vec<Node<'tree>> {
+    let mut comments = comments_before(comment, contents);
+    comments.push(*comment);
+
+    let mut row = comment.end_position().row;
+    let mut current = comment.next_sibling();
+
+    while let Some(sibling) = current {
+        if !is_roxygen_comment(&sibling, contents) || sibling.start_position().row != row + 1 {
+            break;
+        }
+        row = sibling.end_position().row;
+        comments.push(sibling);
+        current = sibling.next_sibling();
+    }
+
+    comments
+}
+
+/// The expression documented by a block, i.e. the first expression following
+/// its last comment
+pub(crate) fn documented_node<'tree>(comments: &[Node<'tree>]) -> Option<Node<'tree>> {
+    let mut current = comments.last()?.next_named_sibling();
+
+    while let Some(node) = current {
+        if !node.is_comment() {
+            return Some(node);
+        }
+        current = node.next_named_sibling();
+    }
+
+    None
+}
+
+/// The function definition bound by an assignment like `f <- function() {}`
+pub(crate) fn assigned_function<'tree>(node: &Node<'tree>) -> Option<Node<'tree>> {
+    if !matches!(
+        node.node_type(),
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+    ) {
+        return None;
+    }
+
+    node.child_by_field_name("rhs")
+        .filter(|rhs| rhs.is_function_definition())
+}
+
+/// The formals of a function definition, including `...`, along with the
+/// node of their name
+pub(crate) fn function_formals<'tree>(
+    function: &Node<'tree>,
+    contents: &Rope,
+) -> Vec<(String, Node<'tree>)> {
+    let Some(parameters) = function.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+
+    let mut cursor = parameters.walk();
+    parameters
+        .children_by_field_name("parameter", &mut cursor)
+        .filter_map(|parameter| parameter.child_by_field_name("name"))
+        .filter_map(|name| {
+            let text = contents.node_slice(&name).ok()?.to_string();
+            Some((text, name))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::documents::Document;
+    use crate::lsp::roxygen::RoxygenBlock;
+
+    fn parse(text: &str) -> RoxygenBlock {
+        let document = Document::new(text, None);
+        let root = document.ast.root_node();
+
+        let mut cursor = root.walk();
+        let function = root
+            .named_children(&mut cursor)
+            .find(|node| !node.is_extra())
+            .unwrap();
+
+        RoxygenBlock::documenting(&function, &document.contents).unwrap()
+    }
+
+    #[test]
+    fn test_roxygen_block_params() {
+        let block = parse(
+            "#' Add numbers
+#'
+#' Adds numbers together.
+#'
+#' @param x,y Numbers to add.
+#' @param ... Unused,
+#'   really.
+#' @export
+f <- function(x, y, ...) x + y
+",
+        );
+
+        assert_eq!(block.intro, "Add numbers\n\nAdds numbers together.");
+        assert!(block.has_tag("export"));
+        assert!(block.requires_params());
+
+        let params: Vec<(String, String, usize, usize)> = block
+            .params()
+            .into_iter()
+            .map(|param| (param.name, param.description, param.row, param.column))
+            .collect();
+
+        assert_eq!(params, vec![
+            (String::from("x"), String::from("Numbers to add."), 4, 10),
+            (String::from("y"), String::from("Numbers to add."), 4, 12),
+            (
+                String::from("..."),
+                String::from("Unused,\n  really."),
+                5,
+                10
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_roxygen_block_markdown() {
+        let block = parse(
+            "#' Add numbers
+#'
+#' @param x A number.
+#' @returns The sum.
+#' @examples
+#' f(1)
+f <- function(x) x
+",
+        );
+
+        assert_eq!(
+            block.markdown(),
+            "**Add numbers**
+
+**Parameters**
+
+- `x`: A number.
+
+**Value**
+
+The sum.
+
+**Examples**
+
+```r
+f(1)
+```"
+        );
+    }
+
+    #[test]
+    fn test_roxygen_block_without_title_requires_no_params() {
+        let block = parse("#' @importFrom rlang abort\nf <- function(x) x\n");
+        assert!(!block.requires_params());
+
+        let block = parse("#' Title\n#' @inheritParams g\nf <- function(x) x\n");
+        assert!(!block.requires_params());
+    }
+}
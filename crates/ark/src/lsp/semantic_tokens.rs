@@ -0,0 +1,760 @@
+//
+// semantic_tokens.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Range;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::SemanticToken;
+use tower_lsp::lsp_types::SemanticTokenModifier;
+use tower_lsp::lsp_types::SemanticTokenType;
+use tower_lsp::lsp_types::SemanticTokens;
+use tower_lsp::lsp_types::SemanticTokensDelta;
+use tower_lsp::lsp_types::SemanticTokensDeltaParams;
+use tower_lsp::lsp_types::SemanticTokensEdit;
+use tower_lsp::lsp_types::SemanticTokensFullDeltaResult;
+use tower_lsp::lsp_types::SemanticTokensLegend;
+use tower_lsp::lsp_types::SemanticTokensParams;
+use tower_lsp::lsp_types::SemanticTokensRangeParams;
+use tower_lsp::lsp_types::SemanticTokensRangeResult;
+use tower_lsp::lsp_types::SemanticTokensResult;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::diagnostics::DiagnosticContext;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::references::node_is_argument_name;
+use crate::lsp::references::node_is_super_assignment_target;
+use crate::lsp::references::node_local_binding;
+use crate::lsp::references::node_reference_kind;
+use crate::lsp::references::node_symbol_name;
+use crate::lsp::references::ReferenceKind;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+use crate::treesitter::UnaryOperatorType;
+
+/// Token types, in the order of their index in the legend
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+];
+
+/// Token modifiers, in the order of their bit in the legend
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+    // Defined in the console session rather than in the workspace
+    SemanticTokenModifier::new("global"),
+    // Not defined anywhere we know of
+    SemanticTokenModifier::new("unresolved"),
+    // Used within a formula, typically referring to a data column
+    SemanticTokenModifier::new("formula"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TokenType {
+    Namespace = 0,
+    Function = 1,
+    Parameter = 2,
+    Variable = 3,
+    Property = 4,
+}
+
+const MODIFIER_DECLARATION: u32 = 1 << 0;
+const MODIFIER_DEFAULT_LIBRARY: u32 = 1 << 1;
+const MODIFIER_GLOBAL: u32 = 1 << 2;
+const MODIFIER_UNRESOLVED: u32 = 1 << 3;
+const MODIFIER_FORMULA: u32 = 1 << 4;
+
+pub(crate) fn semantic_tokens_legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// Tokens last sent to the client for each document, so that subsequent
+/// requests can be answered with a delta. Lives in `LspState` since the
+/// cache is updated by every full request.
+#[derive(Debug, Default)]
+pub(crate) struct SemanticTokensCache {
+    results: HashMap<Url, CachedTokens>,
+    next_id: u64,
+}
+
+#[derive(Debug)]
+struct CachedTokens {
+    result_id: String,
+    tokens: Vec<SemanticToken>,
+}
+
+impl SemanticTokensCache {
+    fn insert(&mut self, uri: Url, tokens: Vec<SemanticToken>) -> String {
+        self.next_id += 1;
+        let result_id = self.next_id.to_string();
+
+        self.results.insert(uri, CachedTokens {
+            result_id: result_id.clone(),
+            tokens,
+        });
+
+        result_id
+    }
+
+    pub(crate) fn remove(&mut self, uri: &Url) {
+        self.results.remove(uri);
+    }
+}
+
+#[derive(Debug)]
+struct Token {
+    start: Point,
+    end: Point,
+    token_type: TokenType,
+    modifiers: u32,
+}
+
+struct TokensContext<'a> {
+    /// Scope stack and known symbols, shared with semantic diagnostics. Each
+    /// function scope contains all of the symbols it binds, so that lookups
+    /// don't depend on the order of definitions.
+    symbols: DiagnosticContext<'a>,
+
+    /// Formals of the function scopes on the `symbols` stack. Offset by one
+    /// since the root scope has no formals.
+    parameters: Vec<HashSet<String>>,
+
+    /// When set, only the tokens overlapping this byte range are collected
+    range: Option<Range<usize>>,
+
+    tokens: Vec<Token>,
+}
+
+/// Resolution of a symbol, from innermost to outermost scope
+enum Resolution {
+    Parameter,
+    Local,
+    Document,
+    Session,
+    Unresolved,
+}
+
+pub(crate) fn semantic_tokens_full(
+    params: SemanticTokensParams,
+    cache: &mut SemanticTokensCache,
+    state: &WorldState,
+) -> anyhow::Result<Option<SemanticTokensResult>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    let tokens = document_semantic_tokens(document, state, None);
+    let result_id = cache.insert(uri, tokens.clone());
+
+    Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: Some(result_id),
+        data: tokens,
+    })))
+}
+
+pub(crate) fn semantic_tokens_full_delta(
+    params: SemanticTokensDeltaParams,
+    cache: &mut SemanticTokensCache,
+    state: &WorldState,
+) -> anyhow::Result<Option<SemanticTokensFullDeltaResult>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    let tokens = document_semantic_tokens(document, state, None);
+
+    // Take the previous tokens out of the cache. If the client refers to a
+    // result we no longer have, fall back to sending all tokens.
+    let previous = cache
+        .results
+        .remove(&uri)
+        .filter(|cached| cached.result_id == params.previous_result_id);
+
+    let edits = previous.map(|previous| semantic_tokens_edits(&previous.tokens, &tokens));
+    let result_id = cache.insert(uri, tokens.clone());
+
+    let result = match edits {
+        Some(edits) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+            result_id: Some(result_id),
+            edits,
+        }),
+        None => SemanticTokensFullDeltaResult::Tokens(SemanticTokens {
+            result_id: Some(result_id),
+            data: tokens,
+        }),
+    };
+
+    Ok(Some(result))
+}
+
+pub(crate) fn semantic_tokens_range(
+    params: SemanticTokensRangeParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<SemanticTokensRangeResult>> {
+    let document = state.get_document(&params.text_document.uri)?;
+    let contents = &document.contents;
+
+    let start = convert_position_to_point(contents, params.range.start);
+    let end = convert_position_to_point(contents, params.range.end);
+    let range = contents.point_to_byte(start)..contents.point_to_byte(end);
+
+    let tokens = document_semantic_tokens(document, state, Some(range));
+
+    Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: tokens,
+    })))
+}
+
+/// Computes the encoded semantic tokens of a document, optionally restricted
+/// to a byte range.
+pub(crate) fn document_semantic_tokens(
+    document: &Document,
+    state: &WorldState,
+    range: Option<Range<usize>>,
+) -> Vec<SemanticToken> {
+    let symbols = DiagnosticContext::from_state(&document.contents, state);
+    let tokens = collect_tokens(document, symbols, range);
+    encode_tokens(tokens, &document.contents)
+}
+
+fn collect_tokens(
+    document: &Document,
+    symbols: DiagnosticContext,
+    range: Option<Range<usize>>,
+) -> Vec<Token> {
+    let root = document.ast.root_node();
+
+    let mut context = TokensContext {
+        symbols,
+        parameters: Vec::new(),
+        range,
+        tokens: Vec::new(),
+    };
+
+    // Fill the root scope with the top-level definitions of the document
+    let contents = context.symbols.contents;
+    let root_symbols = context.symbols.document_symbols.last_mut().unwrap();
+    collect_bindings(&root, contents, root_symbols);
+    collect_super_assignments(&root, contents, root_symbols);
+
+    recurse(root, &mut context);
+
+    context.tokens
+}
+
+/// Collect the symbols bound within `node`, excluding nested functions
+fn collect_bindings(
+    node: &Node,
+    contents: &Rope,
+    symbols: &mut HashMap<String, tree_sitter::Range>,
+) {
+    let mut cursor = node.walk();
+    cursor.recurse(|node| {
+        if node.is_function_definition() {
+            return false;
+        }
+
+        if let Some(target) = node_local_binding(&node) {
+            if let Some(name) = node_symbol_name(&target, contents) {
+                symbols.entry(name).or_insert(target.range());
+            }
+        }
+
+        true
+    });
+}
+
+/// Collect the targets of `<<-` and `->>` anywhere within `node`
+fn collect_super_assignments(
+    node: &Node,
+    contents: &Rope,
+    symbols: &mut HashMap<String, tree_sitter::Range>,
+) {
+    let mut cursor = node.walk();
+    cursor.recurse(|node| {
+        if node_is_super_assignment_target(&node) {
+            if let Some(name) = node_symbol_name(&node, contents) {
+                symbols.entry(name).or_insert(node.range());
+            }
+        }
+        true
+    });
+}
+
+fn recurse(node: Node, context: &mut TokensContext) {
+    if let Some(range) = &context.range {
+        if node.end_byte() <= range.start || node.start_byte() >= range.end {
+            return;
+        }
+    }
+
+    match node.node_type() {
+        NodeType::FunctionDefinition => recurse_function(node, context),
+        NodeType::UnaryOperator(UnaryOperatorType::Tilde) |
+        NodeType::BinaryOperator(BinaryOperatorType::Tilde) => recurse_formula(node, context),
+        NodeType::NamespaceOperator(_) => recurse_namespace(node, context),
+        NodeType::Identifier => handle_identifier(node, context),
+        _ => recurse_children(node, context),
+    }
+}
+
+fn recurse_children(node: Node, context: &mut TokensContext) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        recurse(child, context);
+    }
+}
+
+fn recurse_function(node: Node, context: &mut TokensContext) {
+    let contents = context.symbols.contents;
+
+    let mut symbols = HashMap::new();
+    let mut parameters = HashSet::new();
+
+    if let Some(formals) = node.child_by_field_name("parameters") {
+        let mut cursor = formals.walk();
+        for formal in formals.children_by_field_name("parameter", &mut cursor) {
+            let Some(name) = formal.child_by_field_name("name") else {
+                continue;
+            };
+            let Some(symbol) = node_symbol_name(&name, contents) else {
+                continue;
+            };
+            symbols.insert(symbol.clone(), name.range());
+            parameters.insert(symbol);
+        }
+    }
+
+    if let Some(body) = node.child_by_field_name("body") {
+        collect_bindings(&body, contents, &mut symbols);
+    }
+
+    context.symbols.document_symbols.push(symbols);
+    context.parameters.push(parameters);
+
+    recurse_children(node, context);
+
+    context.symbols.document_symbols.pop();
+    context.parameters.pop();
+}
+
+fn recurse_formula(node: Node, context: &mut TokensContext) {
+    let in_formula = context.symbols.in_formula;
+    context.symbols.in_formula = true;
+
+    recurse_children(node, context);
+
+    context.symbols.in_formula = in_formula;
+}
+
+fn recurse_namespace(node: Node, context: &mut TokensContext) {
+    if let Some(lhs) = node.child_by_field_name("lhs") {
+        if lhs.is_identifier() {
+            push_token(&lhs, TokenType::Namespace, 0, context);
+        }
+    }
+
+    if let Some(rhs) = node.child_by_field_name("rhs") {
+        if rhs.is_identifier() {
+            let token_type = if node_is_callee(&node) {
+                TokenType::Function
+            } else {
+                TokenType::Variable
+            };
+            push_token(&rhs, token_type, MODIFIER_DEFAULT_LIBRARY, context);
+        }
+    }
+}
+
+fn handle_identifier(node: Node, context: &mut TokensContext) {
+    // Argument names refer to the formals of the callee, which we don't
+    // resolve here
+    if node_is_argument_name(&node) {
+        return;
+    }
+
+    match node_reference_kind(&node) {
+        ReferenceKind::DollarName | ReferenceKind::AtName => {
+            push_token(&node, TokenType::Property, 0, context);
+            return;
+        },
+        ReferenceKind::SymbolName => {},
+    }
+
+    let Some(symbol) = node_symbol_name(&node, context.symbols.contents) else {
+        return;
+    };
+
+    let is_super_assignment = node_is_super_assignment_target(&node);
+    let is_declaration = is_super_assignment ||
+        node_is_formal_name(&node) ||
+        node.parent()
+            .map_or(false, |parent| node_local_binding(&parent) == Some(node));
+
+    let resolution = resolve_symbol(&symbol, is_super_assignment, context);
+
+    let token_type = if node_is_callee(&node) || node_is_bound_to_function(&node) {
+        TokenType::Function
+    } else {
+        match resolution {
+            Resolution::Parameter => TokenType::Parameter,
+            _ => TokenType::Variable,
+        }
+    };
+
+    let mut modifiers = 0;
+    if is_declaration {
+        modifiers |= MODIFIER_DECLARATION;
+    }
+    if context.symbols.in_formula {
+        modifiers |= MODIFIER_FORMULA;
+    }
+    match resolution {
+        Resolution::Session => modifiers |= MODIFIER_GLOBAL,
+        // Formula variables usually refer to data columns, we don't consider
+        // them unresolved
+        Resolution::Unresolved if !context.symbols.in_formula => modifiers |= MODIFIER_UNRESOLVED,
+        _ => {},
+    }
+
+    push_token(&node, token_type, modifiers, context);
+}
+
+fn resolve_symbol(symbol: &str, is_super_assignment: bool, context: &TokensContext) -> Resolution {
+    let scopes = &context.symbols.document_symbols;
+
+    // Function scopes, from innermost to outermost. Super-assignments skip
+    // the scope of the function they are called from.
+    let skip = if is_super_assignment { 1 } else { 0 };
+
+    for (i, symbols) in scopes.iter().enumerate().skip(1).rev().skip(skip) {
+        if symbols.contains_key(symbol) {
+            if context.parameters[i - 1].contains(symbol) {
+                return Resolution::Parameter;
+            }
+            return Resolution::Local;
+        }
+    }
+
+    if scopes
+        .first()
+        .map_or(false, |root| root.contains_key(symbol)) ||
+        context.symbols.workspace_symbols.contains(symbol)
+    {
+        return Resolution::Document;
+    }
+
+    if context.symbols.session_symbols.contains(symbol) {
+        return Resolution::Session;
+    }
+
+    Resolution::Unresolved
+}
+
+fn push_token(node: &Node, token_type: TokenType, modifiers: u32, context: &mut TokensContext) {
+    let start = node.start_position();
+    let end = node.end_position();
+
+    // Multiline tokens are not supported by all clients
+    if start.row != end.row {
+        return;
+    }
+
+    if let Some(range) = &context.range {
+        if node.end_byte() <= range.start || node.start_byte() >= range.end {
+            return;
+        }
+    }
+
+    context.tokens.push(Token {
+        start,
+        end,
+        token_type,
+        modifiers,
+    });
+}
+
+/// Is `node` the function of a call, as in `node()`? Also handles namespaced
+/// calls when `node` is a `::` operator.
+fn node_is_callee(node: &Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    parent.is_call() && parent.child_by_field_name("function") == Some(*node)
+}
+
+/// Is `node` the name of a formal, as in `function(node) {}`?
+fn node_is_formal_name(node: &Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    parent.node_type() == NodeType::Parameter && parent.child_by_field_name("name") == Some(*node)
+}
+
+/// Is `node` assigned a function definition, as in `node <- function() {}`?
+fn node_is_bound_to_function(node: &Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+
+    let (target, value) = match parent.node_type() {
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+        NodeType::BinaryOperator(BinaryOperatorType::LeftSuperAssignment) |
+        NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment) => (
+            parent.child_by_field_name("lhs"),
+            parent.child_by_field_name("rhs"),
+        ),
+        NodeType::BinaryOperator(BinaryOperatorType::RightAssignment) |
+        NodeType::BinaryOperator(BinaryOperatorType::RightSuperAssignment) => (
+            parent.child_by_field_name("rhs"),
+            parent.child_by_field_name("lhs"),
+        ),
+        _ => return false,
+    };
+
+    target == Some(*node) && value.map_or(false, |value| value.is_function_definition())
+}
+
+/// Encodes tokens relative to each other, with UTF-16 columns
+fn encode_tokens(tokens: Vec<Token>, contents: &Rope) -> Vec<SemanticToken> {
+    let mut out = Vec::with_capacity(tokens.len());
+
+    let mut line = 0;
+    let mut character = 0;
+
+    for token in tokens {
+        let start = convert_point_to_position(contents, token.start);
+        let end = convert_point_to_position(contents, token.end);
+
+        let delta_line = start.line - line;
+        let delta_start = if delta_line == 0 {
+            start.character - character
+        } else {
+            start.character
+        };
+
+        out.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length: end.character - start.character,
+            token_type: token.token_type as u32,
+            token_modifiers_bitset: token.modifiers,
+        });
+
+        line = start.line;
+        character = start.character;
+    }
+
+    out
+}
+
+/// Computes the edits transforming `old` into `new` as a single edit
+/// spanning the tokens in between their common prefix and suffix. Edit
+/// offsets count integers in the encoded array, i.e. 5 per token.
+fn semantic_tokens_edits(old: &[SemanticToken], new: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let deleted = old.len() - prefix - suffix;
+    let inserted = &new[prefix..new.len() - suffix];
+
+    if deleted == 0 && inserted.is_empty() {
+        return Vec::new();
+    }
+
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: (deleted * 5) as u32,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::SemanticToken;
+
+    use crate::lsp::diagnostics::DiagnosticContext;
+    use crate::lsp::documents::Document;
+    use crate::lsp::semantic_tokens::collect_tokens;
+    use crate::lsp::semantic_tokens::encode_tokens;
+    use crate::lsp::semantic_tokens::semantic_tokens_edits;
+    use crate::lsp::semantic_tokens::TokenType;
+    use crate::lsp::semantic_tokens::MODIFIER_DECLARATION;
+    use crate::lsp::semantic_tokens::MODIFIER_DEFAULT_LIBRARY;
+    use crate::lsp::semantic_tokens::MODIFIER_FORMULA;
+    use crate::lsp::semantic_tokens::MODIFIER_GLOBAL;
+    use crate::lsp::semantic_tokens::MODIFIER_UNRESOLVED;
+    use crate::lsp::traits::rope::RopeExt;
+
+    fn test_tokens(text: &str, session: &[&str]) -> Vec<(String, TokenType, u32)> {
+        let document = Document::new(text, None);
+
+        let mut symbols = DiagnosticContext::new(&document.contents);
+        symbols.document_symbols.push(Default::default());
+        for name in session {
+            symbols.session_symbols.insert(name.to_string());
+        }
+
+        collect_tokens(&document, symbols, None)
+            .into_iter()
+            .map(|token| {
+                let start = document.contents.point_to_byte(token.start);
+                let end = document.contents.point_to_byte(token.end);
+                let text = document.contents.byte_slice(start..end).to_string();
+                (text, token.token_type, token.modifiers)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parameters_and_locals() {
+        let tokens = test_tokens("f <- function(x) { y <- x; y }", &[]);
+        assert_eq!(tokens, vec![
+            ("f".to_string(), TokenType::Function, MODIFIER_DECLARATION),
+            ("x".to_string(), TokenType::Parameter, MODIFIER_DECLARATION),
+            ("y".to_string(), TokenType::Variable, MODIFIER_DECLARATION),
+            ("x".to_string(), TokenType::Parameter, 0),
+            ("y".to_string(), TokenType::Variable, 0),
+        ]);
+    }
+
+    #[test]
+    fn test_session_and_unresolved_symbols() {
+        let tokens = test_tokens("print(foo)\nbar", &["print"]);
+        assert_eq!(tokens, vec![
+            ("print".to_string(), TokenType::Function, MODIFIER_GLOBAL),
+            ("foo".to_string(), TokenType::Variable, MODIFIER_UNRESOLVED),
+            ("bar".to_string(), TokenType::Variable, MODIFIER_UNRESOLVED),
+        ]);
+
+        // Definitions later in the document are resolved too
+        let tokens = test_tokens("bar\nbar <- 1", &[]);
+        assert_eq!(tokens, vec![
+            ("bar".to_string(), TokenType::Variable, 0),
+            ("bar".to_string(), TokenType::Variable, MODIFIER_DECLARATION),
+        ]);
+    }
+
+    #[test]
+    fn test_namespace_calls_and_properties() {
+        let tokens = test_tokens("dplyr::filter(df$col, df@slot)\nbase::letters", &[]);
+        assert_eq!(tokens, vec![
+            ("dplyr".to_string(), TokenType::Namespace, 0),
+            (
+                "filter".to_string(),
+                TokenType::Function,
+                MODIFIER_DEFAULT_LIBRARY
+            ),
+            ("df".to_string(), TokenType::Variable, MODIFIER_UNRESOLVED),
+            ("col".to_string(), TokenType::Property, 0),
+            ("df".to_string(), TokenType::Variable, MODIFIER_UNRESOLVED),
+            ("slot".to_string(), TokenType::Property, 0),
+            ("base".to_string(), TokenType::Namespace, 0),
+            (
+                "letters".to_string(),
+                TokenType::Variable,
+                MODIFIER_DEFAULT_LIBRARY
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_formula_variables() {
+        let tokens = test_tokens("lm(y ~ x, data = df)", &["lm"]);
+        assert_eq!(tokens, vec![
+            ("lm".to_string(), TokenType::Function, MODIFIER_GLOBAL),
+            ("y".to_string(), TokenType::Variable, MODIFIER_FORMULA),
+            ("x".to_string(), TokenType::Variable, MODIFIER_FORMULA),
+            ("df".to_string(), TokenType::Variable, MODIFIER_UNRESOLVED),
+        ]);
+    }
+
+    #[test]
+    fn test_super_assignment_skips_current_function() {
+        let tokens = test_tokens("function(x) function() x <<- 1", &[]);
+        assert_eq!(tokens, vec![
+            ("x".to_string(), TokenType::Parameter, MODIFIER_DECLARATION),
+            ("x".to_string(), TokenType::Parameter, MODIFIER_DECLARATION),
+        ]);
+    }
+
+    #[test]
+    fn test_encoding_is_relative() {
+        let document = Document::new("a <- 1\n  b <- a; a", None);
+
+        let mut symbols = DiagnosticContext::new(&document.contents);
+        symbols.document_symbols.push(Default::default());
+
+        let tokens = collect_tokens(&document, symbols, None);
+        let tokens = encode_tokens(tokens, &document.contents);
+
+        let deltas: Vec<(u32, u32, u32)> = tokens
+            .iter()
+            .map(|token| (token.delta_line, token.delta_start, token.length))
+            .collect();
+        assert_eq!(deltas, vec![(0, 0, 1), (1, 2, 1), (0, 5, 1), (0, 3, 1)]);
+    }
+
+    #[test]
+    fn test_range_restricts_tokens() {
+        let document = Document::new("a <- 1\nb <- 2\nc <- 3", None);
+
+        let mut symbols = DiagnosticContext::new(&document.contents);
+        symbols.document_symbols.push(Default::default());
+
+        let tokens = collect_tokens(&document, symbols, Some(7..13));
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].start.row, 1);
+    }
+
+    #[test]
+    fn test_edits() {
+        let token = |delta_line| SemanticToken {
+            delta_line,
+            delta_start: 0,
+            length: 1,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        };
+
+        let old = vec![token(0), token(1), token(2), token(3)];
+        let new = vec![token(0), token(5), token(6), token(3)];
+
+        let edits = semantic_tokens_edits(&old, &new);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].start, 5);
+        assert_eq!(edits[0].delete_count, 10);
+        assert_eq!(edits[0].data, Some(vec![token(5), token(6)]));
+
+        assert!(semantic_tokens_edits(&old, &old).is_empty());
+    }
+}
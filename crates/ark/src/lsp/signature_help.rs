@@ -5,6 +5,7 @@
 //
 //
 
+use harp::call::RArgument;
 use harp::eval::RParseEvalOptions;
 use harp::object::*;
 use harp::r_null;
@@ -20,6 +21,8 @@ use log::info;
 use stdext::unwrap;
 use stdext::unwrap::IntoResult;
 use tower_lsp::lsp_types::Documentation;
+use tower_lsp::lsp_types::MarkupContent;
+use tower_lsp::lsp_types::MarkupKind;
 use tower_lsp::lsp_types::ParameterInformation;
 use tower_lsp::lsp_types::ParameterLabel;
 use tower_lsp::lsp_types::SignatureHelp;
@@ -29,9 +32,12 @@ use tree_sitter::Point;
 
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::help::RHtmlHelp;
+use crate::lsp::state::WorldState;
 use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::point::PointExt;
 use crate::lsp::traits::rope::RopeExt;
+use crate::lsp::workspace_function::formal_label;
+use crate::lsp::workspace_function::WorkspaceFunction;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
@@ -40,8 +46,44 @@ use crate::treesitter::NodeTypeExt;
 // is the accumulation of a number of smaller changes that have resulted in something
 // that is a bit hard to follow.
 
+/// Signature help for functions defined in the R session. Returns `None` if
+/// the callee isn't found in the session, see `workspace_signature_help()`.
+///
 /// SAFETY: Requires access to the R runtime.
 pub(crate) fn r_signature_help(context: &DocumentContext) -> anyhow::Result<Option<SignatureHelp>> {
+    signature_help(context, |callee, code| {
+        let Some(formals) = callee_formals(code)? else {
+            return Ok(None);
+        };
+        Ok(Some((session_parameters(callee, &formals, context)?, None)))
+    })
+}
+
+/// Signature help for functions defined in the workspace, which might not be
+/// loaded in the session, e.g. while editing a package that isn't installed.
+/// Doesn't require access to the R runtime.
+pub(crate) fn workspace_signature_help(
+    context: &DocumentContext,
+    state: &WorldState,
+) -> anyhow::Result<Option<SignatureHelp>> {
+    signature_help(context, |callee, code| {
+        if !callee.is_identifier_or_string() {
+            return Ok(None);
+        }
+        Ok(WorkspaceFunction::find(code, state).map(|function| workspace_parameters(&function)))
+    })
+}
+
+/// Computes the signature help of the call at the cursor, with the
+/// parameters of the callee returned by `lookup` from the callee node and its
+/// code.
+fn signature_help(
+    context: &DocumentContext,
+    lookup: impl FnOnce(
+        &Node,
+        &str,
+    ) -> anyhow::Result<Option<(Vec<SignatureParameter>, Option<Documentation>)>>,
+) -> anyhow::Result<Option<SignatureHelp>> {
     // Get document AST + completion position.
     let ast = &context.document.ast;
 
@@ -162,59 +204,11 @@ pub(crate) fn r_signature_help(context: &DocumentContext) -> anyhow::Result<Opti
         return Ok(None);
     });
 
-    // TODO: Should we search the document and / or the workspace index
-    // before asking the R session for a definition? Which should take precedence?
-
     // Try to figure out what R object it's associated with.
     let code = context.document.contents.node_slice(&callee)?.to_string();
 
-    let object = harp::parse_eval(code.as_str(), RParseEvalOptions {
-        forbid_function_calls: true,
-        ..Default::default()
-    });
-
-    let object = match object {
-        Ok(object) => object,
-        Err(err) => match err {
-            // LHS of the call was too complex to evaluate.
-            harp::error::Error::UnsafeEvaluationError(_) => return Ok(None),
-            // LHS of the call evaluated to an error. Totally possible if the
-            // user is writing pseudocode. Don't want to propagate an error here.
-            _ => return Ok(None),
-        },
-    };
-
-    if !r_is_function(*object) {
-        // Not uncommon for tree-sitter to detect partially written code as a
-        // call, like:
-        // ---
-        // mtcars$
-        // plot(1:5)
-        // ---
-        // Where it detects `mtcars$plot` as the LHS of the call.
-        // That is actually how R would parse this, but the user might be writing
-        // `mtcars$` and requesting completions for the `$` when this occurs.
-        // In these cases the `r_parse_eval()` above either errors or returns
-        // something that isn't a function, so we ensure we have a function
-        // before proceeding here.
+    let Some((parameters, documentation)) = lookup(&callee, code.as_str())? else {
         return Ok(None);
-    }
-
-    // Get the formal parameter names associated with this function.
-    let formals = r_formals(*object)?;
-
-    // Get the help documentation associated with this function.
-    let help = if callee.is_namespace_operator() {
-        let package = callee.child_by_field_name("lhs").into_result()?;
-        let package = context.document.contents.node_slice(&package)?.to_string();
-
-        let name = callee.child_by_field_name("rhs").into_result()?;
-        let name = context.document.contents.node_slice(&name)?.to_string();
-
-        RHtmlHelp::from_function(name.as_str(), Some(package.as_str()))
-    } else {
-        let name = context.document.contents.node_slice(&callee)?.to_string();
-        RHtmlHelp::from_function(name.as_str(), None)
     };
 
     // The signature label. We generate this as we walk through the
@@ -224,42 +218,31 @@ pub(crate) fn r_signature_help(context: &DocumentContext) -> anyhow::Result<Opti
     label.push('(');
 
     // Get the available parameters.
-    let mut parameters = vec![];
+    let mut parameter_infos = vec![];
 
     // Iterate over the documentation for each parameter, and add the relevant information.
-    for (index, argument) in formals.iter().enumerate() {
-        // Get argument components.
-        let argument_name = &argument.name;
-        let argument_value = &argument.value;
-        let argument_label = argument_label(argument_name.clone(), argument_value.sexp);
-
+    for (index, parameter) in parameters.iter().enumerate() {
         // Compute signature offsets.
         let start = label.len() as u32;
-        let end = start + argument_label.len() as u32;
+        let end = start + parameter.label.len() as u32;
 
         // Add the argument label to the overall label.
-        label.push_str(argument_label.as_str());
+        label.push_str(parameter.label.as_str());
         label.push_str(", ");
 
         // If we had an explicit name, and this name matches the argument,
         // then update the offset now.
-        if active_argument.as_ref() == Some(argument_name) {
+        if active_argument.as_ref() == Some(&parameter.name) {
             offset = Some(index as u32);
         }
 
-        // Get documentation, if any.
-        let mut documentation = None;
-        if let Ok(Some(ref help)) = help {
-            let markup = help.parameter(argument_name);
-            if let Ok(Some(markup)) = markup {
-                documentation = Some(Documentation::MarkupContent(markup));
-            }
-        }
-
         // Add the new parameter.
-        parameters.push(ParameterInformation {
+        parameter_infos.push(ParameterInformation {
             label: ParameterLabel::LabelOffsets([start, end]),
-            documentation,
+            documentation: parameter
+                .documentation
+                .clone()
+                .map(Documentation::MarkupContent),
         });
     }
 
@@ -274,9 +257,9 @@ pub(crate) fn r_signature_help(context: &DocumentContext) -> anyhow::Result<Opti
 
     // Finally, if we don't have an offset, figure it out now.
     if offset.is_none() {
-        for (index, argument) in formals.iter().enumerate() {
+        for (index, parameter) in parameters.iter().enumerate() {
             // Was this argument explicitly provided? If so, skip it.
-            if explicit_parameters.contains(&argument.name) {
+            if explicit_parameters.contains(&parameter.name) {
                 continue;
             }
 
@@ -296,13 +279,13 @@ pub(crate) fn r_signature_help(context: &DocumentContext) -> anyhow::Result<Opti
     // parameter when the offset is set to none, so here we just force it to
     // match no available argument.
     if offset.is_none() {
-        offset = Some((formals.len() + 1).try_into().unwrap_or_default());
+        offset = Some((parameters.len() + 1).try_into().unwrap_or_default());
     }
 
     let signature = SignatureInformation {
         label,
-        documentation: None,
-        parameters: Some(parameters),
+        documentation,
+        parameters: Some(parameter_infos),
         active_parameter: offset,
     };
 
@@ -316,6 +299,137 @@ pub(crate) fn r_signature_help(context: &DocumentContext) -> anyhow::Result<Opti
     Ok(Some(help))
 }
 
+/// A parameter of a signature
+struct SignatureParameter {
+    name: String,
+
+    /// The name of the parameter and its default value, if any
+    label: String,
+
+    documentation: Option<MarkupContent>,
+}
+
+/// The parameters of a function found in the R session, documented from its
+/// help page.
+///
+/// SAFETY: Requires access to the R runtime.
+fn session_parameters(
+    callee: &Node,
+    formals: &[RArgument],
+    context: &DocumentContext,
+) -> anyhow::Result<Vec<SignatureParameter>> {
+    // Get the help documentation associated with this function.
+    let help = if callee.is_namespace_operator() {
+        let package = callee.child_by_field_name("lhs").into_result()?;
+        let package = context.document.contents.node_slice(&package)?.to_string();
+
+        let name = callee.child_by_field_name("rhs").into_result()?;
+        let name = context.document.contents.node_slice(&name)?.to_string();
+
+        RHtmlHelp::from_function(name.as_str(), Some(package.as_str()))
+    } else {
+        let name = context.document.contents.node_slice(&callee)?.to_string();
+        RHtmlHelp::from_function(name.as_str(), None)
+    };
+
+    let parameters = formals
+        .iter()
+        .map(|argument| {
+            // Get documentation, if any.
+            let documentation = match &help {
+                Ok(Some(help)) => help.parameter(&argument.name).ok().flatten(),
+                _ => None,
+            };
+
+            SignatureParameter {
+                name: argument.name.clone(),
+                label: argument_label(argument.name.clone(), argument.value.sexp),
+                documentation,
+            }
+        })
+        .collect();
+
+    Ok(parameters)
+}
+
+/// The parameters of a function defined in the workspace, documented from
+/// its roxygen block. The title of the block documents the signature.
+fn workspace_parameters(
+    function: &WorkspaceFunction,
+) -> (Vec<SignatureParameter>, Option<Documentation>) {
+    let roxygen = function.roxygen.as_ref();
+
+    let parameters = function
+        .formals
+        .iter()
+        .map(|(name, default)| {
+            let label = formal_label(name, default.as_deref());
+
+            let documentation = roxygen
+                .and_then(|roxygen| roxygen.param_description(name))
+                .map(|description| MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: description,
+                });
+
+            SignatureParameter {
+                name: name.clone(),
+                label: argument_label_truncate(label),
+                documentation,
+            }
+        })
+        .collect();
+
+    let documentation = roxygen.and_then(|roxygen| roxygen.title()).map(|title| {
+        Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: title.to_string(),
+        })
+    });
+
+    (parameters, documentation)
+}
+
+/// Evaluates the callee of a call and returns its formals. Returns `None` if
+/// the callee can't be evaluated without calling functions or isn't a function.
+///
+/// SAFETY: Requires access to the R runtime.
+pub(crate) fn callee_formals(code: &str) -> anyhow::Result<Option<Vec<RArgument>>> {
+    let object = harp::parse_eval(code, RParseEvalOptions {
+        forbid_function_calls: true,
+        ..Default::default()
+    });
+
+    let object = match object {
+        Ok(object) => object,
+        Err(err) => match err {
+            // LHS of the call was too complex to evaluate.
+            harp::error::Error::UnsafeEvaluationError(_) => return Ok(None),
+            // LHS of the call evaluated to an error. Totally possible if the
+            // user is writing pseudocode. Don't want to propagate an error here.
+            _ => return Ok(None),
+        },
+    };
+
+    if !r_is_function(*object) {
+        // Not uncommon for tree-sitter to detect partially written code as a
+        // call, like:
+        // ---
+        // mtcars$
+        // plot(1:5)
+        // ---
+        // Where it detects `mtcars$plot` as the LHS of the call.
+        // That is actually how R would parse this, but the user might be writing
+        // `mtcars$` and requesting completions for the `$` when this occurs.
+        // In these cases the `r_parse_eval()` above either errors or returns
+        // something that isn't a function, so we ensure we have a function
+        // before proceeding here.
+        return Ok(None);
+    }
+
+    Ok(Some(r_formals(*object)?))
+}
+
 fn is_within_call_parentheses(x: &Point, node: &Node) -> bool {
     if node.node_type() != NodeType::Call {
         // This would be very weird
@@ -509,11 +623,15 @@ mod tests {
     use harp::RObject;
     use libr::R_xlen_t;
     use tower_lsp::lsp_types::ParameterLabel;
+    use tower_lsp::lsp_types::Url;
 
     use crate::lsp::document_context::DocumentContext;
     use crate::lsp::documents::Document;
+    use crate::lsp::indexer;
     use crate::lsp::signature_help::argument_label;
     use crate::lsp::signature_help::r_signature_help;
+    use crate::lsp::signature_help::workspace_signature_help;
+    use crate::lsp::state::WorldState;
     use crate::test::point_from_cursor;
 
     #[test]
@@ -743,4 +861,35 @@ fn <- function(
             assert_eq!(label, String::from("x = \"hi\""));
         })
     }
+
+    #[test]
+    fn test_workspace_signature_help() {
+        let definition = "
+#' Add numbers
+#' @param x A number.
+sig_help_workspace_fn <- function(x, y = 1) x + y
+";
+        let path = std::env::temp_dir().join("sig_help_workspace.R");
+        let definition = Document::new(definition, None);
+        indexer::update(&definition, &path).unwrap();
+
+        let mut state = WorldState::default();
+        let uri = Url::from_file_path(&path).unwrap();
+        state.documents.insert(uri, definition);
+
+        let (text, point) = point_from_cursor("sig_help_workspace_fn(1, @)");
+        let document = Document::new(&text, None);
+        let context = DocumentContext::new(&document, point, None);
+
+        // Doesn't need the R runtime
+        let help = workspace_signature_help(&context, &state).unwrap().unwrap();
+        indexer::remove(&path);
+
+        let signature = help.signatures.get(0).unwrap();
+        assert_eq!(signature.label, "sig_help_workspace_fn(x, y = 1)");
+        assert_eq!(help.active_parameter, Some(1));
+
+        let parameters = signature.parameters.as_ref().unwrap();
+        assert!(parameters[0].documentation.is_some());
+    }
 }
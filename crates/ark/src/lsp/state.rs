@@ -5,7 +5,9 @@ use anyhow::anyhow;
 use url::Url;
 
 use crate::lsp::config::LspConfig;
+use crate::lsp::diagnostics_calls::ConsoleSignatures;
 use crate::lsp::documents::Document;
+use crate::lsp::package::Package;
 
 #[derive(Clone, Default, Debug)]
 /// The world state, i.e. all the inputs necessary for analysing or refactoring
@@ -44,12 +46,18 @@ pub(crate) struct WorldState {
     /// Currently installed packages
     pub(crate) installed_packages: Vec<String>,
 
+    /// Signatures of the functions visible from the console
+    pub(crate) console_signatures: ConsoleSignatures,
+
     pub(crate) config: LspConfig,
 }
 
 #[derive(Clone, Default, Debug)]
 pub(crate) struct Workspace {
     pub folders: Vec<Url>,
+
+    /// The R package developed in the workspace, if any
+    pub package: Option<Package>,
 }
 
 impl WorldState {
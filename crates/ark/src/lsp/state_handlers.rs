@@ -10,21 +10,35 @@ use std::path::Path;
 use anyhow::anyhow;
 use serde_json::Value;
 use struct_field_names_as_array::FieldNamesAsArray;
+use tower_lsp::lsp_types::CallHierarchyServerCapability;
+use tower_lsp::lsp_types::CodeActionKind;
+use tower_lsp::lsp_types::CodeActionOptions;
+use tower_lsp::lsp_types::CodeActionProviderCapability;
+use tower_lsp::lsp_types::CodeLensOptions;
 use tower_lsp::lsp_types::CompletionOptions;
 use tower_lsp::lsp_types::ConfigurationItem;
 use tower_lsp::lsp_types::DidChangeConfigurationParams;
 use tower_lsp::lsp_types::DidChangeTextDocumentParams;
+use tower_lsp::lsp_types::DidChangeWatchedFilesParams;
 use tower_lsp::lsp_types::DidCloseTextDocumentParams;
 use tower_lsp::lsp_types::DidOpenTextDocumentParams;
 use tower_lsp::lsp_types::DocumentOnTypeFormattingOptions;
 use tower_lsp::lsp_types::ExecuteCommandOptions;
+use tower_lsp::lsp_types::FileChangeType;
+use tower_lsp::lsp_types::FileEvent;
+use tower_lsp::lsp_types::FoldingRangeProviderCapability;
 use tower_lsp::lsp_types::FormattingOptions;
 use tower_lsp::lsp_types::HoverProviderCapability;
 use tower_lsp::lsp_types::ImplementationProviderCapability;
 use tower_lsp::lsp_types::InitializeParams;
 use tower_lsp::lsp_types::InitializeResult;
+use tower_lsp::lsp_types::LinkedEditingRangeServerCapabilities;
 use tower_lsp::lsp_types::OneOf;
+use tower_lsp::lsp_types::RenameOptions;
 use tower_lsp::lsp_types::SelectionRangeProviderCapability;
+use tower_lsp::lsp_types::SemanticTokensFullOptions;
+use tower_lsp::lsp_types::SemanticTokensOptions;
+use tower_lsp::lsp_types::SemanticTokensServerCapabilities;
 use tower_lsp::lsp_types::ServerCapabilities;
 use tower_lsp::lsp_types::ServerInfo;
 use tower_lsp::lsp_types::SignatureHelpOptions;
@@ -40,13 +54,22 @@ use url::Url;
 use crate::lsp;
 use crate::lsp::config::indent_style_from_lsp;
 use crate::lsp::config::DocumentConfig;
+use crate::lsp::config::ProjectConfig;
 use crate::lsp::config::VscDiagnosticsConfig;
 use crate::lsp::config::VscDocumentConfig;
+use crate::lsp::config::VscInlayHintsConfig;
+use crate::lsp::config::PROJECT_CONFIG_FILE;
 use crate::lsp::diagnostics::DiagnosticsConfig;
+use crate::lsp::diagnostics_calls::ConsoleSignatures;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::get_position_encoding_kind;
 use crate::lsp::indexer;
+use crate::lsp::inlay_hints;
 use crate::lsp::main_loop::LspState;
+use crate::lsp::package;
+use crate::lsp::package::Package;
+use crate::lsp::quarto::is_markdown_document;
+use crate::lsp::semantic_tokens::semantic_tokens_legend;
 use crate::lsp::state::workspace_uris;
 use crate::lsp::state::WorldState;
 
@@ -65,6 +88,9 @@ pub struct ConsoleInputs {
     /// Packages currently installed in the library path. TODO: Should send
     /// library paths instead and inspect and cache package information in the LSP.
     pub installed_packages: Vec<String>,
+
+    /// Signatures of the functions visible from the console
+    pub(crate) console_signatures: ConsoleSignatures,
 }
 
 // Handlers taking exclusive references to global state
@@ -82,6 +108,10 @@ pub(crate) fn initialize(
         {
             lsp_state.needs_registration.did_change_configuration = true;
         }
+        if matches!(ws_caps.did_change_watched_files, Some(caps) if matches!(caps.dynamic_registration, Some(true)))
+        {
+            lsp_state.needs_registration.did_change_watched_files = true;
+        }
     }
 
     // Initialize the workspace folders
@@ -97,6 +127,12 @@ pub(crate) fn initialize(
         }
     }
 
+    // Read the `.ark.toml` files of the workspace
+    state.config.project = ProjectConfig::from_workspace(&state.workspace.folders);
+
+    // Read the `DESCRIPTION` and `NAMESPACE` files if the workspace is a package
+    state.workspace.package = Package::from_workspace(&state.workspace.folders);
+
     // Start first round of indexing
     lsp::spawn_blocking(|| {
         indexer::start(folders);
@@ -133,6 +169,34 @@ pub(crate) fn initialize(
             type_definition_provider: None,
             implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
             references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Right(RenameOptions {
+                prepare_provider: Some(true),
+                work_done_progress_options: Default::default(),
+            })),
+            code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                code_action_kinds: Some(vec![
+                    CodeActionKind::QUICKFIX,
+                    CodeActionKind::REFACTOR_EXTRACT,
+                ]),
+                work_done_progress_options: Default::default(),
+                resolve_provider: None,
+            })),
+            call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            code_lens_provider: Some(CodeLensOptions {
+                resolve_provider: Some(false),
+            }),
+            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+            document_highlight_provider: Some(OneOf::Left(true)),
+            linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(true)),
+            semantic_tokens_provider: Some(
+                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens_legend(),
+                    range: Some(true),
+                    full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                    work_done_progress_options: Default::default(),
+                }),
+            ),
             document_symbol_provider: Some(OneOf::Left(true)),
             workspace_symbol_provider: Some(OneOf::Left(true)),
             execute_command_provider: Some(ExecuteCommandOptions {
@@ -146,6 +210,8 @@ pub(crate) fn initialize(
                 }),
                 file_operations: None,
             }),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
             document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
                 first_trigger_character: String::from("\n"),
                 more_trigger_character: None,
@@ -170,7 +236,11 @@ pub(crate) fn did_open(
         .set_language(&tree_sitter_r::LANGUAGE.into())
         .unwrap();
 
-    let document = Document::new_with_parser(contents, &mut parser, Some(version));
+    let document = if is_markdown_document(&uri, &params.text_document.language_id) {
+        Document::new_markdown_with_parser(contents, &mut parser, Some(version))
+    } else {
+        Document::new_with_parser(contents, &mut parser, Some(version))
+    };
 
     lsp_state.parsers.insert(uri.clone(), parser);
     state.documents.insert(uri.clone(), document.clone());
@@ -227,11 +297,116 @@ pub(crate) fn did_close(
         .remove(&uri)
         .ok_or(anyhow!("Failed to remove parser for URI: {uri}"))?;
 
+    lsp_state.semantic_tokens.remove(&uri);
+
     lsp::log_info!("did_close(): closed document with URI: '{uri}'.");
 
     Ok(())
 }
 
+/// Keeps the workspace index current when R files are changed outside the
+/// editor, e.g. after a `git checkout`. Also rereads the package files and
+/// the `.ark.toml` files when they change, e.g. after `roxygen2::roxygenise()`
+/// rewrites `NAMESPACE`.
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn did_change_watched_files(
+    params: DidChangeWatchedFilesParams,
+    state: &mut WorldState,
+) -> anyhow::Result<()> {
+    let mut changed = false;
+
+    if params
+        .changes
+        .iter()
+        .any(|change| is_package_file(&change.uri))
+    {
+        let package = Package::from_workspace(&state.workspace.folders);
+        changed |= state.workspace.package != package;
+        state.workspace.package = package;
+    }
+
+    if params
+        .changes
+        .iter()
+        .any(|change| is_project_config_file(&change.uri))
+    {
+        let project = ProjectConfig::from_workspace(&state.workspace.folders);
+        changed |= state.config.project != project;
+        state.config.project = project;
+    }
+
+    // Open documents are indexed from their editor buffer instead
+    let changes: Vec<FileEvent> = params
+        .changes
+        .into_iter()
+        .filter(|change| !is_package_file(&change.uri) && !is_project_config_file(&change.uri))
+        .filter(|change| !state.documents.contains_key(&change.uri))
+        .collect();
+
+    if changes.is_empty() {
+        if changed {
+            lsp::spawn_diagnostics_refresh_all(state.clone());
+        }
+        return Ok(());
+    }
+
+    let folders: Vec<String> = state
+        .workspace
+        .folders
+        .iter()
+        .filter_map(|folder| folder.to_file_path().ok())
+        .filter_map(|path| path.to_str().map(String::from))
+        .collect();
+
+    let state = state.clone();
+
+    lsp::spawn_blocking(move || {
+        for change in changes.into_iter() {
+            let Ok(path) = change.uri.to_file_path() else {
+                continue;
+            };
+
+            if change.typ == FileChangeType::DELETED {
+                indexer::remove(&path);
+            } else if let Err(err) = indexer::refresh(&path) {
+                lsp::log_error!("Can't index file {path:?}: {err:?}");
+            }
+        }
+
+        indexer::persist(&folders);
+
+        // Workspace symbols feed the diagnostics of open documents
+        lsp::spawn_diagnostics_refresh_all(state);
+
+        Ok(None)
+    });
+
+    Ok(())
+}
+
+/// Is this one of the files that describe the package developed in the
+/// workspace?
+fn is_package_file(uri: &Url) -> bool {
+    let Ok(path) = uri.to_file_path() else {
+        return false;
+    };
+
+    matches!(
+        path.file_name().and_then(|name| name.to_str()),
+        Some("DESCRIPTION" | "NAMESPACE")
+    )
+}
+
+/// Is this a project configuration file, see `ProjectConfig`?
+fn is_project_config_file(uri: &Url) -> bool {
+    let Ok(path) = uri.to_file_path() else {
+        return false;
+    };
+
+    path.file_name()
+        .is_some_and(|name| name == PROJECT_CONFIG_FILE)
+}
+
 pub(crate) async fn did_change_configuration(
     _params: DidChangeConfigurationParams,
     client: &tower_lsp::Client,
@@ -293,6 +468,16 @@ async fn update_config(
         .collect();
     items.append(&mut diagnostics_items);
 
+    let inlay_hints_keys = VscInlayHintsConfig::FIELD_NAMES_AS_ARRAY;
+    let mut inlay_hints_items: Vec<ConfigurationItem> = inlay_hints_keys
+        .iter()
+        .map(|key| ConfigurationItem {
+            scope_uri: None,
+            section: Some(VscInlayHintsConfig::section_from_key(key).into()),
+        })
+        .collect();
+    items.append(&mut inlay_hints_items);
+
     // For document configs we collect all pairs of URIs and config keys of
     // interest in a flat vector
     let document_keys = VscDocumentConfig::FIELD_NAMES_AS_ARRAY;
@@ -313,7 +498,8 @@ async fn update_config(
     // by chunk
     let n_document_items = document_keys.len();
     let n_diagnostics_items = diagnostics_keys.len();
-    let n_items = n_diagnostics_items + (n_document_items * uris.len());
+    let n_inlay_hints_items = inlay_hints_keys.len();
+    let n_items = n_diagnostics_items + n_inlay_hints_items + (n_document_items * uris.len());
 
     if configs.len() != n_items {
         return Err(anyhow!(
@@ -341,13 +527,33 @@ async fn update_config(
     let config: VscDiagnosticsConfig = serde_json::from_value(serde_json::Value::Object(map))?;
     let config: DiagnosticsConfig = config.into();
 
-    let changed = state.config.diagnostics != config;
+    // Also pick up changes to the `.ark.toml` files of the workspace
+    let project = ProjectConfig::from_workspace(&state.workspace.folders);
+    let package = Package::from_workspace(&state.workspace.folders);
+
+    let changed = state.config.diagnostics != config ||
+        state.config.project != project ||
+        state.workspace.package != package;
     state.config.diagnostics = config;
+    state.config.project = project;
+    state.workspace.package = package;
 
     if changed {
         lsp::spawn_diagnostics_refresh_all(state.clone());
     }
 
+    // --- Inlay hints
+    let keys = inlay_hints_keys.into_iter();
+    let items: Vec<Value> = configs.by_ref().take(n_inlay_hints_items).collect();
+
+    let mut map = serde_json::Map::new();
+    std::iter::zip(keys, items).for_each(|(key, item)| {
+        map.insert(key.into(), item);
+    });
+
+    let config: VscInlayHintsConfig = serde_json::from_value(serde_json::Value::Object(map))?;
+    state.config.inlay_hints = config.into();
+
     // --- Documents
     // For each document, deserialise the vector of JSON values into a typed config
     for uri in uris.into_iter() {
@@ -377,8 +583,17 @@ pub(crate) fn did_change_console_inputs(
     inputs: ConsoleInputs,
     state: &mut WorldState,
 ) -> anyhow::Result<()> {
+    // Newly installed packages may export the symbols of quick fixes
+    if inputs.installed_packages != state.installed_packages {
+        package::clear_packages_exporting();
+    }
+
+    // Functions may have been redefined by the last evaluation
+    inlay_hints::clear_callee_formals();
+
     state.console_scopes = inputs.console_scopes;
     state.installed_packages = inputs.installed_packages;
+    state.console_signatures = inputs.console_signatures;
 
     // We currently rely on global console scopes for diagnostics, in particular
     // during package development in conjunction with `devtools::load_all()`.
@@ -401,3 +616,80 @@ fn update_index(uri: &url::Url, doc: &Document) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use tower_lsp::lsp_types::DidChangeWatchedFilesParams;
+    use tower_lsp::lsp_types::FileChangeType;
+    use tower_lsp::lsp_types::FileEvent;
+    use url::Url;
+
+    use crate::lsp::diagnostics::DiagnosticContext;
+    use crate::lsp::diagnostics_rules::RuleSeverity;
+    use crate::lsp::package::Package;
+    use crate::lsp::state::WorldState;
+    use crate::lsp::state_handlers::did_change_watched_files;
+
+    #[test]
+    fn test_did_change_watched_namespace() {
+        let dir = std::env::temp_dir().join(format!("ark-package-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("DESCRIPTION"), "Package: mypkg\n").unwrap();
+        std::fs::write(dir.join("NAMESPACE"), "export(foo)\n").unwrap();
+
+        let mut state = WorldState::default();
+        state.workspace.folders = vec![Url::from_directory_path(&dir).unwrap()];
+        state.workspace.package = Package::from_workspace(&state.workspace.folders);
+
+        let contents = Rope::from("");
+        let context = DiagnosticContext::from_state(&contents, &state);
+        assert!(!context.workspace_symbols.contains("abort"));
+
+        // As rewritten by `roxygen2::roxygenise()`
+        let namespace = dir.join("NAMESPACE");
+        std::fs::write(&namespace, "export(foo)\nimportFrom(rlang,abort)\n").unwrap();
+
+        let params = DidChangeWatchedFilesParams {
+            changes: vec![FileEvent {
+                uri: Url::from_file_path(&namespace).unwrap(),
+                typ: FileChangeType::CHANGED,
+            }],
+        };
+        did_change_watched_files(params, &mut state).unwrap();
+
+        let context = DiagnosticContext::from_state(&contents, &state);
+        assert!(context.workspace_symbols.contains("abort"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_did_change_watched_project_config() {
+        let dir = std::env::temp_dir().join(format!("ark-project-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut state = WorldState::default();
+        state.workspace.folders = vec![Url::from_directory_path(&dir).unwrap()];
+
+        let config = dir.join(".ark.toml");
+        std::fs::write(
+            &config,
+            "[diagnostics.rules]\nsymbol-not-in-scope = \"off\"\n",
+        )
+        .unwrap();
+
+        let params = DidChangeWatchedFilesParams {
+            changes: vec![FileEvent {
+                uri: Url::from_file_path(&config).unwrap(),
+                typ: FileChangeType::CREATED,
+            }],
+        };
+        did_change_watched_files(params, &mut state).unwrap();
+
+        let rules = state.config.diagnostics().rules;
+        assert_eq!(rules.get("symbol-not-in-scope"), Some(&RuleSeverity::Off));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -23,7 +23,11 @@ use tower_lsp::lsp_types::Url;
 use tower_lsp::lsp_types::WorkspaceSymbolParams;
 use tree_sitter::Node;
 
+use crate::lsp::class_definitions;
+use crate::lsp::class_definitions::Definition;
+use crate::lsp::class_definitions::MemberKind;
 use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
 use crate::lsp::indexer;
 use crate::lsp::indexer::IndexEntryData;
 use crate::lsp::state::WorldState;
@@ -70,6 +74,64 @@ pub fn symbols(params: &WorkspaceSymbolParams) -> anyhow::Result<Vec<SymbolInfor
                     container_name: None,
                 });
             },
+
+            IndexEntryData::Class { name, .. } => {
+                info.push(SymbolInformation {
+                    name: name.to_string(),
+                    kind: SymbolKind::CLASS,
+                    location: Location {
+                        uri: Url::from_file_path(path).unwrap(),
+                        range: entry.range,
+                    },
+                    tags: None,
+                    deprecated: None,
+                    container_name: None,
+                });
+            },
+
+            IndexEntryData::Member {
+                class, name, kind, ..
+            } => {
+                info.push(SymbolInformation {
+                    name: name.to_string(),
+                    kind: member_symbol_kind(*kind),
+                    location: Location {
+                        uri: Url::from_file_path(path).unwrap(),
+                        range: entry.range,
+                    },
+                    tags: None,
+                    deprecated: None,
+                    container_name: Some(class.to_string()),
+                });
+            },
+
+            IndexEntryData::Generic { name, arguments: _ } => {
+                info.push(SymbolInformation {
+                    name: name.to_string(),
+                    kind: SymbolKind::FUNCTION,
+                    location: Location {
+                        uri: Url::from_file_path(path).unwrap(),
+                        range: entry.range,
+                    },
+                    tags: None,
+                    deprecated: None,
+                    container_name: None,
+                });
+            },
+
+            IndexEntryData::Method { generic, class, .. } => {
+                info.push(SymbolInformation {
+                    name: generic.to_string(),
+                    kind: SymbolKind::METHOD,
+                    location: Location {
+                        uri: Url::from_file_path(path).unwrap(),
+                        range: entry.range,
+                    },
+                    tags: None,
+                    deprecated: None,
+                    container_name: Some(class.to_string()),
+                });
+            },
         };
     });
 
@@ -142,6 +204,14 @@ fn index_node(
         }
     }
 
+    // if we find a class, generic, or method definition, index it
+    if node.is_call() {
+        if let Some(definition) = class_definitions::definition(*node, contents) {
+            index_definition(definition, contents, parent, symbols)?;
+            return Ok(true);
+        }
+    }
+
     // by default, recurse into children
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
@@ -181,6 +251,10 @@ fn index_assignment(
         return index_assignment_with_function(node, contents, parent, symbols);
     }
 
+    if let Some(definition) = class_definitions::definition(*node, contents) {
+        return index_definition(definition, contents, parent, symbols);
+    }
+
     // otherwise, just index as generic object
     let name = contents.node_slice(&lhs)?.to_string();
 
@@ -255,3 +329,95 @@ fn index_assignment_with_function(
 
     Ok(true)
 }
+
+fn index_definition(
+    definition: Definition,
+    contents: &Rope,
+    parent: &mut DocumentSymbol,
+    symbols: &mut Vec<DocumentSymbol>,
+) -> Result<bool> {
+    let range = |node: &Node| convert_tree_sitter_range_to_lsp_range(contents, node.range());
+
+    let symbol = match definition {
+        Definition::Class(class) => {
+            let mut symbol = DocumentSymbol {
+                name: class.name,
+                kind: SymbolKind::CLASS,
+                detail: Some(format!("{} class", class.system.label())),
+                children: Some(Vec::new()),
+                deprecated: None,
+                tags: None,
+                range: range(&class.node),
+                selection_range: range(&class.name_node),
+            };
+
+            for member in class.members {
+                let detail = match member.kind {
+                    MemberKind::Method => format!("function({})", member.arguments.join(", ")),
+                    MemberKind::Field => String::from("field"),
+                };
+                let detail = if member.private {
+                    format!("private {detail}")
+                } else {
+                    detail
+                };
+
+                let mut child = DocumentSymbol {
+                    name: member.name,
+                    kind: member_symbol_kind(member.kind),
+                    detail: Some(detail),
+                    children: Some(Vec::new()),
+                    deprecated: None,
+                    tags: None,
+                    range: range(&member.node),
+                    selection_range: range(&member.name_node),
+                };
+
+                // recurse into method bodies
+                if let Some(value) = member.node.child_by_field_name("value") {
+                    if member.kind == MemberKind::Method {
+                        index_node(&value, contents, &mut child, symbols)?;
+                    }
+                }
+
+                symbol.children.as_mut().unwrap().push(child);
+            }
+
+            symbol
+        },
+
+        Definition::Generic(generic) => DocumentSymbol {
+            name: generic.name,
+            kind: SymbolKind::FUNCTION,
+            detail: Some(format!("generic({})", generic.arguments.join(", "))),
+            children: Some(Vec::new()),
+            deprecated: None,
+            tags: None,
+            range: range(&generic.node),
+            selection_range: range(&generic.name_node),
+        },
+
+        Definition::Method(method) => DocumentSymbol {
+            name: method.generic,
+            kind: SymbolKind::METHOD,
+            detail: Some(format!("method for {}", method.class)),
+            children: Some(Vec::new()),
+            deprecated: None,
+            tags: None,
+            range: range(&method.node),
+            selection_range: range(&method.name_node),
+        },
+    };
+
+    // add this symbol to the parent node
+    parent.children.as_mut().unwrap().push(symbol);
+
+    Ok(true)
+}
+
+fn member_symbol_kind(kind: MemberKind) -> SymbolKind {
+    match kind {
+        MemberKind::Method => SymbolKind::METHOD,
+        MemberKind::Field => SymbolKind::FIELD,
+    }
+}
@@ -0,0 +1,195 @@
+//
+// workspace_function.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::path::Path;
+
+use ropey::Rope;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::indexer;
+use crate::lsp::indexer::IndexEntry;
+use crate::lsp::indexer::IndexEntryData;
+use crate::lsp::roxygen::assigned_function;
+use crate::lsp::roxygen::RoxygenBlock;
+use crate::lsp::state::with_document;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::rope::RopeExt;
+
+/// A function defined in the workspace, found through the workspace index.
+/// Used by hover and signature help for functions that aren't loaded in the
+/// session, e.g. those of a package under development.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct WorkspaceFunction {
+    pub(crate) name: String,
+
+    /// The formals along with the code of their default value, if any
+    pub(crate) formals: Vec<(String, Option<String>)>,
+
+    /// The roxygen block above the definition, if any
+    pub(crate) roxygen: Option<RoxygenBlock>,
+}
+
+impl WorkspaceFunction {
+    /// Finds the function `name` in the workspace index. The definition is
+    /// read from the document to find default values and roxygen
+    /// documentation. If that fails, the formals recorded in the index are
+    /// used.
+    pub(crate) fn find(name: &str, state: &WorldState) -> Option<Self> {
+        let (path, entry) = indexer::find(name)?;
+
+        let IndexEntryData::Function { name, arguments } = &entry.data else {
+            return None;
+        };
+
+        let function = with_document(Path::new(&path), state, |document| {
+            Ok(Self::from_document(document, &entry))
+        });
+
+        match function {
+            Ok(Some(function)) => Some(function),
+            _ => Some(WorkspaceFunction {
+                name: name.clone(),
+                formals: arguments.iter().map(|name| (name.clone(), None)).collect(),
+                roxygen: None,
+            }),
+        }
+    }
+
+    fn from_document(document: &Document, entry: &IndexEntry) -> Option<Self> {
+        let contents = &document.contents;
+        let point = convert_position_to_point(contents, entry.range.start);
+
+        // Find the assignment binding the function from its name
+        let mut node = document
+            .ast
+            .root_node()
+            .descendant_for_point_range(point, point)?;
+        while assigned_function(&node).is_none() {
+            node = node.parent()?;
+        }
+
+        let name = contents
+            .node_slice(&node.child_by_field_name("lhs")?)
+            .ok()?
+            .to_string();
+
+        let function = assigned_function(&node)?;
+        let formals = function_formals_with_defaults(&function, contents);
+
+        Some(WorkspaceFunction {
+            name,
+            formals,
+            roxygen: RoxygenBlock::documenting(&node, contents),
+        })
+    }
+
+    /// The signature of the function, e.g. `f(x, y = 1)`
+    pub(crate) fn signature(&self) -> String {
+        let formals: Vec<String> = self
+            .formals
+            .iter()
+            .map(|(name, default)| formal_label(name, default.as_deref()))
+            .collect();
+
+        format!("{}({})", self.name, formals.join(", "))
+    }
+
+    /// The signature followed by the roxygen documentation, if any
+    pub(crate) fn markdown(&self) -> String {
+        let mut markdown = format!("```r\n{}\n```", self.signature());
+
+        if let Some(roxygen) = &self.roxygen {
+            let documentation = roxygen.markdown();
+            if !documentation.is_empty() {
+                markdown.push_str("\n\n");
+                markdown.push_str(&documentation);
+            }
+        }
+
+        markdown
+    }
+}
+
+/// The label of a formal in a signature, e.g. `x` or `y = 1`
+pub(crate) fn formal_label(name: &str, default: Option<&str>) -> String {
+    match default {
+        Some(default) => format!("{name} = {default}"),
+        None => name.to_string(),
+    }
+}
+
+fn function_formals_with_defaults(
+    function: &Node,
+    contents: &Rope,
+) -> Vec<(String, Option<String>)> {
+    let Some(parameters) = function.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+
+    let mut cursor = parameters.walk();
+    parameters
+        .children_by_field_name("parameter", &mut cursor)
+        .filter_map(|parameter| {
+            let name = parameter.child_by_field_name("name")?;
+            let name = contents.node_slice(&name).ok()?.to_string();
+
+            let default = parameter
+                .child_by_field_name("default")
+                .and_then(|default| contents.node_slice(&default).ok())
+                .map(|default| default.to_string());
+
+            Some((name, default))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::indexer::IndexEntry;
+    use crate::lsp::indexer::IndexEntryData;
+    use crate::lsp::workspace_function::WorkspaceFunction;
+
+    #[test]
+    fn test_workspace_function_from_document() {
+        let text = "
+#' Add numbers
+#' @param x A number.
+#' @param y Another number.
+add <- function(x, y = c(1, 2), ...) x + y
+";
+        let document = Document::new(text, None);
+
+        let entry = IndexEntry {
+            key: String::from("add"),
+            range: Range::new(Position::new(4, 0), Position::new(4, 3)),
+            data: IndexEntryData::Function {
+                name: String::from("add"),
+                arguments: vec![String::from("x"), String::from("y")],
+            },
+        };
+
+        let function = WorkspaceFunction::from_document(&document, &entry).unwrap();
+        assert_eq!(function.signature(), "add(x, y = c(1, 2), ...)");
+
+        let roxygen = function.roxygen.as_ref().unwrap();
+        assert_eq!(roxygen.title(), Some("Add numbers"));
+        assert_eq!(
+            roxygen.param_description("y"),
+            Some(String::from("Another number."))
+        );
+
+        assert!(function
+            .markdown()
+            .starts_with("```r\nadd(x, y = c(1, 2), ...)\n```\n\n**Add numbers**"));
+    }
+}
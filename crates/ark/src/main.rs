@@ -232,6 +232,12 @@ fn print_usage() {
     println!(
         r#"
 Usage: ark [OPTIONS]
+       ark lint [OPTIONS] [PATHS]...
+
+Available commands:
+
+lint, check              Report diagnostics of R files without starting a
+                         kernel (see `ark lint --help`)
 
 Available options:
 
@@ -263,6 +269,16 @@ fn main() {
     // Skip the first "argument" as it's the path/name to this executable
     argv.next();
 
+    // Commands that run without a kernel. These are only recognised as the
+    // first argument.
+    let mut argv = argv.peekable();
+    if let Some(command) = argv.peek() {
+        if command == "lint" || command == "check" {
+            argv.next();
+            std::process::exit(lsp::lint::run(argv.collect()));
+        }
+    }
+
     let mut connection_file: Option<String> = None;
     let mut startup_file: Option<String> = None;
     let mut session_mode = SessionMode::Console;
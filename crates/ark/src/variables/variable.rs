@@ -51,6 +51,8 @@ use libr::*;
 use stdext::local;
 use stdext::unwrap;
 
+use crate::data_explorer::table_backend::has_methods_backend;
+
 // Constants.
 const MAX_DISPLAY_VALUE_ENTRIES: usize = 1_000;
 const MAX_DISPLAY_VALUE_LENGTH: usize = 100;
@@ -521,7 +523,7 @@ impl PositronVariable {
                 size,
                 has_children: has_children(x),
                 is_truncated,
-                has_viewer: r_is_data_frame(x) || r_is_matrix(x),
+                has_viewer: r_is_data_frame(x) || r_is_matrix(x) || has_methods_backend(x),
                 updated_time: Self::update_timestamp(),
             },
         }
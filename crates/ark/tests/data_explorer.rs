@@ -6,6 +6,11 @@
 //
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::data_explorer_comm::ArraySelection;
+use amalthea::comm::data_explorer_comm::CodeSyntaxName;
+use amalthea::comm::data_explorer_comm::ColumnDisplayType;
+use amalthea::comm::data_explorer_comm::ColumnFilter;
+use amalthea::comm::data_explorer_comm::ColumnFilterParams;
+use amalthea::comm::data_explorer_comm::ColumnFilterType;
 use amalthea::comm::data_explorer_comm::ColumnFrequencyTable;
 use amalthea::comm::data_explorer_comm::ColumnFrequencyTableParams;
 use amalthea::comm::data_explorer_comm::ColumnHistogram;
@@ -19,6 +24,7 @@ use amalthea::comm::data_explorer_comm::ColumnProfileType;
 use amalthea::comm::data_explorer_comm::ColumnSelection;
 use amalthea::comm::data_explorer_comm::ColumnSortKey;
 use amalthea::comm::data_explorer_comm::ColumnValue;
+use amalthea::comm::data_explorer_comm::ConvertToCodeParams;
 use amalthea::comm::data_explorer_comm::DataExplorerBackendReply;
 use amalthea::comm::data_explorer_comm::DataExplorerBackendRequest;
 use amalthea::comm::data_explorer_comm::DataExplorerFrontendEvent;
@@ -30,7 +36,9 @@ use amalthea::comm::data_explorer_comm::ExportFormat;
 use amalthea::comm::data_explorer_comm::ExportedData;
 use amalthea::comm::data_explorer_comm::FilterComparison;
 use amalthea::comm::data_explorer_comm::FilterComparisonOp;
+use amalthea::comm::data_explorer_comm::FilterMatchDataTypes;
 use amalthea::comm::data_explorer_comm::FilterResult;
+use amalthea::comm::data_explorer_comm::FilterSetMembership;
 use amalthea::comm::data_explorer_comm::FilterTextSearch;
 use amalthea::comm::data_explorer_comm::FormatOptions;
 use amalthea::comm::data_explorer_comm::GetColumnProfilesParams;
@@ -41,17 +49,27 @@ use amalthea::comm::data_explorer_comm::RowFilter;
 use amalthea::comm::data_explorer_comm::RowFilterCondition;
 use amalthea::comm::data_explorer_comm::RowFilterParams;
 use amalthea::comm::data_explorer_comm::RowFilterType;
+use amalthea::comm::data_explorer_comm::SearchSchemaParams;
 use amalthea::comm::data_explorer_comm::Selection;
+use amalthea::comm::data_explorer_comm::SetColumnFiltersParams;
 use amalthea::comm::data_explorer_comm::SetRowFiltersParams;
 use amalthea::comm::data_explorer_comm::SetSortColumnsParams;
 use amalthea::comm::data_explorer_comm::SummaryStatsBoolean;
 use amalthea::comm::data_explorer_comm::SummaryStatsNumber;
 use amalthea::comm::data_explorer_comm::SummaryStatsString;
+use amalthea::comm::data_explorer_comm::SupportStatus;
 use amalthea::comm::data_explorer_comm::TableSelection;
 use amalthea::comm::data_explorer_comm::TableSelectionKind;
 use amalthea::comm::data_explorer_comm::TextSearchType;
 use amalthea::comm::event::CommManagerEvent;
 use amalthea::socket;
+use ark::data_explorer::export_file::DataExplorerExtensionReply;
+use ark::data_explorer::export_file::DataExplorerExtensionRequest;
+use ark::data_explorer::export_file::DataExplorerReply;
+use ark::data_explorer::export_file::DataExplorerRequest;
+use ark::data_explorer::export_file::ExportDataToFileParams;
+use ark::data_explorer::export_file::ExportFileFormat;
+use ark::data_explorer::export_file::ExportedFile;
 use ark::data_explorer::format::format_string;
 use ark::data_explorer::r_data_explorer::DataObjectEnvInfo;
 use ark::data_explorer::r_data_explorer::RDataExplorer;
@@ -1244,6 +1262,112 @@ fn test_boolean_filters() {
     })
 }
 
+#[test]
+fn test_set_membership_and_condition_filters() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(species = factor(c('cat', 'dog', 'bird', 'cat', NA)), weight = c(4, 30, 0.5, 6, 10))",
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                let features = state.supported_features.set_row_filters;
+                assert_eq!(features.supports_conditions, SupportStatus::Supported);
+                assert!(features
+                    .supported_types
+                    .iter()
+                    .any(|status| status.row_filter_type == RowFilterType::SetMembership));
+            }
+        );
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+
+        let set_membership = |column: usize, values: &[&str], inclusive, condition| RowFilter {
+            column_schema: schema.columns[column].clone(),
+            filter_type: RowFilterType::SetMembership,
+            filter_id: format!("set-membership-{column}-{}", values.join("-")),
+            condition,
+            is_valid: None,
+            params: Some(RowFilterParams::SetMembership(FilterSetMembership {
+                values: values.iter().map(|value| value.to_string()).collect(),
+                inclusive,
+            })),
+            error_message: None,
+        };
+
+        let num_rows = |filters: Vec<RowFilter>| {
+            let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams { filters });
+            match socket_rpc(&socket, req) {
+                DataExplorerBackendReply::SetRowFiltersReply(FilterResult {
+                    selected_num_rows,
+                    had_errors: Some(false),
+                }) => selected_num_rows,
+                reply => panic!("Unexpected reply: {:?}", reply),
+            }
+        };
+
+        // Factors are matched by their labels. Missing values are only kept
+        // when values are excluded.
+        let and = RowFilterCondition::And;
+        let or = RowFilterCondition::Or;
+        assert_eq!(
+            num_rows(vec![set_membership(0, &["cat", "bird"], true, and.clone())]),
+            3
+        );
+        assert_eq!(
+            num_rows(vec![set_membership(0, &["cat"], false, and.clone())]),
+            3
+        );
+
+        // Numeric columns are matched by value
+        assert_eq!(
+            num_rows(vec![set_membership(1, &["4", "30.0"], true, and.clone())]),
+            2
+        );
+
+        // `and` binds tighter than `or`: the dogs, along with the cats
+        // weighing less than 5
+        let light = RowFilter {
+            column_schema: schema.columns[1].clone(),
+            filter_type: RowFilterType::Compare,
+            filter_id: String::from("light"),
+            condition: and.clone(),
+            is_valid: None,
+            params: Some(RowFilterParams::Comparison(FilterComparison {
+                op: FilterComparisonOp::Lt,
+                value: String::from("5"),
+            })),
+            error_message: None,
+        };
+        assert_eq!(
+            num_rows(vec![
+                set_membership(0, &["dog"], true, and.clone()),
+                set_membership(0, &["cat"], true, or.clone()),
+                light.clone(),
+            ]),
+            2
+        );
+
+        // The dogs and the cats, weighing less than 5
+        assert_eq!(
+            num_rows(vec![
+                set_membership(0, &["dog", "cat"], true, and.clone()),
+                light,
+            ]),
+            1
+        );
+    })
+}
+
 #[test]
 fn test_invalid_filters() {
     r_test(|| {
@@ -1475,6 +1599,47 @@ fn test_data_explorer_special_values() {
     });
 }
 
+#[test]
+fn test_nested_column_schemas() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "local({
+                x <- data.frame(a = 1:2)
+                x$b <- data.frame(c = c('x', 'y'), d = c(TRUE, FALSE))
+                x$l <- list(1, 'a')
+                x
+            })",
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1, 2],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetSchemaReply(schema) => {
+                assert_eq!(schema.columns.len(), 3);
+                assert_eq!(schema.columns[0].type_display, ColumnDisplayType::Number);
+                assert_eq!(schema.columns[0].children, None);
+
+                // The columns of nested data frames are the children of their
+                // schema
+                assert_eq!(schema.columns[1].type_display, ColumnDisplayType::Struct);
+                let children = schema.columns[1].children.clone().unwrap();
+                assert_eq!(children.len(), 2);
+                assert_eq!(children[0].column_name, "c");
+                assert_eq!(children[0].type_display, ColumnDisplayType::String);
+                assert_eq!(children[1].column_name, "d");
+                assert_eq!(children[1].column_index, 1);
+                assert_eq!(children[1].type_display, ColumnDisplayType::Boolean);
+
+                assert_eq!(schema.columns[2].type_display, ColumnDisplayType::Array);
+                assert_eq!(schema.columns[2].children, None);
+            }
+        );
+    });
+}
+
 // The main exporting logic is tested in the data_exporter module. This test
 // is mainly an integration test to check if the data explorer can correctly
 // work with sorting/filtering the data and then exporting it.
@@ -1556,6 +1721,43 @@ fn test_export_data() {
                 assert_eq!(format, ExportFormat::Csv);
             }
         );
+
+        // hide the logical column, then export the whole view to a file
+        let column_filter_req =
+            DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams {
+                filters: vec![ColumnFilter {
+                    filter_type: ColumnFilterType::MatchDataTypes,
+                    params: ColumnFilterParams::MatchDataTypes(FilterMatchDataTypes {
+                        display_types: vec![ColumnDisplayType::Number, ColumnDisplayType::String],
+                    }),
+                }],
+            });
+        socket_rpc(&socket, column_filter_req);
+
+        let path: String = harp::parse_eval_global("tempfile(fileext = '.csv')")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let file_req = DataExplorerRequest::Extension(
+            DataExplorerExtensionRequest::ExportDataToFile(ExportDataToFileParams {
+                path: path.clone(),
+                format: ExportFileFormat::Csv,
+            }),
+        );
+        let reply: DataExplorerReply = socket_rpc_request(&socket.socket, file_req);
+        assert_match!(reply,
+            DataExplorerReply::Extension(DataExplorerExtensionReply::ExportDataToFileReply(
+                ExportedFile { num_rows, num_columns, .. }
+            )) => {
+                assert_eq!(num_rows, 2);
+                assert_eq!(num_columns, 2);
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "\"a\",\"b\"\n2,\"c\"\n1,\"a\"\n"
+        );
+        std::fs::remove_file(path).unwrap();
     })
 }
 
@@ -1844,3 +2046,488 @@ fn test_frequency_table() {
         });
     })
 }
+
+#[test]
+fn test_search_schema_and_column_filters() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(age = 1:3, Age_group = c('a', 'b', 'c'), income = c(1.5, 2, 3), date = Sys.Date() + 0:2)",
+            None,
+        )
+        .unwrap();
+
+        let search = |search_type, term: &str| ColumnFilter {
+            filter_type: ColumnFilterType::TextSearch,
+            params: ColumnFilterParams::TextSearch(FilterTextSearch {
+                search_type,
+                term: term.to_string(),
+                case_sensitive: false,
+            }),
+        };
+
+        // Search the schema, one result at a time
+        let req = DataExplorerBackendRequest::SearchSchema(SearchSchemaParams {
+            filters: vec![search(TextSearchType::StartsWith, "age")],
+            start_index: 1,
+            max_results: 1,
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SearchSchemaReply(result) => {
+                assert_eq!(result.total_num_matches, 2);
+                assert_eq!(result.matches.columns.len(), 1);
+                assert_eq!(result.matches.columns[0].column_name, "Age_group");
+                assert_eq!(result.matches.columns[0].column_index, 1);
+            }
+        );
+
+        // Search by data type
+        let req = DataExplorerBackendRequest::SearchSchema(SearchSchemaParams {
+            filters: vec![ColumnFilter {
+                filter_type: ColumnFilterType::MatchDataTypes,
+                params: ColumnFilterParams::MatchDataTypes(FilterMatchDataTypes {
+                    display_types: vec![ColumnDisplayType::Number],
+                }),
+            }],
+            start_index: 0,
+            max_results: 10,
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SearchSchemaReply(result) => {
+                let names: Vec<String> = result
+                    .matches
+                    .columns
+                    .into_iter()
+                    .map(|column| column.column_name)
+                    .collect();
+                assert_eq!(names, vec!["age", "income"]);
+            }
+        );
+
+        // Hide the columns containing "age"
+        let filters = vec![search(TextSearchType::NotContains, "age")];
+        let req = DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams {
+            filters: filters.clone(),
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetColumnFiltersReply() => {}
+        );
+
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.column_filters, filters);
+                assert_eq!(state.table_shape.num_columns, 2);
+                assert_eq!(state.table_unfiltered_shape.num_columns, 4);
+            }
+        );
+
+        // Schema indices are relative to the filtered columns, but the
+        // schemas keep the index of the column in the unfiltered table
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1, 2],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetSchemaReply(schema) => {
+                let columns: Vec<(String, i64)> = schema
+                    .columns
+                    .into_iter()
+                    .map(|column| (column.column_name, column.column_index))
+                    .collect();
+                assert_eq!(columns, vec![
+                    (String::from("income"), 2),
+                    (String::from("date"), 3),
+                ]);
+            }
+        );
+
+        // Data values are requested with the index of the unfiltered table
+        let req = get_data_values_request(0, 3, vec![2], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0], vec![
+                    ColumnValue::FormattedValue(String::from("1.50")),
+                    ColumnValue::FormattedValue(String::from("2.00")),
+                    ColumnValue::FormattedValue(String::from("3.00")),
+                ]);
+            }
+        );
+
+        // Clearing the filters shows all the columns again
+        let req = DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams {
+            filters: vec![],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetColumnFiltersReply() => {}
+        );
+
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.table_shape.num_columns, 4);
+            }
+        );
+    })
+}
+
+#[test]
+fn test_registered_table_backend() {
+    r_test(|| {
+        // A table that isn't a data frame, answering requests through the
+        // backend registered for its class
+        harp::parse_eval_global(
+            r#".ps.register_table_backend("ark_test_table", list(
+                shape = function(x) list(num_rows = nrow(x$data), prototype = x$data[0, ]),
+                column = function(x, j, i) {
+                    column <- x$data[[j]]
+                    if (is.null(i)) column else column[i]
+                },
+                subset = function(x, i, j) {
+                    if (is.null(i)) i <- seq_len(nrow(x$data))
+                    if (is.null(j)) j <- seq_along(x$data)
+                    x$data[i, j, drop = FALSE]
+                },
+                sort_rows = function(x, keys) {
+                    order(x$data[[keys[[1]]$column_index + 1]], decreasing = !keys[[1]]$ascending)
+                },
+                filter_rows = function(x, row_filters) {
+                    list(indices = which(x$data$x > 1), errors = rep(NA_character_, length(row_filters)))
+                }
+            ))"#,
+        )
+        .unwrap();
+
+        let socket = open_data_explorer_from_expression(
+            "structure(list(data = data.frame(x = c(3, 1, 2), y = c('a', 'b', 'c'))), class = 'ark_test_table')",
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.table_shape.num_rows, 3);
+                assert_eq!(state.table_shape.num_columns, 2);
+                assert!(!state.has_row_labels);
+            }
+        );
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+        assert_eq!(schema.columns[0].type_display, ColumnDisplayType::Number);
+        assert_eq!(schema.columns[1].type_display, ColumnDisplayType::String);
+
+        let values = |socket: &DataExplorerSocket| {
+            let req = get_data_values_request(0, 3, vec![0], default_format_options());
+            match socket_rpc(socket, req) {
+                DataExplorerBackendReply::GetDataValuesReply(data) => data.columns[0].clone(),
+                reply => panic!("Unexpected reply: {:?}", reply),
+            }
+        };
+        let formatted = |values: &[&str]| -> Vec<ColumnValue> {
+            values
+                .iter()
+                .map(|value| ColumnValue::FormattedValue(value.to_string()))
+                .collect()
+        };
+        assert_eq!(values(&socket), formatted(&["3.00", "1.00", "2.00"]));
+
+        // Rows are sorted by the backend
+        let req = DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
+            sort_keys: vec![ColumnSortKey {
+                column_index: 0,
+                ascending: true,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetSortColumnsReply() => {}
+        );
+        assert_eq!(values(&socket), formatted(&["1.00", "2.00", "3.00"]));
+
+        // And filtered by the backend
+        let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+            filters: vec![RowFilter {
+                column_schema: schema.columns[0].clone(),
+                filter_type: RowFilterType::Compare,
+                filter_id: String::from("greater-than-one"),
+                condition: RowFilterCondition::And,
+                is_valid: None,
+                params: Some(RowFilterParams::Comparison(FilterComparison {
+                    op: FilterComparisonOp::Gt,
+                    value: String::from("1"),
+                })),
+                error_message: None,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetRowFiltersReply(
+                FilterResult { selected_num_rows: 2, had_errors: Some(false) }
+            ) => {}
+        );
+        let req = get_data_values_request(0, 2, vec![0], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0], formatted(&["2.00", "3.00"]));
+            }
+        );
+    })
+}
+
+#[test]
+fn test_registered_table_backend_profiles() {
+    r_test(|| {
+        // The backend computes null counts natively and leaves the other
+        // profiles to be computed in memory
+        harp::parse_eval_global(
+            r#".ps.register_table_backend("ark_test_profiled_table", list(
+                shape = function(x) list(num_rows = nrow(x$data), prototype = x$data[0, , drop = FALSE]),
+                column = function(x, j, i) {
+                    column <- x$data[[j]]
+                    if (is.null(i)) column else column[i]
+                },
+                subset = function(x, i, j) x$data,
+                sort_rows = function(x, keys) seq_len(nrow(x$data)),
+                filter_rows = function(x, row_filters) {
+                    list(indices = seq_len(nrow(x$data)), errors = rep(NA_character_, length(row_filters)))
+                },
+                profile = function(x, j, i, row_filters, profile) {
+                    if (identical(profile$profile_type, "null_count")) 42L else NULL
+                }
+            ))"#,
+        )
+        .unwrap();
+
+        let socket = open_data_explorer_from_expression(
+            "structure(list(data = data.frame(x = c(3, 1, 2))), class = 'ark_test_profiled_table')",
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetColumnProfiles(GetColumnProfilesParams {
+            callback_id: String::from("id"),
+            profiles: vec![ColumnProfileRequest {
+                column_index: 0,
+                profiles: vec![
+                    ColumnProfileSpec {
+                        profile_type: ColumnProfileType::NullCount,
+                        params: None,
+                    },
+                    ColumnProfileSpec {
+                        profile_type: ColumnProfileType::SummaryStats,
+                        params: None,
+                    },
+                ],
+            }],
+            format_options: default_format_options(),
+        });
+
+        expect_column_profile_results(&socket, req, |data| {
+            assert_eq!(data.len(), 1);
+            assert_eq!(data[0].null_count, Some(42));
+
+            let number_stats = data[0].summary_stats.clone().unwrap().number_stats.unwrap();
+            assert_eq!(number_stats.min_value, Some(String::from("1.00")));
+            assert_eq!(number_stats.max_value, Some(String::from("3.00")));
+        });
+    })
+}
+
+#[test]
+fn test_lazy_table_backend_filters() {
+    r_test(|| {
+        // Lazy tables are filtered in SQL by an in-memory database
+        let available = harp::parse_eval_global(
+            "requireNamespace('dbplyr', quietly = TRUE) && requireNamespace('RSQLite', quietly = TRUE)",
+        )
+        .unwrap();
+        if !bool::try_from(available).unwrap() {
+            return;
+        }
+
+        let socket = open_data_explorer_from_expression(
+            "dbplyr::memdb_frame(text = c('50%', '', NA, '500', 'a_b', 'axb'))",
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+
+        let num_rows = |filter_type: RowFilterType, params: Option<RowFilterParams>| {
+            let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+                filters: vec![RowFilter {
+                    column_schema: schema.columns[0].clone(),
+                    filter_type,
+                    filter_id: String::from("lazy-table-filter"),
+                    condition: RowFilterCondition::And,
+                    is_valid: None,
+                    params,
+                    error_message: None,
+                }],
+            });
+            match socket_rpc(&socket, req) {
+                DataExplorerBackendReply::SetRowFiltersReply(FilterResult {
+                    selected_num_rows,
+                    had_errors: Some(false),
+                }) => selected_num_rows,
+                reply => panic!("Unexpected reply: {:?}", reply),
+            }
+        };
+
+        // Like in memory, missing values are not empty
+        assert_eq!(num_rows(RowFilterType::IsEmpty, None), 1);
+        assert_eq!(num_rows(RowFilterType::NotEmpty, None), 5);
+
+        // Wildcards of `LIKE` patterns match literally
+        let search = |search_type: TextSearchType, term: &str| {
+            Some(RowFilterParams::TextSearch(FilterTextSearch {
+                case_sensitive: false,
+                search_type,
+                term: String::from(term),
+            }))
+        };
+        let contains = search(TextSearchType::Contains, "50%");
+        assert_eq!(num_rows(RowFilterType::Search, contains), 1);
+        let starts_with = search(TextSearchType::StartsWith, "a_");
+        assert_eq!(num_rows(RowFilterType::Search, starts_with), 1);
+        let not_contains = search(TextSearchType::NotContains, "_");
+        assert_eq!(num_rows(RowFilterType::Search, not_contains), 5);
+    })
+}
+
+#[test]
+fn test_convert_to_code() {
+    r_test(|| {
+        harp::parse_eval_global(
+            "obj <- data.frame(name = c('a', 'b', 'c', 'd'), value = c(3, NA, 1, 5))",
+        )
+        .unwrap();
+        let socket = open_data_explorer_from_expression("obj", None).unwrap();
+
+        let convert = |syntax: &str| {
+            let req = DataExplorerBackendRequest::ConvertToCode(ConvertToCodeParams {
+                code_syntax_name: CodeSyntaxName {
+                    code_syntax_name: String::from(syntax),
+                },
+            });
+            match socket_rpc(&socket, req) {
+                DataExplorerBackendReply::ConvertToCodeReply(code) => code.converted_code,
+                reply => panic!("Unexpected reply: {:?}", reply),
+            }
+        };
+
+        assert_match!(socket_rpc(&socket, DataExplorerBackendRequest::SuggestCodeSyntax),
+            DataExplorerBackendReply::SuggestCodeSyntaxReply(syntax) => {
+                assert_eq!(syntax.code_syntax_name, "base");
+            }
+        );
+
+        // Without filters or sorts, the code is the data itself
+        assert_eq!(convert("base"), vec!["obj"]);
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+
+        // Rows with a value over 2, or with a name containing "B"
+        let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+            filters: vec![
+                RowFilter {
+                    column_schema: schema.columns[1].clone(),
+                    filter_type: RowFilterType::Compare,
+                    filter_id: String::from("greater-than-two"),
+                    condition: RowFilterCondition::And,
+                    is_valid: None,
+                    params: Some(RowFilterParams::Comparison(FilterComparison {
+                        op: FilterComparisonOp::Gt,
+                        value: String::from("2"),
+                    })),
+                    error_message: None,
+                },
+                RowFilter {
+                    column_schema: schema.columns[0].clone(),
+                    filter_type: RowFilterType::Search,
+                    filter_id: String::from("contains-b"),
+                    condition: RowFilterCondition::Or,
+                    is_valid: None,
+                    params: Some(RowFilterParams::TextSearch(FilterTextSearch {
+                        search_type: TextSearchType::Contains,
+                        term: String::from("B"),
+                        case_sensitive: false,
+                    })),
+                    error_message: None,
+                },
+            ],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetRowFiltersReply(
+                FilterResult { selected_num_rows: 3, had_errors: Some(false) }
+            ) => {}
+        );
+
+        let req = DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
+            sort_keys: vec![ColumnSortKey {
+                column_index: 1,
+                ascending: false,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetSortColumnsReply() => {}
+        );
+
+        // Only the string columns are viewed
+        let req = DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams {
+            filters: vec![ColumnFilter {
+                filter_type: ColumnFilterType::MatchDataTypes,
+                params: ColumnFilterParams::MatchDataTypes(FilterMatchDataTypes {
+                    display_types: vec![ColumnDisplayType::String],
+                }),
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetColumnFiltersReply() => {}
+        );
+
+        let base = convert("base");
+        assert_eq!(base, vec![
+            r#"result <- obj[which(obj$value > 2 | grepl("B", obj$name, ignore.case = TRUE)), , drop = FALSE]"#,
+            r#"result <- result[order(result$value, decreasing = TRUE, method = "radix"), "name", drop = FALSE]"#,
+        ]);
+
+        assert_eq!(convert("dplyr"), vec![
+            "obj |>",
+            r#"  dplyr::filter(value > 2 | grepl("B", name, ignore.case = TRUE)) |>"#,
+            "  dplyr::arrange(dplyr::desc(value)) |>",
+            "  dplyr::select(name)",
+        ]);
+
+        assert_eq!(convert("data.table"), vec![
+            r#"data.table::as.data.table(obj)[value > 2 | grepl("B", name, ignore.case = TRUE)][order(-value), "name"]"#,
+        ]);
+
+        // The code reproduces the rows of the view
+        for line in base {
+            harp::parse_eval_global(&line).unwrap();
+        }
+        let names: Vec<String> = harp::parse_eval_global("result$name")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(names, vec!["d", "a", "b"]);
+
+        harp::parse_eval_global("rm(obj, result)").unwrap();
+    })
+}